@@ -2,30 +2,40 @@ use std::marker::PhantomData;
 
 use bevy_autoken::{spawn_entity, RandomEntityExt, SendsEvent};
 use bevy_ecs::entity::Entity;
-use crucible_math::{Angle3D, EntityAabb, EntityVec, WorldVec};
+use crucible_assets::AssetManager;
+use crucible_math::{Angle3D, Angle3DExt as _, EntityAabb, EntityVec, WorldVec};
 use crucible_utils::newtypes::Index;
 use crucible_world::{
     collider::{
         AabbHolder, AabbStore, BlockColliderDescriptor, Collider, ColliderMaterial,
-        ColliderMaterialId,
+        ColliderMaterialId, StepState,
     },
     voxel::{
         BlockData, BlockMaterialRegistry, ChunkVoxelData, PopulateWorld, WorldChunkCreated,
         WorldPointer, WorldVoxelData,
     },
 };
-use main_loop::{Viewport, ViewportManager};
+use main_loop::{GfxContext, Viewport, ViewportManager};
+use typed_glam::glam::Vec3;
+use wgpu_ext::embedded_asset;
 
 use crate::render::{
-    helpers::{CameraManager, CameraSettings, CameraViewState, VirtualCamera},
+    helpers::{
+        CameraManager, CameraSettings, CameraViewState, FogSettings, SsaoQuality, SsaoSettings,
+        VirtualCamera,
+    },
     voxel::MaterialVisualDescriptor,
     GlobalRenderer,
 };
 
-use self::player::PlayerCameraController;
+use self::player::{ControllerMode, PlayerCameraController};
 
 pub mod player;
 
+/// The name of the world the game populates, which keeps its on-disk caches apart from those of
+/// other worlds.
+pub const WORLD_NAME: &str = "sandbox";
+
 // === Systems === //
 
 #[allow(clippy::type_complexity)]
@@ -41,7 +51,7 @@ pub fn init_engine_root(
         &mut PlayerCameraController,
         &mut VirtualCamera,
         &mut WorldVoxelData,
-        (&Viewport, &ViewportManager),
+        (&Viewport, &ViewportManager, &GfxContext, &AssetManager),
         SendsEvent<WorldChunkCreated>,
     )>,
     engine_root: Entity,
@@ -61,6 +71,9 @@ pub fn init_engine_root(
         sensitivity: 0.1,
         ctrl_window: main_viewport,
         has_focus: false,
+        mode: ControllerMode::Walk,
+        fly_speed: 0.5,
+        step: StepState::default(),
     });
     engine_root.insert(AabbHolder::new(
         EntityAabb::ZERO,
@@ -73,19 +86,48 @@ pub fn init_engine_root(
 
     engine_root.get::<CameraManager>().set_active_camera(camera);
 
+    // Thin the fog out above the terrain
+    renderer.set_fog(FogSettings {
+        height_falloff: 0.02,
+        ..FogSettings::default()
+    });
+
+    // SSAO is expensive so only integrated GPUs get the cheaper variant.
+    let gfx = engine_root.get::<GfxContext>();
+    renderer.set_ssao(SsaoSettings::for_quality(
+        match gfx.adapter_info.device_type() {
+            wgpu::DeviceType::DiscreteGpu => SsaoQuality::High,
+            wgpu::DeviceType::IntegratedGpu => SsaoQuality::Low,
+            _ => SsaoQuality::Off,
+        },
+    ));
+
     // Create the basic material
-    let stone = renderer.push_to_atlas(
-        &image::load_from_memory(include_bytes!("res/stone.png"))
-            .unwrap()
-            .into_rgba32f(),
+    let assets = engine_root.get::<AssetManager>();
+    let stone = renderer.push_srgb_to_atlas(
+        embedded_asset!("res/stone.png")
+            .load_image(&assets)
+            .to_rgba32f(),
     );
 
-    let bricks = renderer.push_to_atlas(
-        &image::load_from_memory(include_bytes!("res/bricks.png"))
-            .unwrap()
-            .into_rgba32f(),
+    // Bricks tile seamlessly so they're sampled from the texture array to avoid atlas bleeding.
+    let bricks = renderer.push_srgb_to_layers(
+        embedded_asset!("res/bricks.png")
+            .load_image(&assets)
+            .to_rgba32f(),
     );
 
+    // Create a monitor overlooking the spawn platform. It can see itself so its screen recurses a
+    // few levels deep.
+    let monitor_camera = spawn_entity(()).insert(VirtualCamera::new(
+        CameraViewState::new(
+            Vec3::new(0., 4., -10.),
+            Angle3D::from_facing(Vec3::new(0., -0.6, 1.)),
+        ),
+        CameraSettings::new_persp_deg(70., 0.1, 100.),
+    ));
+    let monitor = renderer.add_monitor(monitor_camera);
+
     let mut registry = engine_root.get::<BlockMaterialRegistry>();
     let _air = registry.register("crucible:air", spawn_entity(()));
 
@@ -102,7 +144,13 @@ pub fn init_engine_root(
     let _bricks = registry.register(
         "crucible:bricks",
         spawn_entity(())
-            .with(MaterialVisualDescriptor::cubic_simple(bricks))
+            .with(MaterialVisualDescriptor::layered_simple(bricks))
+            .with(BlockColliderDescriptor(Collider::Opaque(solid_mat))),
+    );
+    let monitor = registry.register(
+        "crucible:monitor",
+        spawn_entity(())
+            .with(MaterialVisualDescriptor::layered_simple(monitor))
             .with(BlockColliderDescriptor(Collider::Opaque(solid_mat))),
     );
 
@@ -119,4 +167,10 @@ pub fn init_engine_root(
             );
         }
     }
+
+    pointer.move_to(WorldVec::new(0, -4, 4)).set_state(
+        world,
+        BlockData::new(monitor),
+        PopulateWorld,
+    );
 }
@@ -3,7 +3,8 @@ use bevy_ecs::system::{Query, Res};
 use crucible_math::{Angle3D, Angle3DExt as _, EntityAabb, EntityVec, WorldVecExt};
 use crucible_world::{
     collider::{
-        AabbHolder, AabbStore, AnyCollision, BlockColliderDescriptor, VoxelRayCast, WorldCollisions,
+        AabbHolder, AabbStore, AnyCollision, BlockColliderDescriptor, StepState, VoxelRayCast,
+        WorldCollisions,
     },
     voxel::{
         BlockData, BlockMaterialRegistry, ChunkVoxelData, EntityPointer, KeepInWorld,
@@ -15,11 +16,7 @@ use typed_glam::{
     glam::{Vec2, Vec3},
     traits::GlamBacked as _,
 };
-use winit::{
-    event::MouseButton,
-    keyboard::KeyCode,
-    window::{CursorGrabMode, WindowId},
-};
+use winit::{event::MouseButton, keyboard::KeyCode, window::WindowId};
 
 use crate::{
     main_loop::EngineRoot,
@@ -28,6 +25,17 @@ use crate::{
 
 // === Components === //
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ControllerMode {
+    /// Moves the player's collider through the world, sliding along any blocks or actors it hits and
+    /// stepping up onto low ledges.
+    #[default]
+    Walk,
+
+    /// Moves the camera freely through the world, ignoring collisions entirely.
+    Fly,
+}
+
 #[derive(Debug)]
 pub struct PlayerCameraController {
     pub pos: EntityVec,
@@ -35,23 +43,47 @@ pub struct PlayerCameraController {
     pub sensitivity: f32,
     pub ctrl_window: WindowId,
     pub has_focus: bool,
+    pub mode: ControllerMode,
+    pub fly_speed: f64,
+    pub step: StepState,
 }
 
 impl PlayerCameraController {
-    pub fn move_by_colliding(
-        mut self: Obj<Self>,
-        collisions: &mut WorldCollisions,
-        delta: EntityVec,
-    ) {
+    pub const FLY_SPRINT_MULTIPLIER: f64 = 4.;
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            ControllerMode::Walk => ControllerMode::Fly,
+            ControllerMode::Fly => ControllerMode::Walk,
+        };
+    }
+
+    pub fn move_by(mut self: Obj<Self>, collisions: &mut WorldCollisions, delta: EntityVec) {
         let me = self.entity();
-        let derived_aabb = self.derived_aabb();
-        self.pos += collisions.move_rigid_body(derived_aabb, delta, |coll| match coll {
-            AnyCollision::Block(..) => true,
-            AnyCollision::Actor(actor) => actor.entity() != me,
+        let mut step = self.step;
+        self.apply_movement(delta, |aabb, delta| {
+            collisions.step_rigid_body(aabb, delta, &mut step, |coll| match coll {
+                AnyCollision::Block(..) => true,
+                AnyCollision::Actor(actor) => actor.entity() != me,
+            })
         });
+        self.step = step;
         self.update_aabb();
     }
 
+    /// Moves the controller by `delta`. In walk mode, `resolve` is given the controller's bounds and
+    /// the requested delta and returns the delta it can actually travel. Fly mode never calls it.
+    pub fn apply_movement(
+        &mut self,
+        delta: EntityVec,
+        resolve: impl FnOnce(EntityAabb, EntityVec) -> EntityVec,
+    ) {
+        self.pos += match self.mode {
+            ControllerMode::Walk => resolve(self.derived_aabb(), delta),
+            ControllerMode::Fly => delta,
+        };
+    }
+
     pub fn update_aabb(self: Obj<Self>) {
         self.obj::<AabbHolder>().set_aabb(self.derived_aabb());
     }
@@ -65,7 +97,7 @@ random_component!(PlayerCameraController);
 
 // === Systems === //
 
-fn get_heading(win_inputs: &InputManagerWindow) -> Vec3 {
+fn get_heading(win_inputs: &InputManagerWindow, mode: ControllerMode) -> Vec3 {
     let mut heading = Vec3::ZERO;
 
     if win_inputs.physical_key(KeyCode::KeyW).state() {
@@ -84,11 +116,16 @@ fn get_heading(win_inputs: &InputManagerWindow) -> Vec3 {
         heading += Vec3::X;
     }
 
-    if win_inputs.physical_key(KeyCode::KeyQ).state() {
+    let (down, up) = match mode {
+        ControllerMode::Walk => (KeyCode::KeyQ, KeyCode::KeyE),
+        ControllerMode::Fly => (KeyCode::ControlLeft, KeyCode::Space),
+    };
+
+    if win_inputs.physical_key(down).state() {
         heading += Vec3::NEG_Y;
     }
 
-    if win_inputs.physical_key(KeyCode::KeyE).state() {
+    if win_inputs.physical_key(up).state() {
         heading += Vec3::Y;
     }
 
@@ -129,19 +166,11 @@ pub fn sys_process_camera_controller(
         for (&(mut controller), &(mut camera)) in query.iter_mut() {
             let win_inputs = inputs.window(controller.ctrl_window);
             let viewport = viewports.get_viewport(controller.ctrl_window).unwrap();
-            let window = viewport.window();
 
             // Handle controller focus
             if !controller.has_focus {
                 if win_inputs.button(MouseButton::Left).state() {
-                    for mode in [CursorGrabMode::Locked, CursorGrabMode::Confined] {
-                        if window.set_cursor_grab(mode).is_ok() {
-                            break;
-                        }
-                    }
-
-                    window.set_cursor_visible(false);
-
+                    viewport.set_cursor_grab(true);
                     controller.has_focus = true;
                 }
                 continue;
@@ -149,8 +178,7 @@ pub fn sys_process_camera_controller(
 
             // Handle controller un-focus
             if win_inputs.physical_key(KeyCode::Escape).recently_pressed() {
-                let _ = window.set_cursor_grab(CursorGrabMode::None);
-                window.set_cursor_visible(true);
+                viewport.set_cursor_grab(false);
                 controller.has_focus = false;
                 continue;
             }
@@ -160,14 +188,26 @@ pub fn sys_process_camera_controller(
             controller.facing += Angle3D::from_deg(inputs.mouse_delta().as_vec2() * sensitivity);
             controller.facing = controller.facing.wrap_x().clamp_y_90();
 
+            // Toggle noclip
+            if win_inputs.physical_key(KeyCode::KeyF).recently_pressed() {
+                controller.toggle_mode();
+            }
+
             // Process heading
-            let heading = get_heading(&win_inputs);
+            let heading = get_heading(&win_inputs, controller.mode);
             let heading = controller.facing.as_matrix().transform_vector3(heading);
+            let speed = match controller.mode {
+                ControllerMode::Walk => 0.1,
+                ControllerMode::Fly if win_inputs.physical_key(KeyCode::ShiftLeft).state() => {
+                    controller.fly_speed * PlayerCameraController::FLY_SPRINT_MULTIPLIER
+                }
+                ControllerMode::Fly => controller.fly_speed,
+            };
 
             controller.update_aabb();
-            controller.move_by_colliding(
+            controller.move_by(
                 &mut collisions,
-                heading.as_dvec3().cast_glam::<EntityVec>() * 0.1,
+                heading.as_dvec3().cast_glam::<EntityVec>() * speed,
             );
 
             // Handle interaction
@@ -214,7 +254,9 @@ pub fn sys_process_camera_controller(
             // Update camera
             camera.state.pos = controller.pos.as_glam().as_vec3();
             camera.state.facing = controller.facing;
-            camera.settings = if win_inputs.physical_key(KeyCode::Space).state() {
+            camera.settings = if controller.mode == ControllerMode::Walk
+                && win_inputs.physical_key(KeyCode::Space).state()
+            {
                 CameraSettings::new_ortho(Vec2::splat(10.), 1., 100.)
             } else {
                 CameraSettings::new_persp_deg(90., 0.1, 100.)
@@ -222,3 +264,33 @@ pub fn sys_process_camera_controller(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fly_mode_skips_collision_resolution() {
+        let mut controller = PlayerCameraController {
+            pos: EntityVec::ZERO,
+            facing: Angle3D::ZERO,
+            sensitivity: 0.1,
+            ctrl_window: WindowId::dummy(),
+            has_focus: true,
+            mode: ControllerMode::default(),
+            fly_speed: 0.5,
+            step: StepState::default(),
+        };
+        let delta = EntityVec::new(1., 2., -3.);
+
+        // Walk mode defers to the collision resolver, which blocks us here.
+        assert_eq!(controller.mode, ControllerMode::Walk);
+        controller.apply_movement(delta, |_, _| EntityVec::ZERO);
+        assert_eq!(controller.pos, EntityVec::ZERO);
+
+        controller.toggle_mode();
+        assert_eq!(controller.mode, ControllerMode::Fly);
+        controller.apply_movement(delta, |_, _| unreachable!("fly mode resolved collisions"));
+        assert_eq!(controller.pos, delta);
+    }
+}
@@ -1,13 +1,19 @@
-use std::{marker::PhantomData, sync::Arc, time::Instant};
+use std::{
+    marker::PhantomData,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
 use bevy_app::{App, Update};
 use bevy_autoken::{
-    despawn_entity, spawn_entity, world_mut, RandomAccess, RandomAppExt, RandomEntityExt,
-    RandomWorldExt,
+    despawn_entity, send_event, spawn_entity, Obj, RandomAccess, RandomAppExt, RandomEntityExt,
+    RandomWorldExt, SendsEvent,
 };
 use bevy_ecs::{
     entity::Entity,
+    event::EventReader,
     schedule::IntoSystemConfigs,
     system::{Res, Resource},
 };
@@ -20,8 +26,9 @@ use crucible_world::{
     },
 };
 use main_loop::{
-    feat_requires_screen, run_app_with_init, sys_unregister_dead_viewports, FixedRate, GfxContext,
-    InputManager, LimitedRate, Viewport, ViewportManager,
+    feat_requires_screen, recover_lost_device, run_app_with_init, sys_unregister_dead_viewports,
+    AdapterSelector, BackgroundPolicy, BackgroundThrottle, FixedRate, FrameAcquire, FramePacer,
+    GfxContext, GfxDeviceRecreated, InputManager, PaceAction, Viewport, ViewportManager,
 };
 use winit::{
     application::ApplicationHandler,
@@ -31,7 +38,10 @@ use winit::{
 };
 
 use crate::{
-    game::player::{sys_process_camera_controller, PlayerCameraController},
+    game::{
+        self,
+        player::{sys_process_camera_controller, PlayerCameraController},
+    },
     render::{
         helpers::{CameraManager, VirtualCamera},
         voxel::{
@@ -42,6 +52,10 @@ use crate::{
     },
 };
 
+/// The environment variable naming the directory chunk meshes are cached in. Chunk meshes aren't
+/// cached to disk unless it's set.
+pub const MESH_CACHE_DIR_VAR: &str = "CRUCIBLE_MESH_CACHE_DIR";
+
 pub fn main_inner() -> anyhow::Result<()> {
     // Build event loop and start app!
     let event_loop = EventLoop::new().context("failed to create event loop")?;
@@ -71,12 +85,15 @@ pub fn main_inner() -> anyhow::Result<()> {
         app.add_random_component::<WorldVoxelData>();
         app.add_random_component::<WorldVoxelMesh>();
 
+        app.add_event::<GfxDeviceRecreated>();
         app.add_event::<WorldChunkCreated>();
 
         #[rustfmt::skip]
         app.add_systems(
             Update,
             (
+                sys_recover_lost_device,
+                sys_recreate_gpu_resources,
                 sys_process_camera_controller,
                 sys_attach_mesh_to_visual_chunks,
                 sys_queue_dirty_chunks_for_render,
@@ -88,14 +105,23 @@ pub fn main_inner() -> anyhow::Result<()> {
         );
 
         // Initialize engine root
-        let engine_root = app.use_random(|cx| init_engine_root(cx, event_loop))?;
+        let (engine_root, main_viewport) = app.use_random(|cx| init_engine_root(cx, event_loop))?;
         app.insert_resource(EngineRoot(engine_root));
 
+        // Allow game to initialize itself
+        app.use_random(|cx| crate::game::init_engine_root(cx, engine_root));
+
+        // Make main viewport visible
+        app.use_random(|_: PhantomData<&Viewport>| {
+            main_viewport.window().set_visible(true);
+        });
+
         Ok(WinitApp {
             app,
             engine_root,
             update_rate: FixedRate::new(60.),
-            render_rate: LimitedRate::new(60.),
+            frame_pacer: FramePacer::new(Some(60.)),
+            background: BackgroundThrottle::new(Some(60.), BackgroundPolicy::default()),
         })
     })
 }
@@ -104,19 +130,26 @@ struct WinitApp {
     app: App,
     engine_root: Entity,
     update_rate: FixedRate,
-    render_rate: LimitedRate,
+    frame_pacer: FramePacer,
+    background: BackgroundThrottle,
 }
 
 impl ApplicationHandler for WinitApp {
     fn new_events(&mut self, event_loop: &ActiveEventLoop, _cause: StartCause) {
+        // Throttle rendering while the app is in the background
+        self.background.apply(&mut self.frame_pacer);
+        let simulate = self.background.should_simulate();
+
         // Update and queue render if applicable
-        if let Some(times) = self.update_rate.tick(Instant::now()).output {
+        let update = self.update_rate.tick(Instant::now());
+        if let Some(times) = update.output.filter(|_| simulate) {
             for _ in 0..times.get().min(2) {
                 self.app.update();
             }
         }
 
-        if self.render_rate.tick(Instant::now()).output.is_some() {
+        let pace = self.frame_pacer.poll(Instant::now());
+        if pace == PaceAction::Ready {
             self.app
                 .use_random(|_: PhantomData<(&ViewportManager, &Viewport)>| {
                     let vmgr = self.engine_root.get::<ViewportManager>();
@@ -126,7 +159,14 @@ impl ApplicationHandler for WinitApp {
                     }
                 });
         }
-        event_loop.set_control_flow(ControlFlow::Poll);
+
+        // Sleep until either the next update or the next frame is due. We poll while spinning to
+        // hit the frame's deadline precisely.
+        event_loop.set_control_flow(match pace {
+            PaceAction::Sleep(until) if !simulate => ControlFlow::WaitUntil(until),
+            PaceAction::Sleep(until) => ControlFlow::WaitUntil(until.min(update.next_tick)),
+            PaceAction::Ready | PaceAction::Spin => ControlFlow::Poll,
+        });
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
@@ -150,6 +190,8 @@ impl ApplicationHandler for WinitApp {
         window_id: WindowId,
         event: WindowEvent,
     ) {
+        self.background.process_window_event(window_id, &event);
+
         // Tick input manager
         self.app.use_random(|_: PhantomData<&mut InputManager>| {
             self.engine_root
@@ -206,6 +248,72 @@ fn sys_reset_input_tracker(
     });
 }
 
+fn sys_recover_lost_device(
+    mut rand: RandomAccess<(&mut GfxContext, SendsEvent<GfxDeviceRecreated>)>,
+    engine_root: Res<EngineRoot>,
+) {
+    rand.provide(|| {
+        let mut gfx = engine_root.0.get::<GfxContext>();
+        let old_gfx = (*gfx).clone();
+
+        let res = recover_lost_device(
+            &old_gfx.device_lost,
+            || futures::executor::block_on(old_gfx.recreate_device()),
+            |new_gfx| {
+                *gfx = new_gfx;
+                send_event(GfxDeviceRecreated);
+            },
+        );
+
+        if let Err(err) = res {
+            // We'll try again next tick.
+            tracing::error!("Failed to recreate graphics device: {err:?}");
+        }
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn sys_recreate_gpu_resources(
+    mut rand: RandomAccess<(
+        &mut AssetManager,
+        &CameraManager,
+        &GfxContext,
+        &mut GlobalRenderer,
+        &mut ChunkVoxelMesh,
+        &ChunkVoxelData,
+        &mut Viewport,
+        &ViewportManager,
+        &mut ViewportRenderer,
+        &mut WorldVoxelMesh,
+    )>,
+    engine_root: Res<EngineRoot>,
+    mut events: EventReader<GfxDeviceRecreated>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    rand.provide(|| {
+        let engine_root = engine_root.0;
+
+        // Cached pipelines and layouts belong to the old device.
+        engine_root.get::<AssetManager>().clear();
+
+        engine_root
+            .get::<GlobalRenderer>()
+            .recreate_gpu_resources(engine_root);
+
+        engine_root
+            .get::<WorldVoxelMesh>()
+            .invalidate_gpu_resources();
+
+        for &(mut viewport) in engine_root.get::<ViewportManager>().window_map().values() {
+            viewport.mark_config_dirty();
+            *viewport.obj::<ViewportRenderer>() = ViewportRenderer::new(engine_root);
+        }
+    });
+}
+
 #[allow(clippy::type_complexity)]
 fn init_engine_root(
     _cx: PhantomData<(
@@ -227,7 +335,7 @@ fn init_engine_root(
         RenderCx,
     )>,
     event_loop: &ActiveEventLoop,
-) -> anyhow::Result<Entity> {
+) -> anyhow::Result<(Entity, Obj<Viewport>)> {
     let engine_root = spawn_entity(());
 
     // Create main window
@@ -239,8 +347,13 @@ fn init_engine_root(
         )?,
     );
 
-    // Create asset manager
-    engine_root.insert(AssetManager::default());
+    // Create asset manager. Debug builds watch asset sources so that edited shaders are relinked
+    // without restarting.
+    let mut assets = AssetManager::default();
+    if cfg!(debug_assertions) {
+        assets.watch(Duration::from_millis(100));
+    }
+    engine_root.insert(assets);
 
     // Create camera manager
     engine_root.insert(CameraManager::default());
@@ -249,19 +362,31 @@ fn init_engine_root(
     let registry = engine_root.insert(BlockMaterialRegistry::default());
     engine_root.insert(AabbStore::default());
     engine_root.insert(WorldVoxelData::default());
-    engine_root.insert(WorldVoxelMesh::new(registry));
+
+    // Chunks which haven't changed since an earlier run can reuse the meshes it cached. The cache
+    // is opt-in and keeps a directory per world.
+    let mut voxel_mesh = WorldVoxelMesh::new(registry);
+    if let Some(cache_dir) = std::env::var_os(MESH_CACHE_DIR_VAR) {
+        voxel_mesh.set_disk_cache(PathBuf::from(cache_dir).join(game::WORLD_NAME));
+    }
+    engine_root.insert(voxel_mesh);
+
     engine_root.insert(WorldCollisions::new(engine_root));
 
     // Create graphics singleton
-    let (gfx, gfx_surface, _feat_table) =
-        futures::executor::block_on(GfxContext::new(main_window.clone(), feat_requires_screen))?;
+    let (gfx, gfx_surface, _feat_table) = futures::executor::block_on(GfxContext::new(
+        main_window.clone(),
+        &AdapterSelector::default(),
+        feat_requires_screen,
+    ))?;
     let gfx = engine_root.insert(gfx);
 
     // Register main window viewport
     let viewports = engine_root.insert(ViewportManager::default());
 
     let mut gfx_surface_config = gfx_surface.get_default_config(&gfx.adapter, 0, 0).unwrap();
-    gfx_surface_config.format = wgpu::TextureFormat::Bgra8Unorm;
+    // Shaders output linear colors; let the surface encode them into sRGB.
+    gfx_surface_config.format = wgpu::TextureFormat::Bgra8UnormSrgb;
 
     let main_viewport = spawn_entity(());
     let main_viewport_vp = main_viewport.insert(Viewport::new(
@@ -278,13 +403,7 @@ fn init_engine_root(
     // Create input manager
     let _input_mgr = engine_root.insert(InputManager::default());
 
-    // Allow game to initialize itself
-    world_mut().use_random(|cx| crate::game::init_engine_root(cx, engine_root));
-
-    // Make main viewport visible
-    main_viewport_vp.window().set_visible(true);
-
-    Ok(engine_root)
+    Ok((engine_root, main_viewport_vp))
 }
 
 #[allow(clippy::type_complexity)]
@@ -308,14 +427,26 @@ fn render_app(
 ) {
     let vmgr = engine_root.get::<ViewportManager>();
     let gfx = (*engine_root.get::<GfxContext>()).clone();
+
+    // Don't render anything until the update loop has had a chance to recreate the device.
+    if gfx.device_lost.is_lost() {
+        return;
+    }
+
     let mut global_renderer = engine_root.get::<GlobalRenderer>();
 
     let Some(mut viewport) = vmgr.get_viewport(window_id) else {
         return;
     };
 
-    let Ok(Some(texture)) = viewport.get_current_texture(&gfx) else {
-        return;
+    let texture = match viewport.get_current_texture(&gfx) {
+        Ok(FrameAcquire::Frame(texture)) => texture,
+        Ok(FrameAcquire::Reconfigured) => {
+            // Try again as soon as possible with the freshly configured surface.
+            viewport.window().request_redraw();
+            return;
+        }
+        Ok(FrameAcquire::Skip) | Err(_) => return,
     };
 
     let texture_view = texture
@@ -2,6 +2,8 @@ use bevy_autoken::{random_component, Obj};
 use crucible_math::{Angle3D, Angle3DExt};
 use typed_glam::glam::{Mat4, Vec2, Vec3};
 
+use super::Frustum;
+
 // === Math === //
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -117,6 +119,7 @@ pub struct CameraSnapshot {
     pub state: CameraViewState,
     pub settings: CameraSettings,
     pub xforms: CameraTransforms,
+    frustum: Frustum,
 }
 
 impl Default for CameraSnapshot {
@@ -127,11 +130,15 @@ impl Default for CameraSnapshot {
 
 impl CameraSnapshot {
     pub fn new(state: CameraViewState, settings: CameraSettings, aspect: f32) -> Self {
+        let xforms = CameraTransforms::new(state, settings, aspect);
+        let frustum = Frustum::from_camera_xform(xforms.camera);
+
         Self {
             aspect,
             state,
             settings,
-            xforms: CameraTransforms::new(state, settings, aspect),
+            xforms,
+            frustum,
         }
     }
 
@@ -166,6 +173,10 @@ impl CameraSnapshot {
     pub fn i_camera_xform(&self) -> Mat4 {
         self.xforms.i_camera
     }
+
+    pub fn frustum(&self) -> Frustum {
+        self.frustum
+    }
 }
 
 // === Manager === //
@@ -207,3 +218,31 @@ impl VirtualCamera {
         CameraSnapshot::new(self.state, self.settings, aspect)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_frustum_culls_boxes() {
+        let facing = Angle3D::from_deg(Vec2::new(30., -20.));
+        let pos = Vec3::new(4., 2., -1.);
+        let snapshot = CameraSnapshot::new(
+            CameraViewState::new(pos, facing),
+            CameraSettings::new_persp_deg(90., 0.1, 100.),
+            16. / 9.,
+        );
+        let frustum = snapshot.frustum();
+
+        let is_visible = |center: Vec3| frustum.intersects_aabb(center - 0.5, center + 0.5);
+
+        assert!(is_visible(pos + facing.forward() * 10.));
+        assert!(!is_visible(pos - facing.forward() * 10.));
+        assert!(!is_visible(pos + facing.forward() * 200.));
+
+        // Boxes straddling a plane are kept.
+        assert!(
+            frustum.intersects_aabb(pos - facing.forward() * 10., pos + facing.forward() * 10.,)
+        );
+    }
+}
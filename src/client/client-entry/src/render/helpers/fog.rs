@@ -0,0 +1,58 @@
+use typed_glam::glam::Vec3;
+
+// === FogSettings === //
+
+/// Distance fog applied to world geometry to hide where chunks stop being rendered.
+///
+/// Each fragment's color is blended towards `color` by:
+///
+/// ```text
+/// linear = clamp((distance - start) / (end - start), 0, 1)
+/// fog    = linear * exp(-height_falloff * max(height, 0))
+/// out    = mix(albedo, color, fog)
+/// ```
+///
+/// ...where `distance` is the fragment's view-space distance to the camera and `height` is its
+/// world-space height. A `height_falloff` of zero yields uniform fog at all altitudes.
+///
+/// The skybox, being infinitely far away, is instead faded into `color` as its latitude approaches
+/// the horizon, reaching the unmodified sky at `horizon` radians above it.
+#[derive(Debug, Copy, Clone)]
+pub struct FogSettings {
+    pub color: Vec3,
+    pub start: f32,
+    pub end: f32,
+    pub height_falloff: f32,
+    pub horizon: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self::for_view_radius(100.)
+    }
+}
+
+impl FogSettings {
+    /// A pale blue given in linear space. This is roughly `(0.62, 0.72, 0.82)` in sRGB.
+    pub const DEFAULT_COLOR: Vec3 = Vec3::new(0.342, 0.479, 0.637);
+
+    /// Creates fog which fully obscures geometry by `view_radius` so that chunks fade out before
+    /// they're culled.
+    pub fn for_view_radius(view_radius: f32) -> Self {
+        Self {
+            color: Self::DEFAULT_COLOR,
+            start: view_radius * 0.6,
+            end: view_radius,
+            height_falloff: 0.,
+            horizon: 0.15,
+        }
+    }
+
+    pub fn with_view_radius(self, view_radius: f32) -> Self {
+        Self {
+            start: self.start / self.end * view_radius,
+            end: view_radius,
+            ..self
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{cmp::Reverse, collections::BinaryHeap, fmt, hash::Hash};
+
+use crucible_utils::hash::{FxHashMap, FxHashSet};
+
+// === FrameGraph === //
+
+/// A list of render passes which is ordered by the resources each pass reads and writes rather
+/// than by the order in which they were added.
+///
+/// Every writer of a resource runs in the order it was added and the readers of a resource see it
+/// once every pass writing to it has run. Passes which don't contribute to an
+/// [output](Self::add_output) are culled.
+#[derive(Debug)]
+pub struct FrameGraph<R, P> {
+    passes: Vec<PassNode<R, P>>,
+    outputs: FxHashSet<R>,
+}
+
+#[derive(Debug)]
+struct PassNode<R, P> {
+    payload: P,
+    reads: Vec<R>,
+    writes: Vec<R>,
+}
+
+impl<R, P> Default for FrameGraph<R, P> {
+    fn default() -> Self {
+        Self {
+            passes: Vec::new(),
+            outputs: FxHashSet::default(),
+        }
+    }
+}
+
+impl<R: fmt::Debug + Copy + Hash + Eq, P> FrameGraph<R, P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pass which samples the `reads` resources and renders into the `writes` attachments.
+    /// Passes which read an attachment they also write load its previous contents instead of
+    /// clearing it.
+    pub fn add_pass(
+        &mut self,
+        payload: P,
+        reads: impl IntoIterator<Item = R>,
+        writes: impl IntoIterator<Item = R>,
+    ) {
+        self.passes.push(PassNode {
+            payload,
+            reads: reads.into_iter().collect(),
+            writes: writes.into_iter().collect(),
+        });
+    }
+
+    /// Marks a resource as being consumed outside of the graph, keeping the passes writing to it
+    /// alive.
+    pub fn add_output(&mut self, resource: R) {
+        self.outputs.insert(resource);
+    }
+
+    /// Determines the order in which the passes contributing to an output should run and the
+    /// operations they should use on the attachments they write.
+    pub fn compile(self) -> Vec<ScheduledPass<R, P>> {
+        // Determine each pass' dependencies. Each writer depends on the previous writer of the
+        // resource and each reader depends on its last writer.
+        let mut last_writers = FxHashMap::<R, usize>::default();
+        let mut deps = (0..self.passes.len())
+            .map(|_| Vec::<(usize, R)>::new())
+            .collect::<Vec<_>>();
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            for &resource in &pass.writes {
+                if let Some(prev) = last_writers.insert(resource, i) {
+                    deps[i].push((prev, resource));
+                }
+            }
+        }
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            for &resource in &pass.reads {
+                if pass.writes.contains(&resource) {
+                    continue;
+                }
+
+                if let Some(&writer) = last_writers.get(&resource) {
+                    deps[i].push((writer, resource));
+                }
+            }
+        }
+
+        // Cull passes which don't contribute to an output.
+        let mut live = vec![false; self.passes.len()];
+        let mut stack = self
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(_, pass)| pass.writes.iter().any(|res| self.outputs.contains(res)))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        while let Some(i) = stack.pop() {
+            if !live[i] {
+                live[i] = true;
+                stack.extend(deps[i].iter().map(|&(dep, _)| dep));
+            }
+        }
+
+        // Order the live passes, preferring the order in which they were added.
+        let mut dependents = vec![Vec::new(); self.passes.len()];
+        let mut remaining = vec![0; self.passes.len()];
+        let mut consumed = FxHashSet::default();
+
+        for (i, deps) in deps.iter().enumerate().filter(|&(i, _)| live[i]) {
+            for &(dep, resource) in deps {
+                dependents[dep].push(i);
+                remaining[i] += 1;
+                consumed.insert((dep, resource));
+            }
+        }
+
+        let mut ready = (0..self.passes.len())
+            .filter(|&i| live[i] && remaining[i] == 0)
+            .map(Reverse)
+            .collect::<BinaryHeap<_>>();
+
+        let mut order = Vec::new();
+
+        while let Some(Reverse(i)) = ready.pop() {
+            order.push(i);
+
+            for &dependent in &dependents[i] {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    ready.push(Reverse(dependent));
+                }
+            }
+        }
+
+        assert_eq!(
+            order.len(),
+            live.iter().filter(|&&live| live).count(),
+            "frame graph contains a dependency cycle",
+        );
+
+        // Determine attachment operations.
+        let mut passes = self.passes.into_iter().map(Some).collect::<Vec<_>>();
+
+        order
+            .into_iter()
+            .map(|i| {
+                let pass = passes[i].take().unwrap();
+                let attachments = pass
+                    .writes
+                    .iter()
+                    .map(|&resource| {
+                        let ops = AttachmentOps {
+                            clear: !pass.reads.contains(&resource)
+                                && !deps[i].iter().any(|&(_, dep_res)| dep_res == resource),
+                            store: self.outputs.contains(&resource)
+                                || consumed.contains(&(i, resource)),
+                        };
+                        (resource, ops)
+                    })
+                    .collect();
+
+                ScheduledPass {
+                    payload: pass.payload,
+                    attachments,
+                }
+            })
+            .collect()
+    }
+}
+
+// === ScheduledPass === //
+
+#[derive(Debug)]
+pub struct ScheduledPass<R, P> {
+    pub payload: P,
+    attachments: FxHashMap<R, AttachmentOps>,
+}
+
+impl<R: fmt::Debug + Copy + Hash + Eq, P> ScheduledPass<R, P> {
+    /// Fetches the operations this pass should use on an attachment it declared as written.
+    pub fn ops(&self, resource: R) -> AttachmentOps {
+        *self
+            .attachments
+            .get(&resource)
+            .unwrap_or_else(|| panic!("pass does not write to {resource:?}"))
+    }
+}
+
+/// The operations a pass should perform on one of its attachments.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct AttachmentOps {
+    /// Whether the attachment should be cleared rather than loaded. Attachments are cleared when
+    /// no earlier pass wrote to them.
+    pub clear: bool,
+
+    /// Whether the attachment should be stored rather than discarded. Attachments are stored when
+    /// a later pass or the graph's outputs consume them.
+    pub store: bool,
+}
+
+impl AttachmentOps {
+    pub fn operations<V>(self, clear_value: V) -> wgpu::Operations<V> {
+        wgpu::Operations {
+            load: if self.clear {
+                wgpu::LoadOp::Clear(clear_value)
+            } else {
+                wgpu::LoadOp::Load
+            },
+            store: if self.store {
+                wgpu::StoreOp::Store
+            } else {
+                wgpu::StoreOp::Discard
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_and_culls_passes() {
+        let mut graph = FrameGraph::new();
+
+        // Passes are added out of order to ensure that the graph sorts them.
+        graph.add_pass("lighting", ["gbuffer", "shadows"], ["frame"]);
+        graph.add_pass("debug overlay", [], ["debug"]);
+        graph.add_pass("gbuffer", [], ["gbuffer", "depth"]);
+        graph.add_pass("shadows", [], ["shadows"]);
+        graph.add_pass("particles", [], ["frame"]);
+        graph.add_output("frame");
+
+        let passes = graph.compile();
+        let order = passes.iter().map(|pass| pass.payload).collect::<Vec<_>>();
+        assert_eq!(order, ["gbuffer", "shadows", "lighting", "particles"]);
+
+        let ops = |pass: usize, resource| passes[pass].ops(resource);
+        let clear_store = AttachmentOps {
+            clear: true,
+            store: true,
+        };
+
+        assert_eq!(ops(0, "gbuffer"), clear_store);
+        assert_eq!(
+            ops(0, "depth"),
+            AttachmentOps {
+                clear: true,
+                store: false
+            }
+        );
+        assert_eq!(ops(1, "shadows"), clear_store);
+        assert_eq!(ops(2, "frame"), clear_store);
+        assert_eq!(
+            ops(3, "frame"),
+            AttachmentOps {
+                clear: false,
+                store: true
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "dependency cycle")]
+    fn rejects_cycles() {
+        let mut graph = FrameGraph::new();
+        graph.add_pass("a", ["y"], ["x"]);
+        graph.add_pass("b", ["x"], ["y"]);
+        graph.add_output("x");
+        graph.compile();
+    }
+}
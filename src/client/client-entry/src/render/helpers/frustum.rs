@@ -0,0 +1,40 @@
+use typed_glam::glam::{Mat4, Vec3, Vec4};
+
+// === Frustum === //
+
+/// The six planes bounding a camera's view volume. Each plane is stored as `(normal, distance)`
+/// with its normal pointing into the volume so that a point is inside if it is on the positive
+/// side of every plane.
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a combined `proj * view` matrix whose clip-space depth ranges from
+    /// `0` to `1`, as is the case for wgpu.
+    pub fn from_camera_xform(xform: Mat4) -> Self {
+        let [r0, r1, r2, r3] = [xform.row(0), xform.row(1), xform.row(2), xform.row(3)];
+
+        Self {
+            planes: [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| {
+                let len = plane.truncate().length();
+                if len > 0. {
+                    plane / len
+                } else {
+                    plane
+                }
+            }),
+        }
+    }
+
+    /// Conservatively tests whether the axis-aligned box between `min` and `max` overlaps the
+    /// frustum. Boxes near the frustum's corners may be reported as visible when they aren't.
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let farthest = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(farthest) + plane.w >= 0.
+        })
+    }
+}
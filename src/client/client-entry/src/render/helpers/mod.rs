@@ -1,2 +1,14 @@
 mod camera;
 pub use camera::*;
+
+mod fog;
+pub use fog::*;
+
+mod frame_graph;
+pub use frame_graph::*;
+
+mod frustum;
+pub use frustum::*;
+
+mod ssao;
+pub use ssao::*;
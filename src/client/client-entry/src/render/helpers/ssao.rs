@@ -0,0 +1,145 @@
+use typed_glam::glam::{UVec2, Vec3, Vec4};
+
+// === SsaoSettings === //
+
+/// How much effort is spent on screen-space ambient occlusion. Anything above [`Off`](Self::Off)
+/// costs a G-buffer prepass, the occlusion pass itself, and a blur pass every frame.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Default)]
+pub enum SsaoQuality {
+    #[default]
+    Off,
+    Low,
+    High,
+}
+
+impl SsaoQuality {
+    pub fn is_enabled(self) -> bool {
+        self != Self::Off
+    }
+
+    /// The default number of hemisphere samples taken for each pixel at this quality.
+    pub fn kernel_size(self) -> u32 {
+        match self {
+            Self::Off => 0,
+            Self::Low => 8,
+            Self::High => 32,
+        }
+    }
+
+    /// The default fraction of the viewport's resolution at which occlusion is computed.
+    pub fn resolution_scale(self) -> f32 {
+        match self {
+            Self::Off => 0.,
+            Self::Low => 0.5,
+            Self::High => 1.,
+        }
+    }
+}
+
+/// Screen-space ambient occlusion darkens the ambient term of voxel lighting in creases and
+/// corners.
+///
+/// Each pixel takes `kernel_size` samples in the hemisphere around its normal, out to `radius`
+/// world units. A sample is occluded if the scene depth in front of it is closer to the camera by
+/// more than `bias`, which keeps flat surfaces from shadowing themselves.
+#[derive(Debug, Copy, Clone)]
+pub struct SsaoSettings {
+    pub quality: SsaoQuality,
+    pub kernel_size: u32,
+    pub radius: f32,
+    pub bias: f32,
+    pub resolution_scale: f32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self::for_quality(SsaoQuality::default())
+    }
+}
+
+impl SsaoSettings {
+    pub fn for_quality(quality: SsaoQuality) -> Self {
+        Self {
+            quality,
+            kernel_size: quality.kernel_size(),
+            radius: 0.75,
+            bias: 0.025,
+            resolution_scale: quality.resolution_scale(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.quality.is_enabled() && self.kernel_size > 0
+    }
+
+    /// The size of the occlusion target for a viewport of size `viewport_size`, or `None` if SSAO
+    /// is disabled.
+    pub fn target_size(&self, viewport_size: UVec2) -> Option<UVec2> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let size = (viewport_size.as_vec2() * self.resolution_scale)
+            .round()
+            .as_uvec2();
+        Some(size.max(UVec2::ONE))
+    }
+
+    /// Generates the hemisphere sample kernel, oriented along `+Z`. Samples are deterministic and
+    /// are packed more densely towards the origin since nearby occluders matter the most.
+    pub fn kernel(&self) -> Vec<Vec4> {
+        let mut rng = fastrand::Rng::with_seed(0x55a0);
+        let size = self.kernel_size.max(1);
+
+        (0..size)
+            .map(|i| {
+                let dir = loop {
+                    let dir = Vec3::new(
+                        rng.f32() * 2. - 1.,
+                        rng.f32() * 2. - 1.,
+                        // Keep samples away from the tangent plane so that they don't alias with
+                        // the surface they're testing.
+                        rng.f32() * 0.9 + 0.1,
+                    );
+
+                    if dir.length_squared() <= 1. {
+                        break dir.normalize();
+                    }
+                };
+
+                let t = (i + 1) as f32 / size as f32;
+                let scale = 0.1 + 0.9 * t * t;
+
+                (dir * scale).extend(0.)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_lies_in_the_unit_hemisphere() {
+        let settings = SsaoSettings::for_quality(SsaoQuality::High);
+        let kernel = settings.kernel();
+
+        assert_eq!(kernel.len(), 32);
+        assert_eq!(kernel, settings.kernel());
+
+        for sample in &kernel {
+            assert!(sample.z > 0.);
+            assert!(sample.truncate().length() <= 1.);
+        }
+
+        assert_eq!(
+            SsaoSettings::default().target_size(UVec2::new(640, 480)),
+            None
+        );
+        assert_eq!(
+            SsaoSettings::for_quality(SsaoQuality::Low).target_size(UVec2::new(641, 480)),
+            Some(UVec2::new(321, 240)),
+        );
+    }
+}
@@ -1,19 +1,34 @@
-use std::{sync::Mutex, time::Duration};
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use bevy_autoken::{random_component, Obj, RandomEntityExt};
+use bevy_autoken::{non_send_random_component, random_component, Obj, RandomEntityExt};
 use bevy_ecs::entity::Entity;
 use crucible_assets::AssetManager;
-use crucible_math::{Angle3D, Angle3DExt};
-use image::Rgba32FImage;
+use crucible_math::{Angle3D, Angle3DExt, SrgbColor};
+use image::{Rgba32FImage, RgbaImage};
 use main_loop::{GfxContext, Viewport};
 use typed_glam::glam::{UVec2, Vec2, Vec3, Vec4};
 use wgpu::util::DeviceExt;
-use wgpu_ext::{AtlasTexture, AtlasTextureGfx, DynamicBuffer, FullScreenTexture, MultiPassDriver};
+use wgpu_ext::{
+    embedded_asset, AtlasHandle, AtlasTextureGfx, DynamicBuffer, EmbeddedAsset, EmbeddedDecoder,
+    FullScreenTexture, MultiPassDriver, PairedAtlasTexture, TextureArray,
+};
 
 use self::{
-    helpers::{CameraManager, CameraSettings, CameraSnapshot, CameraViewState},
+    helpers::{
+        CameraManager, CameraSettings, CameraSnapshot, CameraViewState, FogSettings, FrameGraph,
+        SsaoSettings, VirtualCamera,
+    },
     pipelines::{
+        library::ShaderLibrary,
         skybox::{load_skybox_pipeline, SkyboxUniforms},
+        ssao::{
+            load_ssao_blur_pipeline, load_ssao_pipeline, load_voxel_gbuffer_pipeline, SsaoTargets,
+            OCCLUSION_FORMAT,
+        },
         voxel::{load_voxel_csm_pipeline, load_voxel_opaque_pipeline, VoxelUniforms},
     },
     voxel::WorldVoxelMesh,
@@ -27,8 +42,37 @@ pub mod voxel;
 
 const MESH_TIME_LIMIT: Option<Duration> = Some(Duration::from_millis(10));
 
+/// The maximum number of nested [`GlobalRenderer::render_to_texture`] calls. This bounds the work
+/// done by portals and monitors which can see one another, or themselves.
+const MAX_VIEW_DEPTH: usize = 4;
+
+/// The size of each layer in the block texture array. This matches the atlas' tile size.
+const BLOCK_LAYER_SIZE: UVec2 = UVec2::splat(16);
+
+/// The number of layers in the block texture array.
+const BLOCK_LAYER_COUNT: u32 = 16;
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 pub type RenderCx = (&'static mut GlobalRenderer, &'static mut ViewportRenderer);
 
+/// The resources shared between the passes of [`GlobalRenderer::render_view`].
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+enum ViewResource {
+    Frame,
+    Depth,
+    ShadowMap,
+    Occlusion,
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+enum ViewPass {
+    Skybox,
+    Csm,
+    Ssao,
+    Voxel,
+}
+
 #[derive(Debug)]
 pub struct GlobalRenderer {
     // Services
@@ -37,33 +81,150 @@ pub struct GlobalRenderer {
     camera: Obj<CameraManager>,
 
     // Atlas
-    atlas: AtlasTexture,
+    atlas: PairedAtlasTexture,
     atlas_gfx: AtlasTextureGfx,
+    normal_atlas_gfx: AtlasTextureGfx,
     is_atlas_dirty: bool,
 
+    // Block texture array
+    layer_images: Vec<Rgba32FImage>,
+    layer_texture: TextureArray,
+    uploaded_layers: usize,
+
     // CSM textures
     csm: wgpu::Texture,
     csm_view: wgpu::TextureView,
 
+    // Fog
+    fog: FogSettings,
+
+    // Ambient occlusion
+    ssao: SsaoSettings,
+    /// Bound in place of the occlusion target for views which don't render SSAO.
+    no_occlusion_view: wgpu::TextureView,
+
+    // Skybox
+    skybox_panorama: wgpu::TextureView,
+
+    // Shaders
+    shaders: ShaderLibrary,
+
     // Rendering subsystems
-    skybox: SkyboxUniforms,
     voxel: Obj<WorldVoxelMesh>,
-    voxel_uniforms: VoxelUniforms,
     voxel_dynamics: Mutex<DynamicBuffer>,
+
+    // Views
+    /// Per-view uniforms. The first entry belongs to the main view and the remainder are handed out
+    /// to [`render_to_texture`](Self::render_to_texture) calls. Each view needs its own buffers
+    /// because every buffer write in a frame lands before any of its commands execute.
+    views: Vec<ViewResources>,
+    /// The number of offscreen views rendered so far this frame.
+    offscreen_views: usize,
+    /// The number of [`render_to_texture`](Self::render_to_texture) calls on the stack.
+    view_depth: usize,
+
+    // Monitors
+    monitors: Vec<Monitor>,
+    /// The render target shared by every monitor. Each view is copied into its monitor's layer as
+    /// soon as it's rendered so that the next view can reuse it.
+    monitor_target: Arc<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+/// A view rendered into a layer of the block texture array every frame.
+#[derive(Debug, Copy, Clone)]
+struct Monitor {
+    camera: Obj<VirtualCamera>,
+    layer: u32,
+}
+
+/// Decodes the skybox's panorama into the 8-bit RGBA texels it's uploaded as.
+struct PanoramaDecoder;
+
+impl EmbeddedDecoder for PanoramaDecoder {
+    type Output = RgbaImage;
+
+    fn decode(asset: EmbeddedAsset) -> Self::Output {
+        asset.decode_image().into_rgba8()
+    }
+}
+
+#[derive(Debug)]
+struct ViewResources {
+    skybox: SkyboxUniforms,
+    voxel: VoxelUniforms,
+    depth: Option<(UVec2, wgpu::TextureView)>,
+    ssao: Option<SsaoTargets>,
 }
 
-random_component!(GlobalRenderer);
+// The shader library's linker session isn't thread-safe.
+non_send_random_component!(GlobalRenderer);
 
 impl GlobalRenderer {
     pub fn new(engine_root: Entity) -> Self {
+        let atlas = PairedAtlasTexture::new(UVec2::splat(16), UVec2::splat(32), 4);
+        Self::new_with_atlas(engine_root, atlas, Vec::new(), false)
+    }
+
+    /// Recreates every GPU resource owned by the renderer against the engine root's current
+    /// [`GfxContext`]. The CPU-side atlas and texture array layers are kept and re-uploaded on the
+    /// next frame.
+    pub fn recreate_gpu_resources(&mut self, engine_root: Entity) {
+        let atlas = mem::replace(
+            &mut self.atlas,
+            PairedAtlasTexture::new(UVec2::ONE, UVec2::ONE, 1),
+        );
+        let fog = self.fog;
+        let layer_images = mem::take(&mut self.layer_images);
+        let ssao = self.ssao;
+        let shaders = mem::take(&mut self.shaders);
+        let monitors = mem::take(&mut self.monitors);
+        *self = Self::new_with_atlas(engine_root, atlas, layer_images, true);
+        self.fog = fog;
+        self.ssao = ssao;
+        self.shaders = shaders;
+        self.monitors = monitors;
+    }
+
+    fn new_with_atlas(
+        engine_root: Entity,
+        atlas: PairedAtlasTexture,
+        layer_images: Vec<Rgba32FImage>,
+        is_atlas_dirty: bool,
+    ) -> Self {
         // Fetch services
         let assets = engine_root.get::<AssetManager>();
         let gfx = (*engine_root.get::<GfxContext>()).clone();
         let camera = engine_root.get::<CameraManager>();
 
         // Generate atlas textures
-        let atlas = AtlasTexture::new(UVec2::splat(16), UVec2::splat(32), 4);
-        let atlas_gfx = AtlasTextureGfx::new(&gfx, &atlas, Some("voxel texture atlas"));
+        let atlas_gfx = AtlasTextureGfx::new(&gfx, atlas.albedo(), Some("voxel texture atlas"));
+        let normal_atlas_gfx =
+            AtlasTextureGfx::new(&gfx, atlas.normal(), Some("voxel normal map atlas"));
+
+        // Generate the block texture array. Its layers are uploaded on the next frame.
+        let layer_texture = TextureArray::new(
+            &gfx.device,
+            Some("voxel texture array"),
+            BLOCK_LAYER_SIZE,
+            BLOCK_LAYER_COUNT,
+        );
+
+        let monitor_target = gfx.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("monitor render target"),
+            size: wgpu::Extent3d {
+                width: BLOCK_LAYER_SIZE.x,
+                height: BLOCK_LAYER_SIZE.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: layer_texture.texture.format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let monitor_target_view =
+            monitor_target.create_view(&wgpu::TextureViewDescriptor::default());
 
         // Create CSM textures
         let csm = gfx.device.create_texture(&wgpu::TextureDescriptor {
@@ -82,10 +243,33 @@ impl GlobalRenderer {
         });
         let csm_view = csm.create_view(&wgpu::TextureViewDescriptor::default());
 
+        // Create a fully unoccluded texel for when SSAO is disabled
+        let no_occlusion_view = gfx
+            .device
+            .create_texture_with_data(
+                &gfx.queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("SSAO fallback texture"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: OCCLUSION_FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                &[u8::MAX],
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
         // load skybox subsystem
-        let skybox = image::load_from_memory(include_bytes!("../game/res/default_skybox.png"))
-            .unwrap()
-            .into_rgba8();
+        let skybox =
+            embedded_asset!("../game/res/default_skybox.png").load::<PanoramaDecoder>(&assets);
 
         let skybox = gfx.device.create_texture_with_data(
             &gfx.queue,
@@ -99,21 +283,30 @@ impl GlobalRenderer {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
+                // The panorama is sRGB-encoded; have the sampler decode it into linear space.
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             },
             wgpu::util::TextureDataOrder::LayerMajor,
             &skybox,
         );
-        let skybox = skybox.create_view(&wgpu::TextureViewDescriptor::default());
-        let skybox = SkyboxUniforms::new(&assets, &gfx, &skybox);
+        let skybox_panorama = skybox.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Load shaders, relinking them as they're edited if the asset manager is watching for
+        // changes.
+        let mut shaders = ShaderLibrary::default();
+        if assets.is_watching() {
+            let root = concat!(env!("CARGO_MANIFEST_DIR"), "/src/render/shaders");
+            if let Err(err) = shaders.watch(&assets, root) {
+                tracing::warn!("failed to watch shader sources in {root:?}: {err}");
+            }
+        }
 
         // Load voxel subsystem
         let voxel = engine_root.get::<WorldVoxelMesh>();
-        let voxel_uniforms = VoxelUniforms::new(&assets, &gfx, &atlas_gfx.view, &csm_view);
 
-        Self {
+        let mut renderer = Self {
             // Services
             assets,
             gfx,
@@ -123,25 +316,172 @@ impl GlobalRenderer {
             csm,
             csm_view,
 
+            // Fog
+            fog: FogSettings::default(),
+
+            // Ambient occlusion
+            ssao: SsaoSettings::default(),
+            no_occlusion_view,
+
             // Atlas
             atlas,
             atlas_gfx,
-            is_atlas_dirty: false,
+            normal_atlas_gfx,
+            is_atlas_dirty,
+
+            // Block texture array
+            layer_images,
+            layer_texture,
+            uploaded_layers: 0,
+
+            // Skybox
+            skybox_panorama,
+
+            // Shaders
+            shaders,
 
             // Rendering subsystems
-            skybox,
             voxel,
-            voxel_uniforms,
             voxel_dynamics: Mutex::new(DynamicBuffer::new(
                 Some("voxel dynamic data buffer"),
                 wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             )),
+
+            // Views
+            views: Vec::new(),
+            offscreen_views: 0,
+            view_depth: 0,
+
+            // Monitors
+            monitors: Vec::new(),
+            monitor_target: Arc::new((monitor_target, monitor_target_view)),
+        };
+
+        // Create the main view's resources
+        renderer.ensure_view(0);
+        renderer
+    }
+
+    fn ensure_view(&mut self, index: usize) {
+        while self.views.len() <= index {
+            self.views.push(ViewResources {
+                skybox: SkyboxUniforms::new(&self.assets, &self.gfx, &self.skybox_panorama),
+                voxel: VoxelUniforms::new(
+                    &self.assets,
+                    &self.gfx,
+                    &self.atlas_gfx.view,
+                    &self.normal_atlas_gfx.view,
+                    &self.layer_texture.view,
+                    &self.csm_view,
+                    &self.no_occlusion_view,
+                ),
+                depth: None,
+                ssao: None,
+            });
+        }
+    }
+
+    /// Recreates the view's SSAO targets if they don't match the current settings or the size of
+    /// the viewport they're being rendered for.
+    fn ensure_ssao_targets(&mut self, index: usize, viewport_size: UVec2) {
+        let view = &mut self.views[index];
+        let size = self.ssao.target_size(viewport_size);
+
+        if view.ssao.as_ref().map(|targets| targets.size()) == size {
+            return;
+        }
+
+        view.ssao = size.map(|size| SsaoTargets::new(&self.assets, &self.gfx, self.ssao, size));
+        view.voxel.set_opaque_textures(
+            &self.assets,
+            &self.gfx,
+            &self.csm_view,
+            view.ssao
+                .as_ref()
+                .map_or(&self.no_occlusion_view, |targets| targets.occlusion_view()),
+        );
+    }
+
+    /// Sets the fog parameters. The fog's end distance is rescaled every frame to match the voxel
+    /// [view distance](WorldVoxelMesh::view_distance) so that chunks are fully fogged before they
+    /// get culled.
+    pub fn set_fog(&mut self, fog: FogSettings) {
+        self.fog = fog;
+    }
+
+    /// Sets the screen-space ambient occlusion parameters. SSAO is skipped entirely while
+    /// [`SsaoSettings::is_enabled`] is `false`.
+    pub fn set_ssao(&mut self, ssao: SsaoSettings) {
+        self.ssao = ssao;
+
+        // Force every view to recreate its targets with the new kernel.
+        for view in &mut self.views {
+            view.ssao = None;
+            view.voxel.set_opaque_textures(
+                &self.assets,
+                &self.gfx,
+                &self.csm_view,
+                &self.no_occlusion_view,
+            );
         }
     }
 
-    pub fn push_to_atlas(&mut self, image: &Rgba32FImage) -> UVec2 {
+    /// Pushes an image whose pixels are already in linear space to the atlas. Its normal map is
+    /// left flat.
+    pub fn push_to_atlas(&mut self, image: &Rgba32FImage) -> AtlasHandle {
+        self.push_to_atlas_with_normal(image, None)
+    }
+
+    /// Pushes an image whose pixels are already in linear space to the atlas alongside its
+    /// tangent-space normal map. Both share the returned handle's UV rect.
+    pub fn push_to_atlas_with_normal(
+        &mut self,
+        image: &Rgba32FImage,
+        normal: Option<&Rgba32FImage>,
+    ) -> AtlasHandle {
         self.is_atlas_dirty = true;
-        self.atlas.add(image)
+        self.atlas.add(image, normal)
+    }
+
+    /// Pushes an sRGB-encoded image to the atlas. This is what images decoded with
+    /// [`into_rgba32f`](image::DynamicImage::into_rgba32f) contain since `image` doesn't apply any
+    /// transfer function when converting to floats.
+    pub fn push_srgb_to_atlas(&mut self, mut image: Rgba32FImage) -> AtlasHandle {
+        for pixel in image.pixels_mut() {
+            pixel.0 = SrgbColor::from_array(pixel.0).to_linear().to_array();
+        }
+
+        self.push_to_atlas(&image)
+    }
+
+    /// Pushes an sRGB-encoded image to a new layer of the block texture array and returns the
+    /// layer's index for use in a [`Layered`](voxel::MaterialVisualDescriptor::Layered) material.
+    /// The image must be as large as the atlas' tiles.
+    pub fn push_srgb_to_layers(&mut self, mut image: Rgba32FImage) -> u32 {
+        for pixel in image.pixels_mut() {
+            pixel.0 = SrgbColor::from_array(pixel.0).to_linear().to_array();
+        }
+
+        self.push_to_layers(image)
+    }
+
+    fn push_to_layers(&mut self, image: Rgba32FImage) -> u32 {
+        assert!(
+            self.layer_images.len() < BLOCK_LAYER_COUNT as usize,
+            "the block texture array only has room for {BLOCK_LAYER_COUNT} layers"
+        );
+
+        self.layer_images.push(image);
+        self.layer_images.len() as u32 - 1
+    }
+
+    /// Adds a monitor which shows what `camera` sees and returns the layer of the block texture
+    /// array it's rendered into for use in a [`Layered`](voxel::MaterialVisualDescriptor::Layered)
+    /// material. Monitors are rendered at the start of every frame.
+    pub fn add_monitor(&mut self, camera: Obj<VirtualCamera>) -> u32 {
+        let layer = self.push_to_layers(Rgba32FImage::new(BLOCK_LAYER_SIZE.x, BLOCK_LAYER_SIZE.y));
+        self.monitors.push(Monitor { camera, layer });
+        layer
     }
 
     pub fn render(
@@ -151,39 +491,179 @@ impl GlobalRenderer {
         viewport_renderer: &mut ViewportRenderer,
         frame: &wgpu::TextureView,
     ) {
-        // Process dirty buffers
-        if self.is_atlas_dirty {
-            self.is_atlas_dirty = false;
-            self.atlas_gfx.update(&self.gfx, &self.atlas);
-        }
-
-        self.voxel.update(&self.gfx, &self.atlas, MESH_TIME_LIMIT);
+        self.update_resources();
 
         // Determine camera settings
         let aspect = viewport.curr_surface_aspect().unwrap_or(1.);
         let camera = self.camera.snapshot(aspect);
 
-        // Load pipelines
-        let skybox = load_skybox_pipeline(&self.assets, &self.gfx, viewport.curr_config().format);
-        let voxel_opaque = load_voxel_opaque_pipeline(
-            &self.assets,
+        if let Some(size) = viewport.curr_surface_size() {
+            self.ensure_ssao_targets(0, size);
+        }
+
+        self.voxel.update(
             &self.gfx,
+            self.atlas.albedo(),
+            camera.pos(),
+            MESH_TIME_LIMIT,
+        );
+
+        // Render the monitors first so that the main view sees this frame's images.
+        self.render_monitors(cmd);
+
+        self.write_voxel_uniforms(0, &camera);
+        self.render_view(
+            cmd,
+            0,
+            &camera,
             viewport.curr_config().format,
-            viewport_renderer.depth.format(),
+            frame,
+            viewport_renderer.depth.acquire_view(&self.gfx, viewport),
         );
-        let voxel_csm = load_voxel_csm_pipeline(&self.assets, &self.gfx, self.csm.format());
 
-        // Prepare passes
-        let voxels_pass = self.voxel.prepare_pass();
-        let multipass = MultiPassDriver::new();
+        // The main view is the last one rendered each frame.
+        self.offscreen_views = 0;
+    }
 
-        // Write uniforms
+    /// Renders the scene as seen by `camera` into `target`, which must be a `viewport_size` texture
+    /// of the given `format` with [`RENDER_ATTACHMENT`](wgpu::TextureUsages::RENDER_ATTACHMENT)
+    /// usage. Give it [`TEXTURE_BINDING`](wgpu::TextureUsages::TEXTURE_BINDING) usage as well to
+    /// sample it from a material in the main view, which should be rendered later in the frame.
+    ///
+    /// `nested` is called before the view is rendered so that it can render the textures which the
+    /// view samples, typically by calling this method again for every portal the view can see.
+    /// Once [`MAX_VIEW_DEPTH`] calls are nested, this returns `false` without calling `nested` or
+    /// rendering anything, which keeps portals which can see one another from recursing forever.
+    pub fn render_to_texture(
+        &mut self,
+        cmd: &mut wgpu::CommandEncoder,
+        camera: &VirtualCamera,
+        target: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        viewport_size: UVec2,
+        nested: impl FnOnce(&mut Self, &mut wgpu::CommandEncoder),
+    ) -> bool {
+        if self.view_depth >= MAX_VIEW_DEPTH {
+            return false;
+        }
+
+        self.view_depth += 1;
+        nested(self, cmd);
+        self.view_depth -= 1;
+
+        self.offscreen_views += 1;
+        let index = self.offscreen_views;
+
+        self.update_resources();
+        self.ensure_view(index);
+        self.ensure_ssao_targets(index, viewport_size);
+
+        // Create a depth texture matching the target
+        let depth = &mut self.views[index].depth;
+        if depth.as_ref().map(|(size, _)| *size) != Some(viewport_size) {
+            let texture = self.gfx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("offscreen depth texture"),
+                size: wgpu::Extent3d {
+                    width: viewport_size.x,
+                    height: viewport_size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+
+            *depth = Some((
+                viewport_size,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ));
+        }
+
+        let aspect = viewport_size.x as f32 / viewport_size.y.max(1) as f32;
+        let camera = camera.snapshot(aspect);
+        self.write_voxel_uniforms(index, &camera);
+        let depth = &self.views[index].depth.as_ref().unwrap().1;
+
+        self.render_view(cmd, index, &camera, format, target, depth);
+        true
+    }
+
+    /// Renders every monitor into its layer of the block texture array. Each monitor's view first
+    /// renders every monitor again since it may see any of them.
+    fn render_monitors(&mut self, cmd: &mut wgpu::CommandEncoder) {
+        for monitor in self.monitors.clone() {
+            let target = self.monitor_target.clone();
+            let camera = (*monitor.camera).clone();
+            let rendered = self.render_to_texture(
+                cmd,
+                &camera,
+                &target.1,
+                target.0.format(),
+                BLOCK_LAYER_SIZE,
+                Self::render_monitors,
+            );
+
+            if !rendered {
+                // This leaves the innermost screens showing the last frame's image.
+                continue;
+            }
+
+            cmd.copy_texture_to_texture(
+                target.0.as_image_copy(),
+                wgpu::ImageCopyTexture {
+                    texture: &self.layer_texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: monitor.layer,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                target.0.size(),
+            );
+        }
+    }
+
+    fn update_resources(&mut self) {
+        // Relink edited shaders. Pipelines built from them are rebuilt the next time they're loaded.
+        self.assets.poll_watched(Instant::now());
+        self.shaders.poll();
+
+        // Process dirty buffers
+        if self.is_atlas_dirty {
+            self.is_atlas_dirty = false;
+            self.atlas_gfx.update(&self.gfx, self.atlas.albedo());
+            self.normal_atlas_gfx.update(&self.gfx, self.atlas.normal());
+        }
+
+        for (index, image) in self
+            .layer_images
+            .iter()
+            .enumerate()
+            .skip(self.uploaded_layers)
+        {
+            self.layer_texture
+                .upload_layer(&self.gfx.queue, index as u32, image);
+        }
+        self.uploaded_layers = self.layer_images.len();
+    }
+
+    /// Writes the view's voxel uniforms. This is kept out of [`render_view`](Self::render_view)
+    /// since the uniforms need mutable access to track which of their fields changed.
+    fn write_voxel_uniforms(&mut self, view_index: usize, camera: &CameraSnapshot) {
         let light_dir = Vec3::new(3., 10., 5.).normalize();
+        let fog = self.fog.with_view_radius(self.voxel.view_distance());
 
-        self.voxel_uniforms.set_camera_matrix(
+        self.views[view_index].voxel.set_camera_matrix(
             &self.gfx,
             // camera_proj
             camera.camera_xform(),
+            // camera_pos
+            camera.pos(),
             // light_proj
             {
                 let pos = camera.state.pos + light_dir * 250.;
@@ -198,93 +678,159 @@ impl GlobalRenderer {
             },
             // light_dir
             -light_dir,
+            // fog
+            &fog,
         );
+    }
 
-        self.skybox.set_camera_matrix(&self.gfx, {
-            // Skybox view projection does not take translation or scale into account. We must compute
-            // the matrix manually.
-            let i_proj = camera.i_proj_xform();
-            let mut i_view = camera.i_view_xform();
-            i_view.w_axis = Vec4::new(0.0, 0.0, 0.0, i_view.w_axis.w);
-            i_view * i_proj
-        });
+    fn render_view(
+        &self,
+        cmd: &mut wgpu::CommandEncoder,
+        view_index: usize,
+        camera: &CameraSnapshot,
+        format: wgpu::TextureFormat,
+        frame: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+    ) {
+        let view = &self.views[view_index];
 
-        // Draw skybox
-        let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("skybox pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: frame,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-        skybox.bind_pipeline(&mut pass);
-        self.skybox.write_pass_state(&mut pass);
-        pass.draw(0..6, 0..1);
-        drop(pass);
-
-        // Update CSM
-        let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("CSM pass"),
-            color_attachments: &[],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.csm_view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-        voxels_pass.render_csm(&voxel_csm, &self.voxel_uniforms, &mut pass);
-        drop(pass);
-
-        // Draw voxels
-        let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("voxel pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: frame,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: viewport_renderer.depth.acquire_view(&self.gfx, viewport),
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
+        // Load pipelines
+        let skybox = load_skybox_pipeline(&self.assets, &self.gfx, &self.shaders, format);
+        let voxel_opaque = load_voxel_opaque_pipeline(
+            &self.assets,
+            &self.gfx,
+            &self.shaders,
+            format,
+            DEPTH_FORMAT,
+        );
+        let voxel_csm =
+            load_voxel_csm_pipeline(&self.assets, &self.gfx, &self.shaders, self.csm.format());
 
-        multipass.drive(
+        // Prepare passes
+        let voxels_pass = { self.voxel }.prepare_pass(camera.pos());
+        let multipass = MultiPassDriver::new();
+
+        // Write uniforms
+        let fog = self.fog.with_view_radius(self.voxel.view_distance());
+
+        view.skybox.set_camera_matrix(
             &self.gfx,
-            &mut pass,
-            &mut self.voxel_dynamics.lock().unwrap(),
-            |pass| {
-                voxels_pass.render_opaque(
-                    &self.assets,
-                    &self.gfx,
-                    &voxel_opaque,
-                    &self.voxel_uniforms,
-                    pass,
-                );
+            {
+                // Skybox view projection does not take translation or scale into account. We must compute
+                // the matrix manually.
+                let i_proj = camera.i_proj_xform();
+                let mut i_view = camera.i_view_xform();
+                i_view.w_axis = Vec4::new(0.0, 0.0, 0.0, i_view.w_axis.w);
+                i_view * i_proj
             },
+            &fog,
+        );
+
+        // Schedule passes
+        let mut graph = FrameGraph::new();
+        graph.add_pass(ViewPass::Skybox, [], [ViewResource::Frame]);
+        graph.add_pass(ViewPass::Csm, [], [ViewResource::ShadowMap]);
+
+        if view.ssao.is_some() {
+            graph.add_pass(ViewPass::Ssao, [], [ViewResource::Occlusion]);
+        }
+
+        graph.add_pass(
+            ViewPass::Voxel,
+            [ViewResource::ShadowMap, ViewResource::Occlusion],
+            [ViewResource::Frame, ViewResource::Depth],
         );
-        drop(pass);
+        graph.add_output(ViewResource::Frame);
+
+        for scheduled in graph.compile() {
+            match scheduled.payload {
+                ViewPass::Skybox => {
+                    let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("skybox pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: frame,
+                            resolve_target: None,
+                            ops: scheduled
+                                .ops(ViewResource::Frame)
+                                .operations(wgpu::Color::BLACK),
+                        })],
+                        depth_stencil_attachment: None,
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    skybox.bind_pipeline(&mut pass);
+                    view.skybox.write_pass_state(&mut pass);
+                    pass.draw(0..6, 0..1);
+                }
+                ViewPass::Csm => {
+                    let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("CSM pass"),
+                        color_attachments: &[],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: &self.csm_view,
+                            depth_ops: Some(scheduled.ops(ViewResource::ShadowMap).operations(1.)),
+                            stencil_ops: None,
+                        }),
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+                    voxels_pass.render_csm(&voxel_csm, &view.voxel, &mut pass);
+                }
+                ViewPass::Ssao => {
+                    // The SSAO targets are private to the pass so it manages their operations
+                    // itself.
+                    let ssao = view.ssao.as_ref().unwrap();
+                    let gbuffer =
+                        load_voxel_gbuffer_pipeline(&self.assets, &self.gfx, &self.shaders);
+                    let occlusion = load_ssao_pipeline(&self.assets, &self.gfx, &self.shaders);
+                    let blur = load_ssao_blur_pipeline(&self.assets, &self.gfx, &self.shaders);
+
+                    ssao.set_camera_matrix(&self.gfx, camera.camera_xform());
+
+                    let mut pass = ssao.begin_gbuffer_pass(cmd);
+                    voxels_pass.render_gbuffer(&gbuffer, &view.voxel, camera.frustum(), &mut pass);
+                    drop(pass);
+
+                    ssao.render_occlusion(cmd, &occlusion, &blur);
+                }
+                ViewPass::Voxel => {
+                    let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("voxel pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: frame,
+                            resolve_target: None,
+                            ops: scheduled
+                                .ops(ViewResource::Frame)
+                                .operations(wgpu::Color::BLACK),
+                        })],
+                        depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                            view: depth,
+                            depth_ops: Some(scheduled.ops(ViewResource::Depth).operations(1.)),
+                            stencil_ops: None,
+                        }),
+                        timestamp_writes: None,
+                        occlusion_query_set: None,
+                    });
+
+                    multipass.drive(
+                        &self.gfx,
+                        &mut pass,
+                        &mut self.voxel_dynamics.lock().unwrap(),
+                        None,
+                        |pass| {
+                            voxels_pass.render_opaque(
+                                &self.assets,
+                                &self.gfx,
+                                &voxel_opaque,
+                                &view.voxel,
+                                camera.frustum(),
+                                pass,
+                            );
+                        },
+                    );
+                }
+            }
+        }
     }
 }
 
@@ -302,10 +848,162 @@ impl ViewportRenderer {
         // Generate depth texture
         let depth = FullScreenTexture::new(
             Some("depth texture"),
-            wgpu::TextureFormat::Depth32Float,
+            DEPTH_FORMAT,
             wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT,
         );
 
         Self { depth }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{spawn_entity, RandomArena, RandomWorldExt as _};
+    use bevy_ecs::world::World;
+    use crucible_world::voxel::BlockMaterialRegistry;
+    use wgpu_ext::read_back_buffer;
+
+    use super::*;
+
+    /// Runs `f` against a renderer with an empty world. This skips `f` on machines without a
+    /// (software) GPU.
+    fn with_renderer(f: impl FnOnce(&GfxContext, &mut GlobalRenderer)) {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+
+        let mut world = World::new();
+        world.init_resource::<RandomArena<AssetManager>>();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<CameraManager>>();
+        world.init_resource::<RandomArena<GfxContext>>();
+        world.init_resource::<RandomArena<VirtualCamera>>();
+        world.init_resource::<RandomArena<WorldVoxelMesh>>();
+        world.init_non_send_resource::<RandomArena<GlobalRenderer>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut AssetManager,
+                &mut BlockMaterialRegistry,
+                &mut CameraManager,
+                &mut GfxContext,
+                &mut VirtualCamera,
+                &mut WorldVoxelMesh,
+                &mut GlobalRenderer,
+            )>| {
+                let engine_root = spawn_entity(());
+                engine_root.insert(AssetManager::default());
+                engine_root.insert(CameraManager::default());
+                let registry = engine_root.insert(BlockMaterialRegistry::default());
+                engine_root.insert(WorldVoxelMesh::new(registry));
+                engine_root.insert(gfx.clone());
+                let mut renderer = engine_root.insert(GlobalRenderer::new(engine_root));
+
+                f(&gfx, &mut renderer);
+            },
+        );
+    }
+
+    #[test]
+    fn monitors_recurse_up_to_the_depth_cap() {
+        with_renderer(|gfx, renderer| {
+            let camera = spawn_entity(()).insert(VirtualCamera::new(
+                CameraViewState::default(),
+                CameraSettings::new_persp_deg(70., 0.1, 100.),
+            ));
+            renderer.add_monitor(camera);
+
+            let mut cmd = gfx
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+            // A monitor which can see itself renders one view per nesting level...
+            renderer.render_monitors(&mut cmd);
+            assert_eq!(renderer.offscreen_views, MAX_VIEW_DEPTH);
+            assert_eq!(renderer.view_depth, 0);
+
+            // ...and the cap limits how deep views nest rather than how many a frame renders.
+            renderer.render_monitors(&mut cmd);
+            assert_eq!(renderer.offscreen_views, 2 * MAX_VIEW_DEPTH);
+            assert_eq!(renderer.views.len(), 2 * MAX_VIEW_DEPTH + 1);
+
+            gfx.queue.submit([cmd.finish()]);
+            gfx.device.poll(wgpu::Maintain::Wait);
+        });
+    }
+
+    #[test]
+    fn monitors_show_what_their_camera_sees() {
+        with_renderer(|gfx, renderer| {
+            // Looking straight down at an empty world only shows the sky below the horizon, which
+            // is entirely faded into the fog color.
+            renderer.set_fog(FogSettings {
+                color: Vec3::X,
+                ..FogSettings::default()
+            });
+
+            let camera = spawn_entity(()).insert(VirtualCamera::new(
+                CameraViewState::new(Vec3::ZERO, Angle3D::from_facing(Vec3::NEG_Y)),
+                CameraSettings::new_persp_deg(70., 0.1, 100.),
+            ));
+            let layer = renderer.add_monitor(camera);
+
+            let mut cmd = gfx
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+            renderer.render_monitors(&mut cmd);
+
+            // 16 texels of 16 bytes each keeps the row at wgpu's required copy alignment.
+            let layer_bytes = (BLOCK_LAYER_SIZE.x * BLOCK_LAYER_SIZE.y * 16) as u64;
+            let staging = gfx.device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: layer_bytes,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+
+            cmd.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: &renderer.layer_texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(BLOCK_LAYER_SIZE.x * 16),
+                        rows_per_image: Some(BLOCK_LAYER_SIZE.y),
+                    },
+                },
+                wgpu::Extent3d {
+                    width: BLOCK_LAYER_SIZE.x,
+                    height: BLOCK_LAYER_SIZE.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+            gfx.queue.submit([cmd.finish()]);
+
+            let read = read_back_buffer(&gfx.device, &gfx.queue, &staging, 0..layer_bytes);
+            gfx.device.poll(wgpu::Maintain::Wait);
+            let bytes = futures::executor::block_on(read).unwrap();
+            let texels = bytemuck::cast_slice::<u8, [f32; 4]>(&bytes);
+
+            let center = BLOCK_LAYER_SIZE / 2;
+            let [r, g, b, _] = texels[(center.y * BLOCK_LAYER_SIZE.x + center.x) as usize];
+            assert!(
+                (r - 1.).abs() < 1e-3 && g.abs() < 1e-3 && b.abs() < 1e-3,
+                "expected the fog color but read {:?}",
+                [r, g, b],
+            );
+        });
+    }
+}
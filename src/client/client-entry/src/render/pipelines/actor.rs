@@ -8,6 +8,8 @@ use typed_wgpu::{
 };
 use wgpu_ext::{BindGroupExt as _, PipelineLayoutExt as _};
 
+use super::library::ShaderLibrary;
+
 // === Uniforms === //
 
 #[derive(Debug)]
@@ -51,7 +53,7 @@ impl ActorVertex {
         VertexBufferLayout::builder()
             .with_attribute(Std430VertexFormat::Float32x3) // pos
             .with_attribute(Std430VertexFormat::Float32x3) // color
-            .finish(wgpu::VertexStepMode::Vertex)
+            .finish_vertex()
     }
 }
 
@@ -75,7 +77,7 @@ impl ActorInstance {
             .with_attribute(Std430VertexFormat::Float32x3) // affine_y
             .with_attribute(Std430VertexFormat::Float32x3) // affine_z
             .with_attribute(Std430VertexFormat::Float32x3) // translation
-            .finish(wgpu::VertexStepMode::Instance)
+            .finish_instance()
     }
 }
 
@@ -87,14 +89,19 @@ pub type OpaqueActorPipeline =
 pub fn load_opaque_actor_pipeline(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
     surface_format: wgpu::TextureFormat,
     depth_format: wgpu::TextureFormat,
 ) -> Asset<OpaqueActorPipeline> {
     assets.load(
-        gfx,
-        (&surface_format, &depth_format),
-        |assets, gfx, (&surface_format, &depth_format)| {
-            let shader = &*load_opaque_actor_shader(assets, gfx);
+        (gfx, shaders),
+        (
+            &surface_format,
+            &depth_format,
+            &shaders.generation("actor_opaque.wgsl"),
+        ),
+        |assets, (gfx, shaders), (&surface_format, &depth_format, _)| {
+            let shader = &*load_opaque_actor_shader(assets, gfx, shaders);
 
             RenderPipeline::builder()
                 .with_layout(&PipelineLayout::load_default(assets, gfx))
@@ -114,14 +121,21 @@ pub fn load_opaque_actor_pipeline(
 pub fn load_opaque_actor_shader(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
 ) -> Asset<wgpu::ShaderModule> {
-    assets.load(gfx, (), |_assets, gfx, ()| {
-        gfx.device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("actor/opaque.wgsl"),
-                source: wgpu::ShaderSource::Wgsl(include_shader!("actor_opaque.wgsl").into()),
-            })
-    })
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("actor_opaque.wgsl"),),
+        |_assets, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("actor/opaque.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shader_source!(shaders, "actor_opaque.wgsl").into(),
+                    ),
+                })
+        },
+    )
 }
 
 // === Uniform Management === //
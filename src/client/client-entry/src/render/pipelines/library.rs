@@ -0,0 +1,262 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crucible_assets::{AssetManager, WatchedAsset};
+use crucible_utils::hash::FxHashMap;
+use wgsl_link::driver::session::{Session, Wgsl};
+
+// === ShaderLibrary === //
+
+/// The sources of the shaders the renderer compiles. Shaders are linked and embedded into the binary
+/// at build time but, once the library is [`watch`](Self::watch)ing their source directory, edited
+/// shaders are relinked at runtime.
+///
+/// Pipeline loaders key their assets by the [`generation`](Self::generation) of the shaders they
+/// use so that only the pipelines built from a reloaded shader are rebuilt. A shader which fails
+/// to relink keeps its previous source and generation so its pipelines stay as they were.
+#[derive(Debug, Default)]
+pub struct ShaderLibrary {
+    reloaded: FxHashMap<String, ReloadedShader>,
+    watcher: Option<ShaderWatcher>,
+}
+
+#[derive(Debug)]
+struct ReloadedShader {
+    generation: u64,
+    source: String,
+}
+
+struct ShaderWatcher {
+    root: PathBuf,
+    session: Session,
+    files: Vec<WatchedSource>,
+}
+
+struct WatchedSource {
+    path: PathBuf,
+    asset: WatchedAsset<()>,
+    version: u64,
+}
+
+impl fmt::Debug for ShaderWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShaderWatcher")
+            .field("root", &self.root)
+            .field("files", &self.files.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShaderLibrary {
+    /// Starts relinking the entry shaders directly inside `root` whenever `assets` reloads one of
+    /// the sources under it. `assets` must be in [watch](AssetManager::watch) mode for edits to be
+    /// noticed. Sources created after this call aren't watched.
+    pub fn watch(&mut self, assets: &AssetManager, root: impl AsRef<Path>) -> io::Result<()> {
+        let root = root.as_ref().canonicalize()?;
+        let mut files = Vec::new();
+        let mut dirs = vec![root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "wgsl") {
+                    let asset = assets.load_file(&path, |_, _| ())?;
+                    files.push(WatchedSource {
+                        version: asset.version(),
+                        asset,
+                        path,
+                    });
+                }
+            }
+        }
+
+        // Link every entry shader up front so that the session knows which entries each source
+        // affects. Their output matches the embedded shaders so it's discarded.
+        let mut session = Session::new(Wgsl::default());
+
+        for file in &files {
+            if file.path.parent() == Some(&root) {
+                if let Err(diag) = session.parse(&file.path) {
+                    tracing::warn!(
+                        "shader source {:?} doesn't link:\n{}",
+                        file.path,
+                        session.format_diagnostics(&diag),
+                    );
+                }
+            }
+        }
+
+        self.watcher = Some(ShaderWatcher {
+            root,
+            session,
+            files,
+        });
+
+        Ok(())
+    }
+
+    /// Relinks the entry shaders affected by the sources [`AssetManager::poll_watched`] reloaded
+    /// since the last call. Returns the names of the shaders whose source was replaced.
+    pub fn poll(&mut self) -> Vec<String> {
+        let Some(ShaderWatcher {
+            root,
+            session,
+            files,
+        }) = &mut self.watcher
+        else {
+            return Vec::new();
+        };
+
+        let mut invalidated = Vec::new();
+
+        for file in files {
+            let version = file.asset.version();
+            if version != file.version {
+                file.version = version;
+                invalidated.extend(session.invalidate(&file.path));
+            }
+        }
+
+        let mut reloaded = Vec::new();
+
+        for path in invalidated {
+            // Imported modules are relinked as part of the entries importing them.
+            if path.parent() != Some(&**root) {
+                continue;
+            }
+
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+            match session.parse(&path) {
+                Ok(module) => {
+                    let source = session.build([module]);
+                    let shader = self.reloaded.entry(name.clone()).or_insert(ReloadedShader {
+                        generation: 0,
+                        source: String::new(),
+                    });
+
+                    shader.generation += 1;
+                    shader.source = source;
+
+                    tracing::info!("reloaded shader {name:?}");
+                    reloaded.push(name);
+                }
+                Err(diag) => {
+                    tracing::error!(
+                        "failed to reload shader {name:?}; keeping its previous version:\n{}",
+                        session.format_diagnostics(&diag),
+                    );
+                }
+            }
+        }
+
+        reloaded
+    }
+
+    /// The number of times the shader named `name` has been reloaded.
+    pub fn generation(&self, name: &str) -> u64 {
+        self.reloaded
+            .get(name)
+            .map_or(0, |shader| shader.generation)
+    }
+
+    /// The current source of the shader named `name`, which is `embedded` until it's reloaded.
+    pub fn source<'a>(&'a self, name: &str, embedded: &'a str) -> &'a str {
+        self.reloaded
+            .get(name)
+            .map_or(embedded, |shader| &shader.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn edits_relink_and_broken_edits_keep_the_last_good_pipeline() {
+        let dir = std::env::temp_dir().join(format!(
+            "crucible-shader-library-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(dir.join("utils")).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let main = dir.join("main.wgsl");
+        let dep = dir.join("utils/dep.wgsl");
+
+        let write_main = |entry: &str| {
+            fs::write(
+                &main,
+                format!(
+                    "//#use helper in \"utils/dep.wgsl\"\n\
+                     @fragment fn {entry}() -> @location(0) vec4f {{ return vec4f(helper()); }}\n"
+                ),
+            )
+            .unwrap();
+        };
+
+        fs::write(&dep, "fn helper() -> f32 { return 1.0; }\n").unwrap();
+        write_main("fs_first");
+
+        let mut assets = AssetManager::new();
+        assets.watch(Duration::ZERO);
+
+        let mut library = ShaderLibrary::default();
+        library.watch(&assets, &dir).unwrap();
+
+        let edit = |library: &mut ShaderLibrary, path: &Path, write: &dyn Fn()| {
+            write();
+            let now = Instant::now();
+            assets.notify_changed(path, now);
+            assets.poll_watched(now);
+            library.poll()
+        };
+
+        // Stands in for a pipeline built from the shader.
+        let load_pipeline = |library: &ShaderLibrary| {
+            assets.load(
+                library,
+                (&library.generation("main.wgsl"),),
+                |_, library, _| library.source("main.wgsl", "embedded").to_string(),
+            )
+        };
+
+        assert_eq!(*load_pipeline(&library), "embedded");
+
+        // Editing the entry relinks it.
+        assert_eq!(
+            edit(&mut library, &main, &|| write_main("fs_second")),
+            ["main.wgsl"]
+        );
+        assert_eq!(library.generation("main.wgsl"), 1);
+
+        let good = load_pipeline(&library);
+        assert!(good.contains("fs_second"));
+
+        // A broken edit is rejected and the previous pipeline is reused.
+        let broken = || fs::write(&main, "@fragment fn fs_broken( {").unwrap();
+        assert!(edit(&mut library, &main, &broken).is_empty());
+        assert_eq!(library.generation("main.wgsl"), 1);
+        assert!(std::ptr::eq(&*good, &*load_pipeline(&library)));
+
+        // Fixing the entry recovers...
+        assert_eq!(
+            edit(&mut library, &main, &|| write_main("fs_third")),
+            ["main.wgsl"]
+        );
+        assert!(load_pipeline(&library).contains("fs_third"));
+
+        // ...and editing an imported module relinks the entries importing it.
+        let dep_edit = || fs::write(&dep, "fn helper() -> f32 { return 0.5; }\n").unwrap();
+        assert_eq!(edit(&mut library, &dep, &dep_edit), ["main.wgsl"]);
+        assert_eq!(library.generation("main.wgsl"), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,16 @@
-macro_rules! include_shader {
-    ($name:expr) => {
-        include_str!(concat!(env!("OUT_DIR"), "/shaders/", $name))
+/// Fetches the current source of the shader named `$name` from a
+/// [`ShaderLibrary`](library::ShaderLibrary), falling back to the version linked at build time.
+macro_rules! shader_source {
+    ($shaders:expr, $name:expr) => {
+        $shaders.source(
+            $name,
+            include_str!(concat!(env!("OUT_DIR"), "/shaders/", $name)),
+        )
     };
 }
 
 pub mod actor;
+pub mod library;
 pub mod skybox;
+pub mod ssao;
 pub mod voxel;
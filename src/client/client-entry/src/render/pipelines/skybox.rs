@@ -8,6 +8,10 @@ use typed_wgpu::{
 };
 use wgpu_ext::{BindGroupExt as _, PipelineLayoutExt as _, SamplerDesc};
 
+use crate::render::helpers::FogSettings;
+
+use super::library::ShaderLibrary;
+
 // === Uniforms === //
 
 #[derive(Debug)]
@@ -20,6 +24,8 @@ pub struct SkyboxBindGroup<'a> {
 #[derive(Debug, AsStd430)]
 pub struct SkyboxUniformData {
     pub inv_proj_and_view: glam::Mat4,
+    pub fog_color: glam::Vec3,
+    pub fog_horizon: f32,
 }
 
 impl GpuStruct for SkyboxUniformData {
@@ -57,30 +63,40 @@ pub type SkyboxPipeline = RenderPipeline<(SkyboxBindGroup<'static>,), ()>;
 pub fn load_skybox_shader_module(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
 ) -> Asset<wgpu::ShaderModule> {
-    assets.load(gfx, (), |_assets, gfx, ()| {
-        gfx.device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Skybox shader module"),
-                source: wgpu::ShaderSource::Wgsl(include_shader!("skybox.wgsl").into()),
-            })
-    })
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("skybox.wgsl"),),
+        |_assets, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("Skybox shader module"),
+                    source: wgpu::ShaderSource::Wgsl(shader_source!(shaders, "skybox.wgsl").into()),
+                })
+        },
+    )
 }
 
 pub fn load_skybox_pipeline(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
     surface_format: wgpu::TextureFormat,
 ) -> Asset<SkyboxPipeline> {
-    assets.load(gfx, (&surface_format,), |assets, gfx, (surface_format,)| {
-        let shader = load_skybox_shader_module(assets, gfx);
-
-        SkyboxPipeline::builder()
-            .with_layout(&PipelineLayout::load_default(assets, gfx))
-            .with_vertex_shader(&shader, "vs_main", &())
-            .with_fragment_shader(&shader, "fs_main", *surface_format)
-            .finish(&gfx.device)
-    })
+    assets.load(
+        (gfx, shaders),
+        (&surface_format, &shaders.generation("skybox.wgsl")),
+        |assets, (gfx, shaders), (surface_format, _)| {
+            let shader = load_skybox_shader_module(assets, gfx, shaders);
+
+            SkyboxPipeline::builder()
+                .with_layout(&PipelineLayout::load_default(assets, gfx))
+                .with_vertex_shader(&shader, "vs_main", &())
+                .with_fragment_shader(&shader, "fs_main", *surface_format)
+                .finish(&gfx.device)
+        },
+    )
 }
 
 // === Uniform Management === //
@@ -117,11 +133,21 @@ impl SkyboxUniforms {
         SkyboxPipeline::bind_group_static(pass, &self.bind_group, &[]);
     }
 
-    pub fn set_camera_matrix(&self, gfx: &GfxContext, inv_proj_and_view: glam::Mat4) {
+    pub fn set_camera_matrix(
+        &self,
+        gfx: &GfxContext,
+        inv_proj_and_view: glam::Mat4,
+        fog: &FogSettings,
+    ) {
         self.buffer.write(
             &gfx.queue,
             0,
-            &[SkyboxUniformData { inv_proj_and_view }.as_std430()],
+            &[SkyboxUniformData {
+                inv_proj_and_view,
+                fog_color: fog.color,
+                fog_horizon: fog.horizon,
+            }
+            .as_std430()],
         );
     }
 }
@@ -0,0 +1,614 @@
+use crevice::std430::AsStd430;
+use crucible_assets::{Asset, AssetManager};
+use main_loop::GfxContext;
+use typed_glam::glam::{self, UVec2};
+use typed_wgpu::{
+    BindGroup, BindGroupBuilder, BindGroupInstance, BufferBinding, GpuStruct, NoDynamicOffsets,
+    PipelineLayout, RenderPipeline,
+};
+use wgpu_ext::{BindGroupExt as _, PipelineLayoutExt as _};
+
+use crate::render::helpers::SsaoSettings;
+
+use super::{
+    library::ShaderLibrary,
+    voxel::{VoxelCommonBindGroup, VoxelVertex},
+};
+
+/// World-space normals in `xyz` and the fragment's depth in `w`. Depth is kept in a color target
+/// rather than sampled from the depth attachment since GL can't load from depth textures.
+pub const GBUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+pub const GBUFFER_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+pub const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+// === Uniforms === //
+
+#[derive(Debug)]
+pub struct SsaoBindGroup<'a> {
+    pub uniforms: BufferBinding<'a, SsaoUniformData>,
+    pub kernel: BufferBinding<'a, SsaoKernelSample>,
+    pub gbuffer: &'a wgpu::TextureView,
+}
+
+#[derive(Debug, AsStd430)]
+pub struct SsaoUniformData {
+    pub camera: glam::Mat4,
+    pub inv_camera: glam::Mat4,
+    pub radius: f32,
+    pub bias: f32,
+}
+
+impl GpuStruct for SsaoUniformData {
+    type Pod = <Self as AsStd430>::Output;
+}
+
+#[derive(Debug, AsStd430)]
+pub struct SsaoKernelSample {
+    pub offset: glam::Vec4,
+}
+
+impl GpuStruct for SsaoKernelSample {
+    type Pod = <Self as AsStd430>::Output;
+}
+
+impl BindGroup for SsaoBindGroup<'_> {
+    type Config = ();
+    type DynamicOffsets = NoDynamicOffsets;
+
+    fn layout(builder: &mut impl BindGroupBuilder<Self>, (): &Self::Config) {
+        builder
+            .with_uniform_buffer(wgpu::ShaderStages::FRAGMENT, false, |c| {
+                c.uniforms.raw.clone()
+            })
+            .with_storage_buffer(wgpu::ShaderStages::FRAGMENT, true, false, |c| {
+                c.kernel.raw.clone()
+            })
+            .with_texture(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false,
+                |c| c.gbuffer,
+            );
+    }
+}
+
+#[derive(Debug)]
+pub struct SsaoBlurBindGroup<'a> {
+    pub occlusion_texture: &'a wgpu::TextureView,
+}
+
+impl BindGroup for SsaoBlurBindGroup<'_> {
+    type Config = ();
+    type DynamicOffsets = NoDynamicOffsets;
+
+    fn layout(builder: &mut impl BindGroupBuilder<Self>, (): &Self::Config) {
+        builder.with_texture(
+            wgpu::ShaderStages::FRAGMENT,
+            wgpu::TextureSampleType::Float { filterable: false },
+            wgpu::TextureViewDimension::D2,
+            false,
+            |c| c.occlusion_texture,
+        );
+    }
+}
+
+// === Pipelines === //
+
+pub type VoxelGBufferPipeline = RenderPipeline<(VoxelCommonBindGroup<'static>,), (VoxelVertex,)>;
+
+pub fn load_voxel_gbuffer_pipeline(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<VoxelGBufferPipeline> {
+    let generation = shaders.generation("voxel_gbuffer.wgsl");
+
+    assets.load(
+        (gfx, shaders),
+        (&generation,),
+        |assets, (gfx, shaders), _| {
+            let shader = load_voxel_gbuffer_shader(assets, gfx, shaders);
+
+            VoxelGBufferPipeline::builder()
+                .with_layout(&PipelineLayout::load_default(assets, gfx))
+                .with_vertex_shader(&shader, "vs_main", &(VoxelVertex::layout(),))
+                .with_fragment_shader(&shader, "fs_main", GBUFFER_FORMAT)
+                .with_cull_mode(wgpu::Face::Back)
+                .with_depth(GBUFFER_DEPTH_FORMAT, true, wgpu::CompareFunction::Less)
+                .finish(&gfx.device)
+        },
+    )
+}
+
+pub fn load_voxel_gbuffer_shader(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<wgpu::ShaderModule> {
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("voxel_gbuffer.wgsl"),),
+        |_, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("voxel_gbuffer.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shader_source!(shaders, "voxel_gbuffer.wgsl").into(),
+                    ),
+                })
+        },
+    )
+}
+
+pub type SsaoPipeline = RenderPipeline<(SsaoBindGroup<'static>,), ()>;
+
+pub fn load_ssao_pipeline(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<SsaoPipeline> {
+    let generation = shaders.generation("ssao.wgsl");
+
+    assets.load(
+        (gfx, shaders),
+        (&generation,),
+        |assets, (gfx, shaders), _| {
+            let shader = load_ssao_shader(assets, gfx, shaders);
+
+            SsaoPipeline::builder()
+                .with_layout(&PipelineLayout::load_default(assets, gfx))
+                .with_vertex_shader(&shader, "vs_main", &())
+                .with_fragment_shader(&shader, "fs_main", OCCLUSION_FORMAT)
+                .finish(&gfx.device)
+        },
+    )
+}
+
+pub fn load_ssao_shader(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<wgpu::ShaderModule> {
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("ssao.wgsl"),),
+        |_, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("ssao.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(shader_source!(shaders, "ssao.wgsl").into()),
+                })
+        },
+    )
+}
+
+pub type SsaoBlurPipeline = RenderPipeline<(SsaoBlurBindGroup<'static>,), ()>;
+
+pub fn load_ssao_blur_pipeline(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<SsaoBlurPipeline> {
+    let generation = shaders.generation("ssao_blur.wgsl");
+
+    assets.load(
+        (gfx, shaders),
+        (&generation,),
+        |assets, (gfx, shaders), _| {
+            let shader = load_ssao_blur_shader(assets, gfx, shaders);
+
+            SsaoBlurPipeline::builder()
+                .with_layout(&PipelineLayout::load_default(assets, gfx))
+                .with_vertex_shader(&shader, "vs_main", &())
+                .with_fragment_shader(&shader, "fs_main", OCCLUSION_FORMAT)
+                .finish(&gfx.device)
+        },
+    )
+}
+
+pub fn load_ssao_blur_shader(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<wgpu::ShaderModule> {
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("ssao_blur.wgsl"),),
+        |_, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("ssao_blur.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shader_source!(shaders, "ssao_blur.wgsl").into(),
+                    ),
+                })
+        },
+    )
+}
+
+// === SsaoTargets === //
+
+/// The G-buffer and occlusion targets for a single view, all of which share the resolution given by
+/// [`SsaoSettings::target_size`].
+#[derive(Debug)]
+pub struct SsaoTargets {
+    size: UVec2,
+    settings: SsaoSettings,
+    uniforms: typed_wgpu::Buffer<SsaoUniformData>,
+    gbuffer_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    raw_occlusion_view: wgpu::TextureView,
+    /// Only kept so that tests can read the blurred occlusion back.
+    #[cfg(test)]
+    occlusion: wgpu::Texture,
+    occlusion_view: wgpu::TextureView,
+    ssao_bind_group: BindGroupInstance<SsaoBindGroup<'static>>,
+    blur_bind_group: BindGroupInstance<SsaoBlurBindGroup<'static>>,
+}
+
+impl SsaoTargets {
+    pub fn new(
+        assets: &AssetManager,
+        gfx: &GfxContext,
+        settings: SsaoSettings,
+        size: UVec2,
+    ) -> Self {
+        let create_target = |label: &str, format: wgpu::TextureFormat| {
+            gfx.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+
+        let gbuffer_view = create_target("G-buffer", GBUFFER_FORMAT)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_view = create_target("G-buffer depth", GBUFFER_DEPTH_FORMAT)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let raw_occlusion_view = create_target("SSAO target", OCCLUSION_FORMAT)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let occlusion = create_target("blurred SSAO target", OCCLUSION_FORMAT);
+        let occlusion_view = occlusion.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Create buffers
+        let uniforms = typed_wgpu::Buffer::create(
+            &gfx.device,
+            &wgpu::BufferDescriptor {
+                label: Some("SSAO uniform buffer"),
+                mapped_at_creation: false,
+                size: 1,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        let kernel = settings
+            .kernel()
+            .into_iter()
+            .map(|offset| SsaoKernelSample { offset }.as_std430())
+            .collect::<Vec<_>>();
+
+        let kernel = typed_wgpu::Buffer::create_init(
+            &gfx.device,
+            &typed_wgpu::BufferInitDescriptor {
+                label: Some("SSAO kernel buffer"),
+                contents: &kernel,
+                usage: wgpu::BufferUsages::STORAGE,
+            },
+        );
+
+        // Create bind groups
+        let ssao_bind_group = SsaoBindGroup {
+            uniforms: uniforms.as_entire_buffer_binding(),
+            kernel: kernel.as_entire_buffer_binding(),
+            gbuffer: &gbuffer_view,
+        }
+        .load_instance(assets, gfx, ());
+
+        let blur_bind_group = SsaoBlurBindGroup {
+            occlusion_texture: &raw_occlusion_view,
+        }
+        .load_instance(assets, gfx, ());
+
+        Self {
+            size,
+            settings,
+            uniforms,
+            gbuffer_view,
+            depth_view,
+            raw_occlusion_view,
+            #[cfg(test)]
+            occlusion,
+            occlusion_view,
+            ssao_bind_group,
+            blur_bind_group,
+        }
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// The blurred occlusion factor, which is `1` for fully unoccluded pixels.
+    pub fn occlusion_view(&self) -> &wgpu::TextureView {
+        &self.occlusion_view
+    }
+
+    pub fn set_camera_matrix(&self, gfx: &GfxContext, camera: glam::Mat4) {
+        self.uniforms.write(
+            &gfx.queue,
+            0,
+            &[SsaoUniformData {
+                camera,
+                inv_camera: camera.inverse(),
+                radius: self.settings.radius,
+                bias: self.settings.bias,
+            }
+            .as_std430()],
+        );
+    }
+
+    /// Begins the pass which renders scene normals and depth into the G-buffer. This must be
+    /// ended before calling [`render_occlusion`](Self::render_occlusion).
+    pub fn begin_gbuffer_pass<'a>(
+        &'a self,
+        cmd: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("G-buffer pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.gbuffer_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    // Pixels which aren't covered by any geometry sit on the far plane.
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+
+    /// Computes the occlusion target from the G-buffer and blurs it.
+    pub fn render_occlusion(
+        &self,
+        cmd: &mut wgpu::CommandEncoder,
+        ssao: &SsaoPipeline,
+        blur: &SsaoBlurPipeline,
+    ) {
+        // Compute occlusion
+        let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSAO pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.raw_occlusion_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        ssao.bind_pipeline(&mut pass);
+        ssao.bind_group(&mut pass, &self.ssao_bind_group, &[]);
+        pass.draw(0..6, 0..1);
+        drop(pass);
+
+        // Blur occlusion
+        let mut pass = cmd.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("SSAO blur pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.occlusion_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        blur.bind_pipeline(&mut pass);
+        blur.bind_group(&mut pass, &self.blur_bind_group, &[]);
+        pass.draw(0..6, 0..1);
+        drop(pass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use typed_glam::glam::{Mat4, Vec2, Vec3};
+    use wgpu_ext::{read_back_buffer, TextureArray};
+
+    use crate::render::{helpers::SsaoQuality, pipelines::voxel::VoxelUniforms};
+
+    use super::*;
+
+    fn create_texture(gfx: &GfxContext, format: wgpu::TextureFormat) -> wgpu::TextureView {
+        gfx.device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    #[test]
+    fn corners_are_darker_than_open_ground() {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+        let assets = AssetManager::new();
+
+        // 256 single-byte texels per row keeps the read back at wgpu's required copy alignment.
+        let settings = SsaoSettings {
+            radius: 1.,
+            ..SsaoSettings::for_quality(SsaoQuality::Low)
+        };
+        let size = settings.target_size(UVec2::splat(512)).unwrap();
+        let targets = SsaoTargets::new(&assets, &gfx, settings, size);
+        assert_eq!(targets.size(), UVec2::splat(256));
+
+        // Build a floor which runs into a wall along the X axis. Each quad is emitted with both
+        // windings so that the scene doesn't depend on which faces get culled.
+        let quads = [
+            (
+                [
+                    Vec3::new(-4., 0., 0.),
+                    Vec3::new(4., 0., 0.),
+                    Vec3::new(4., 0., 4.),
+                    Vec3::new(-4., 0., 4.),
+                ],
+                Vec3::Y,
+            ),
+            (
+                [
+                    Vec3::new(-4., 0., 0.),
+                    Vec3::new(4., 0., 0.),
+                    Vec3::new(4., 4., 0.),
+                    Vec3::new(-4., 4., 0.),
+                ],
+                Vec3::Z,
+            ),
+        ];
+
+        let vertices = quads
+            .into_iter()
+            .flat_map(|([a, b, c, d], normal)| {
+                [a, b, c, a, c, d, a, c, b, a, d, c].map(|position| {
+                    VoxelVertex {
+                        position,
+                        uv: Vec2::ZERO,
+                        light: 1.,
+                        normal,
+                        layer: -1,
+                    }
+                    .as_std430()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let vertices = typed_wgpu::Buffer::<VoxelVertex>::create_init(
+            &gfx.device,
+            &typed_wgpu::BufferInitDescriptor {
+                label: None,
+                contents: &vertices,
+                usage: wgpu::BufferUsages::VERTEX,
+            },
+        );
+
+        // Look down at the crease from above the floor.
+        let camera = Mat4::perspective_lh(70f32.to_radians(), 1., 0.1, 100.)
+            * Mat4::look_at_lh(Vec3::new(0., 2.5, 4.), Vec3::new(0., 0.5, 0.), Vec3::Y);
+
+        let mut uniforms = VoxelUniforms::new(
+            &assets,
+            &gfx,
+            &create_texture(&gfx, wgpu::TextureFormat::Rgba8Unorm),
+            &create_texture(&gfx, wgpu::TextureFormat::Rgba8Unorm),
+            &TextureArray::new(&gfx.device, None, UVec2::ONE, 1).view,
+            &create_texture(&gfx, wgpu::TextureFormat::Depth32Float),
+            &create_texture(&gfx, OCCLUSION_FORMAT),
+        );
+        uniforms.set_camera_matrix(
+            &gfx,
+            camera,
+            Vec3::ZERO,
+            Mat4::IDENTITY,
+            Vec3::NEG_Y,
+            &Default::default(),
+        );
+        targets.set_camera_matrix(&gfx, camera);
+
+        // Render the occlusion target
+        let shaders = ShaderLibrary::default();
+        let gbuffer = load_voxel_gbuffer_pipeline(&assets, &gfx, &shaders);
+        let ssao = load_ssao_pipeline(&assets, &gfx, &shaders);
+        let blur = load_ssao_blur_pipeline(&assets, &gfx, &shaders);
+
+        let mut cmd = gfx.device.create_command_encoder(&Default::default());
+
+        let mut pass = targets.begin_gbuffer_pass(&mut cmd);
+        gbuffer.bind_pipeline(&mut pass);
+        gbuffer.bind_group(&mut pass, uniforms.common_bind_group(), &[]);
+        gbuffer.bind_vertex_buffer(&mut pass, vertices.slice(..));
+        pass.draw(0..24, 0..1);
+        drop(pass);
+
+        targets.render_occlusion(&mut cmd, &ssao, &blur);
+
+        // Read it back
+        let staging = gfx.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (size.x * size.y) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        cmd.copy_texture_to_buffer(
+            targets.occlusion.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size.x),
+                    rows_per_image: Some(size.y),
+                },
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        gfx.queue.submit([cmd.finish()]);
+
+        let read = read_back_buffer(&gfx.device, &gfx.queue, &staging, 0..staging.size());
+        gfx.device.poll(wgpu::Maintain::Wait);
+        let texels = futures::executor::block_on(read).unwrap();
+
+        let occlusion_at = |pos: Vec3| {
+            let ndc = camera.project_point3(pos);
+            let uv = Vec2::new(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+            let texel = (uv * size.as_vec2()).as_uvec2().min(size - 1);
+            texels[(texel.y * size.x + texel.x) as usize]
+        };
+
+        let corner = occlusion_at(Vec3::new(0., 0.1, 0.1));
+        let open = occlusion_at(Vec3::new(0., 0., 1.5));
+        assert!(corner < open, "corner: {corner}, open: {open}");
+    }
+}
@@ -4,10 +4,15 @@ use main_loop::GfxContext;
 use typed_glam::glam;
 use typed_wgpu::{
     BindGroup, BindGroupBuilder, BindGroupInstance, BufferBinding, DynamicOffset, GpuStruct,
-    NoDynamicOffsets, PipelineLayout, RenderPipeline, Std430VertexFormat, VertexBufferLayout,
+    NoDynamicOffsets, PipelineLayout, RenderPipeline, Std430VertexFormat, TrackedBuffer,
+    VertexBufferLayout,
 };
 use wgpu_ext::{BindGroupExt as _, PipelineLayoutExt as _, SamplerDesc};
 
+use crate::render::helpers::FogSettings;
+
+use super::library::ShaderLibrary;
+
 // === Uniforms === //
 
 #[derive(Debug)]
@@ -15,13 +20,22 @@ pub struct VoxelCommonBindGroup<'a> {
     pub uniforms: BufferBinding<'a, VoxelCommonUniformData>,
     pub texture: &'a wgpu::TextureView,
     pub nearest_sampler: &'a wgpu::Sampler,
+    pub normal_texture: &'a wgpu::TextureView,
+    pub layer_texture: &'a wgpu::TextureView,
 }
 
+// N.B. the scalars are interleaved with the `Vec3`s so that they occupy the trailing padding of
+// each `vec3f` in the WGSL `Uniforms` struct.
 #[derive(Debug, AsStd430)]
 pub struct VoxelCommonUniformData {
     pub camera: glam::Mat4,
     pub light: glam::Mat4,
     pub light_dir: glam::Vec3,
+    pub fog_start: f32,
+    pub fog_color: glam::Vec3,
+    pub fog_end: f32,
+    pub camera_pos: glam::Vec3,
+    pub fog_height_falloff: f32,
 }
 
 impl GpuStruct for VoxelCommonUniformData {
@@ -48,6 +62,20 @@ impl BindGroup for VoxelCommonBindGroup<'_> {
                 wgpu::ShaderStages::FRAGMENT,
                 wgpu::SamplerBindingType::NonFiltering,
                 |c| c.nearest_sampler,
+            )
+            .with_texture(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false,
+                |c| c.normal_texture,
+            )
+            .with_texture(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2Array,
+                false,
+                |c| c.layer_texture,
             );
     }
 }
@@ -55,6 +83,8 @@ impl BindGroup for VoxelCommonBindGroup<'_> {
 #[derive(Debug)]
 pub struct VoxelOpaqueBindGroup<'a> {
     pub depth_texture: &'a wgpu::TextureView,
+    pub occlusion_texture: &'a wgpu::TextureView,
+    pub occlusion_sampler: &'a wgpu::Sampler,
 }
 
 impl BindGroup for VoxelOpaqueBindGroup<'_> {
@@ -62,13 +92,26 @@ impl BindGroup for VoxelOpaqueBindGroup<'_> {
     type DynamicOffsets = NoDynamicOffsets;
 
     fn layout(builder: &mut impl BindGroupBuilder<Self>, (): &Self::Config) {
-        builder.with_texture(
-            wgpu::ShaderStages::FRAGMENT,
-            wgpu::TextureSampleType::Float { filterable: false },
-            wgpu::TextureViewDimension::D2,
-            false,
-            |c| c.depth_texture,
-        );
+        builder
+            .with_texture(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: false },
+                wgpu::TextureViewDimension::D2,
+                false,
+                |c| c.depth_texture,
+            )
+            .with_texture(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureViewDimension::D2,
+                false,
+                |c| c.occlusion_texture,
+            )
+            .with_sampler(
+                wgpu::ShaderStages::FRAGMENT,
+                wgpu::SamplerBindingType::Filtering,
+                |c| c.occlusion_sampler,
+            );
     }
 }
 
@@ -103,6 +146,8 @@ pub struct VoxelVertex {
     pub uv: glam::Vec2,
     pub light: f32,
     pub normal: glam::Vec3,
+    /// The layer of the block texture array to sample, or `-1` to sample the atlas.
+    pub layer: i32,
 }
 
 impl GpuStruct for VoxelVertex {
@@ -116,7 +161,8 @@ impl VoxelVertex {
             .with_attribute(Std430VertexFormat::Float32x2) // uv
             .with_attribute(Std430VertexFormat::Float32) // light
             .with_attribute(Std430VertexFormat::Float32x3) // normal
-            .finish(wgpu::VertexStepMode::Vertex)
+            .with_attribute(Std430VertexFormat::Sint32) // layer
+            .finish_vertex()
     }
 }
 
@@ -134,14 +180,19 @@ pub type VoxelOpaquePipeline = RenderPipeline<
 pub fn load_voxel_opaque_pipeline(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
     surface_format: wgpu::TextureFormat,
     depth_format: wgpu::TextureFormat,
 ) -> Asset<VoxelOpaquePipeline> {
     assets.load(
-        gfx,
-        (&surface_format, &depth_format),
-        |assets, gfx, (&surface_format, &depth_format)| {
-            let shader = load_voxel_opaque_shader(assets, gfx);
+        (gfx, shaders),
+        (
+            &surface_format,
+            &depth_format,
+            &shaders.generation("voxel_opaque.wgsl"),
+        ),
+        |assets, (gfx, shaders), (&surface_format, &depth_format, _)| {
+            let shader = load_voxel_opaque_shader(assets, gfx, shaders);
 
             VoxelOpaquePipeline::builder()
                 .with_layout(&PipelineLayout::load_default(assets, gfx))
@@ -157,14 +208,21 @@ pub fn load_voxel_opaque_pipeline(
 pub fn load_voxel_opaque_shader(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
 ) -> Asset<wgpu::ShaderModule> {
-    assets.load(gfx, (), |_, gfx, ()| {
-        gfx.device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("voxel_opaque.wgsl"),
-                source: wgpu::ShaderSource::Wgsl(include_shader!("voxel_opaque.wgsl").into()),
-            })
-    })
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("voxel_opaque.wgsl"),),
+        |_, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("voxel_opaque.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shader_source!(shaders, "voxel_opaque.wgsl").into(),
+                    ),
+                })
+        },
+    )
 }
 
 pub type VoxelCsmPipeline = RenderPipeline<(VoxelCommonBindGroup<'static>,), (VoxelVertex,)>;
@@ -172,35 +230,50 @@ pub type VoxelCsmPipeline = RenderPipeline<(VoxelCommonBindGroup<'static>,), (Vo
 pub fn load_voxel_csm_pipeline(
     assets: &AssetManager,
     gfx: &GfxContext,
+    shaders: &ShaderLibrary,
     depth_format: wgpu::TextureFormat,
 ) -> Asset<VoxelCsmPipeline> {
-    assets.load(gfx, (&depth_format,), |assets, gfx, (&depth_format,)| {
-        let shader = load_voxel_csm_shader(assets, gfx);
-
-        VoxelCsmPipeline::builder()
-            .with_layout(&PipelineLayout::load_default(assets, gfx))
-            .with_vertex_shader(&shader, "vs_main", &(VoxelVertex::layout(),))
-            .with_cull_mode(wgpu::Face::Back)
-            .with_depth(depth_format, true, wgpu::CompareFunction::Less)
-            .finish(&gfx.device)
-    })
+    assets.load(
+        (gfx, shaders),
+        (&depth_format, &shaders.generation("voxel_csm.wgsl")),
+        |assets, (gfx, shaders), (&depth_format, _)| {
+            let shader = load_voxel_csm_shader(assets, gfx, shaders);
+
+            VoxelCsmPipeline::builder()
+                .with_layout(&PipelineLayout::load_default(assets, gfx))
+                .with_vertex_shader(&shader, "vs_main", &(VoxelVertex::layout(),))
+                .with_cull_mode(wgpu::Face::Back)
+                .with_depth(depth_format, true, wgpu::CompareFunction::Less)
+                .finish(&gfx.device)
+        },
+    )
 }
 
-pub fn load_voxel_csm_shader(assets: &AssetManager, gfx: &GfxContext) -> Asset<wgpu::ShaderModule> {
-    assets.load(gfx, (), |_, gfx, ()| {
-        gfx.device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("voxel_csm.wgsl"),
-                source: wgpu::ShaderSource::Wgsl(include_shader!("voxel_csm.wgsl").into()),
-            })
-    })
+pub fn load_voxel_csm_shader(
+    assets: &AssetManager,
+    gfx: &GfxContext,
+    shaders: &ShaderLibrary,
+) -> Asset<wgpu::ShaderModule> {
+    assets.load(
+        (gfx, shaders),
+        (&shaders.generation("voxel_csm.wgsl"),),
+        |_, (gfx, shaders), _| {
+            gfx.device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("voxel_csm.wgsl"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        shader_source!(shaders, "voxel_csm.wgsl").into(),
+                    ),
+                })
+        },
+    )
 }
 
 // === Uniform Management === //
 
 #[derive(Debug)]
 pub struct VoxelUniforms {
-    buffer: typed_wgpu::Buffer<VoxelCommonUniformData>,
+    buffer: TrackedBuffer<VoxelCommonUniformData>,
     common_bind_group: BindGroupInstance<VoxelCommonBindGroup<'static>>,
     opaque_bind_group: BindGroupInstance<VoxelOpaqueBindGroup<'static>>,
 }
@@ -210,16 +283,16 @@ impl VoxelUniforms {
         assets: &AssetManager,
         gfx: &GfxContext,
         texture: &wgpu::TextureView,
+        normal_texture: &wgpu::TextureView,
+        layer_texture: &wgpu::TextureView,
         depth_texture: &wgpu::TextureView,
+        occlusion_texture: &wgpu::TextureView,
     ) -> Self {
-        let buffer = typed_wgpu::Buffer::create(
+        let buffer = TrackedBuffer::new(
             &gfx.device,
-            &wgpu::BufferDescriptor {
-                label: Some("uniform buffer"),
-                mapped_at_creation: false,
-                size: 1,
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            },
+            Some("uniform buffer"),
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::Zeroable::zeroed(),
         );
 
         // Create `common_bind_group`
@@ -232,15 +305,17 @@ impl VoxelUniforms {
         .load(assets, gfx);
 
         let common_bind_group = VoxelCommonBindGroup {
-            uniforms: buffer.as_entire_buffer_binding(),
+            uniforms: buffer.buffer().as_entire_buffer_binding(),
             texture,
             nearest_sampler: &nearest_sampler,
+            normal_texture,
+            layer_texture,
         }
         .load_instance(assets, gfx, ());
 
         // Create `opaque_bind_group`
         let opaque_bind_group =
-            VoxelOpaqueBindGroup { depth_texture }.load_instance(assets, gfx, ());
+            Self::create_opaque_bind_group(assets, gfx, depth_texture, occlusion_texture);
 
         Self {
             buffer,
@@ -249,23 +324,56 @@ impl VoxelUniforms {
         }
     }
 
+    fn create_opaque_bind_group(
+        assets: &AssetManager,
+        gfx: &GfxContext,
+        depth_texture: &wgpu::TextureView,
+        occlusion_texture: &wgpu::TextureView,
+    ) -> BindGroupInstance<VoxelOpaqueBindGroup<'static>> {
+        VoxelOpaqueBindGroup {
+            depth_texture,
+            occlusion_texture,
+            occlusion_sampler: &SamplerDesc::FILTER_CLAMP_EDGES.load(assets, gfx),
+        }
+        .load_instance(assets, gfx, ())
+    }
+
+    /// Rebinds the textures used by the opaque pass. This must be called whenever the occlusion
+    /// target is resized.
+    pub fn set_opaque_textures(
+        &mut self,
+        assets: &AssetManager,
+        gfx: &GfxContext,
+        depth_texture: &wgpu::TextureView,
+        occlusion_texture: &wgpu::TextureView,
+    ) {
+        self.opaque_bind_group =
+            Self::create_opaque_bind_group(assets, gfx, depth_texture, occlusion_texture);
+    }
+
+    /// Updates the uniforms, only uploading the fields which changed since the last call.
     pub fn set_camera_matrix(
-        &self,
+        &mut self,
         gfx: &GfxContext,
         camera: glam::Mat4,
+        camera_pos: glam::Vec3,
         light: glam::Mat4,
         light_dir: glam::Vec3,
+        fog: &FogSettings,
     ) {
-        self.buffer.write(
-            &gfx.queue,
-            0,
-            &[VoxelCommonUniformData {
-                camera,
-                light,
-                light_dir,
-            }
-            .as_std430()],
-        );
+        *self.buffer.get_mut() = VoxelCommonUniformData {
+            camera,
+            light,
+            light_dir,
+            fog_start: fog.start,
+            fog_color: fog.color,
+            fog_end: fog.end,
+            camera_pos,
+            fog_height_falloff: fog.height_falloff,
+        }
+        .as_std430();
+
+        self.buffer.flush(&gfx.queue);
     }
 
     pub fn common_bind_group(&self) -> &BindGroupInstance<VoxelCommonBindGroup<'static>> {
@@ -276,3 +384,29 @@ impl VoxelUniforms {
         &self.opaque_bind_group
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{offset_of, size_of};
+
+    use super::*;
+
+    type Std430VoxelCommonUniformData = <VoxelCommonUniformData as AsStd430>::Output;
+
+    #[test]
+    fn common_uniforms_match_wgsl_layout() {
+        // These offsets mirror the `Uniforms` struct in `shared/voxel.wgsl`.
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, camera), 0);
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, light), 64);
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, light_dir), 128);
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, fog_start), 140);
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, fog_color), 144);
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, fog_end), 156);
+        assert_eq!(offset_of!(Std430VoxelCommonUniformData, camera_pos), 160);
+        assert_eq!(
+            offset_of!(Std430VoxelCommonUniformData, fog_height_falloff),
+            172
+        );
+        assert_eq!(size_of::<Std430VoxelCommonUniformData>(), 176);
+    }
+}
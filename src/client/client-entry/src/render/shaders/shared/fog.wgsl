@@ -0,0 +1,14 @@
+// Computes how much of a fragment's color should be replaced by the fog color.
+//
+// `distance` is the fragment's view-space distance to the camera and `height` is its world-space
+// height. See `FogSettings` for the full formula.
+fn fog_factor(
+    distance: f32,
+    height: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_height_falloff: f32,
+) -> f32 {
+    let linear = clamp((distance - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    return linear * exp(-fog_height_falloff * max(height, 0.0));
+}
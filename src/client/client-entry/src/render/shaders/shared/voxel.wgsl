@@ -3,12 +3,19 @@ struct VertexInput {
 	@location(1) uv: vec2f,
     @location(2) light: f32,
     @location(3) normal: vec3f,
+    // The layer of `layer_texture` to sample or `-1` to sample the atlas.
+    @location(4) layer: i32,
 }
 
 struct Uniforms {
     camera: mat4x4f,
     light: mat4x4f,
     light_dir: vec3f,
+    fog_start: f32,
+    fog_color: vec3f,
+    fog_end: f32,
+    camera_pos: vec3f,
+    fog_height_falloff: f32,
 }
 
 struct PerChunkUniforms {
@@ -1,7 +1,13 @@
 //#use PI in "utils/constants.wgsl"
 
+struct Uniforms {
+	inv_proj_and_view: mat4x4<f32>,
+	fog_color: vec3<f32>,
+	fog_horizon: f32,
+}
+
 @group(0) @binding(0)
-var<uniform> inv_proj_and_view: mat4x4<f32>;
+var<uniform> uniforms: Uniforms;
 
 @group(0) @binding(1)
 var panorama_tex: texture_2d<f32>;
@@ -42,7 +48,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 	let ndc_pos = vec4<f32>(in.uv, 1.0, 1.0);
 
 	// The position of the fragment in view-space
-	var view_pos = inv_proj_and_view * ndc_pos;
+	var view_pos = uniforms.inv_proj_and_view * ndc_pos;
 	view_pos /= view_pos.w;
 
 	// The position of the fragment projected down to the unit sphere.
@@ -62,5 +68,11 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 		(latitude + PI / 2.0) / PI,
 	);
 
-	return textureSample(panorama_tex, panorama_sampler, vec2<f32>(eqp.x, 1.0 - eqp.y));
+	let sky = textureSample(panorama_tex, panorama_sampler, vec2<f32>(eqp.x, 1.0 - eqp.y));
+
+	// The sky is infinitely far away so distance fog would cover it entirely. Instead, we fade it
+	// into the fog color towards the horizon so that fogged terrain blends into it.
+	let fog = 1.0 - smoothstep(0.0, max(uniforms.fog_horizon, 0.0001), latitude);
+
+	return vec4<f32>(mix(sky.rgb, uniforms.fog_color, fog), sky.a);
 }
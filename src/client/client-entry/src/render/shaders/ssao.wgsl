@@ -0,0 +1,112 @@
+struct Uniforms {
+    camera: mat4x4f,
+    inv_camera: mat4x4f,
+    radius: f32,
+    bias: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+@group(0) @binding(1)
+var<storage, read> kernel: array<vec4f>;
+
+// World-space normals in `xyz` and depth in `w`.
+@group(0) @binding(2)
+var gbuffer: texture_2d<f32>;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4f,
+	@location(0) uv: vec2f,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+	var clip_position: vec2f;
+
+	switch in_vertex_index {
+		// Triangle 1
+		case 0u { clip_position = vec2f(-1.0, -1.0); }
+		case 1u { clip_position = vec2f(1.0, -1.0); }
+		case 2u { clip_position = vec2f(-1.0, 1.0); }
+		// Triangle 2
+		case 3u { clip_position = vec2f(1.0, 1.0); }
+		case 4u { clip_position = vec2f(-1.0, 1.0); }
+		default { clip_position = vec2f(1.0, -1.0); }
+	};
+
+	var out: VertexOutput;
+	out.clip_position = vec4f(clip_position, 0.0, 1.0);
+	// Texture coordinates run from the top-left corner of the target to its bottom-right corner.
+	out.uv = vec2f(clip_position.x * 0.5 + 0.5, 0.5 - clip_position.y * 0.5);
+
+	return out;
+}
+
+fn texel_at(uv: vec2f) -> vec2i {
+    let size = vec2i(textureDimensions(gbuffer));
+    return clamp(vec2i(uv * vec2f(size)), vec2i(0), size - 1);
+}
+
+fn world_pos_at(uv: vec2f, depth: f32) -> vec3f {
+    let ndc = vec4f(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let world = uniforms.inv_camera * ndc;
+    return world.xyz / world.w;
+}
+
+// Interleaved gradient noise. This rotates the kernel differently for neighboring pixels, trading
+// banding for a high-frequency pattern the blur pass can remove.
+fn noise_angle(pixel: vec2f) -> f32 {
+    return 6.2831853 * fract(52.9829189 * fract(dot(pixel, vec2f(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let texel = texel_at(in.uv);
+    let texel_data = textureLoad(gbuffer, texel, 0);
+    let depth = texel_data.w;
+
+    // The sky can't be occluded.
+    if depth >= 1.0 {
+        return vec4f(1.0);
+    }
+
+    let origin = world_pos_at(in.uv, depth);
+    let origin_dist = (uniforms.camera * vec4f(origin, 1.0)).w;
+    let normal = normalize(texel_data.xyz);
+
+    // Build a randomly rotated tangent frame around the normal.
+    let angle = noise_angle(in.clip_position.xy);
+    let random = vec3f(cos(angle), sin(angle), 0.0);
+    var tangent = random - normal * dot(random, normal);
+    if dot(tangent, tangent) < 0.0001 {
+        tangent = vec3f(0.0, 0.0, 1.0) - normal * normal.z;
+    }
+    tangent = normalize(tangent);
+    let tbn = mat3x3f(tangent, cross(normal, tangent), normal);
+
+    let sample_count = arrayLength(&kernel);
+    var occlusion = 0.0;
+
+    for (var i = 0u; i < sample_count; i++) {
+        let sample_pos = origin + tbn * kernel[i].xyz * uniforms.radius;
+        let sample_clip = uniforms.camera * vec4f(sample_pos, 1.0);
+        let sample_ndc = sample_clip.xy / sample_clip.w;
+        let sample_uv = vec2f(sample_ndc.x * 0.5 + 0.5, 0.5 - sample_ndc.y * 0.5);
+
+        // Find how far the visible surface at that pixel is from the camera.
+        let scene_depth = textureLoad(gbuffer, texel_at(sample_uv), 0).w;
+        let scene_pos = world_pos_at(sample_uv, scene_depth);
+        let scene_dist = (uniforms.camera * vec4f(scene_pos, 1.0)).w;
+
+        // Ignore occluders well outside the sample radius so that silhouettes don't darken
+        // whatever happens to be behind them.
+        let range = smoothstep(0.0, 1.0, uniforms.radius / max(abs(origin_dist - scene_dist), 0.0001));
+
+        if scene_dist < sample_clip.w - uniforms.bias {
+            occlusion += range;
+        }
+    }
+
+    return vec4f(1.0 - occlusion / f32(sample_count));
+}
@@ -0,0 +1,47 @@
+@group(0) @binding(0)
+var occlusion_texture: texture_2d<f32>;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4f,
+	@location(0) uv: vec2f,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+	var clip_position: vec2f;
+
+	switch in_vertex_index {
+		// Triangle 1
+		case 0u { clip_position = vec2f(-1.0, -1.0); }
+		case 1u { clip_position = vec2f(1.0, -1.0); }
+		case 2u { clip_position = vec2f(-1.0, 1.0); }
+		// Triangle 2
+		case 3u { clip_position = vec2f(1.0, 1.0); }
+		case 4u { clip_position = vec2f(-1.0, 1.0); }
+		default { clip_position = vec2f(1.0, -1.0); }
+	};
+
+	var out: VertexOutput;
+	out.clip_position = vec4f(clip_position, 0.0, 1.0);
+	// Texture coordinates run from the top-left corner of the target to its bottom-right corner.
+	out.uv = vec2f(clip_position.x * 0.5 + 0.5, 0.5 - clip_position.y * 0.5);
+
+	return out;
+}
+
+// A 4x4 box blur which smooths out the noise introduced by rotating the SSAO kernel per pixel.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let size = vec2i(textureDimensions(occlusion_texture));
+    let center = vec2i(in.clip_position.xy);
+
+    var sum = 0.0;
+    for (var x = -2; x < 2; x++) {
+        for (var y = -2; y < 2; y++) {
+            let texel = clamp(center + vec2i(x, y), vec2i(0), size - 1);
+            sum += textureLoad(occlusion_texture, texel, 0).r;
+        }
+    }
+
+    return vec4f(sum / 16.0);
+}
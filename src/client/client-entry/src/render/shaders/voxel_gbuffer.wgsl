@@ -0,0 +1,24 @@
+//#use VertexInput, Uniforms in "shared/voxel.wgsl"
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4f,
+    @location(0) normal: vec3f,
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+	var out: VertexOutput;
+	out.clip_position = uniforms.camera * vec4f(in.position, 1.0);
+    out.normal = in.normal;
+	return out;
+}
+
+// The depth attachment only resolves visibility. We copy the depth into the G-buffer itself so
+// that the SSAO pass can read it on backends which can't load from depth textures.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    return vec4f(normalize(in.normal), in.clip_position.z);
+}
@@ -1,5 +1,6 @@
 //#use VertexInput, Uniforms, PerChunkUniforms in "shared/voxel.wgsl"
 //#use shadow_level in "shared/light_map.wgsl"
+//#use fog_factor in "shared/fog.wgsl"
 
 // Uniforms
 @group(0) @binding(0)
@@ -11,9 +12,24 @@ var texture: texture_2d<f32>;
 @group(0) @binding(2)
 var nearest_sampler: sampler;
 
+// Shares its layout with `texture` so it can be sampled with the same UV.
+@group(0) @binding(3)
+var normal_texture: texture_2d<f32>;
+
+// Block textures addressed by layer rather than by a sub-rect of the atlas.
+@group(0) @binding(4)
+var layer_texture: texture_2d_array<f32>;
+
 @group(1) @binding(0)
 var light_map: texture_2d<f32>;
 
+// The blurred SSAO target. This is a white texel when SSAO is disabled.
+@group(1) @binding(1)
+var occlusion_texture: texture_2d<f32>;
+
+@group(1) @binding(2)
+var occlusion_sampler: sampler;
+
 @group(2) @binding(0)
 var<uniform> uniforms_pc: PerChunkUniforms;
 
@@ -24,6 +40,9 @@ struct VertexOutput {
 	@location(1) uv: vec2f,
     @location(2) light: f32,
     @location(3) normal: vec3f,
+    @location(4) world_pos: vec3f,
+    @location(5) screen_pos: vec4f,
+    @location(6) @interpolate(flat) layer: i32,
 }
 
 @vertex
@@ -36,13 +55,32 @@ fn vs_main(in: VertexInput) -> VertexOutput {
 	out.uv = in.uv;
     out.light = in.light;
     out.normal = in.normal;
+    out.world_pos = position;
+    out.screen_pos = out.clip_position;
+    out.layer = in.layer;
 	return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4f {
-    let albedo = vec4f(textureSample(texture, nearest_sampler, in.uv)) * in.light;
+    // Both textures are sampled since sampling must happen in uniform control flow.
+    let atlas_albedo = textureSample(texture, nearest_sampler, in.uv);
+    let layer_albedo = textureSample(layer_texture, nearest_sampler, in.uv, max(in.layer, 0));
+    let albedo = select(atlas_albedo, layer_albedo, in.layer >= 0) * in.light;
     let shadow_level = shadow_level(light_map, nearest_sampler, uniforms.light_dir, in.light_space, in.normal);
 
-    return albedo * (1f + shadow_level) / 2f;
+    let screen_uv = vec2f(0.5, -0.5) * in.screen_pos.xy / in.screen_pos.w + 0.5;
+    let occlusion = textureSample(occlusion_texture, occlusion_sampler, screen_uv).r;
+
+    let color = albedo * (occlusion + shadow_level) / 2f;
+
+    let fog = fog_factor(
+        distance(in.world_pos, uniforms.camera_pos),
+        in.world_pos.y,
+        uniforms.fog_start,
+        uniforms.fog_end,
+        uniforms.fog_height_falloff,
+    );
+
+    return vec4f(mix(color.rgb, uniforms.fog_color, fog), color.a);
 }
@@ -1,41 +1,69 @@
-use std::{num::NonZeroU64, sync::Arc, time::Duration};
+use std::{mem, num::NonZeroU64, path::PathBuf, sync::Arc, time::Duration};
 
 use bevy_autoken::{random_component, Obj, RandomAccess, RandomEntityExt};
 use bevy_ecs::{event::EventReader, query::With, system::Query};
 use crevice::std430::AsStd430 as _;
 use crucible_assets::AssetManager;
 use crucible_math::{
-    AaQuad, BlockFace, BlockVec, BlockVecExt as _, Sign, Tri, WorldVec, WorldVecExt as _, QUAD_UVS,
+    AaQuad, BlockFace, BlockVec, BlockVecExt as _, ChunkVec, Sign, Tri, WorldVec, WorldVecExt as _,
+    CHUNK_EDGE, QUAD_UVS,
 };
 use crucible_utils::{
-    hash::FxHashSet,
-    newtypes::{EnumIndex as _, IndexArray},
+    hash::{FxHashMap, FxHashSet},
+    iter::VolumetricIter,
+    newtypes::{EnumIndex as _, IndexArray, LargeIndex as _},
 };
 use crucible_world::{
     material::MaterialCache,
     mesh::QuadMeshLayer,
     voxel::{
-        BlockMaterial, BlockMaterialCache, BlockMaterialRegistry, ChunkQueue, ChunkVoxelData,
-        WorldChunkCreated, WorldPointer, WorldVoxelData,
+        chunk_mesh_key, BlockMaterial, BlockMaterialCache, BlockMaterialRegistry, ChunkMeshCache,
+        ChunkQueue, ChunkVoxelData, LoadedChunkMesh, MeshKeyHasher, WorldChunkCreated,
+        WorldPointer, WorldVoxelData,
     },
 };
 use main_loop::GfxContext;
-use typed_glam::glam::{UVec2, Vec3};
-use typed_wgpu::BufferBinding;
-use wgpu_ext::{AtlasTexture, BindGroupExt as _, MultiPass};
-
-use super::pipelines::voxel::{
-    VoxelChunkInstanceBindGroup, VoxelChunkUniformData, VoxelCsmPipeline, VoxelOpaquePipeline,
-    VoxelUniforms, VoxelVertex,
+use typed_glam::glam::{IVec3, UVec3, Vec2, Vec3};
+use typed_wgpu::{BufferBinding, GpuStruct};
+use wgpu_ext::{AtlasHandle, AtlasTexture, BindGroupExt as _, MultiPass};
+
+use super::{
+    helpers::Frustum,
+    pipelines::{
+        ssao::VoxelGBufferPipeline,
+        voxel::{
+            VoxelChunkInstanceBindGroup, VoxelChunkUniformData, VoxelCsmPipeline,
+            VoxelOpaquePipeline, VoxelUniforms, VoxelVertex,
+        },
+    },
 };
 
 // === WorldVoxelMesh === //
 
+/// The number of level-of-detail variants a chunk can be meshed at. A chunk meshed at LOD `n` merges
+/// every `2^n` by `2^n` by `2^n` cube of blocks into a single cell.
+pub const CHUNK_LOD_COUNT: usize = 3;
+
+/// The default distances (in blocks from the camera to the chunk's center) beyond which a chunk is
+/// meshed at LOD `1`, `2`, and so on.
+pub const DEFAULT_LOD_DISTANCES: [f32; CHUNK_LOD_COUNT - 1] = [96., 192.];
+
+/// The default distance, in chunks, out to which chunks are rendered.
+pub const DEFAULT_VIEW_RADIUS: u32 = 6;
+
+/// The version of the meshes produced by the chunk mesher. This must be bumped whenever the mesher's
+/// output changes so that meshes in a [`ChunkMeshCache`] written by older builds are rejected.
+pub const CHUNK_MESH_FORMAT_VERSION: u32 = 2;
+
 #[derive(Debug)]
 pub struct WorldVoxelMesh {
     material_cache: BlockMaterialCache<MaterialVisualDescriptor>,
     rendered_chunks: FxHashSet<Obj<ChunkVoxelMesh>>,
     dirty_queue: ChunkQueue<Obj<ChunkVoxelMesh>>,
+    lod_distances: [f32; CHUNK_LOD_COUNT - 1],
+    view_radius: u32,
+    disk_cache: Option<ChunkMeshCache>,
+    pending_loads: FxHashMap<(ChunkVec, u8, u64), Obj<ChunkVoxelMesh>>,
 }
 
 random_component!(WorldVoxelMesh);
@@ -46,14 +74,102 @@ impl WorldVoxelMesh {
             material_cache: MaterialCache::new(registry),
             rendered_chunks: FxHashSet::default(),
             dirty_queue: ChunkQueue::default(),
+            lod_distances: DEFAULT_LOD_DISTANCES,
+            view_radius: DEFAULT_VIEW_RADIUS,
+            disk_cache: None,
+            pending_loads: FxHashMap::default(),
         }
     }
 
-    pub fn update(&mut self, gfx: &GfxContext, atlas: &AtlasTexture, time_limit: Option<Duration>) {
+    /// Persists chunk meshes to `dir` so that chunks which are loaded again without having changed
+    /// can skip meshing. The directory must be specific to the world being meshed.
+    pub fn set_disk_cache(&mut self, dir: impl Into<PathBuf>) {
+        self.disk_cache = Some(ChunkMeshCache::new(dir, CHUNK_MESH_FORMAT_VERSION));
+    }
+
+    /// The distance in blocks out to which chunks are rendered. Chunks with no block within this
+    /// distance of the camera are culled.
+    pub fn view_distance(&self) -> f32 {
+        (self.view_radius as i32 * CHUNK_EDGE) as f32
+    }
+
+    pub fn lod_for_chunk(&self, chunk: ChunkVec, camera_pos: Vec3) -> u8 {
+        chunk_lod(&self.lod_distances, chunk, camera_pos)
+    }
+
+    /// Drops every chunk mesh and queues the chunks to be meshed again. This is used when the
+    /// graphics device is recreated since the old vertex buffers belong to the lost device.
+    pub fn invalidate_gpu_resources(&mut self) {
+        for &chunk in &self.rendered_chunks {
+            if !chunk.is_alive() {
+                continue;
+            }
+
+            chunk.invalidate_meshes(&mut self.dirty_queue);
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        gfx: &GfxContext,
+        atlas: &AtlasTexture,
+        camera_pos: Vec3,
+        time_limit: Option<Duration>,
+    ) {
+        // Update the desired LOD of every rendered chunk, queueing those whose variant for that
+        // LOD hasn't been meshed yet.
+        for &chunk in &self.rendered_chunks {
+            if !chunk.is_alive() {
+                continue;
+            }
+
+            let lod = self.lod_for_chunk(chunk.data().pos(), camera_pos);
+            chunk.request_lod(&mut self.dirty_queue, lod);
+        }
+
         if !self.dirty_queue.is_empty() {
             tracing::info!("Dirty chunk count: {}", self.dirty_queue.len());
         }
 
+        // Install the cached meshes which finished loading and queue the chunks which missed the
+        // cache to be meshed.
+        let loaded = self
+            .disk_cache
+            .as_ref()
+            .map_or_else(Vec::new, ChunkMeshCache::poll_loaded);
+
+        for LoadedChunkMesh {
+            pos,
+            lod,
+            key,
+            mesh,
+        } in loaded
+        {
+            let Some(mut chunk) = self.pending_loads.remove(&(pos, lod, key)) else {
+                continue;
+            };
+
+            if !chunk.is_alive() || chunk.cache_lookup != Some(CacheLookup::Pending { lod, key }) {
+                continue;
+            }
+
+            let vertex_size = mem::size_of::<<VoxelVertex as GpuStruct>::Pod>();
+            match mesh.filter(|bytes| bytes.len() % vertex_size == 0) {
+                Some(bytes) => {
+                    chunk.cache_lookup = None;
+                    let vertices = bytemuck::pod_collect_to_vec(&bytes);
+                    install_chunk_mesh(gfx, &mut self.rendered_chunks, chunk, lod, &vertices);
+                }
+                None => {
+                    chunk.cache_lookup = Some(CacheLookup::Missed { lod, key });
+                    chunk.enqueue(&mut self.dirty_queue);
+                }
+            }
+        }
+
+        let fingerprint = (self.disk_cache.is_some() && !self.dirty_queue.is_empty())
+            .then(|| mesh_fingerprint(&mut self.material_cache, atlas));
+
         cbit::cbit!(for mut chunk in self.dirty_queue.process(time_limit) {
             // Ensure that the chunk is still alive
             if !chunk.is_alive() {
@@ -61,217 +177,570 @@ impl WorldVoxelMesh {
             }
 
             // Ensure that the chunk is only re-rendered once
-            if !chunk.dirty {
+            if !chunk.queued {
                 continue;
             }
 
-            chunk.dirty = false;
+            chunk.queued = false;
 
             let data = &*chunk.data();
 
-            let mut vertices = Vec::new();
+            // Determine the LOD at which the chunk should be meshed and skip it if we already have
+            // an up-to-date variant for it.
+            let lod = chunk_lod(&self.lod_distances, data.pos(), camera_pos);
+            chunk.lod = lod;
+
+            if chunk.lods[lod as usize]
+                .as_ref()
+                .is_some_and(|mesh| !mesh.is_stale)
+            {
+                continue;
+            }
+
+            // Only mesh chunks with a disk cache once the cache has been checked for them. The
+            // lookup happens on the cache's thread to keep disk I/O out of our time budget.
+            let cache = self
+                .disk_cache
+                .as_ref()
+                .zip(fingerprint)
+                .map(|(cache, fingerprint)| (cache, chunk_mesh_key(data, fingerprint)));
+
+            if let Some((cache, key)) = cache {
+                if chunk.cache_lookup != Some(CacheLookup::Missed { lod, key }) {
+                    if chunk.cache_lookup != Some(CacheLookup::Pending { lod, key }) {
+                        chunk.cache_lookup = Some(CacheLookup::Pending { lod, key });
+                        self.pending_loads.insert((data.pos(), lod, key), chunk);
+                        cache.request_load(data.pos(), lod, key);
+                    }
 
-            for center_pos in BlockVec::iter() {
-                // Decode material
-                let material = data.block_or_air(center_pos).material;
-                if material == BlockMaterial::AIR {
                     continue;
                 }
-                let material = self.material_cache.get(material).unwrap();
 
-                // Determine the center block mesh origin
-                // (this is used by all three branches)
-                let center_origin = WorldVec::compose(data.pos(), center_pos)
+                chunk.cache_lookup = None;
+            }
+
+            let vertices = mesh_chunk(&mut self.material_cache, atlas, data, lod);
+
+            if let Some((cache, key)) = cache {
+                cache.store(
+                    data.pos(),
+                    lod,
+                    key,
+                    bytemuck::cast_slice(&vertices).to_vec(),
+                );
+            }
+
+            install_chunk_mesh(gfx, &mut self.rendered_chunks, chunk, lod, &vertices);
+        });
+    }
+
+    /// Collects the meshes of every chunk within the [`view_distance`](Self::view_distance) of
+    /// `camera_pos`.
+    pub fn prepare_pass(&mut self, camera_pos: Vec3) -> ChunkRenderPass {
+        let view_distance = self.view_distance();
+        let mut meshes = Vec::new();
+        self.rendered_chunks.retain(|chunk| {
+            if !chunk.is_alive() {
+                return false;
+            }
+
+            if let Some(ChunkLodMesh {
+                buffer: Some(buffer),
+                vertex_count,
+                ..
+            }) = chunk.best_available_lod()
+            {
+                let min = WorldVec::compose(chunk.data().pos(), BlockVec::ZERO)
                     .to_glam()
                     .as_vec3();
+                let max = min + Vec3::splat(CHUNK_EDGE as f32);
+
+                if camera_pos.clamp(min, max).distance(camera_pos) <= view_distance {
+                    meshes.push(ChunkDraw {
+                        buffer: buffer.clone(),
+                        vertex_count: *vertex_count,
+                        aabb: (min, max),
+                    });
+                }
+            }
 
-                // Process material
-                match &*material {
-                    MaterialVisualDescriptor::Cubic { textures } => {
-                        // For every side of a solid block...
-                        for face in BlockFace::variants() {
-                            let neighbor_block = center_pos + face.unit();
-
-                            // If the neighbor isn't solid...
-                            let is_solid = 'a: {
-                                let state = if neighbor_block.is_valid() {
-                                    data.block_or_air(neighbor_block)
-                                } else {
-                                    let Some(neighbor) = data.neighbor(face) else {
-                                        break 'a false;
-                                    };
-
-                                    neighbor.block_or_air(neighbor_block.wrap())
-                                };
-
-                                if state.is_air() {
-                                    break 'a false;
-                                }
+            true
+        });
+
+        ChunkRenderPass { meshes }
+    }
+}
+
+fn chunk_lod(lod_distances: &[f32], chunk: ChunkVec, camera_pos: Vec3) -> u8 {
+    let center = WorldVec::compose(chunk, BlockVec::ZERO).to_glam().as_vec3()
+        + Vec3::splat(CHUNK_EDGE as f32 / 2.);
+
+    let dist = center.distance(camera_pos);
+
+    lod_distances
+        .iter()
+        .take_while(|&&threshold| dist > threshold)
+        .count() as u8
+}
+
+/// Fingerprints the state besides the world which meshes depend on: the order in which materials
+/// were registered and the visuals and atlas UVs of each material.
+fn mesh_fingerprint(
+    material_cache: &mut BlockMaterialCache<MaterialVisualDescriptor>,
+    atlas: &AtlasTexture,
+) -> u64 {
+    let mut hasher = MeshKeyHasher::default();
+
+    let write_uv_rect = |hasher: &mut MeshKeyHasher, texture: AtlasHandle| {
+        for corner in atlas.uv_rect(texture) {
+            hasher.write_f32(corner.x);
+            hasher.write_f32(corner.y);
+        }
+    };
+
+    let registry = material_cache.registry();
+    for (material, name) in registry.names() {
+        hasher.write_u16(material.as_raw());
+        hasher.write_str(name);
+
+        match material_cache.get(material).as_deref() {
+            None => hasher.write_u8(0),
+            Some(MaterialVisualDescriptor::Cubic { textures }) => {
+                hasher.write_u8(1);
+
+                for face in BlockFace::variants() {
+                    write_uv_rect(&mut hasher, textures[face]);
+                }
+            }
+            Some(MaterialVisualDescriptor::Mesh { mesh }) => {
+                hasher.write_u8(2);
+                hasher.write_u32(mesh.quads.len() as u32);
+
+                for (quad, texture) in mesh.iter_cloned() {
+                    for axis in quad.origin.to_array() {
+                        hasher.write_f32(axis);
+                    }
+                    hasher.write_u8(quad.face as u8);
+                    hasher.write_f32(quad.size.0);
+                    hasher.write_f32(quad.size.1);
+                    write_uv_rect(&mut hasher, texture);
+                }
+            }
+            Some(MaterialVisualDescriptor::Layered { layers }) => {
+                hasher.write_u8(3);
+
+                for face in BlockFace::variants() {
+                    hasher.write_u32(layers[face]);
+                }
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+fn mesh_chunk(
+    material_cache: &mut BlockMaterialCache<MaterialVisualDescriptor>,
+    atlas: &AtlasTexture,
+    data: &ChunkVoxelData,
+    lod: u8,
+) -> Vec<<VoxelVertex as GpuStruct>::Pod> {
+    if lod == 0 {
+        mesh_chunk_full(material_cache, atlas, data)
+    } else {
+        mesh_chunk_decimated(material_cache, atlas, data, lod)
+    }
+}
+
+/// Replaces the chunk's mesh at the given LOD with one built from `vertices`.
+fn install_chunk_mesh(
+    gfx: &GfxContext,
+    rendered_chunks: &mut FxHashSet<Obj<ChunkVoxelMesh>>,
+    mut chunk: Obj<ChunkVoxelMesh>,
+    lod: u8,
+    vertices: &[<VoxelVertex as GpuStruct>::Pod],
+) {
+    let pos = chunk.data().pos();
+    let buffer = if !vertices.is_empty() {
+        Some(Arc::new(typed_wgpu::Buffer::create_init(
+            &gfx.device,
+            &typed_wgpu::BufferInitDescriptor {
+                label: Some(format!("chunk mesh {pos:?} (LOD {lod})").as_str()),
+                usage: wgpu::BufferUsages::VERTEX,
+                contents: vertices,
+            },
+        )))
+    } else {
+        None
+    };
+
+    chunk.lods[lod as usize] = Some(ChunkLodMesh {
+        is_stale: false,
+        vertex_count: vertices.len() as u32,
+        buffer,
+    });
 
-                                let material = self.material_cache.get(state.material).unwrap();
+    rendered_chunks.insert(chunk);
+
+    // Log some debug info
+    tracing::info!(
+        "Meshed {} {} for chunk {:?} at LOD {lod}",
+        vertices.len(),
+        if vertices.len() == 1 {
+            "vertex"
+        } else {
+            "vertices"
+        },
+        chunk,
+    );
+}
 
-                                matches!(&*material, MaterialVisualDescriptor::Cubic { .. })
+fn mesh_chunk_full(
+    material_cache: &mut BlockMaterialCache<MaterialVisualDescriptor>,
+    atlas: &AtlasTexture,
+    data: &ChunkVoxelData,
+) -> Vec<<VoxelVertex as GpuStruct>::Pod> {
+    let mut vertices = Vec::new();
+
+    for center_pos in BlockVec::iter() {
+        // Decode material
+        let material = data.block_or_air(center_pos).material;
+        if material == BlockMaterial::AIR {
+            continue;
+        }
+        let material = material_cache.get(material).unwrap();
+
+        // Determine the center block mesh origin
+        // (this is used by all three branches)
+        let center_origin = WorldVec::compose(data.pos(), center_pos)
+            .to_glam()
+            .as_vec3();
+
+        // Process material
+        match &*material {
+            cube @ (MaterialVisualDescriptor::Cubic { .. }
+            | MaterialVisualDescriptor::Layered { .. }) => {
+                // For every side of a solid block...
+                for face in BlockFace::variants() {
+                    let neighbor_block = center_pos + face.unit();
+
+                    // If the neighbor isn't solid...
+                    let is_solid = 'a: {
+                        let state = if neighbor_block.is_valid() {
+                            data.block_or_air(neighbor_block)
+                        } else {
+                            let Some(neighbor) = data.neighbor(face) else {
+                                break 'a false;
                             };
 
-                            if is_solid {
-                                continue;
-                            }
+                            neighbor.block_or_air(neighbor_block.wrap())
+                        };
 
-                            // Mesh it!
-                            {
-                                // Decode the texture bounds
-                                let (uv_origin, uv_size) =
-                                    atlas.decode_uv_percent_bounds(textures[face]);
-
-                                // Determine the quad origin
-                                let center_origin = if face.sign() == Sign::Positive {
-                                    center_origin + face.axis().unit_f()
-                                } else {
-                                    center_origin
-                                };
-
-                                // Construct the quad
-                                let quad = AaQuad::new_unit(center_origin, face);
-                                let quad = quad
-                                    .as_quad_ccw_whmask()
-                                    // Determine UV
-                                    .zip(QUAD_UVS.map(|v| uv_origin + v * uv_size))
-                                    // Determine occlusion
-                                    .map(|((pos, whmask), uv)| {
-                                        let mut is_occluded = false;
-                                        let (h_rel, v_rel) = face.axis().ortho_hv();
-                                        let h_rel = h_rel.unit_typed::<WorldVec>()
-                                            * if whmask.x { 1 } else { -1 };
-                                        let v_rel = v_rel.unit_typed::<WorldVec>()
-                                            * if whmask.y { 1 } else { -1 };
-
-                                        let occlude_origin =
-                                            WorldVec::compose(data.pos(), center_pos) + face.unit();
-
-                                        for (h_mul, v_mul) in [(1, 0), (0, 1), (1, 1)] {
-                                            let rel = h_rel * h_mul + v_rel * v_mul;
-
-                                            is_occluded |= WorldPointer::new(occlude_origin + rel)
-                                                .state_or_air(data.world())
-                                                .is_not_air();
-                                        }
-
-                                        (pos, uv, if is_occluded { 0.8 } else { 1. })
-                                    });
-
-                                let [Tri([a, b, c]), Tri([d, e, f])] = quad.to_tris();
-                                let quad_vertices = [a, b, c, d, e, f];
-
-                                // Write the quad
-                                let quad_vertices = quad_vertices.map(|(position, uv, light)| {
-                                    VoxelVertex {
-                                        position,
-                                        uv,
-                                        light,
-                                        normal: face.unit_typed(),
-                                    }
-                                    .as_std430()
-                                });
-
-                                vertices.extend(quad_vertices);
-                            }
+                        if state.is_air() {
+                            break 'a false;
                         }
+
+                        material_cache.get(state.material).unwrap().is_cube()
+                    };
+
+                    if is_solid {
+                        continue;
                     }
-                    MaterialVisualDescriptor::Mesh { mesh } => {
-                        // Push the mesh
-                        for (quad, material) in mesh.iter_cloned() {
-                            let normal = quad.face.unit_typed();
-
-                            // Translate the quad relative to the block
-                            let quad = quad.translated(center_origin);
-
-                            // Decode the texture bounds
-                            let (uv_origin, uv_size) = atlas.decode_uv_percent_bounds(material);
-
-                            // Give it UVs
-                            let quad = quad
-                                .as_quad_ccw()
-                                .zip(QUAD_UVS.map(|v| uv_origin + v * uv_size));
-
-                            // Convert to triangles
-                            let [Tri([a, b, c]), Tri([d, e, f])] = quad.to_tris();
-                            let quad_vertices = [a, b, c, d, e, f];
-
-                            // Convert to std340
-                            let quad_vertices = quad_vertices.map(|(position, uv)| {
-                                VoxelVertex {
-                                    position,
-                                    uv,
-                                    light: 1.,
-                                    normal,
+
+                    // Mesh it!
+                    {
+                        // Decode the texture bounds
+                        let ([uv_min, uv_max], layer) =
+                            cube.cube_face(face).unwrap().uv_rect_and_layer(atlas);
+
+                        // Determine the quad origin
+                        let center_origin = if face.sign() == Sign::Positive {
+                            center_origin + face.axis().unit_f()
+                        } else {
+                            center_origin
+                        };
+
+                        // Construct the quad
+                        let quad = AaQuad::new_unit(center_origin, face);
+                        let quad = quad
+                            .as_quad_ccw_whmask()
+                            // Determine UV
+                            .zip(QUAD_UVS.map(|v| uv_min + v * (uv_max - uv_min)))
+                            // Determine occlusion
+                            .map(|((pos, whmask), uv)| {
+                                let mut is_occluded = false;
+                                let (h_rel, v_rel) = face.axis().ortho_hv();
+                                let h_rel =
+                                    h_rel.unit_typed::<WorldVec>() * if whmask.x { 1 } else { -1 };
+                                let v_rel =
+                                    v_rel.unit_typed::<WorldVec>() * if whmask.y { 1 } else { -1 };
+
+                                let occlude_origin =
+                                    WorldVec::compose(data.pos(), center_pos) + face.unit();
+
+                                for (h_mul, v_mul) in [(1, 0), (0, 1), (1, 1)] {
+                                    let rel = h_rel * h_mul + v_rel * v_mul;
+
+                                    is_occluded |= WorldPointer::new(occlude_origin + rel)
+                                        .state_or_air(data.world())
+                                        .is_not_air();
                                 }
-                                .as_std430()
+
+                                (pos, uv, if is_occluded { 0.8 } else { 1. })
                             });
 
-                            // Write to the vertex buffer
-                            vertices.extend(quad_vertices);
-                        }
+                        let [Tri([a, b, c]), Tri([d, e, f])] = quad.to_tris();
+                        let quad_vertices = [a, b, c, d, e, f];
+
+                        // Write the quad
+                        let quad_vertices = quad_vertices.map(|(position, uv, light)| {
+                            VoxelVertex {
+                                position,
+                                uv,
+                                light,
+                                normal: face.unit_typed(),
+                                layer,
+                            }
+                            .as_std430()
+                        });
+
+                        vertices.extend(quad_vertices);
                     }
                 }
             }
+            MaterialVisualDescriptor::Mesh { mesh } => {
+                // Push the mesh
+                for (quad, material) in mesh.iter_cloned() {
+                    let normal = quad.face.unit_typed();
+
+                    // Translate the quad relative to the block
+                    let quad = quad.translated(center_origin);
+
+                    // Decode the texture bounds
+                    let [uv_min, uv_max] = atlas.uv_rect(material);
+
+                    // Give it UVs
+                    let quad = quad
+                        .as_quad_ccw()
+                        .zip(QUAD_UVS.map(|v| uv_min + v * (uv_max - uv_min)));
+
+                    // Convert to triangles
+                    let [Tri([a, b, c]), Tri([d, e, f])] = quad.to_tris();
+                    let quad_vertices = [a, b, c, d, e, f];
+
+                    // Convert to std340
+                    let quad_vertices = quad_vertices.map(|(position, uv)| {
+                        VoxelVertex {
+                            position,
+                            uv,
+                            light: 1.,
+                            normal,
+                            layer: ATLAS_LAYER,
+                        }
+                        .as_std430()
+                    });
+
+                    // Write to the vertex buffer
+                    vertices.extend(quad_vertices);
+                }
+            }
+        }
+    }
+
+    vertices
+}
+
+/// Meshes a chunk at a reduced resolution where each cell of `2^lod` blocks per edge is
+/// collapsed into a single cube made of the cell's dominant material.
+///
+/// To avoid cracks where this chunk meets a neighbor meshed at a different LOD, faces on the
+/// chunk's boundary are never culled against the neighbor. These act as skirts which seal the
+/// chunk's volume regardless of how the neighboring chunk was decimated.
+fn mesh_chunk_decimated(
+    material_cache: &mut BlockMaterialCache<MaterialVisualDescriptor>,
+    atlas: &AtlasTexture,
+    data: &ChunkVoxelData,
+    lod: u8,
+) -> Vec<<VoxelVertex as GpuStruct>::Pod> {
+    let cell_size = 1 << lod;
+    let cells_per_edge = CHUNK_EDGE / cell_size;
+
+    let is_in_chunk =
+        |pos: IVec3| pos.cmpge(IVec3::ZERO).all() && pos.cmplt(IVec3::splat(cells_per_edge)).all();
+
+    let cell_index =
+        |pos: IVec3| (pos.x + pos.y * cells_per_edge + pos.z * cells_per_edge.pow(2)) as usize;
+
+    // Decimate the chunk into a grid of cells.
+    let mut cells = (0..cells_per_edge.pow(3)).map(|_| None).collect::<Vec<_>>();
+    let mut counts = Vec::new();
+
+    for cell in VolumetricIter::new_exclusive_iter([cells_per_edge as u32; 3]) {
+        let cell = UVec3::from_array(cell).as_ivec3();
+        cells[cell_index(cell)] = sample_lod_cell(
+            material_cache,
+            data,
+            BlockVec::from_glam(cell * cell_size),
+            cell_size,
+            &mut counts,
+        );
+    }
+
+    // Mesh the cells
+    let mut vertices = Vec::new();
+
+    for cell in VolumetricIter::new_exclusive_iter([cells_per_edge as u32; 3]) {
+        let cell = UVec3::from_array(cell).as_ivec3();
+        let Some(cell_data) = &cells[cell_index(cell)] else {
+            continue;
+        };
+
+        let cell_origin = WorldVec::compose(data.pos(), BlockVec::from_glam(cell * cell_size))
+            .to_glam()
+            .as_vec3();
+
+        for face in BlockFace::variants() {
+            // Faces facing into the chunk are only emitted for filled cells bordering a cell which
+            // isn't filled. Sparse cells still emit faces on the chunk boundary so that neighboring
+            // chunks meshed at a finer LOD don't see through the seam.
+            let neighbor = cell + face.unit_typed::<IVec3>();
+            if is_in_chunk(neighbor)
+                && (!cell_data.is_filled
+                    || cells[cell_index(neighbor)]
+                        .as_ref()
+                        .is_some_and(|neighbor| neighbor.is_filled))
+            {
+                continue;
+            }
 
-            // Replace the chunk mesh
-            let buffer = if !vertices.is_empty() {
-                Some(Arc::new(typed_wgpu::Buffer::create_init(
-                    &gfx.device,
-                    &typed_wgpu::BufferInitDescriptor {
-                        label: Some(format!("chunk mesh {:?}", data.pos()).as_str()),
-                        usage: wgpu::BufferUsages::VERTEX,
-                        contents: &vertices,
-                    },
-                )))
+            // Decode the texture bounds
+            let ([uv_min, uv_max], layer) = cell_data.textures[face].uv_rect_and_layer(atlas);
+
+            // Determine the quad origin
+            let quad_origin = if face.sign() == Sign::Positive {
+                cell_origin + face.axis().unit_f() * cell_size as f32
             } else {
-                None
+                cell_origin
             };
 
-            chunk.buffer = buffer;
-            chunk.vertex_count = vertices.len() as u32;
-
-            self.rendered_chunks.insert(chunk);
-
-            // Log some debug info
-            tracing::info!(
-                "Meshed {} {} for chunk {:?}",
-                vertices.len(),
-                if vertices.len() == 1 {
-                    "vertex"
-                } else {
-                    "vertices"
-                },
-                chunk,
-            );
-        });
+            // Construct the quad
+            let quad = AaQuad {
+                origin: quad_origin,
+                face,
+                size: (cell_size as f32, cell_size as f32),
+            };
+            let quad = quad
+                .as_quad_ccw()
+                .zip(QUAD_UVS.map(|v| uv_min + v * (uv_max - uv_min)));
+
+            let [Tri([a, b, c]), Tri([d, e, f])] = quad.to_tris();
+            let quad_vertices = [a, b, c, d, e, f].map(|(position, uv)| {
+                VoxelVertex {
+                    position,
+                    uv,
+                    light: 1.,
+                    normal: face.unit_typed(),
+                    layer,
+                }
+                .as_std430()
+            });
+
+            vertices.extend(quad_vertices);
+        }
     }
 
-    pub fn prepare_pass(&mut self) -> ChunkRenderPass {
-        let mut meshes = Vec::new();
-        self.rendered_chunks.retain(|chunk| {
-            if !chunk.is_alive() {
-                return false;
-            }
+    vertices
+}
 
-            if let Some(mesh) = &chunk.buffer {
-                meshes.push((mesh.clone(), chunk.vertex_count));
-            }
+/// A decimated cell of blocks produced by [`sample_lod_cell`].
+#[derive(Debug)]
+struct LodCell {
+    /// The cubic textures of the dominant material in the cell.
+    textures: IndexArray<BlockFace, CubeFaceTexture>,
 
-            true
-        });
+    /// Whether at least half of the cell is solid. Cells which aren't filled are only meshed on the
+    /// chunk boundary as a skirt.
+    is_filled: bool,
+}
 
-        ChunkRenderPass { meshes }
+/// Samples the cell of `cell_size` blocks per edge starting at `origin`, or returns `None` if the
+/// cell contains no solid blocks.
+///
+/// Materials which aren't [cubes](MaterialVisualDescriptor::is_cube) count as empty space since they cannot be meaningfully decimated.
+fn sample_lod_cell(
+    material_cache: &mut BlockMaterialCache<MaterialVisualDescriptor>,
+    data: &ChunkVoxelData,
+    origin: BlockVec,
+    cell_size: i32,
+    counts: &mut Vec<(BlockMaterial, u32)>,
+) -> Option<LodCell> {
+    counts.clear();
+    let mut solid = 0;
+
+    for offset in VolumetricIter::new_exclusive_iter([cell_size as u32; 3]) {
+        let pos = origin + BlockVec::from_glam(UVec3::from_array(offset).as_ivec3());
+        let material = data.block_or_air(pos).material;
+        if material.is_air() {
+            continue;
+        }
+
+        let Some(descriptor) = material_cache.get(material) else {
+            continue;
+        };
+
+        if !descriptor.is_cube() {
+            continue;
+        }
+
+        solid += 1;
+        match counts.iter_mut().find(|(other, _)| *other == material) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((material, 1)),
+        }
     }
+
+    let material = dominant_material(counts)?;
+
+    let descriptor = material_cache.get(material).unwrap();
+
+    Some(LodCell {
+        textures: BlockFace::variants()
+            .map(|face| descriptor.cube_face(face).unwrap())
+            .collect(),
+        is_filled: solid * 2 >= cell_size.pow(3),
+    })
+}
+
+/// Picks the material with the highest count. Ties are broken in favor of the material encountered
+/// first.
+fn dominant_material<M: Copy>(counts: &[(M, u32)]) -> Option<M> {
+    counts
+        .iter()
+        .copied()
+        .reduce(|best, other| if other.1 > best.1 { other } else { best })
+        .map(|(material, _)| material)
 }
 
 #[derive(Debug)]
 pub struct ChunkRenderPass {
-    meshes: Vec<(Arc<typed_wgpu::Buffer<VoxelVertex>>, u32)>,
+    meshes: Vec<ChunkDraw>,
+}
+
+#[derive(Debug)]
+struct ChunkDraw {
+    buffer: Arc<typed_wgpu::Buffer<VoxelVertex>>,
+    vertex_count: u32,
+    aabb: (Vec3, Vec3),
 }
 
 impl ChunkRenderPass {
+    /// Yields the meshes of every chunk whose bounds overlap `frustum`.
+    fn visible_meshes(&self, frustum: Frustum) -> impl Iterator<Item = &ChunkDraw> {
+        self.meshes
+            .iter()
+            .filter(move |draw| frustum.intersects_aabb(draw.aabb.0, draw.aabb.1))
+    }
+
     pub fn render_csm<'a>(
         &'a self,
         pipeline: &'a VoxelCsmPipeline,
@@ -281,9 +750,26 @@ impl ChunkRenderPass {
         pipeline.bind_pipeline(pass);
         pipeline.bind_group(pass, uniforms.common_bind_group(), &[]);
 
-        for (mesh, vertex_count) in &self.meshes {
-            pipeline.bind_vertex_buffer(pass, mesh.slice(..));
-            pass.draw(0..*vertex_count, 0..1);
+        // Chunks outside of the view can still cast shadows into it so these aren't culled.
+        for draw in &self.meshes {
+            pipeline.bind_vertex_buffer(pass, draw.buffer.slice(..));
+            pass.draw(0..draw.vertex_count, 0..1);
+        }
+    }
+
+    pub fn render_gbuffer<'a>(
+        &'a self,
+        pipeline: &'a VoxelGBufferPipeline,
+        uniforms: &'a VoxelUniforms,
+        frustum: Frustum,
+        pass: &mut wgpu::RenderPass<'a>,
+    ) {
+        pipeline.bind_pipeline(pass);
+        pipeline.bind_group(pass, uniforms.common_bind_group(), &[]);
+
+        for draw in self.visible_meshes(frustum) {
+            pipeline.bind_vertex_buffer(pass, draw.buffer.slice(..));
+            pass.draw(0..draw.vertex_count, 0..1);
         }
     }
 
@@ -293,8 +779,15 @@ impl ChunkRenderPass {
         gfx: &GfxContext,
         pipeline: &'p VoxelOpaquePipeline,
         uniforms: &'p VoxelUniforms,
+        frustum: Frustum,
         pass: &mut MultiPass<'_, 'p>,
     ) {
+        // Nothing gets written to the dynamic buffer if no chunks are visible and wgpu won't bind
+        // an empty buffer.
+        if self.visible_meshes(frustum).next().is_none() {
+            return;
+        }
+
         let dyn_bind_group = pass.alloc(|buffer| {
             VoxelChunkInstanceBindGroup {
                 buffer: BufferBinding::wrap(wgpu::BufferBinding {
@@ -312,7 +805,7 @@ impl ChunkRenderPass {
             pipeline.bind_group(pass, uniforms.opaque_bind_group(), &[]);
         });
 
-        for (mesh, vertex_count) in &self.meshes {
+        for draw in self.visible_meshes(frustum) {
             let offset = pass
                 .write_typed(gfx, || {
                     VoxelChunkUniformData { offset: Vec3::ZERO }.as_std430()
@@ -321,8 +814,8 @@ impl ChunkRenderPass {
 
             pass.draw(|pass| {
                 pipeline.bind_group(pass, dyn_bind_group.unwrap(), &(offset,));
-                pipeline.bind_vertex_buffer(pass, mesh.slice(..));
-                pass.draw(0..*vertex_count, 0..1);
+                pipeline.bind_vertex_buffer(pass, draw.buffer.slice(..));
+                pass.draw(0..draw.vertex_count, 0..1);
             });
         }
     }
@@ -332,7 +825,22 @@ impl ChunkRenderPass {
 
 #[derive(Debug, Default)]
 pub struct ChunkVoxelMesh {
-    dirty: bool,
+    queued: bool,
+    lod: u8,
+    lods: [Option<ChunkLodMesh>; CHUNK_LOD_COUNT],
+    cache_lookup: Option<CacheLookup>,
+}
+
+/// The progress of looking a chunk's mesh up in the [`ChunkMeshCache`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CacheLookup {
+    Pending { lod: u8, key: u64 },
+    Missed { lod: u8, key: u64 },
+}
+
+#[derive(Debug)]
+struct ChunkLodMesh {
+    is_stale: bool,
     vertex_count: u32,
     buffer: Option<Arc<typed_wgpu::Buffer<VoxelVertex>>>,
 }
@@ -349,12 +857,54 @@ impl ChunkVoxelMesh {
     }
 
     pub fn mark_dirty(mut self: Obj<Self>) {
-        if self.dirty {
+        // Keep rendering the old meshes until their replacements are ready.
+        for mesh in self.lods.iter_mut().flatten() {
+            mesh.is_stale = true;
+        }
+
+        // Any lookup in progress is for the chunk's old contents.
+        self.cache_lookup = None;
+
+        let mut world = self.world();
+        self.enqueue(&mut world.dirty_queue);
+    }
+
+    fn request_lod(mut self: Obj<Self>, queue: &mut ChunkQueue<Obj<ChunkVoxelMesh>>, lod: u8) {
+        if self.lod == lod {
+            return;
+        }
+
+        self.lod = lod;
+
+        if !matches!(&self.lods[lod as usize], Some(mesh) if !mesh.is_stale) {
+            self.enqueue(queue);
+        }
+    }
+
+    fn invalidate_meshes(mut self: Obj<Self>, queue: &mut ChunkQueue<Obj<ChunkVoxelMesh>>) {
+        self.lods = Default::default();
+        self.cache_lookup = None;
+        self.enqueue(queue);
+    }
+
+    fn enqueue(mut self: Obj<Self>, queue: &mut ChunkQueue<Obj<ChunkVoxelMesh>>) {
+        if self.queued {
             return;
         }
 
-        self.dirty = true;
-        self.world().dirty_queue.push(self);
+        self.queued = true;
+        queue.push(self);
+    }
+
+    /// Fetches the mesh for the chunk's current LOD or, if it hasn't been meshed yet, the cached
+    /// variant closest to it.
+    fn best_available_lod(&self) -> Option<&ChunkLodMesh> {
+        let lod = self.lod as usize;
+
+        (0..CHUNK_LOD_COUNT)
+            .filter(|&other| self.lods[other].is_some())
+            .min_by_key(|&other| other.abs_diff(lod))
+            .and_then(|other| self.lods[other].as_ref())
     }
 }
 
@@ -363,21 +913,68 @@ impl ChunkVoxelMesh {
 #[derive(Debug)]
 pub enum MaterialVisualDescriptor {
     Cubic {
-        textures: IndexArray<BlockFace, UVec2>,
+        textures: IndexArray<BlockFace, AtlasHandle>,
     },
     Mesh {
-        mesh: QuadMeshLayer<UVec2>,
+        mesh: QuadMeshLayer<AtlasHandle>,
+    },
+    /// A cube whose faces are sampled from layers of the renderer's block
+    /// [`TextureArray`](wgpu_ext::TextureArray) rather than from the atlas. Layers are allocated
+    /// with [`GlobalRenderer::push_srgb_to_layers`](super::GlobalRenderer::push_srgb_to_layers).
+    Layered {
+        layers: IndexArray<BlockFace, u32>,
     },
 }
 
 random_component!(MaterialVisualDescriptor);
 
 impl MaterialVisualDescriptor {
-    pub fn cubic_simple(atlas: UVec2) -> Self {
+    pub fn cubic_simple(atlas: AtlasHandle) -> Self {
         Self::Cubic {
             textures: IndexArray::new([atlas; BlockFace::COUNT]),
         }
     }
+
+    pub fn layered_simple(layer: u32) -> Self {
+        Self::Layered {
+            layers: IndexArray::new([layer; BlockFace::COUNT]),
+        }
+    }
+
+    /// Whether the material fills its entire block, hiding the faces of neighboring cubes.
+    fn is_cube(&self) -> bool {
+        matches!(self, Self::Cubic { .. } | Self::Layered { .. })
+    }
+
+    /// Fetches the texture of the given face of a [cube](Self::is_cube).
+    fn cube_face(&self, face: BlockFace) -> Option<CubeFaceTexture> {
+        match self {
+            Self::Cubic { textures } => Some(CubeFaceTexture::Atlas(textures[face])),
+            Self::Layered { layers } => Some(CubeFaceTexture::Layer(layers[face])),
+            Self::Mesh { .. } => None,
+        }
+    }
+}
+
+/// The value of [`VoxelVertex::layer`] for vertices which sample the atlas.
+const ATLAS_LAYER: i32 = -1;
+
+/// Where the face of a cube samples its texture from.
+#[derive(Debug, Copy, Clone)]
+enum CubeFaceTexture {
+    Atlas(AtlasHandle),
+    Layer(u32),
+}
+
+impl CubeFaceTexture {
+    /// Fetches the `[min, max]` bounds of the face's texture alongside the texture array layer
+    /// they're relative to.
+    fn uv_rect_and_layer(self, atlas: &AtlasTexture) -> ([Vec2; 2], i32) {
+        match self {
+            Self::Atlas(handle) => (atlas.uv_rect(handle), ATLAS_LAYER),
+            Self::Layer(layer) => ([Vec2::ZERO, Vec2::ONE], layer as i32),
+        }
+    }
 }
 
 // === Systems === //
@@ -435,3 +1032,366 @@ pub fn sys_queue_dirty_chunks_for_render(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, marker::PhantomData};
+
+    use bevy_autoken::{spawn_entity, RandomArena, RandomWorldExt as _, SendsEvent};
+    use bevy_ecs::{event::Events, world::World};
+    use crucible_world::voxel::{BlockData, ChunkData};
+    use image::Rgba32FImage;
+    use typed_glam::glam::UVec2;
+
+    use super::*;
+
+    #[test]
+    fn lod_increases_with_distance() {
+        let distances = DEFAULT_LOD_DISTANCES;
+        let center = Vec3::splat(CHUNK_EDGE as f32 / 2.);
+
+        assert_eq!(chunk_lod(&distances, ChunkVec::ZERO, center), 0);
+        assert_eq!(chunk_lod(&distances, ChunkVec::new(8, 0, 0), center), 1);
+        assert_eq!(chunk_lod(&distances, ChunkVec::new(16, 0, 0), center), 2);
+    }
+
+    #[test]
+    fn dominant_material_picks_majority() {
+        assert_eq!(dominant_material::<u32>(&[]), None);
+        assert_eq!(dominant_material(&[(1, 2), (2, 5), (3, 1)]), Some(2));
+        assert_eq!(dominant_material(&[(1, 3), (2, 3)]), Some(1));
+    }
+
+    #[test]
+    fn decimated_meshes_merge_cells_and_emit_skirts() {
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<MaterialVisualDescriptor>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                &mut BlockMaterialRegistry,
+                &MaterialVisualDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut atlas = AtlasTexture::new(UVec2::splat(1), UVec2::splat(1), 1);
+                let texture = atlas.add(&Rgba32FImage::new(1, 1));
+
+                let root = spawn_entity(());
+                let voxels = root.insert(WorldVoxelData::default());
+                let mut registry = root.insert(BlockMaterialRegistry::default());
+                let _air = registry.register("crucible:air", spawn_entity(()));
+                let stone = registry.register(
+                    "crucible:stone",
+                    spawn_entity(()).with(MaterialVisualDescriptor::cubic_simple(texture)),
+                );
+                let mut material_cache = MaterialCache::new(registry);
+
+                let mut chunk = voxels.get_or_insert(ChunkVec::ZERO);
+                chunk.initialize_data(ChunkData::AllAir);
+
+                // A 2x2x2 cube which fills exactly one LOD1 cell...
+                for pos in VolumetricIter::new_exclusive_iter([2; 3]) {
+                    let pos = BlockVec::from_glam(UVec3::from_array(pos).as_ivec3());
+                    chunk.set_block_no_dirty(pos, BlockData::new(stone));
+                }
+
+                // ...and a lone block on the chunk boundary which only fills an eighth of its cell.
+                chunk.set_block_no_dirty(BlockVec::new(15, 4, 4), BlockData::new(stone));
+
+                let face_count = |vertices: &[<VoxelVertex as GpuStruct>::Pod]| vertices.len() / 6;
+
+                let full = mesh_chunk_full(&mut material_cache, &atlas, &chunk);
+                assert_eq!(face_count(&full), 6 * 4 + 6);
+
+                // The lone block's cell only emits its skirt face on the chunk boundary.
+                let decimated = mesh_chunk_decimated(&mut material_cache, &atlas, &chunk, 1);
+                assert_eq!(face_count(&decimated), 6 + 1);
+
+                // Sparse cells away from the chunk boundary don't emit a skirt.
+                chunk.set_block_no_dirty(BlockVec::new(15, 4, 4), BlockData::AIR);
+                chunk.set_block_no_dirty(BlockVec::new(9, 4, 4), BlockData::new(stone));
+
+                let decimated = mesh_chunk_decimated(&mut material_cache, &atlas, &chunk, 1);
+                assert_eq!(face_count(&decimated), 6);
+            },
+        );
+    }
+
+    #[test]
+    fn layered_materials_sample_their_layers() {
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<MaterialVisualDescriptor>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                &mut BlockMaterialRegistry,
+                &MaterialVisualDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut atlas = AtlasTexture::new(UVec2::splat(1), UVec2::splat(1), 1);
+                let texture = atlas.add(&Rgba32FImage::new(1, 1));
+
+                let root = spawn_entity(());
+                let voxels = root.insert(WorldVoxelData::default());
+                let mut registry = root.insert(BlockMaterialRegistry::default());
+                let _air = registry.register("crucible:air", spawn_entity(()));
+                let stone = registry.register(
+                    "crucible:stone",
+                    spawn_entity(()).with(MaterialVisualDescriptor::cubic_simple(texture)),
+                );
+                let glass = registry.register(
+                    "crucible:glass",
+                    spawn_entity(()).with(MaterialVisualDescriptor::Layered {
+                        layers: BlockFace::variants().map(|face| face as u32).collect(),
+                    }),
+                );
+                let mut material_cache = MaterialCache::new(registry);
+
+                let mut chunk = voxels.get_or_insert(ChunkVec::ZERO);
+                chunk.initialize_data(ChunkData::AllAir);
+                chunk.set_block_no_dirty(BlockVec::new(4, 4, 4), BlockData::new(glass));
+                chunk.set_block_no_dirty(BlockVec::new(5, 4, 4), BlockData::new(stone));
+
+                // Layered blocks and atlas blocks hide the faces they share...
+                let full = mesh_chunk_full(&mut material_cache, &atlas, &chunk);
+                assert_eq!(full.len(), 6 * 10);
+
+                // ...and each face of the layered block samples its own layer in its entirety.
+                let layered = full
+                    .iter()
+                    .map(|&vertex| VoxelVertex::from_std430(vertex))
+                    .filter(|vertex| vertex.layer != ATLAS_LAYER)
+                    .collect::<Vec<_>>();
+                assert_eq!(layered.len(), 6 * 5);
+
+                for vertex in &layered {
+                    let face = BlockFace::variants()
+                        .find(|&face| face.unit_typed::<Vec3>() == vertex.normal)
+                        .unwrap();
+
+                    assert_ne!(face, BlockFace::PositiveX);
+                    assert_eq!(vertex.layer, face as i32);
+                    assert!([0., 1.].contains(&vertex.uv.x) && [0., 1.].contains(&vertex.uv.y));
+                }
+
+                // Decimated cells keep sampling the layers of their dominant material.
+                for pos in VolumetricIter::new_exclusive_iter([2; 3]) {
+                    let pos = BlockVec::from_glam(UVec3::from_array(pos).as_ivec3());
+                    chunk.set_block_no_dirty(pos, BlockData::new(glass));
+                }
+
+                let decimated = mesh_chunk_decimated(&mut material_cache, &atlas, &chunk, 1);
+                let layers = decimated
+                    .iter()
+                    .map(|&vertex| VoxelVertex::from_std430(vertex).layer)
+                    .filter(|&layer| layer != ATLAS_LAYER)
+                    .collect::<FxHashSet<_>>();
+                assert_eq!(layers.len(), BlockFace::COUNT);
+            },
+        );
+    }
+
+    fn load_cached(cache: &ChunkMeshCache, lod: u8, key: u64) -> Option<Vec<u8>> {
+        cache.request_load(ChunkVec::ZERO, lod, key);
+        cache.flush();
+        cache.poll_loaded().pop().unwrap().mesh
+    }
+
+    #[test]
+    fn cached_meshes_are_keyed_by_content() {
+        let dir = std::env::temp_dir().join(format!("crucible-mesh-cache-{}", std::process::id()));
+        let cache = ChunkMeshCache::new(&dir, CHUNK_MESH_FORMAT_VERSION);
+
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<MaterialVisualDescriptor>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                &mut BlockMaterialRegistry,
+                &MaterialVisualDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut atlas = AtlasTexture::new(UVec2::splat(1), UVec2::new(2, 1), 1);
+                let texture = atlas.add(&Rgba32FImage::new(1, 1));
+
+                let root = spawn_entity(());
+                let voxels = root.insert(WorldVoxelData::default());
+                let mut registry = root.insert(BlockMaterialRegistry::default());
+                let _air = registry.register("crucible:air", spawn_entity(()));
+                let stone = registry.register(
+                    "crucible:stone",
+                    spawn_entity(()).with(MaterialVisualDescriptor::cubic_simple(texture)),
+                );
+                let mut material_cache = MaterialCache::new(registry);
+
+                for pos in [ChunkVec::ZERO, ChunkVec::X] {
+                    voxels.get_or_insert(pos).initialize_data(ChunkData::AllAir);
+                }
+
+                let mut chunk = voxels.get(ChunkVec::ZERO).unwrap();
+                chunk.set_block_no_dirty(BlockVec::new(3, 3, 3), BlockData::new(stone));
+
+                // Cache the chunk's mesh.
+                let fingerprint = mesh_fingerprint(&mut material_cache, &atlas);
+                let key = chunk_mesh_key(&chunk, fingerprint);
+
+                let meshed = mesh_chunk(&mut material_cache, &atlas, &chunk, 0);
+                assert_eq!(meshed.len(), 6 * 6);
+
+                cache.store(
+                    ChunkVec::ZERO,
+                    0,
+                    key,
+                    bytemuck::cast_slice(&meshed).to_vec(),
+                );
+                assert_eq!(
+                    load_cached(&cache, 0, key).as_deref(),
+                    Some(bytemuck::cast_slice::<_, u8>(&meshed))
+                );
+                assert_eq!(load_cached(&cache, 1, key), None);
+
+                // The cached mesh is rejected if the mesher changed...
+                let bumped = ChunkMeshCache::new(&dir, CHUNK_MESH_FORMAT_VERSION + 1);
+                assert_eq!(load_cached(&bumped, 0, key), None);
+                drop(bumped);
+
+                // ...or if the atlas was laid out differently...
+                let mut resized = AtlasTexture::new(UVec2::splat(1), UVec2::new(2, 2), 1);
+                assert_eq!(resized.add(&Rgba32FImage::new(1, 1)), texture);
+
+                let resized_fingerprint = mesh_fingerprint(&mut material_cache, &resized);
+                assert_ne!(resized_fingerprint, fingerprint);
+                assert_eq!(
+                    load_cached(&cache, 0, chunk_mesh_key(&chunk, resized_fingerprint)),
+                    None
+                );
+
+                // ...or if materials were registered in a different order...
+                let mut reordered = spawn_entity(()).insert(BlockMaterialRegistry::default());
+                reordered.register("crucible:air", spawn_entity(()));
+                reordered.register("crucible:dirt", registry.lookup_by_idx(stone));
+                reordered.register("crucible:stone", registry.lookup_by_idx(stone));
+                assert_ne!(
+                    mesh_fingerprint(&mut MaterialCache::new(reordered), &atlas),
+                    fingerprint
+                );
+
+                // ...or if a neighboring chunk was edited.
+                voxels
+                    .get(ChunkVec::X)
+                    .unwrap()
+                    .set_block_no_dirty(BlockVec::new(0, 3, 3), BlockData::new(stone));
+
+                let edited_key = chunk_mesh_key(&chunk, fingerprint);
+                assert_ne!(edited_key, key);
+                assert_eq!(load_cached(&cache, 0, edited_key), None);
+            },
+        );
+
+        drop(cache);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn mesher_reuses_cached_meshes() {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+
+        let dir =
+            std::env::temp_dir().join(format!("crucible-mesher-cache-{}", std::process::id()));
+
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<WorldVoxelMesh>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelMesh>>();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<MaterialVisualDescriptor>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut WorldVoxelMesh,
+                &mut ChunkVoxelData,
+                &mut ChunkVoxelMesh,
+                &mut BlockMaterialRegistry,
+                &MaterialVisualDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut atlas = AtlasTexture::new(UVec2::splat(1), UVec2::splat(1), 1);
+                let texture = atlas.add(&Rgba32FImage::new(1, 1));
+
+                let root = spawn_entity(());
+                let voxels = root.insert(WorldVoxelData::default());
+                let mut registry = root.insert(BlockMaterialRegistry::default());
+                let _air = registry.register("crucible:air", spawn_entity(()));
+                let stone = registry.register(
+                    "crucible:stone",
+                    spawn_entity(()).with(MaterialVisualDescriptor::cubic_simple(texture)),
+                );
+
+                let mut mesher = root.insert(WorldVoxelMesh::new(registry));
+                mesher.set_disk_cache(&dir);
+
+                let mut chunk = voxels.get_or_insert(ChunkVec::ZERO);
+                chunk.initialize_data(ChunkData::AllAir);
+                chunk.set_block_no_dirty(BlockVec::new(3, 3, 3), BlockData::new(stone));
+
+                let chunk_mesh = chunk.entity().insert(ChunkVoxelMesh::default());
+                let vertex_count = || chunk_mesh.lods[0].as_ref().map(|mesh| mesh.vertex_count);
+
+                // Waits for the cache's thread to answer our lookups before updating again.
+                let (gfx, atlas) = (&gfx, &atlas);
+                let update = move || {
+                    let mut mesher = mesher;
+                    mesher.update(gfx, atlas, Vec3::ZERO, Some(Duration::ZERO));
+                    mesher.disk_cache.as_ref().unwrap().flush();
+                };
+
+                // The first update only looks the chunk up, the second one finds that it missed
+                // and meshes it, and that mesh is cached.
+                chunk_mesh.mark_dirty();
+                update();
+                assert_eq!(vertex_count(), None);
+                update();
+                assert_eq!(vertex_count(), Some(6 * 6));
+
+                // Replace the cached mesh with an empty one to tell it apart from a fresh mesh.
+                let fingerprint = mesh_fingerprint(&mut MaterialCache::new(registry), atlas);
+                let key = chunk_mesh_key(&chunk, fingerprint);
+                let cache = mesher.disk_cache.as_ref().unwrap();
+                cache.store(ChunkVec::ZERO, 0, key, Vec::new());
+                cache.flush();
+
+                // Re-meshing the unchanged chunk installs the cached mesh once it has loaded.
+                chunk_mesh.mark_dirty();
+                update();
+                update();
+                assert_eq!(vertex_count(), Some(0));
+            },
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,6 +1,8 @@
+use std::{fmt, marker::PhantomData};
+
 use crucible_utils::traits::ArrayLike;
 use typed_glam::{
-    glam::{self, BVec2, Vec2},
+    glam::{self, BVec2, Vec2, Vec3, Vec4},
     traits::SignedNumericVector3,
     typed::{FlavorCastFrom, TypedVector, VecFlavor},
 };
@@ -257,6 +259,155 @@ impl FlavorCastFrom<glam::Vec4> for Color4Flavor {
     }
 }
 
+// === Color Spaces === //
+
+/// Decodes a single sRGB-encoded channel in the range `[0, 1]` into linear space.
+pub fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a single linear channel in the range `[0, 1]` into sRGB space.
+pub fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear_vec3(v: Vec3) -> Vec3 {
+    Vec3::from_array(v.to_array().map(srgb_to_linear))
+}
+
+pub fn linear_to_srgb_vec3(v: Vec3) -> Vec3 {
+    Vec3::from_array(v.to_array().map(linear_to_srgb))
+}
+
+/// Decodes the color channels of an sRGB color into linear space. Alpha is always linear and is
+/// left untouched.
+pub fn srgb_to_linear_vec4(v: Vec4) -> Vec4 {
+    srgb_to_linear_vec3(v.truncate()).extend(v.w)
+}
+
+/// Encodes the color channels of a linear color into sRGB space. Alpha is always linear and is
+/// left untouched.
+pub fn linear_to_srgb_vec4(v: Vec4) -> Vec4 {
+    linear_to_srgb_vec3(v.truncate()).extend(v.w)
+}
+
+pub trait ColorSpace: Sized + 'static {
+    const DEBUG_NAME: &'static str;
+
+    fn to_linear(rgba: Vec4) -> Vec4;
+
+    fn from_linear(rgba: Vec4) -> Vec4;
+}
+
+#[non_exhaustive]
+pub struct Linear;
+
+impl ColorSpace for Linear {
+    const DEBUG_NAME: &'static str = "Linear";
+
+    fn to_linear(rgba: Vec4) -> Vec4 {
+        rgba
+    }
+
+    fn from_linear(rgba: Vec4) -> Vec4 {
+        rgba
+    }
+}
+
+#[non_exhaustive]
+pub struct Srgb;
+
+impl ColorSpace for Srgb {
+    const DEBUG_NAME: &'static str = "Srgb";
+
+    fn to_linear(rgba: Vec4) -> Vec4 {
+        srgb_to_linear_vec4(rgba)
+    }
+
+    fn from_linear(rgba: Vec4) -> Vec4 {
+        linear_to_srgb_vec4(rgba)
+    }
+}
+
+pub type LinearColor = Color<Linear>;
+pub type SrgbColor = Color<Srgb>;
+
+/// An RGBA color tagged with the [`ColorSpace`] its channels are encoded in so that colors from
+/// different spaces can't be mixed without an explicit conversion.
+pub struct Color<S: ColorSpace> {
+    _ty: PhantomData<fn() -> S>,
+    rgba: Vec4,
+}
+
+impl<S: ColorSpace> fmt::Debug for Color<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple(S::DEBUG_NAME).field(&self.rgba).finish()
+    }
+}
+
+impl<S: ColorSpace> Copy for Color<S> {}
+
+impl<S: ColorSpace> Clone for Color<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: ColorSpace> PartialEq for Color<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.rgba == other.rgba
+    }
+}
+
+impl<S: ColorSpace> Color<S> {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::from_vec4(Vec4::new(r, g, b, a))
+    }
+
+    pub const fn from_vec3(rgb: Vec3) -> Self {
+        Self::from_vec4(Vec4::new(rgb.x, rgb.y, rgb.z, 1.))
+    }
+
+    pub const fn from_vec4(rgba: Vec4) -> Self {
+        Self {
+            _ty: PhantomData,
+            rgba,
+        }
+    }
+
+    pub fn from_array(rgba: [f32; 4]) -> Self {
+        Self::from_vec4(Vec4::from_array(rgba))
+    }
+
+    pub fn rgb(self) -> Vec3 {
+        self.rgba.truncate()
+    }
+
+    pub fn rgba(self) -> Vec4 {
+        self.rgba
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        self.rgba.to_array()
+    }
+
+    pub fn to_linear(self) -> LinearColor {
+        LinearColor::from_vec4(S::to_linear(self.rgba))
+    }
+
+    pub fn to_srgb(self) -> SrgbColor {
+        SrgbColor::from_vec4(Srgb::from_linear(S::to_linear(self.rgba)))
+    }
+}
+
 // === UI Coordinate === //
 
 pub type UiVec = TypedVector<UiVecFlavor>;
@@ -287,3 +438,39 @@ impl FlavorCastFrom<glam::Vec2> for UiVecFlavor {
         UiVec::from_glam(vec)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} != {b}");
+    }
+
+    #[test]
+    fn srgb_conversions_match_reference() {
+        // Reference values from the sRGB transfer function.
+        assert_close(srgb_to_linear(0.), 0.);
+        assert_close(srgb_to_linear(0.5), 0.214);
+        assert_close(srgb_to_linear(1.), 1.);
+        assert_close(linear_to_srgb(0.214), 0.5);
+        assert_close(linear_to_srgb(0.18), 0.461);
+
+        for i in 0..=255 {
+            let v = i as f32 / 255.;
+            assert_close(linear_to_srgb(srgb_to_linear(v)), v);
+        }
+    }
+
+    #[test]
+    fn color_conversion_preserves_alpha() {
+        let color = SrgbColor::new(0.5, 0.5, 1., 0.5).to_linear();
+        assert_close(color.rgba().x, 0.214);
+        assert_close(color.rgba().z, 1.);
+        assert_eq!(color.rgba().w, 0.5);
+
+        let round_trip = color.to_srgb();
+        assert_close(round_trip.rgba().x, 0.5);
+        assert_eq!(round_trip.rgba().w, 0.5);
+    }
+}
@@ -192,3 +192,155 @@ pub fn update_kinematic(
         update_velocity(velocity, acceleration, friction_coef, time),
     )
 }
+
+// === Gravity === //
+
+/// World-wide gravity parameters applied by [`KinematicBody::integrate`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GravitySettings {
+    /// The downward acceleration due to gravity, measured in `blocks * second^-2`.
+    pub accel: f64,
+
+    /// The maximum downward speed gravity can accelerate a body to, measured in
+    /// `blocks * second^-1`.
+    pub terminal_velocity: f64,
+
+    /// The friction coefficient applied to airborne bodies, measured in `second^-1`.
+    pub air_friction: EntityVec,
+
+    /// The friction coefficient applied to grounded bodies, measured in `second^-1`.
+    pub ground_friction: EntityVec,
+}
+
+impl Default for GravitySettings {
+    fn default() -> Self {
+        // These mirror Minecraft's player physics.
+        Self {
+            accel: 0.08 * MC_TICKS_TO_SECS_SQUARED,
+            terminal_velocity: 3.92 * MC_TICKS_TO_SECS,
+            air_friction: tick_friction_coef_to_coef_qty(
+                EntityVec::new(0.91, 0.98, 0.91),
+                MC_TICKS_TO_SECS,
+            ),
+            ground_friction: tick_friction_coef_to_coef_qty(
+                EntityVec::new(0.6 * 0.91, 0.98, 0.6 * 0.91),
+                MC_TICKS_TO_SECS,
+            ),
+        }
+    }
+}
+
+/// The kinematic state of a single body.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct KinematicBody {
+    /// The body's velocity, measured in `blocks * second^-1`.
+    pub velocity: EntityVec,
+
+    /// Whether [`GravitySettings::accel`] applies to this body. Flying players and projectiles
+    /// with their own ballistics opt out of this.
+    pub affected_by_gravity: bool,
+
+    /// Whether the body is resting on the ground, selecting between the air and ground friction
+    /// coefficients.
+    pub on_ground: bool,
+}
+
+impl Default for KinematicBody {
+    fn default() -> Self {
+        Self {
+            velocity: EntityVec::ZERO,
+            affected_by_gravity: true,
+            on_ground: false,
+        }
+    }
+}
+
+impl KinematicBody {
+    /// Advances the body's velocity by `time` seconds under the environmental `acceleration` and
+    /// `gravity` and returns the position delta it travelled. Downward speed gained from gravity
+    /// is clamped to [`GravitySettings::terminal_velocity`].
+    ///
+    /// See [`update_velocity_axis`] for the kinematic considerations of this integration.
+    #[must_use]
+    pub fn integrate(
+        &mut self,
+        mut acceleration: EntityVec,
+        gravity: &GravitySettings,
+        time: f64,
+    ) -> EntityVec {
+        if self.affected_by_gravity {
+            *acceleration.y_mut() -= gravity.accel;
+        }
+
+        let friction = if self.on_ground {
+            gravity.ground_friction
+        } else {
+            gravity.air_friction
+        };
+
+        let start_velocity = self.velocity;
+        let (mut delta, velocity) = update_kinematic(start_velocity, acceleration, friction, time);
+        self.velocity = velocity;
+
+        if self.affected_by_gravity {
+            let terminal = gravity.terminal_velocity;
+
+            // Bodies already moving faster than terminal velocity (e.g. from an explosion) keep
+            // their speed but gravity cannot add to it.
+            let floor = (-terminal).min(start_velocity.y());
+            *self.velocity.y_mut() = self.velocity.y().max(floor);
+            *delta.y_mut() = delta.y().max(floor * time);
+        }
+
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falling_bodies_approach_terminal_velocity() {
+        let gravity = GravitySettings {
+            accel: 30.0,
+            terminal_velocity: 50.0,
+            air_friction: EntityVec::ZERO,
+            ground_friction: EntityVec::ZERO,
+        };
+
+        let mut body = KinematicBody::default();
+        let mut fallen = 0.0;
+
+        for _ in 0..600 {
+            let delta = body.integrate(EntityVec::ZERO, &gravity, 1.0 / 120.0);
+            fallen -= delta.y();
+
+            assert!(body.velocity.y() <= 0.0);
+            assert!(body.velocity.y() >= -gravity.terminal_velocity);
+        }
+
+        assert!((body.velocity.y() + gravity.terminal_velocity).abs() < 1e-9);
+
+        // The body spent 50 / 30 seconds accelerating and the remainder at terminal velocity.
+        let accel_time = gravity.terminal_velocity / gravity.accel;
+        let expected = gravity.accel * accel_time * accel_time / 2.0
+            + gravity.terminal_velocity * (5.0 - accel_time);
+        assert!((fallen - expected).abs() < 0.5, "{fallen} vs {expected}");
+    }
+
+    #[test]
+    fn bodies_can_opt_out_of_gravity() {
+        let gravity = GravitySettings::default();
+        let mut body = KinematicBody {
+            velocity: EntityVec::new(1.0, 0.0, 0.0),
+            affected_by_gravity: false,
+            on_ground: false,
+        };
+
+        let delta = body.integrate(EntityVec::ZERO, &gravity, 1.0);
+        assert_eq!(delta.y(), 0.0);
+        assert_eq!(body.velocity.y(), 0.0);
+        assert!(body.velocity.x() > 0.0 && body.velocity.x() < 1.0);
+    }
+}
@@ -1,11 +1,14 @@
-use crucible_utils::iter::VolumetricIter;
+use crucible_utils::{iter::VolumetricIter, newtypes::EnumIndex as _};
 use num_traits::Signed;
 use typed_glam::{
     glam,
     traits::{NumericVector3, SignedNumericVector2, SignedNumericVector3},
 };
 
-use crate::{lerp_percent_at, BlockFace, EntityVec, EntityVecExt, Sign, VecCompExt, WorldVec};
+use crate::{
+    lerp_percent_at, Axis3, BlockFace, EntityVec, EntityVecExt, Sign, VecCompExt, WorldVec,
+    WorldVecExt,
+};
 
 // === Line3 === //
 
@@ -327,6 +330,31 @@ impl<V: SignedNumericVector3> Aabb3<V> {
         .contains(other.origin)
     }
 
+    /// Computes the region covered by both AABBs, returning `None` if they don't overlap. Like
+    /// [`intersects`](Self::intersects), AABBs which merely touch aren't considered to overlap.
+    #[must_use]
+    pub fn intersection(&self, other: Self) -> Option<Self>
+    where
+        V::Comp: PartialOrd,
+    {
+        let min = self.origin.max(other.origin);
+        let max = self.max_corner().min(other.max_corner());
+
+        (min.x() < max.x() && min.y() < max.y() && min.z() < max.z()).then(|| Self {
+            origin: min,
+            size: max - min,
+        })
+    }
+
+    /// Computes the smallest AABB enclosing both AABBs.
+    #[must_use]
+    pub fn union(&self, other: Self) -> Self {
+        Self::from_corners_max_excl(
+            self.origin.min(other.origin),
+            self.max_corner().max(other.max_corner()),
+        )
+    }
+
     #[must_use]
     pub fn offset_by(&self, delta: V) -> Self {
         Self {
@@ -337,6 +365,72 @@ impl<V: SignedNumericVector3> Aabb3<V> {
 }
 
 impl EntityAabb {
+    #[must_use]
+    pub fn from_center_half_extents(center: EntityVec, half_extents: EntityVec) -> Self {
+        Self {
+            origin: center - half_extents,
+            size: half_extents * 2.,
+        }
+    }
+
+    /// Computes the tightest bounds enclosing every point, returning `None` if the iterator is
+    /// empty. A single point produces a zero-sized AABB at that point.
+    #[must_use]
+    pub fn from_points(points: impl IntoIterator<Item = EntityVec>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), point| {
+            (min.min(point), max.max(point))
+        });
+
+        Some(Self {
+            origin: min,
+            size: max - min,
+        })
+    }
+
+    #[must_use]
+    pub fn center(&self) -> EntityVec {
+        self.origin + self.half_extents()
+    }
+
+    #[must_use]
+    pub fn half_extents(&self) -> EntityVec {
+        self.size * 0.5
+    }
+
+    /// Computes the fraction of `delta` this AABB can move before it starts overlapping `target`,
+    /// returning `None` if it never does. AABBs which already overlap have a time of impact of
+    /// zero while AABBs which merely touch are not considered to be colliding.
+    #[must_use]
+    pub fn sweep_toi(&self, delta: EntityVec, target: EntityAabb) -> Option<f64> {
+        // Sweeping a box against a box is equivalent to casting our origin against the target grown
+        // by our size.
+        let min = target.origin - self.size;
+        let max = target.max_corner();
+
+        let mut enter = f64::NEG_INFINITY;
+        let mut exit = f64::INFINITY;
+
+        for axis in Axis3::variants() {
+            let (start, delta) = (self.origin.comp(axis), delta.comp(axis));
+            let (min, max) = (min.comp(axis), max.comp(axis));
+
+            if delta == 0. {
+                if start <= min || start >= max {
+                    return None;
+                }
+                continue;
+            }
+
+            let (a, b) = ((min - start) / delta, (max - start) / delta);
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+
+        (enter < exit && enter < 1. && exit > 0.).then_some(enter.max(0.))
+    }
+
     pub fn as_blocks(&self) -> WorldAabb {
         let max_corner = self.max_corner();
         let nudge_mask = max_corner.fract().cmpeq(EntityVec::ZERO);
@@ -364,3 +458,192 @@ impl WorldAabb {
         .map(move |[x, y, z]| self.origin + WorldVec::new(x as i32, y as i32, z as i32))
     }
 }
+
+// === Shape === //
+
+/// A solid region of entity-space which can be rasterized into blocks.
+#[derive(Debug, Copy, Clone)]
+pub enum Shape {
+    Box(EntityAabb),
+    Sphere { center: EntityVec, radius: f64 },
+    Ellipsoid { center: EntityVec, radii: EntityVec },
+}
+
+impl Shape {
+    #[must_use]
+    pub fn bounds(&self) -> EntityAabb {
+        match *self {
+            Shape::Box(aabb) => aabb,
+            Shape::Sphere { center, radius } => {
+                EntityAabb::from_center_half_extents(center, EntityVec::splat(radius))
+            }
+            Shape::Ellipsoid { center, radii } => {
+                EntityAabb::from_center_half_extents(center, radii)
+            }
+        }
+    }
+
+    /// Determines whether the point lies within the shape. Boxes follow [`Aabb3::contains`]'s
+    /// half-open convention so that adjacent boxes never share a point while spheres and ellipsoids
+    /// include the points on their surface.
+    #[must_use]
+    pub fn contains(&self, point: EntityVec) -> bool {
+        match *self {
+            Shape::Box(aabb) => aabb.contains(point),
+            Shape::Sphere { center, radius } => {
+                (point - center).length_squared() <= radius * radius
+            }
+            Shape::Ellipsoid { center, radii } => ((point - center) / radii).length_squared() <= 1.,
+        }
+    }
+
+    /// Yields the position of every block whose center lies within the shape according to
+    /// [`contains`](Self::contains).
+    pub fn voxelize(self) -> impl Iterator<Item = WorldVec> {
+        // Find the range of blocks whose centers lie within the shape's bounds, inclusive on both
+        // ends so that `contains` gets the final say on the boundary.
+        let bounds = self.bounds();
+        let half = EntityVec::splat(0.5);
+        let min = (bounds.origin - half).ceil().block_pos();
+        let max = (bounds.max_corner() - half).floor().block_pos();
+
+        min.cmple(max)
+            .all()
+            .then(|| WorldAabb::from_blocks_corners(min, max))
+            .into_iter()
+            .flat_map(WorldAabb::iter_blocks)
+            .filter(move |block| self.contains(block.negative_most_corner() + half))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_and_half_extents_round_trip() {
+        let center = EntityVec::new(1.5, -2.0, 8.25);
+        let half = EntityVec::new(0.3, 0.9, 0.3);
+        let aabb = EntityAabb::from_center_half_extents(center, half);
+
+        assert!(aabb.center().abs_diff_eq(center, 1e-9));
+        assert!(aabb.half_extents().abs_diff_eq(half, 1e-9));
+        assert!(aabb
+            .origin
+            .abs_diff_eq(EntityVec::new(1.2, -2.9, 7.95), 1e-9));
+    }
+
+    #[test]
+    fn point_cloud_bounds() {
+        let aabb = EntityAabb::from_points([
+            EntityVec::new(1.0, 0.0, -1.0),
+            EntityVec::new(-2.0, 3.0, 0.5),
+            EntityVec::new(0.0, -1.0, 2.0),
+        ])
+        .unwrap();
+
+        assert_eq!(aabb.origin, EntityVec::new(-2.0, -1.0, -1.0));
+        assert_eq!(aabb.max_corner(), EntityVec::new(1.0, 3.0, 2.0));
+
+        let point = EntityVec::new(4.0, 5.0, 6.0);
+        let degenerate = EntityAabb::from_points([point]).unwrap();
+        assert_eq!(degenerate.origin, point);
+        assert_eq!(degenerate.size, EntityVec::ZERO);
+        assert_eq!(degenerate.center(), point);
+
+        assert!(EntityAabb::from_points([]).is_none());
+    }
+
+    #[test]
+    fn block_regions_iterate_and_intersect() {
+        let a = WorldAabb::from_blocks_corners(WorldVec::new(2, 0, -1), WorldVec::new(0, 1, -1));
+        assert_eq!(a.origin, WorldVec::new(0, 0, -1));
+        assert_eq!(a.size, WorldVec::new(3, 2, 1));
+
+        // Both corners are included in the iteration.
+        let blocks = a.iter_blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 6);
+        assert!(blocks.iter().all(|&block| a.contains(block)));
+        assert!(blocks.contains(&WorldVec::new(0, 0, -1)));
+        assert!(blocks.contains(&WorldVec::new(2, 1, -1)));
+
+        let b = WorldAabb::from_blocks_corners(WorldVec::new(2, 1, -3), WorldVec::new(5, 5, 5));
+        let overlap = a.intersection(b).unwrap();
+        assert_eq!(
+            overlap.iter_blocks().collect::<Vec<_>>(),
+            [WorldVec::new(2, 1, -1)]
+        );
+        assert_eq!(b.intersection(a).unwrap().origin, overlap.origin);
+
+        let union = a.union(b);
+        assert_eq!(union.origin, WorldVec::new(0, 0, -3));
+        assert_eq!(union.max_corner(), WorldVec::new(6, 6, 6));
+
+        // Regions which only share a face don't intersect.
+        let neighbor = a.translated(WorldVec::new(3, 0, 0));
+        assert!(!a.intersects(neighbor));
+        assert!(a.intersection(neighbor).is_none());
+        assert_eq!(a.union(neighbor).size, WorldVec::new(6, 2, 1));
+    }
+
+    #[test]
+    fn entity_aabbs_intersect_and_union() {
+        let a = EntityAabb::from_center_half_extents(EntityVec::ZERO, EntityVec::splat(1.));
+        let b = EntityAabb::from_center_half_extents(EntityVec::splat(1.5), EntityVec::splat(1.));
+
+        let overlap = a.intersection(b).unwrap();
+        assert_eq!(overlap.origin, EntityVec::splat(0.5));
+        assert_eq!(overlap.size, EntityVec::splat(0.5));
+
+        let union = a.union(b);
+        assert_eq!(union.origin, EntityVec::splat(-1.));
+        assert_eq!(union.max_corner(), EntityVec::splat(2.5));
+
+        assert!(a.intersection(a.translated(EntityVec::X * 2.)).is_none());
+    }
+
+    #[test]
+    fn voxelizes_spheres() {
+        let origin = WorldVec::new(4, -3, 10);
+        let sphere = Shape::Sphere {
+            center: origin.negative_most_corner() + EntityVec::splat(0.5),
+            radius: 2.,
+        };
+
+        // The blocks at most two blocks away from the center: the center itself, its 6 face
+        // neighbors, its 12 edge neighbors, its 8 corner neighbors, and the 6 blocks on the tips.
+        let blocks = sphere.voxelize().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 33);
+        assert!(blocks.contains(&(origin + WorldVec::new(2, 0, 0))));
+        assert!(blocks.contains(&(origin + WorldVec::new(-1, 1, -1))));
+        assert!(!blocks.contains(&(origin + WorldVec::new(2, 2, 2))));
+        assert!(!blocks.contains(&(origin + WorldVec::new(-2, 2, 0))));
+        assert!(!blocks.contains(&(origin + WorldVec::new(2, -1, 1))));
+
+        let ellipsoid = Shape::Ellipsoid {
+            center: EntityVec::splat(0.5),
+            radii: EntityVec::splat(2.),
+        };
+        assert_eq!(ellipsoid.voxelize().count(), 33);
+    }
+
+    #[test]
+    fn voxelizes_boxes_half_open() {
+        // Block centers on the negative faces are included while those on the positive faces are
+        // not.
+        let aabb = EntityAabb {
+            origin: EntityVec::splat(0.5),
+            size: EntityVec::new(2., 1., 3.),
+        };
+        let blocks = Shape::Box(aabb).voxelize().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 6);
+        assert!(blocks.contains(&WorldVec::ZERO));
+        assert!(!blocks.contains(&WorldVec::new(2, 0, 0)));
+
+        let sliver = Shape::Box(EntityAabb {
+            origin: EntityVec::splat(0.1),
+            size: EntityVec::splat(0.2),
+        });
+        assert_eq!(sliver.voxelize().count(), 0);
+    }
+}
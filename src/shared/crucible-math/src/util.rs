@@ -1,13 +1,18 @@
-use std::f32::consts::{PI, TAU};
+use std::{
+    f32::consts::{PI, TAU},
+    ops::RangeInclusive,
+};
 
 use crucible_utils::newtypes::{enum_index, EnumIndex};
-use num_traits::Signed;
+use num_traits::{Float, Signed};
 use typed_glam::{
     glam::{DVec2, DVec3, IVec2, IVec3, Mat4, Vec2, Vec3},
     traits::{NumericVector, NumericVector2, NumericVector3, SignedNumericVector3},
     typed::{FlavorCastFrom, TypedVector, VecFlavor},
 };
 
+use crate::WorldVec;
+
 // === Sign === //
 
 enum_index! {
@@ -329,12 +334,80 @@ impl Angle3DExt for Angle3D {
 
 // === Misc Math === //
 
-pub fn lerp_percent_at(val: f64, start: f64, end: f64) -> f64 {
+/// Computes how far `val` is along the way from `start` to `end`. This is the inverse of linear
+/// interpolation and is unclamped, so values outside of the range produce percentages outside of
+/// `0..=1`.
+pub fn lerp_percent_at<T: Float>(val: T, start: T, end: T) -> T {
     // start + (end - start) * percent = val
     // (val - start) / (end - start) = percent
     (val - start) / (end - start)
 }
 
+/// Linearly maps `value` from `in_range` onto `out_range`. Like [`lerp_percent_at`], this does not
+/// clamp its result.
+pub fn remap<T: Float>(value: T, in_range: RangeInclusive<T>, out_range: RangeInclusive<T>) -> T {
+    let t = lerp_percent_at(value, *in_range.start(), *in_range.end());
+    *out_range.start() + (*out_range.end() - *out_range.start()) * t
+}
+
+// === Easing === //
+
+// The easing functions below map a progress `t` in `0..=1` to an eased progress which starts at `0`
+// and ends at `1`. Inputs outside of that range are clamped. Combine them with `lerp_percent_at` to
+// ease over an arbitrary range.
+
+fn ease_const<T: Float>(value: f64) -> T {
+    T::from(value).unwrap()
+}
+
+fn ease_clamp<T: Float>(t: T) -> T {
+    t.max(T::zero()).min(T::one())
+}
+
+/// Hermite interpolation with zero slope at both ends.
+pub fn smoothstep<T: Float>(t: T) -> T {
+    let t = ease_clamp(t);
+    t * t * (ease_const::<T>(3.) - ease_const::<T>(2.) * t)
+}
+
+/// Like [`smoothstep`] but with zero curvature at both ends as well, as described by Ken Perlin.
+pub fn smootherstep<T: Float>(t: T) -> T {
+    let t = ease_clamp(t);
+    t * t * t * (t * (t * ease_const(6.) - ease_const(15.)) + ease_const(10.))
+}
+
+pub fn ease_in_cubic<T: Float>(t: T) -> T {
+    let t = ease_clamp(t);
+    t * t * t
+}
+
+pub fn ease_out_cubic<T: Float>(t: T) -> T {
+    let inv = T::one() - ease_clamp(t);
+    T::one() - inv * inv * inv
+}
+
+pub fn ease_in_out_cubic<T: Float>(t: T) -> T {
+    let t = ease_clamp(t);
+
+    if t < ease_const(0.5) {
+        ease_const::<T>(4.) * t * t * t
+    } else {
+        let inv = ease_const::<T>(-2.) * t + ease_const(2.);
+        T::one() - inv * inv * inv / ease_const(2.)
+    }
+}
+
+/// Overshoots `1` by roughly 10% before settling back, so this is *not* monotonic.
+pub fn ease_out_back<T: Float>(t: T) -> T {
+    const OVERSHOOT: f64 = 1.70158;
+
+    let c1 = ease_const::<T>(OVERSHOOT);
+    let c3 = c1 + T::one();
+    let t = ease_clamp(t) - T::one();
+
+    T::one() + c3 * t * t * t + c1 * t * t
+}
+
 // === BlockFace === //
 
 enum_index! {
@@ -366,13 +439,15 @@ impl BlockFace {
         for axis in Axis3::variants() {
             let comp = vec.comp(axis);
 
-            if comp != 0 && choice.is_some() {
-                return None;
+            if comp == 0 {
+                continue;
             }
 
-            if comp.abs() == 1 {
-                choice = Some(BlockFace::compose(axis, Sign::of(comp).unwrap()));
+            if comp.abs() != 1 || choice.is_some() {
+                return None;
             }
+
+            choice = Some(BlockFace::compose(axis, Sign::of(comp).unwrap()));
         }
 
         choice
@@ -433,6 +508,27 @@ impl BlockFace {
         self.unit_typed()
     }
 
+    pub fn normal(self) -> WorldVec {
+        self.unit_typed()
+    }
+
+    /// Rotates the face counter-clockwise about the `+y` axis (when viewed from above) by the given
+    /// number of quarter turns. Negative turns rotate clockwise. The top and bottom faces are left
+    /// unchanged.
+    pub fn rotate_y(self, quarter_turns: i32) -> Self {
+        let Some(index) = Self::SIDES.iter().position(|&side| side == self) else {
+            return self;
+        };
+
+        Self::SIDES[(index as i32 + quarter_turns).rem_euclid(4) as usize]
+    }
+
+    /// Computes the face pointing along the cross product of the two faces' normals, returning
+    /// `None` if the faces are parallel.
+    pub fn cross(self, other: Self) -> Option<Self> {
+        Self::from_vec(self.unit().cross(other.unit()))
+    }
+
     pub fn unit_typed<V>(self) -> V
     where
         V: SignedNumericVector3,
@@ -471,3 +567,96 @@ pub fn compose_f32(sign: Sign, exp: u8, mantissa: u32) -> f32 {
 
     f32::from_bits(bits)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_invert_is_opposite() {
+        for face in BlockFace::variants() {
+            assert_eq!(face.normal() + face.invert().normal(), WorldVec::ZERO);
+            assert_eq!(face.invert().invert(), face);
+        }
+    }
+
+    #[test]
+    fn face_rotate_y_cycles() {
+        assert_eq!(BlockFace::PositiveX.rotate_y(1), BlockFace::NegativeZ);
+        assert_eq!(BlockFace::PositiveX.rotate_y(-1), BlockFace::PositiveZ);
+        assert_eq!(BlockFace::TOP.rotate_y(1), BlockFace::TOP);
+
+        for face in BlockFace::variants() {
+            let rotated = (0..4).fold(face, |face, _| face.rotate_y(1));
+            assert_eq!(rotated, face);
+
+            let rotation = Mat4::from_rotation_y(PI / 2.);
+            let expected = rotation.transform_vector3(face.unit().as_vec3()).round();
+            assert_eq!(face.rotate_y(1).unit().as_vec3(), expected);
+        }
+    }
+
+    #[test]
+    fn face_cross_and_from_vec() {
+        assert_eq!(
+            BlockFace::PositiveX.cross(BlockFace::PositiveY),
+            Some(BlockFace::PositiveZ)
+        );
+        assert_eq!(BlockFace::PositiveX.cross(BlockFace::NegativeX), None);
+
+        for face in BlockFace::variants() {
+            assert_eq!(BlockFace::from_vec(face.unit()), Some(face));
+        }
+
+        assert_eq!(BlockFace::from_vec(IVec3::ZERO), None);
+        assert_eq!(BlockFace::from_vec(IVec3::new(1, 1, 0)), None);
+        assert_eq!(BlockFace::from_vec(IVec3::new(2, 0, 0)), None);
+        assert_eq!(BlockFace::from_vec(IVec3::new(2, 1, 0)), None);
+    }
+
+    #[test]
+    fn easings_span_zero_to_one() {
+        type Easing = fn(f64) -> f64;
+
+        let monotonic: [(&str, Easing); 5] = [
+            ("smoothstep", smoothstep),
+            ("smootherstep", smootherstep),
+            ("ease_in_cubic", ease_in_cubic),
+            ("ease_out_cubic", ease_out_cubic),
+            ("ease_in_out_cubic", ease_in_out_cubic),
+        ];
+
+        for (name, f) in monotonic {
+            assert!(f(0.).abs() < 1e-12, "{name}(0) = {}", f(0.));
+            assert!((f(1.) - 1.).abs() < 1e-12, "{name}(1) = {}", f(1.));
+            assert_eq!(f(-1.), f(0.), "{name} should clamp");
+            assert_eq!(f(2.), f(1.), "{name} should clamp");
+
+            let mut prev = f(0.);
+            for i in 1..=100 {
+                let curr = f(i as f64 / 100.);
+                assert!(curr >= prev, "{name} decreases at t = {}", i as f64 / 100.);
+                prev = curr;
+            }
+        }
+
+        // `ease_out_back` overshoots but must still start and end in the right places.
+        assert!(ease_out_back(0f64).abs() < 1e-12);
+        assert!((ease_out_back(1f64) - 1.).abs() < 1e-12);
+        assert!((0..=100).any(|i| ease_out_back(i as f64 / 100.) > 1.));
+
+        // The `f32` versions agree with the `f64` ones.
+        assert!((smoothstep(0.25f32) as f64 - smoothstep(0.25f64)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn remap_and_lerp_percent_at() {
+        assert_eq!(lerp_percent_at(15., 10., 20.), 0.5);
+        assert_eq!(lerp_percent_at(30., 10., 20.), 2.);
+        assert_eq!(lerp_percent_at(12.5f32, 20., 10.), 0.75);
+
+        assert_eq!(remap(5., 0.0..=10., 100.0..=200.), 150.);
+        assert_eq!(remap(-5., 0.0..=10., 100.0..=200.), 50.);
+        assert_eq!(remap(0.25f32, 0.0..=1., 1.0..=-1.), 0.5);
+    }
+}
@@ -2,7 +2,7 @@ use std::ops::ControlFlow;
 
 use bevy_autoken::{random_component, Obj, RandomAccess, RandomEntityExt};
 use bevy_ecs::removal_detection::RemovedComponents;
-use crucible_math::EntityAabb;
+use crucible_math::{EntityAabb, EntityVec};
 use rustc_hash::FxHashSet;
 
 use super::ColliderMaterial;
@@ -35,6 +35,32 @@ impl AabbStore {
         }
         ControlFlow::Continue(())
     }
+
+    /// Finds every collider `aabb` would run into while moving by `delta`, ordered by time of
+    /// impact. Times are expressed as a fraction of `delta` so fast-moving bodies can't tunnel
+    /// through colliders which they never overlap at the end of a tick.
+    pub fn sweep_query(
+        &self,
+        aabb: EntityAabb,
+        delta: EntityVec,
+    ) -> impl Iterator<Item = (Obj<AabbHolder>, f64)> {
+        let moved = aabb.translated(delta);
+        let swept = EntityAabb::from_corners_max_excl(
+            aabb.origin.min(moved.origin),
+            aabb.max_corner().max(moved.max_corner()),
+        );
+
+        let mut hits = Vec::new();
+
+        cbit::cbit!(for collider in self.scan(swept) {
+            if let Some(toi) = aabb.sweep_toi(delta, collider.aabb) {
+                hits.push((collider, toi));
+            }
+        });
+
+        hits.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        hits.into_iter()
+    }
 }
 
 pub struct AabbHolder {
@@ -91,3 +117,65 @@ pub fn sys_unregister_dead_aabbs(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{spawn_entity, RandomArena, RandomWorldExt as _};
+    use bevy_ecs::world::World;
+    use crucible_utils::newtypes::Index as _;
+
+    use crate::collider::ColliderMaterialId;
+
+    use super::*;
+
+    #[test]
+    fn sweeps_report_colliders_by_time_of_impact() {
+        let mut world = World::new();
+        world.init_resource::<RandomArena<AabbStore>>();
+        world.init_resource::<RandomArena<AabbHolder>>();
+
+        let material = ColliderMaterial {
+            id: ColliderMaterialId::from_usize(0),
+            meta: 0,
+        };
+
+        world.use_random(|_: PhantomData<(&mut AabbStore, &mut AabbHolder)>| {
+            let store = spawn_entity(()).insert(AabbStore::default());
+            let unit = |x| EntityAabb {
+                origin: EntityVec::new(x, 0., 0.),
+                size: EntityVec::ONE,
+            };
+
+            let spawn = |aabb| {
+                let holder = spawn_entity(()).insert(AabbHolder::new(aabb, material));
+                store.register(holder);
+                holder
+            };
+
+            let near = spawn(unit(10.));
+            let far = spawn(unit(30.));
+            let _behind = spawn(unit(-10.));
+            let _beyond = spawn(unit(60.));
+
+            // A box moving 50 units in a single tick would skip straight past both targets.
+            let hits = store
+                .sweep_query(unit(0.), EntityVec::new(50., 0., 0.))
+                .collect::<Vec<_>>();
+
+            assert_eq!(hits.len(), 2);
+            assert_eq!(hits[0].0, near);
+            assert!((hits[0].1 - 9. / 50.).abs() < 1e-9);
+            assert_eq!(hits[1].0, far);
+            assert!((hits[1].1 - 29. / 50.).abs() < 1e-9);
+
+            // Moving past the target on another axis misses it entirely.
+            let hits = store.sweep_query(
+                unit(0.).translated(EntityVec::new(0., 2., 0.)),
+                EntityVec::new(50., 0., 0.),
+            );
+            assert_eq!(hits.count(), 0);
+        });
+    }
+}
@@ -7,8 +7,8 @@ use crucible_math::{EntityAabb, EntityVec};
 use crate::voxel::{BlockMaterialCache, VoxelPointer, WorldPointer, WorldVoxelData};
 
 use super::{
-    move_rigid_body_voxels, occluding_volumes_in_entity_volume, AabbHolder, AabbStore,
-    BlockColliderDescriptor, ColliderMaterial,
+    move_rigid_body_voxels, occluding_volumes_in_entity_volume, step_rigid_body_voxels, AabbHolder,
+    AabbStore, BlockColliderDescriptor, ColliderMaterial, StepState,
 };
 
 #[derive(Debug)]
@@ -76,6 +76,25 @@ impl WorldCollisions {
             filter(AnyCollision::Block(ptr, mat))
         })
     }
+
+    /// Like [`move_rigid_body`](Self::move_rigid_body) but climbs ledges up to
+    /// `state.step_height` tall. See [`step_rigid_body_voxels`] for details.
+    pub fn step_rigid_body(
+        &mut self,
+        aabb: EntityAabb,
+        delta: EntityVec,
+        state: &mut StepState,
+        mut filter: impl FnMut(AnyCollision) -> bool,
+    ) -> EntityVec {
+        step_rigid_body_voxels(
+            self.voxels,
+            &mut self.cache,
+            aabb,
+            delta,
+            state,
+            |ptr, mat| filter(AnyCollision::Block(ptr, mat)),
+        )
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -1,4 +1,9 @@
+use std::iter;
+
+use crucible_math::{Aabb3, Axis3, BlockFace, Sign, VecCompExt};
 use crucible_utils::newtypes::define_index;
+use smallvec::SmallVec;
+use typed_glam::glam::Vec3;
 
 use crate::mesh::{QuadMeshLayer, VolumetricMeshLayer};
 
@@ -16,6 +21,16 @@ pub struct ColliderMaterial {
 pub enum Collider {
     Transparent,
     Opaque(ColliderMaterial),
+    /// A full-width volume occupying the bottom `height` of the block.
+    Slab {
+        height: f32,
+        material: ColliderMaterial,
+    },
+    /// A bottom half-slab topped by a half-block step on the side of the horizontal `facing` face.
+    Stair {
+        facing: BlockFace,
+        material: ColliderMaterial,
+    },
     Mesh {
         volumes: VolumetricMeshLayer<ColliderMaterial>,
         extra_quads: QuadMeshLayer<ColliderMaterial>,
@@ -29,4 +44,48 @@ impl Collider {
             extra_quads: QuadMeshLayer::default(),
         }
     }
+
+    /// Iterates over the block-relative volumes making up this collider.
+    pub fn volumes(&self) -> impl Iterator<Item = (Aabb3<Vec3>, ColliderMaterial)> + '_ {
+        let mut simple = SmallVec::<[_; 2]>::new();
+        let mut mesh = None;
+
+        match self {
+            Collider::Transparent => {}
+            Collider::Opaque(material) => simple.push((Aabb3::ONE, *material)),
+            Collider::Slab { height, material } => simple.push((
+                Aabb3 {
+                    origin: Vec3::ZERO,
+                    size: Vec3::new(1., *height, 1.),
+                },
+                *material,
+            )),
+            Collider::Stair { facing, material } => {
+                debug_assert_ne!(facing.axis(), Axis3::Y, "stairs must face horizontally");
+
+                let mut step = Aabb3 {
+                    origin: Vec3::new(0., 0.5, 0.),
+                    size: Vec3::new(1., 0.5, 1.),
+                };
+                *step.size.comp_mut(facing.axis()) = 0.5;
+                if facing.sign() == Sign::Positive {
+                    *step.origin.comp_mut(facing.axis()) = 0.5;
+                }
+
+                simple.push((
+                    Aabb3 {
+                        origin: Vec3::ZERO,
+                        size: Vec3::new(1., 0.5, 1.),
+                    },
+                    *material,
+                ));
+                simple.push((step, *material));
+            }
+            Collider::Mesh { volumes, .. } => mesh = Some(volumes),
+        }
+
+        simple
+            .into_iter()
+            .chain(iter::once(mesh).flatten().flat_map(|v| v.iter_cloned()))
+    }
 }
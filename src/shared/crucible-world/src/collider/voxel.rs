@@ -10,7 +10,12 @@ use crucible_utils::{newtypes::EnumIndex, traits::VecLike};
 use smallvec::SmallVec;
 use typed_glam::{glam::DVec2, traits::NumericVector};
 
-use crate::voxel::{BlockData, BlockMaterialCache, EntityPointer, WorldPointer, WorldVoxelData};
+use crate::{
+    mesh::{merge_chunk_cells, VolumetricMeshLayer},
+    voxel::{
+        BlockData, BlockMaterialCache, ChunkData, EntityPointer, WorldPointer, WorldVoxelData,
+    },
+};
 
 use super::{Collider, ColliderMaterial};
 
@@ -37,35 +42,56 @@ pub fn occluding_volumes_in_block<B>(
     };
 
     // Determine collision volumes
-    match &descriptor.0 {
-        Collider::Transparent => {}
-        Collider::Opaque(meta) => {
-            let origin = block.pos.negative_most_corner();
-            f((
-                block,
-                Aabb3 {
-                    origin,
-                    size: EntityVec::ONE,
-                },
-                *meta,
-            ))?;
+    for (aabb, meta) in descriptor.0.volumes() {
+        let origin = aabb.origin.cast::<EntityVec>() + block.pos.negative_most_corner();
+        f((
+            block,
+            Aabb3 {
+                origin,
+                size: aabb.size.cast(),
+            },
+            meta,
+        ))?;
+    }
+
+    ControlFlow::Continue(())
+}
+
+/// Builds the collision representation of a chunk in chunk-relative block units. Full blocks are
+/// merged into boxes with [`merge_chunk_cells`] while other shapes contribute their volumes as-is.
+pub fn chunk_collision_volumes(
+    data: &ChunkData,
+    collider_mats: &mut BlockMaterialCache<BlockColliderDescriptor>,
+) -> VolumetricMeshLayer<ColliderMaterial> {
+    let mut shaped = Vec::new();
+    let mut layer = merge_chunk_cells(|pos| {
+        let state = data.block(pos);
+        if state.is_air() {
+            return None;
         }
-        Collider::Mesh { volumes, .. } => {
-            for (aabb, meta) in volumes.iter_cloned() {
-                let origin = aabb.origin.cast::<EntityVec>() + block.pos.negative_most_corner();
-                f((
-                    block,
-                    Aabb3 {
-                        origin,
-                        size: aabb.size.cast(),
-                    },
-                    meta,
-                ))?;
+
+        let descriptor = collider_mats.get(state.material)?;
+
+        match &descriptor.0 {
+            Collider::Opaque(material) => Some(*material),
+            collider => {
+                let offset = pos.to_glam().as_vec3();
+                shaped.extend(collider.volumes().map(|(aabb, material)| {
+                    (
+                        Aabb3 {
+                            origin: aabb.origin + offset,
+                            size: aabb.size,
+                        },
+                        material,
+                    )
+                }));
+                None
             }
         }
-    }
+    });
 
-    ControlFlow::Continue(())
+    layer.aabbs.extend(shaped);
+    layer
 }
 
 pub fn occluding_faces_in_block<B>(
@@ -87,55 +113,38 @@ pub fn occluding_faces_in_block<B>(
     // Determine an offset from block-relative coordinates to world coordinates
     let quad_offset = block.pos.negative_most_corner();
 
-    // Determine collision volumes
-    match &descriptor.0 {
-        Collider::Transparent => {}
-        Collider::Opaque(meta) => {
-            f((
-                Aabb3 {
-                    origin: quad_offset,
-                    size: EntityVec::ONE,
-                }
-                .quad(face),
-                *meta,
-            ))?;
-        }
-        Collider::Mesh {
-            volumes,
-            extra_quads,
-        } => {
-            // Yield faces from volumes
-            for (aabb, meta) in volumes.iter_cloned() {
-                f((
-                    Aabb3 {
-                        origin: aabb.origin.cast::<EntityVec>() + quad_offset,
-                        size: aabb.size.cast(),
-                    }
-                    .quad(face),
-                    meta,
-                ))?;
+    // Yield faces from volumes
+    for (aabb, meta) in descriptor.0.volumes() {
+        f((
+            Aabb3 {
+                origin: aabb.origin.cast::<EntityVec>() + quad_offset,
+                size: aabb.size.cast(),
             }
+            .quad(face),
+            meta,
+        ))?;
+    }
 
-            // Yield additional faces
-            for (quad, material) in extra_quads
-                .iter_cloned()
-                .filter(|(quad, _)| quad.face == face)
-            {
-                let AaQuad {
-                    origin,
-                    size: (sx, sy),
-                    ..
-                } = quad;
-
-                f((
-                    AaQuad {
-                        origin: origin.cast::<EntityVec>() + quad_offset,
-                        face,
-                        size: (sx.into(), sy.into()),
-                    },
-                    material,
-                ))?;
-            }
+    // Yield additional faces
+    if let Collider::Mesh { extra_quads, .. } = &descriptor.0 {
+        for (quad, material) in extra_quads
+            .iter_cloned()
+            .filter(|(quad, _)| quad.face == face)
+        {
+            let AaQuad {
+                origin,
+                size: (sx, sy),
+                ..
+            } = quad;
+
+            f((
+                AaQuad {
+                    origin: origin.cast::<EntityVec>() + quad_offset,
+                    face,
+                    size: (sx.into(), sy.into()),
+                },
+                material,
+            ))?;
         }
     }
 
@@ -581,3 +590,437 @@ pub fn move_rigid_body_voxels(
 
     aabb.origin - start_origin
 }
+
+/// The default height of the tallest ledge [`step_rigid_body_voxels`] will climb onto.
+pub const DEFAULT_STEP_HEIGHT: f64 = 0.6;
+
+/// The default distance [`step_rigid_body_voxels`] lifts a climbing body by per call.
+pub const DEFAULT_STEP_LIFT_SPEED: f64 = 0.1;
+
+/// How far below a body [`step_rigid_body_voxels`] looks for the ground it must be standing on to
+/// step.
+pub const STEP_GROUND_PROBE: f64 = 0.01;
+
+/// The state a body keeps between calls to [`step_rigid_body_voxels`] to climb ledges over several
+/// calls.
+#[derive(Debug, Copy, Clone)]
+pub struct StepState {
+    /// The height of the tallest ledge the body will climb onto.
+    pub step_height: f64,
+
+    /// The furthest the body is lifted by within a single call.
+    pub lift_speed: f64,
+
+    /// How much further the body has to rise to finish the climb it's in the middle of.
+    remaining_lift: f64,
+}
+
+impl Default for StepState {
+    fn default() -> Self {
+        Self::new(DEFAULT_STEP_HEIGHT, DEFAULT_STEP_LIFT_SPEED)
+    }
+}
+
+impl StepState {
+    pub fn new(step_height: f64, lift_speed: f64) -> Self {
+        Self {
+            step_height,
+            lift_speed,
+            remaining_lift: 0.,
+        }
+    }
+
+    pub fn is_climbing(&self) -> bool {
+        self.remaining_lift > 0.
+    }
+}
+
+/// Moves a rigid body like [`move_rigid_body_voxels`] but, if its horizontal motion is blocked by a
+/// ledge at most `state.step_height` tall with enough headroom above it, lifts the body onto the
+/// ledge rather than stopping it. Only bodies resting on the ground start stepping and bodies
+/// moving upwards never do.
+///
+/// The lift is spread over as many calls as it takes to rise by at most `state.lift_speed` per
+/// call. While climbing, the body only rises and slides along the ledge's side, skipping any
+/// downward motion, until it clears the ledge and moves onto it. Climbing stops early once the
+/// body stops moving horizontally or bumps into a ceiling.
+pub fn step_rigid_body_voxels(
+    world: Obj<WorldVoxelData>,
+    collider_mats: &mut BlockMaterialCache<BlockColliderDescriptor>,
+    aabb: EntityAabb,
+    delta: EntityVec,
+    state: &mut StepState,
+    mut filter: impl FnMut(WorldPointer, ColliderMaterial) -> bool,
+) -> EntityVec {
+    let horizontal = |v: EntityVec| EntityVec::new(v.x(), 0., v.z());
+
+    if state.is_climbing() {
+        if horizontal(delta) != EntityVec::ZERO && delta.y() <= 0. {
+            return continue_climb(world, collider_mats, aabb, delta, state, &mut filter);
+        }
+
+        state.remaining_lift = 0.;
+    }
+
+    let direct = move_rigid_body_voxels(world, collider_mats, aabb, delta, &mut filter);
+
+    if state.step_height <= 0.
+        || delta.y() > 0.
+        || (horizontal(direct) - horizontal(delta)).length() < COLLISION_TOLERANCE
+    {
+        return direct;
+    }
+
+    // Airborne bodies can't push off of anything to climb.
+    let probe = EntityVec::new(0., -STEP_GROUND_PROBE, 0.);
+    let fall = move_rigid_body_voxels(world, collider_mats, aabb, probe, &mut filter);
+    if fall.y() <= probe.y() + COLLISION_TOLERANCE {
+        return direct;
+    }
+
+    // Lift the body as far as the headroom allows, retry the horizontal motion from up there, and
+    // then settle back down onto whatever we've moved over.
+    let mut stepped = aabb;
+    let mut stepped_move = |stepped: &mut EntityAabb, delta: EntityVec| {
+        let actual = move_rigid_body_voxels(world, collider_mats, *stepped, delta, &mut filter);
+        stepped.origin += actual;
+        actual
+    };
+
+    let lift = stepped_move(&mut stepped, EntityVec::new(0., state.step_height, 0.));
+    stepped_move(&mut stepped, horizontal(delta));
+    stepped_move(&mut stepped, EntityVec::new(0., delta.y() - lift.y(), 0.));
+
+    let stepped = stepped.origin - aabb.origin;
+
+    // If lifting didn't get us any further, the obstacle is too tall or there's too little headroom
+    // to clear it.
+    if horizontal(stepped).length() <= horizontal(direct).length() + COLLISION_TOLERANCE {
+        return direct;
+    }
+
+    // Otherwise, climb to the height the trial step settled at, starting from where the direct
+    // motion left us against the ledge.
+    if stepped.y() <= state.lift_speed {
+        return stepped;
+    }
+
+    state.remaining_lift = stepped.y() - direct.y();
+
+    let mut against = aabb;
+    against.origin += direct;
+    direct + continue_climb(world, collider_mats, against, delta, state, &mut filter)
+}
+
+fn continue_climb(
+    world: Obj<WorldVoxelData>,
+    collider_mats: &mut BlockMaterialCache<BlockColliderDescriptor>,
+    mut aabb: EntityAabb,
+    delta: EntityVec,
+    state: &mut StepState,
+    mut filter: impl FnMut(WorldPointer, ColliderMaterial) -> bool,
+) -> EntityVec {
+    let start_origin = aabb.origin;
+
+    let wanted = state.remaining_lift.min(state.lift_speed);
+    let lift = move_rigid_body_voxels(
+        world,
+        collider_mats,
+        aabb,
+        EntityVec::new(0., wanted, 0.),
+        &mut filter,
+    );
+    aabb.origin += lift;
+
+    state.remaining_lift -= lift.y();
+    if lift.y() < wanted - COLLISION_TOLERANCE || state.remaining_lift < COLLISION_TOLERANCE {
+        state.remaining_lift = 0.;
+    }
+
+    let slide = EntityVec::new(delta.x(), 0., delta.z());
+    aabb.origin += move_rigid_body_voxels(world, collider_mats, aabb, slide, &mut filter);
+
+    // Settle onto the ledge once we've cleared it.
+    if !state.is_climbing() {
+        let settle = EntityVec::new(0., delta.y(), 0.);
+        aabb.origin += move_rigid_body_voxels(world, collider_mats, aabb, settle, &mut filter);
+    }
+
+    aabb.origin - start_origin
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{
+        spawn_entity, RandomArena, RandomEntityExt as _, RandomWorldExt as _, SendsEvent,
+    };
+    use crucible_math::{ChunkVec, WorldVec};
+    use crucible_utils::newtypes::Index as _;
+    use typed_glam::glam::Vec3;
+
+    use crate::{
+        collider::ColliderMaterialId,
+        voxel::{
+            test_voxel_world, BlockMaterialRegistry, ChunkVoxelData, PopulateWorld,
+            WorldChunkCreated,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn ray_hits_slab_top_surface() {
+        let (mut world, voxels) = test_voxel_world();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<BlockColliderDescriptor>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                &mut BlockMaterialRegistry,
+                &BlockColliderDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut registry = voxels.entity().insert(BlockMaterialRegistry::default());
+
+                let material = ColliderMaterial {
+                    id: ColliderMaterialId::from_usize(0),
+                    meta: 0,
+                };
+
+                registry.register("crucible:air", spawn_entity(()));
+                let slab = registry.register(
+                    "crucible:slab",
+                    spawn_entity(()).with(BlockColliderDescriptor(Collider::Slab {
+                        height: 0.5,
+                        material,
+                    })),
+                );
+
+                let slab_pos = WorldVec::new(2, 0, 3);
+                WorldPointer::new(slab_pos).set_state(voxels, BlockData::new(slab), PopulateWorld);
+
+                let mut cache = BlockMaterialCache::new(registry);
+                let (isect, meta) = VoxelRayCast::new_at(
+                    EntityPointer::new(EntityVec::new(2.5, 4., 3.5)),
+                    EntityVec::NEG_Y,
+                )
+                .step_intersect_for(voxels, &mut cache, 10.)
+                .next()
+                .unwrap();
+
+                assert_eq!(isect.block.pos, slab_pos);
+                assert_eq!(isect.enter_face, BlockFace::PositiveY);
+                assert!((isect.pos.y() - 0.5).abs() < 1e-9);
+                assert!((isect.distance - 3.5).abs() < 1e-9);
+                assert_eq!(meta, material);
+            },
+        );
+    }
+
+    fn with_blocks(
+        blocks: impl IntoIterator<Item = (WorldVec, &'static str)>,
+        f: impl FnOnce(Obj<WorldVoxelData>, &mut BlockMaterialCache<BlockColliderDescriptor>),
+    ) {
+        let (mut world, voxels) = test_voxel_world();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<BlockColliderDescriptor>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                &mut BlockMaterialRegistry,
+                &BlockColliderDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut registry = voxels.entity().insert(BlockMaterialRegistry::default());
+
+                let material = ColliderMaterial {
+                    id: ColliderMaterialId::from_usize(0),
+                    meta: 0,
+                };
+
+                registry.register("crucible:air", spawn_entity(()));
+                registry.register(
+                    "crucible:stone",
+                    spawn_entity(()).with(BlockColliderDescriptor(Collider::Opaque(material))),
+                );
+                registry.register(
+                    "crucible:slab",
+                    spawn_entity(()).with(BlockColliderDescriptor(Collider::Slab {
+                        height: 0.5,
+                        material,
+                    })),
+                );
+
+                // Lay down a stone floor for the body to walk along.
+                let floor = (0..6).flat_map(|x| (0..3).map(move |z| WorldVec::new(x, 0, z)));
+                let floor = floor.map(|pos| (pos, "crucible:stone"));
+
+                for (pos, name) in floor.chain(blocks) {
+                    let data = BlockData::new(registry.lookup_by_name(name).unwrap());
+                    WorldPointer::new(pos).set_state(voxels, data, PopulateWorld);
+                }
+
+                f(voxels, &mut BlockMaterialCache::new(registry));
+            },
+        );
+    }
+
+    fn walk_into_ledge(
+        voxels: Obj<WorldVoxelData>,
+        cache: &mut BlockMaterialCache<BlockColliderDescriptor>,
+    ) -> EntityVec {
+        walk_into_ledge_at(voxels, cache, 1. + COLLISION_TOLERANCE * 2.)
+    }
+
+    fn walk_into_ledge_at(
+        voxels: Obj<WorldVoxelData>,
+        cache: &mut BlockMaterialCache<BlockColliderDescriptor>,
+        y: f64,
+    ) -> EntityVec {
+        let body = EntityAabb {
+            origin: EntityVec::new(1.5, y, 1.25),
+            size: EntityVec::splat(0.5),
+        };
+
+        // Lift the body in full within a single call.
+        let mut state = StepState::new(DEFAULT_STEP_HEIGHT, DEFAULT_STEP_HEIGHT);
+
+        step_rigid_body_voxels(
+            voxels,
+            cache,
+            body,
+            EntityVec::new(2., 0., 0.),
+            &mut state,
+            |_, _| true,
+        )
+    }
+
+    #[test]
+    fn bodies_step_onto_low_ledges() {
+        with_blocks(
+            [(WorldVec::new(3, 1, 1), "crucible:slab")],
+            |voxels, cache| {
+                let moved = walk_into_ledge(voxels, cache);
+
+                assert!((moved.x() - 2.).abs() < 1e-9);
+                assert!((moved.y() - 0.5).abs() < COLLISION_TOLERANCE * 2.);
+            },
+        );
+    }
+
+    #[test]
+    fn bodies_climb_ledges_over_several_calls() {
+        with_blocks(
+            [(WorldVec::new(3, 1, 1), "crucible:slab")],
+            |voxels, cache| {
+                let mut body = EntityAabb {
+                    origin: EntityVec::new(1.5, 1. + COLLISION_TOLERANCE * 2., 1.25),
+                    size: EntityVec::splat(0.5),
+                };
+                let mut state = StepState::default();
+                let mut rising_calls = 0;
+
+                // Walk until we're on top of the slab, which ends at `x = 4`.
+                for _ in 0..40 {
+                    if body.origin.x() > 3. {
+                        break;
+                    }
+
+                    let moved = step_rigid_body_voxels(
+                        voxels,
+                        cache,
+                        body,
+                        EntityVec::new(0.1, -0.05, 0.),
+                        &mut state,
+                        |_, _| true,
+                    );
+
+                    assert!(moved.y() > -COLLISION_TOLERANCE * 2.);
+                    assert!(moved.y() <= DEFAULT_STEP_LIFT_SPEED + 1e-9);
+                    if moved.y() > COLLISION_TOLERANCE {
+                        rising_calls += 1;
+                    }
+
+                    body.origin += moved;
+                }
+
+                assert!(!state.is_climbing());
+                assert!(rising_calls >= 5);
+                assert!(body.origin.x() > 3.);
+                assert!((body.origin.y() - 1.5).abs() < COLLISION_TOLERANCE * 2.);
+            },
+        );
+    }
+
+    #[test]
+    fn airborne_bodies_do_not_step() {
+        with_blocks(
+            [(WorldVec::new(3, 1, 1), "crucible:slab")],
+            |voxels, cache| {
+                let moved = walk_into_ledge_at(voxels, cache, 1.2);
+
+                assert!((moved.x() - (3. - 0.5 - 1.5)).abs() < COLLISION_TOLERANCE * 2.);
+                assert_eq!(moved.y(), 0.);
+            },
+        );
+    }
+
+    #[test]
+    fn chunk_collision_merges_full_blocks_and_keeps_shaped_volumes() {
+        with_blocks(
+            [(WorldVec::new(3, 1, 1), "crucible:slab")],
+            |voxels, cache| {
+                let chunk = voxels.get(ChunkVec::ZERO).unwrap();
+                let layer = chunk_collision_volumes(chunk.data().unwrap(), cache);
+                let aabbs = layer
+                    .aabbs
+                    .iter()
+                    .map(|(aabb, _)| (aabb.origin, aabb.size))
+                    .collect::<Vec<_>>();
+
+                // The stone floor collapses into a single box while the slab keeps its shape.
+                assert_eq!(
+                    aabbs,
+                    [
+                        (Vec3::ZERO, Vec3::new(6., 1., 3.)),
+                        (Vec3::new(3., 1., 1.), Vec3::new(1., 0.5, 1.)),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn bodies_do_not_step_onto_tall_walls_or_under_low_ceilings() {
+        let blocked_at = 3. - 0.5 - 1.5;
+
+        // A one-block wall is taller than the default step height, let alone a two-block one.
+        for height in 1..=2 {
+            let wall = (1..=height).map(|y| (WorldVec::new(3, y, 1), "crucible:stone"));
+
+            with_blocks(wall, |voxels, cache| {
+                let moved = walk_into_ledge(voxels, cache);
+
+                assert!((moved.x() - blocked_at).abs() < COLLISION_TOLERANCE * 2.);
+                assert_eq!(moved.y(), 0.);
+            });
+        }
+
+        // A low ledge with a ceiling right above the body leaves no room to lift it.
+        let ceiling = (1..5).map(|x| (WorldVec::new(x, 2, 1), "crucible:stone"));
+        let blocks = ceiling.chain([(WorldVec::new(3, 1, 1), "crucible:slab")]);
+
+        with_blocks(blocks, |voxels, cache| {
+            let moved = walk_into_ledge(voxels, cache);
+
+            assert!((moved.x() - blocked_at).abs() < COLLISION_TOLERANCE * 2.);
+            assert_eq!(moved.y(), 0.);
+        });
+    }
+}
@@ -0,0 +1,10 @@
+//! The high-level surface of the world which gameplay code is expected to go through. Lower-level
+//! building blocks stay in their own modules.
+
+pub use crate::{
+    collider::{AnyCollision, WorldCollisions},
+    voxel::{
+        BlockData, BlockMaterial, BlockMaterialRegistry, KeepInWorld, PopulateWorld,
+        SetStatePolicy, Structure, StructurePlaceMode, WorldEdit, WorldPointer, WorldVoxelData,
+    },
+};
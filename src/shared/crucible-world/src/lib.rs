@@ -1,6 +1,7 @@
 #![feature(arbitrary_self_types)]
 
 pub mod collider;
+pub mod facade;
 pub mod material;
 pub mod mesh;
 pub mod voxel;
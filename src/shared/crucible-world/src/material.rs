@@ -47,6 +47,18 @@ impl<K: LargeIndex> MaterialRegistry<K> {
     pub fn lookup_desc_by_name(&self, name: &str) -> Option<Entity> {
         self.lookup_by_name(name).map(|idx| self.lookup_by_idx(idx))
     }
+
+    /// Lists every registered material and its name in the order they were registered.
+    pub fn names(&self) -> Vec<(K, &str)> {
+        let mut names = self
+            .name_map
+            .iter()
+            .map(|(name, &idx)| (idx, name.as_str()))
+            .collect::<Vec<_>>();
+
+        names.sort_unstable_by_key(|&(idx, _)| idx);
+        names
+    }
 }
 
 // === MaterialCache === //
@@ -70,6 +82,10 @@ where
         }
     }
 
+    pub fn registry(&self) -> Obj<MaterialRegistry<K>> {
+        self.registry
+    }
+
     pub fn get(&mut self, id: K) -> Option<Obj<V>> {
         match self.cache.entry(id) {
             Some(entry) => Some(*entry),
@@ -1,7 +1,7 @@
-use crucible_math::{AaQuad, Aabb3, BlockFace};
+use crucible_math::{AaQuad, Aabb3, BlockFace, BlockVec, BlockVecExt, CHUNK_EDGE};
 use crucible_utils::newtypes::EnumIndex;
 use derive_where::derive_where;
-use typed_glam::glam::Vec3;
+use typed_glam::glam::{IVec3, Vec3};
 
 // === Volumetric === //
 
@@ -128,3 +128,109 @@ impl<M> Extend<(AaQuad<Vec3>, M)> for QuadMeshLayer<M> {
         self.quads.extend(iter);
     }
 }
+
+// === Collision === //
+
+/// Greedily merges the occupied cells of a chunk into boxes, producing a collision representation
+/// which is independent of the render mesh. `cell` reports the material of each occupied cell and
+/// neighboring cells are only merged if their materials are equal. Boxes are in chunk-relative
+/// block units.
+///
+/// Each box grows along X, then Y, then Z for as long as the entire row or layer it would absorb
+/// is unclaimed and shares its material, so a solid slab collapses into a handful of boxes.
+pub fn merge_chunk_cells<M>(mut cell: impl FnMut(BlockVec) -> Option<M>) -> VolumetricMeshLayer<M>
+where
+    M: Clone + PartialEq,
+{
+    let mut cells = BlockVec::iter().map(&mut cell).collect::<Vec<_>>();
+    let mut layer = VolumetricMeshLayer::default();
+
+    for start in 0..cells.len() {
+        let Some(material) = cells[start].take() else {
+            continue;
+        };
+
+        let origin = BlockVec::from_index(start).to_glam();
+        let is_mergeable = |cells: &[Option<M>], offset: IVec3| {
+            cells[BlockVec::from_glam(origin + offset).to_index()].as_ref() == Some(&material)
+        };
+
+        let mut size = IVec3::ONE;
+
+        while origin.x + size.x < CHUNK_EDGE && is_mergeable(&cells, IVec3::new(size.x, 0, 0)) {
+            size.x += 1;
+        }
+
+        while origin.y + size.y < CHUNK_EDGE
+            && (0..size.x).all(|x| is_mergeable(&cells, IVec3::new(x, size.y, 0)))
+        {
+            size.y += 1;
+        }
+
+        while origin.z + size.z < CHUNK_EDGE
+            && (0..size.y)
+                .all(|y| (0..size.x).all(|x| is_mergeable(&cells, IVec3::new(x, y, size.z))))
+        {
+            size.z += 1;
+        }
+
+        // Claim every cell in the box.
+        for z in 0..size.z {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    cells[BlockVec::from_glam(origin + IVec3::new(x, y, z)).to_index()] = None;
+                }
+            }
+        }
+
+        layer.push_aabb(
+            Aabb3 {
+                origin: origin.as_vec3(),
+                size: size.as_vec3(),
+            },
+            material,
+        );
+    }
+
+    layer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slabs_merge_into_few_boxes() {
+        // A four-block-thick floor with a hole in its corner and a small cube of another material
+        // floating above it.
+        let cell = |pos: BlockVec| {
+            if pos.y() < 4 && pos != BlockVec::ZERO {
+                Some(1)
+            } else if (5..7).contains(&pos.x())
+                && (8..10).contains(&pos.y())
+                && (5..7).contains(&pos.z())
+            {
+                Some(2)
+            } else {
+                None
+            }
+        };
+
+        let layer = merge_chunk_cells(cell);
+        assert!(layer.aabbs.len() <= 4, "{} boxes", layer.aabbs.len());
+
+        // Every solid cell is covered by exactly one box of the right material.
+        for pos in BlockVec::iter() {
+            let center = pos.to_glam().as_vec3() + Vec3::splat(0.5);
+            let covering = layer
+                .iter()
+                .filter(|(aabb, _)| {
+                    center.cmpge(aabb.origin).all() && center.cmplt(aabb.origin + aabb.size).all()
+                })
+                .map(|(_, &material)| material)
+                .collect::<Vec<_>>();
+
+            assert_eq!(covering, Vec::from_iter(cell(pos)), "at {pos:?}");
+        }
+    }
+}
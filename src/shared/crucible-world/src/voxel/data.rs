@@ -10,6 +10,7 @@ use crucible_math::{
 };
 use crucible_utils::newtypes::{define_index, EnumIndex as _, IndexArray, IndexBitArray};
 use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
 use typed_glam::traits::{CastVecFrom, NumericVector};
 
 use crate::material::{MaterialCache, MaterialRegistry};
@@ -24,6 +25,8 @@ random_component!(BlockMaterialRegistry);
 pub type BlockMaterialCache<V> = MaterialCache<BlockMaterial, V>;
 
 define_index! {
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
     pub struct BlockMaterial: u16;
 }
 
@@ -40,7 +43,7 @@ impl BlockMaterial {
 }
 
 // Block Data
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct BlockData {
     pub material: BlockMaterial,
     pub variant: u32,
@@ -140,6 +143,24 @@ impl WorldVoxelData {
             chunk.dirty_corners.clear();
         }
     }
+
+    /// Copies the data of every loaded chunk within `radius` chunks of `center` (inclusive, along
+    /// each axis) into a [`ChunkSnapshot`].
+    pub fn snapshot_region(&self, center: ChunkVec, radius: i32) -> ChunkSnapshot {
+        let mut chunks = FxHashMap::default();
+
+        for (&pos, chunk) in &self.chunks {
+            if (pos - center).abs().max_element() > radius {
+                continue;
+            }
+
+            if let Some(data) = &chunk.data {
+                chunks.insert(pos, data.clone());
+            }
+        }
+
+        ChunkSnapshot { chunks }
+    }
 }
 
 #[derive(Debug)]
@@ -162,6 +183,15 @@ pub enum ChunkData {
     Complex(Box<[BlockData; CHUNK_VOLUME as usize]>),
 }
 
+impl ChunkData {
+    pub fn block(&self, block: BlockVec) -> BlockData {
+        match self {
+            ChunkData::AllAir => BlockData::AIR,
+            ChunkData::Complex(v) => v[block.to_index()],
+        }
+    }
+}
+
 random_component!(ChunkVoxelData);
 
 impl ChunkVoxelData {
@@ -195,11 +225,12 @@ impl ChunkVoxelData {
         self.data.is_some()
     }
 
+    pub fn data(&self) -> Option<&ChunkData> {
+        self.data.as_ref()
+    }
+
     pub fn block(&self, block: BlockVec) -> Option<BlockData> {
-        self.data.as_ref().map(|v| match v {
-            ChunkData::AllAir => BlockData::AIR,
-            ChunkData::Complex(v) => v[block.to_index()],
-        })
+        self.data.as_ref().map(|v| v.block(block))
     }
 
     pub fn block_or_air(&self, block: BlockVec) -> BlockData {
@@ -290,6 +321,34 @@ impl ChunkVoxelData {
     }
 }
 
+// === Snapshots === //
+
+/// An immutable, `Send`-able copy of a region of a [`WorldVoxelData`] which worker threads can read
+/// while the main thread keeps editing the live world.
+///
+/// Snapshots deep-copy the chunks they cover rather than sharing them through an `Arc`. This costs
+/// one allocation and copy per non-empty chunk but keeps the live world free of copy-on-write
+/// bookkeeping on every block edit, which are far more frequent than snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkSnapshot {
+    chunks: FxHashMap<ChunkVec, ChunkData>,
+}
+
+impl ChunkSnapshot {
+    pub fn chunk(&self, pos: ChunkVec) -> Option<&ChunkData> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn block(&self, pos: WorldVec) -> Option<BlockData> {
+        let (chunk, block) = pos.decompose();
+        self.chunk(chunk).map(|chunk| chunk.block(block))
+    }
+
+    pub fn block_or_air(&self, pos: WorldVec) -> BlockData {
+        self.block(pos).unwrap_or(BlockData::AIR)
+    }
+}
+
 // === Pointer === //
 
 pub type WorldPointer = VoxelPointer<WorldVec>;
@@ -548,3 +607,65 @@ pub fn sys_clear_dirty_chunk_lists(
         }
     });
 }
+
+/// Creates a world with the arenas and events needed to store voxel data and spawns an empty
+/// [`WorldVoxelData`] into it. Tests needing more components can initialize them afterwards.
+#[cfg(test)]
+pub(crate) fn test_voxel_world() -> (bevy_ecs::world::World, Obj<WorldVoxelData>) {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{RandomArena, RandomWorldExt as _};
+    use bevy_ecs::{event::Events, world::World};
+
+    let mut world = World::new();
+    world.init_resource::<RandomArena<WorldVoxelData>>();
+    world.init_resource::<RandomArena<ChunkVoxelData>>();
+    world.init_resource::<Events<WorldChunkCreated>>();
+
+    let voxels = world.use_random(|_: PhantomData<&mut WorldVoxelData>| {
+        spawn_entity(()).insert(WorldVoxelData::default())
+    });
+
+    (world, voxels)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{RandomWorldExt as _, SendsEvent};
+    use crucible_utils::newtypes::Index as _;
+
+    use super::*;
+
+    #[test]
+    fn snapshots_are_unaffected_by_edits() {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let (mut world, voxels) = test_voxel_world();
+        let stone = BlockData::new(BlockMaterial::from_usize(1));
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                for pos in [ChunkVec::ZERO, ChunkVec::X, ChunkVec::new(5, 0, 0)] {
+                    voxels.get_or_insert(pos).initialize_data(ChunkData::AllAir);
+                }
+
+                let edited = WorldVec::new(CHUNK_EDGE + 3, 2, 1);
+                let snapshot = voxels.snapshot_region(ChunkVec::ZERO, 1);
+                assert_send(&snapshot);
+
+                WorldPointer::new(edited).set_state(voxels, stone, KeepInWorld);
+                assert_eq!(WorldPointer::new(edited).state(voxels), Some(stone));
+
+                assert_eq!(snapshot.block(edited), Some(BlockData::AIR));
+                assert!(snapshot.chunk(ChunkVec::ZERO).is_some());
+                assert!(snapshot.chunk(ChunkVec::new(5, 0, 0)).is_none());
+            },
+        );
+    }
+}
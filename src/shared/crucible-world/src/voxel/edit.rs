@@ -0,0 +1,174 @@
+use bevy_autoken::Obj;
+use crucible_math::{WorldVec, WorldVecExt as _};
+use rustc_hash::FxHashMap;
+
+use super::{BlockData, SetStatePolicy, WorldPointer, WorldVoxelData};
+
+// === WorldEdit === //
+
+/// A batch of block edits which is applied to the world all at once.
+///
+/// Nothing touches the world until [`commit`](Self::commit) so the chunks an edit covers are only
+/// queued for remeshing (and any other follow-up work driven by the dirty chunk list) once, no
+/// matter how many of their blocks were set. Dropping the edit or calling [`abort`](Self::abort)
+/// discards it.
+#[derive(Debug)]
+#[must_use = "edits are only applied once committed"]
+pub struct WorldEdit {
+    world: Obj<WorldVoxelData>,
+    blocks: FxHashMap<WorldVec, BlockData>,
+}
+
+impl WorldVoxelData {
+    pub fn edit(self: Obj<Self>) -> WorldEdit {
+        WorldEdit {
+            world: self,
+            blocks: FxHashMap::default(),
+        }
+    }
+}
+
+impl WorldEdit {
+    pub fn world(&self) -> Obj<WorldVoxelData> {
+        self.world
+    }
+
+    /// Sets the block at `pos`. Setting the same position more than once keeps the last state.
+    pub fn set(&mut self, pos: WorldVec, data: BlockData) {
+        self.blocks.insert(pos, data);
+    }
+
+    /// Reads the block at `pos` as it will be once this edit is committed.
+    pub fn state(&self, pos: WorldVec) -> Option<BlockData> {
+        self.blocks
+            .get(&pos)
+            .copied()
+            .or_else(|| WorldPointer::new(pos).state(self.world))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Applies every edit through `policy`, skipping blocks which already have their target state.
+    /// Returns the number of blocks which were changed.
+    pub fn commit(self, mut policy: impl SetStatePolicy) -> usize {
+        let mut blocks = self.blocks.into_iter().collect::<Vec<_>>();
+
+        // Visit blocks chunk-by-chunk so that the pointer's chunk cache stays warm.
+        blocks.sort_unstable_by_key(|(pos, _)| {
+            let (chunk, block) = pos.decompose();
+            (chunk.to_glam().to_array(), block.to_glam().to_array())
+        });
+
+        let mut pointer = WorldPointer::default();
+        let mut changed = 0;
+
+        for (pos, data) in blocks {
+            pointer.move_to(pos);
+
+            if pointer.state(self.world) == Some(data) {
+                continue;
+            }
+
+            pointer.set_state(self.world, data, &mut policy);
+            changed += 1;
+        }
+
+        changed
+    }
+
+    /// Discards every edit. This is equivalent to dropping the edit.
+    pub fn abort(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{
+        spawn_entity, RandomArena, RandomEntityExt as _, RandomWorldExt as _, SendsEvent,
+    };
+    use bevy_ecs::{event::Events, world::World};
+    use crucible_math::{ChunkVec, CHUNK_EDGE};
+    use crucible_utils::newtypes::Index as _;
+
+    use crate::voxel::{
+        BlockMaterial, ChunkData, ChunkVoxelData, KeepInWorld, PopulateWorld, WorldChunkCreated,
+    };
+
+    use super::*;
+
+    #[test]
+    fn edits_dirty_chunks_once_on_commit() {
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        let stone = BlockData::new(BlockMaterial::from_usize(1));
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut voxels = spawn_entity(()).insert(WorldVoxelData::default());
+                for pos in [ChunkVec::ZERO, ChunkVec::X] {
+                    voxels.get_or_insert(pos).initialize_data(ChunkData::AllAir);
+                }
+
+                let dirty_count = move || voxels.iter_dirty().count();
+
+                // Sets are buffered until the edit is committed.
+                let mut edit = voxels.edit();
+                for x in 0..4 {
+                    edit.set(WorldVec::new(CHUNK_EDGE - 2 + x, 0, 0), stone);
+                }
+                edit.set(WorldVec::new(1, 1, 1), stone);
+                edit.set(WorldVec::new(1, 1, 1), BlockData::AIR);
+
+                assert_eq!(edit.state(WorldVec::new(CHUNK_EDGE, 0, 0)), Some(stone));
+                assert_eq!(
+                    WorldPointer::new(WorldVec::new(CHUNK_EDGE, 0, 0)).state(voxels),
+                    Some(BlockData::AIR)
+                );
+                assert_eq!(dirty_count(), 0);
+
+                // Committing applies everything and dirties each touched chunk exactly once. The
+                // block set back to air was a no-op.
+                assert_eq!(edit.commit(KeepInWorld), 4);
+                assert_eq!(dirty_count(), 2);
+
+                for x in 0..4 {
+                    let pos = WorldVec::new(CHUNK_EDGE - 2 + x, 0, 0);
+                    assert_eq!(WorldPointer::new(pos).state(voxels), Some(stone));
+                }
+
+                // Aborted edits leave the world untouched.
+                voxels.clear_dirty();
+
+                let mut edit = voxels.edit();
+                edit.set(WorldVec::new(5, 5, 5), stone);
+                edit.abort();
+
+                assert_eq!(
+                    WorldPointer::new(WorldVec::new(5, 5, 5)).state(voxels),
+                    Some(BlockData::AIR)
+                );
+                assert_eq!(dirty_count(), 0);
+
+                // Edits can populate chunks which don't exist yet.
+                let mut edit = voxels.edit();
+                edit.set(WorldVec::new(0, -1, 0), stone);
+                assert_eq!(edit.commit(PopulateWorld), 1);
+                assert!(voxels.get(ChunkVec::NEG_Y).is_some());
+            },
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+
+use bevy_autoken::{random_component, Obj};
+use crucible_math::{
+    BlockFace, BlockVec, BlockVecExt, ChunkVec, WorldVec, WorldVecExt, CHUNK_VOLUME,
+};
+use crucible_utils::newtypes::EnumIndex;
+use rustc_hash::FxHashMap;
+
+use super::{
+    BlockData, BlockMaterialCache, ChunkVoxelData, SetStatePolicy, WorldPointer, WorldVoxelData,
+};
+
+// === BlockLightDescriptor === //
+
+/// The brightest block light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Makes blocks of a material emit block light. `emission` ranges from `0` (no light) to
+/// [`MAX_LIGHT`].
+#[derive(Debug, Copy, Clone)]
+pub struct BlockLightDescriptor {
+    pub emission: u8,
+}
+
+random_component!(BlockLightDescriptor);
+
+// === WorldLightData === //
+
+/// Block light levels for a [`WorldVoxelData`]. Light floods outwards from emissive blocks
+/// through air, losing one level per block traveled.
+#[derive(Debug, Default)]
+pub struct WorldLightData {
+    chunks: FxHashMap<ChunkVec, Box<[u8; CHUNK_VOLUME as usize]>>,
+}
+
+random_component!(WorldLightData);
+
+impl WorldLightData {
+    pub fn light(&self, pos: WorldVec) -> u8 {
+        let (chunk, block) = pos.decompose();
+        self.chunks
+            .get(&chunk)
+            .map_or(0, |levels| levels[block.to_index()])
+    }
+
+    fn set_light(&mut self, pos: WorldVec, level: u8) {
+        let (chunk, block) = pos.decompose();
+
+        if level == 0 {
+            if let Some(levels) = self.chunks.get_mut(&chunk) {
+                levels[block.to_index()] = 0;
+            }
+            return;
+        }
+
+        self.chunks
+            .entry(chunk)
+            .or_insert_with(|| Box::new([0; CHUNK_VOLUME as usize]))[block.to_index()] = level;
+    }
+
+    /// Updates the light around `pos` after the block there has been changed. Edits made through
+    /// the [`RelightBlocks`] policy call this automatically.
+    ///
+    /// Light which was emitted by or passed through the old block is cleared by flooding outwards
+    /// until we reach blocks which are lit at least as brightly by some other source. Those
+    /// blocks, along with the new block if it's emissive, then re-flood the cleared region.
+    pub fn relight_block(
+        &mut self,
+        world: Obj<WorldVoxelData>,
+        emissions: &mut BlockMaterialCache<BlockLightDescriptor>,
+        pos: WorldVec,
+    ) {
+        let mut pointer = WorldPointer::new(pos);
+        let mut spread = VecDeque::new();
+
+        // Remove the old light.
+        let old_level = self.light(pos);
+
+        if old_level > 0 {
+            let mut darken = VecDeque::from([(pointer, old_level)]);
+            self.set_light(pos, 0);
+
+            while let Some((pointer, level)) = darken.pop_front() {
+                for face in BlockFace::variants() {
+                    let mut neighbor = pointer.neighbor(face);
+                    let neighbor_level = self.light(neighbor.pos);
+
+                    if neighbor_level == 0 {
+                        continue;
+                    }
+
+                    if neighbor_level >= level {
+                        spread.push_back(neighbor);
+                        continue;
+                    }
+
+                    self.set_light(neighbor.pos, 0);
+                    darken.push_back((neighbor, neighbor_level));
+
+                    // Emissive blocks we darkened have to relight themselves.
+                    let emission = Self::emission(world, emissions, &mut neighbor);
+                    if emission > 0 {
+                        self.set_light(neighbor.pos, emission);
+                        spread.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        // Seed the new block's light.
+        let emission = Self::emission(world, emissions, &mut pointer);
+
+        if emission > 0 {
+            self.set_light(pos, emission.max(self.light(pos)));
+            spread.push_back(pointer);
+        } else if Self::is_transparent(world, &mut pointer) {
+            spread.extend(
+                BlockFace::variants()
+                    .map(|face| pointer.neighbor(face))
+                    .filter(|neighbor| self.light(neighbor.pos) > 0),
+            );
+        }
+
+        // Flood the light outwards.
+        while let Some(pointer) = spread.pop_front() {
+            let level = self.light(pointer.pos);
+
+            for face in BlockFace::variants() {
+                let mut neighbor = pointer.neighbor(face);
+
+                if self.light(neighbor.pos) + 1 >= level
+                    || !Self::is_transparent(world, &mut neighbor)
+                {
+                    continue;
+                }
+
+                self.set_light(neighbor.pos, level - 1);
+                spread.push_back(neighbor);
+            }
+        }
+    }
+
+    fn emission(
+        world: Obj<WorldVoxelData>,
+        emissions: &mut BlockMaterialCache<BlockLightDescriptor>,
+        pointer: &mut WorldPointer,
+    ) -> u8 {
+        pointer
+            .state(world)
+            .filter(|state| state.is_not_air())
+            .and_then(|state| emissions.get(state.material))
+            .map_or(0, |descriptor| descriptor.emission.min(MAX_LIGHT))
+    }
+
+    fn is_transparent(world: Obj<WorldVoxelData>, pointer: &mut WorldPointer) -> bool {
+        pointer.state(world).is_some_and(|state| state.is_air())
+    }
+}
+
+// === RelightBlocks === //
+
+/// A [`SetStatePolicy`] which updates the [`WorldLightData`] of every block `policy` changes so
+/// that edits don't leave stale light behind.
+#[derive(Debug)]
+pub struct RelightBlocks<'a, P> {
+    pub light: &'a mut WorldLightData,
+    pub emissions: &'a mut BlockMaterialCache<BlockLightDescriptor>,
+    pub policy: P,
+}
+
+impl<P: SetStatePolicy> SetStatePolicy for RelightBlocks<'_, P> {
+    fn fetch_chunk(
+        &mut self,
+        world: Obj<WorldVoxelData>,
+        pos: ChunkVec,
+    ) -> Option<Obj<ChunkVoxelData>> {
+        self.policy.fetch_chunk(world, pos)
+    }
+
+    fn set_block(&mut self, chunk: Obj<ChunkVoxelData>, pos: BlockVec, data: BlockData) {
+        self.policy.set_block(chunk, pos, data);
+        self.light.relight_block(
+            chunk.world(),
+            self.emissions,
+            WorldVec::compose(chunk.pos(), pos),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{
+        spawn_entity, RandomArena, RandomEntityExt as _, RandomWorldExt as _, SendsEvent,
+    };
+    use bevy_ecs::{event::Events, world::World};
+    use crucible_math::CHUNK_EDGE;
+
+    use crate::voxel::{BlockMaterialRegistry, ChunkData, PopulateWorld, WorldChunkCreated};
+
+    use super::*;
+
+    #[test]
+    fn emissive_blocks_light_and_darken_their_surroundings() {
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<RandomArena<BlockMaterialRegistry>>();
+        world.init_resource::<RandomArena<BlockLightDescriptor>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                &mut BlockMaterialRegistry,
+                &BlockLightDescriptor,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let root = spawn_entity(());
+                let voxels = root.insert(WorldVoxelData::default());
+                let mut registry = root.insert(BlockMaterialRegistry::default());
+                let mut light = WorldLightData::default();
+
+                for x in -1..=1 {
+                    for y in -1..=1 {
+                        for z in -1..=1 {
+                            voxels
+                                .get_or_insert(ChunkVec::new(x, y, z))
+                                .initialize_data(ChunkData::AllAir);
+                        }
+                    }
+                }
+
+                registry.register("crucible:air", spawn_entity(()));
+                let glowstone = BlockData::new(registry.register(
+                    "crucible:glowstone",
+                    spawn_entity(()).with(BlockLightDescriptor {
+                        emission: MAX_LIGHT,
+                    }),
+                ));
+                let torch = BlockData::new(registry.register(
+                    "crucible:torch",
+                    spawn_entity(()).with(BlockLightDescriptor { emission: 5 }),
+                ));
+                let stone = BlockData::new(registry.register("crucible:stone", spawn_entity(())));
+
+                let mut cache = BlockMaterialCache::new(registry);
+                let mut set = |light: &mut WorldLightData, pos: WorldVec, state: BlockData| {
+                    let policy = RelightBlocks {
+                        light,
+                        emissions: &mut cache,
+                        policy: PopulateWorld,
+                    };
+                    WorldPointer::new(pos).set_state(voxels, state, policy);
+                };
+
+                // Light falls off by one level per block of Manhattan distance, even across chunk
+                // boundaries.
+                let center = WorldVec::new(CHUNK_EDGE - 2, 3, 3);
+                set(&mut light, center, glowstone);
+
+                for (offset, level) in [
+                    (WorldVec::ZERO, 15),
+                    (WorldVec::new(1, 0, 0), 14),
+                    (WorldVec::new(2, 0, 0), 13),
+                    (WorldVec::new(0, -3, 0), 12),
+                    (WorldVec::new(1, 1, -1), 12),
+                    (WorldVec::new(-14, 0, 0), 1),
+                    (WorldVec::new(-15, 0, 0), 0),
+                ] {
+                    assert_eq!(light.light(center + offset), level, "at {offset:?}");
+                }
+
+                // Opaque blocks cast shadows which light has to bend around.
+                set(&mut light, center + WorldVec::new(1, 0, 0), stone);
+                assert_eq!(light.light(center + WorldVec::new(1, 0, 0)), 0);
+                assert_eq!(light.light(center + WorldVec::new(2, 0, 0)), 11);
+
+                // A dimmer torch far away outshines the glowstone's remaining light.
+                let torch_pos = center + WorldVec::new(0, 0, -12);
+                set(&mut light, torch_pos, torch);
+                assert_eq!(light.light(torch_pos), 5);
+                assert_eq!(light.light(torch_pos + WorldVec::new(0, 0, -1)), 4);
+                assert_eq!(light.light(torch_pos + WorldVec::new(0, 0, 1)), 4);
+
+                // Removing the glowstone darkens everything except what the torch still lights.
+                set(&mut light, center, BlockData::AIR);
+
+                assert_eq!(light.light(center), 0);
+                assert_eq!(light.light(center + WorldVec::new(2, 0, 0)), 0);
+                assert_eq!(light.light(center + WorldVec::new(0, 0, -10)), 3);
+                assert_eq!(light.light(torch_pos), 5);
+                assert_eq!(light.light(torch_pos + WorldVec::new(0, 0, -1)), 4);
+
+                set(&mut light, torch_pos, BlockData::AIR);
+
+                for x in -16..16 {
+                    for y in -16..16 {
+                        for z in -16..16 {
+                            let pos = center + WorldVec::new(x, y, z);
+                            assert_eq!(light.light(pos), 0, "at {pos:?}");
+                        }
+                    }
+                }
+            },
+        );
+    }
+}
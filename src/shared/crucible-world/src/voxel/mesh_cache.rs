@@ -0,0 +1,389 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{mpsc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use crucible_math::ChunkVec;
+use crucible_utils::{iter::VolumetricIter, newtypes::LargeIndex as _};
+
+use super::{ChunkData, ChunkVoxelData};
+
+// === Keys === //
+
+/// The version of the encoding [`chunk_mesh_key`] hashes. This must be bumped whenever the encoding
+/// changes so that keys from older builds can't collide with keys from newer ones.
+const KEY_ENCODING_VERSION: u8 = 1;
+
+/// A 64-bit FNV-1a hasher.
+///
+/// Unlike [`Hash`](std::hash::Hash) implementations and most [`Hasher`](std::hash::Hasher)s, its
+/// output only depends on the bytes written to it, so it's safe to persist across builds and
+/// platforms. Multi-byte values are written in little-endian order.
+#[derive(Debug, Clone)]
+pub struct MeshKeyHasher(u64);
+
+impl Default for MeshKeyHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl MeshKeyHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Writes a length-prefixed string so that adjacent strings can't run into one another.
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u32(value.len() as u32);
+        self.write(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes everything about a chunk's surroundings which could affect its mesh: its position and the
+/// contents of the chunk and of its 26 neighbors, which determine culled faces and ambient occlusion.
+///
+/// Meshes also depend on state outside the world such as the order in which materials were
+/// registered and the layout of the texture atlas. The mesher summarizes that state with a
+/// [`MeshKeyHasher`] and passes the result as the `fingerprint`.
+///
+/// Editing any of these chunks or changing the fingerprint changes the key, which is what
+/// invalidates cached meshes.
+pub fn chunk_mesh_key(chunk: &ChunkVoxelData, fingerprint: u64) -> u64 {
+    let mut hasher = MeshKeyHasher::default();
+    hasher.write_u8(KEY_ENCODING_VERSION);
+    hasher.write_u64(fingerprint);
+
+    let pos = chunk.pos();
+    hasher.write_i32(pos.x());
+    hasher.write_i32(pos.y());
+    hasher.write_i32(pos.z());
+
+    let world = chunk.world();
+    for [x, y, z] in VolumetricIter::new_exclusive_iter([3, 3, 3]) {
+        let rel = ChunkVec::new(x as i32 - 1, y as i32 - 1, z as i32 - 1);
+        let neighbor = world.get(pos + rel);
+
+        match neighbor.as_deref().and_then(ChunkVoxelData::data) {
+            None => hasher.write_u8(0),
+            Some(ChunkData::AllAir) => hasher.write_u8(1),
+            Some(ChunkData::Complex(blocks)) => {
+                hasher.write_u8(2);
+
+                for block in blocks.iter() {
+                    hasher.write_u16(block.material.as_raw());
+                    hasher.write_u32(block.variant);
+                }
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+// === ChunkMeshCache === //
+
+const MAGIC: [u8; 4] = *b"CMSH";
+const HEADER_LEN: usize = 32;
+
+/// An on-disk cache of chunk meshes which lets chunks which are loaded again in an unchanged state
+/// skip meshing.
+///
+/// Meshes are stored per chunk position and LOD alongside the [`chunk_mesh_key`] they were built for
+/// and the mesher's format version. A mesh is only reused if both still match, so edited chunks and
+/// meshes written by an older mesher are rejected and overwritten once the chunk is re-meshed. The
+/// header also records the mesh's length and checksum so that truncated or corrupted files are
+/// rejected too.
+///
+/// The cache is oblivious to the mesh's contents. Changes to how the mesher builds meshes must be
+/// covered by the format version while the state it reads besides the world, such as the layout of
+/// the texture atlas, must be covered by the fingerprint passed to [`chunk_mesh_key`].
+///
+/// All disk I/O happens on a background thread. Loads are requested with
+/// [`request_load`](Self::request_load) and their results collected with
+/// [`poll_loaded`](Self::poll_loaded) while [`store`](Self::store) returns immediately. Dropping the
+/// cache waits for the stores queued so far to be written.
+#[derive(Debug)]
+pub struct ChunkMeshCache {
+    requests: Option<mpsc::Sender<CacheRequest>>,
+    loaded: Mutex<mpsc::Receiver<LoadedChunkMesh>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// The result of a [`ChunkMeshCache::request_load`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LoadedChunkMesh {
+    pub pos: ChunkVec,
+    pub lod: u8,
+    pub key: u64,
+
+    /// The cached mesh or `None` if there wasn't a valid one for this key.
+    pub mesh: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+enum CacheRequest {
+    Load {
+        pos: ChunkVec,
+        lod: u8,
+        key: u64,
+    },
+    Store {
+        pos: ChunkVec,
+        lod: u8,
+        key: u64,
+        mesh: Vec<u8>,
+    },
+    Flush(mpsc::Sender<()>),
+}
+
+impl ChunkMeshCache {
+    /// Opens the cache in `dir`, which must be specific to the world whose chunks are cached.
+    pub fn new(dir: impl Into<PathBuf>, format_version: u32) -> Self {
+        let dir = CacheDir {
+            dir: dir.into(),
+            format_version,
+        };
+
+        let (requests, request_rx) = mpsc::channel();
+        let (loaded_tx, loaded) = mpsc::channel();
+
+        let worker = thread::Builder::new()
+            .name("chunk mesh cache".to_string())
+            .spawn(move || dir.serve(request_rx, loaded_tx))
+            .expect("failed to spawn the chunk mesh cache thread");
+
+        Self {
+            requests: Some(requests),
+            loaded: Mutex::new(loaded),
+            worker: Some(worker),
+        }
+    }
+
+    fn send(&self, request: CacheRequest) {
+        // The worker only exits once we drop the sender.
+        let _ = self.requests.as_ref().unwrap().send(request);
+    }
+
+    /// Requests the cached mesh of the chunk at `pos`. Its [`LoadedChunkMesh`] is eventually
+    /// returned by [`poll_loaded`](Self::poll_loaded), without a mesh if there isn't one or if it
+    /// was built for a different key or format version.
+    pub fn request_load(&self, pos: ChunkVec, lod: u8, key: u64) {
+        self.send(CacheRequest::Load { pos, lod, key });
+    }
+
+    /// Collects the results of every load which has finished since the last call.
+    pub fn poll_loaded(&self) -> Vec<LoadedChunkMesh> {
+        self.loaded.lock().unwrap().try_iter().collect()
+    }
+
+    /// Caches the mesh of the chunk at `pos`, replacing any mesh previously cached for it.
+    pub fn store(&self, pos: ChunkVec, lod: u8, key: u64, mesh: Vec<u8>) {
+        self.send(CacheRequest::Store {
+            pos,
+            lod,
+            key,
+            mesh,
+        });
+    }
+
+    /// Blocks until every request made so far has been handled.
+    pub fn flush(&self) {
+        let (done_tx, done_rx) = mpsc::channel();
+        self.send(CacheRequest::Flush(done_tx));
+        let _ = done_rx.recv();
+    }
+}
+
+impl Drop for ChunkMeshCache {
+    fn drop(&mut self) {
+        self.requests = None;
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheDir {
+    dir: PathBuf,
+    format_version: u32,
+}
+
+impl CacheDir {
+    fn serve(&self, requests: mpsc::Receiver<CacheRequest>, loaded: mpsc::Sender<LoadedChunkMesh>) {
+        for request in requests {
+            match request {
+                CacheRequest::Load { pos, lod, key } => {
+                    let mesh = self.load(pos, lod, key);
+                    let _ = loaded.send(LoadedChunkMesh {
+                        pos,
+                        lod,
+                        key,
+                        mesh,
+                    });
+                }
+                CacheRequest::Store {
+                    pos,
+                    lod,
+                    key,
+                    mesh,
+                } => {
+                    if let Err(err) = self.store(pos, lod, key, &mesh) {
+                        tracing::warn!("Failed to cache mesh for chunk {pos:?}: {err}");
+                    }
+                }
+                CacheRequest::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    fn path(&self, pos: ChunkVec, lod: u8) -> PathBuf {
+        self.dir
+            .join(format!("{}_{}_{}_{lod}.mesh", pos.x(), pos.y(), pos.z()))
+    }
+
+    fn load(&self, pos: ChunkVec, lod: u8, key: u64) -> Option<Vec<u8>> {
+        let mut data = fs::read(self.path(pos, lod)).ok()?;
+        if data.len() < HEADER_LEN {
+            return None;
+        }
+
+        let (magic, rest) = data.split_at(4);
+        let (version, rest) = rest.split_at(4);
+        let (stored_key, rest) = rest.split_at(8);
+        let (len, rest) = rest.split_at(8);
+        let (checksum, mesh) = rest.split_at(8);
+
+        if magic != MAGIC
+            || u32::from_le_bytes(version.try_into().unwrap()) != self.format_version
+            || u64::from_le_bytes(stored_key.try_into().unwrap()) != key
+            || u64::from_le_bytes(len.try_into().unwrap()) != mesh.len() as u64
+            || u64::from_le_bytes(checksum.try_into().unwrap()) != mesh_checksum(mesh)
+        {
+            return None;
+        }
+
+        data.drain(..HEADER_LEN);
+        Some(data)
+    }
+
+    fn store(&self, pos: ChunkVec, lod: u8, key: u64, mesh: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut data = Vec::with_capacity(HEADER_LEN + mesh.len());
+        data.extend_from_slice(&MAGIC);
+        data.extend_from_slice(&self.format_version.to_le_bytes());
+        data.extend_from_slice(&key.to_le_bytes());
+        data.extend_from_slice(&(mesh.len() as u64).to_le_bytes());
+        data.extend_from_slice(&mesh_checksum(mesh).to_le_bytes());
+        data.extend_from_slice(mesh);
+
+        // Write to a temporary file first so that a crash mid-write can't leave a partial mesh
+        // behind under the real name.
+        let path = self.path(pos, lod);
+        let temp = path.with_extension("mesh.tmp");
+        fs::write(&temp, data)?;
+        fs::rename(&temp, &path)
+    }
+}
+
+fn mesh_checksum(mesh: &[u8]) -> u64 {
+    let mut hasher = MeshKeyHasher::default();
+    hasher.write(mesh);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mesh_key_hasher_is_stable() {
+        // Keys are persisted so the hash of a given encoding must never change.
+        let mut hasher = MeshKeyHasher::default();
+        assert_eq!(hasher.finish(), 0xcbf2_9ce4_8422_2325);
+
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+
+        let mut hasher = MeshKeyHasher::default();
+        hasher.write_u32(0x6463_6261);
+        assert_eq!(hasher.finish(), {
+            let mut bytes = MeshKeyHasher::default();
+            bytes.write(b"abcd");
+            bytes.finish()
+        });
+    }
+
+    #[test]
+    fn cache_rejects_damaged_meshes() {
+        let dir = std::env::temp_dir().join(format!("crucible-chunk-cache-{}", std::process::id()));
+        let load = |cache: &ChunkMeshCache, key| {
+            cache.request_load(ChunkVec::ZERO, 0, key);
+            cache.flush();
+            cache.poll_loaded().pop().unwrap().mesh
+        };
+
+        let cache = ChunkMeshCache::new(&dir, 1);
+        cache.store(ChunkVec::ZERO, 0, 42, b"vertices".to_vec());
+        assert_eq!(load(&cache, 42).as_deref(), Some(&b"vertices"[..]));
+        assert_eq!(load(&cache, 43), None);
+
+        // Only the finished file is left behind.
+        let files = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(files, 1);
+
+        let path = dir.join("0_0_0_0.mesh");
+        let data = fs::read(&path).unwrap();
+
+        // Truncated files fail the length check...
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert_eq!(load(&cache, 42), None);
+
+        // ...and corrupted ones fail the checksum.
+        let mut corrupted = data.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        fs::write(&path, corrupted).unwrap();
+        assert_eq!(load(&cache, 42), None);
+
+        drop(cache);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
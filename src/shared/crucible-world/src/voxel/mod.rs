@@ -4,5 +4,17 @@ pub use data::*;
 mod ref_count;
 pub use ref_count::*;
 
+mod light;
+pub use light::*;
+
 mod loader;
 pub use loader::*;
+
+mod mesh_cache;
+pub use mesh_cache::*;
+
+mod structure;
+pub use structure::*;
+
+mod edit;
+pub use edit::*;
@@ -0,0 +1,288 @@
+use std::collections::hash_map;
+
+use bevy_autoken::Obj;
+use crucible_math::WorldVec;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use typed_glam::glam::IVec3;
+
+use super::{BlockData, SetStatePolicy, WorldPointer, WorldVoxelData};
+
+// === Structure === //
+
+/// A multi-block template (e.g. a tree or a building) which can be stamped into the world with
+/// [`WorldVoxelData::place_structure`].
+///
+/// Positions which were never [`set`](Self::set) are masked out and leave the world untouched when
+/// the structure is placed. To carve out a space, set the position to [`BlockData::AIR`]
+/// explicitly.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "SerializedStructure")]
+pub struct Structure {
+    palette: Vec<BlockData>,
+    blocks: Vec<StructureBlock>,
+    #[serde(skip)]
+    palette_map: FxHashMap<BlockData, u16>,
+    #[serde(skip)]
+    block_map: FxHashMap<[i32; 3], usize>,
+}
+
+/// The serialized fields of a [`Structure`], from which its lookup maps are rebuilt.
+#[derive(Deserialize)]
+struct SerializedStructure {
+    palette: Vec<BlockData>,
+    blocks: Vec<StructureBlock>,
+}
+
+impl From<SerializedStructure> for Structure {
+    fn from(SerializedStructure { palette, blocks }: SerializedStructure) -> Self {
+        let mut structure = Self {
+            palette_map: palette
+                .iter()
+                .enumerate()
+                .map(|(i, &data)| (data, i as u16))
+                .collect(),
+            palette,
+            blocks: Vec::with_capacity(blocks.len()),
+            block_map: FxHashMap::default(),
+        };
+
+        // Go through `set_state` so that duplicate offsets collapse into their last state.
+        for block in blocks {
+            structure.set_state(block.offset, block.state);
+        }
+
+        structure
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+struct StructureBlock {
+    offset: [i32; 3],
+    state: u16,
+}
+
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Default)]
+pub enum StructurePlaceMode {
+    /// Every block in the structure replaces whatever was in the world.
+    #[default]
+    Overwrite,
+
+    /// Blocks in the structure are only placed where the world is currently air.
+    KeepExisting,
+}
+
+impl Structure {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the block at `offset` relative to the structure's origin. Setting the same offset more
+    /// than once keeps the last state.
+    pub fn set(&mut self, offset: WorldVec, data: BlockData) {
+        let state = self.palette_index(data);
+        self.set_state(offset.to_glam().to_array(), state);
+    }
+
+    fn set_state(&mut self, offset: [i32; 3], state: u16) {
+        match self.block_map.entry(offset) {
+            hash_map::Entry::Occupied(entry) => {
+                self.blocks[*entry.get()].state = state;
+            }
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(self.blocks.len());
+                self.blocks.push(StructureBlock { offset, state });
+            }
+        }
+    }
+
+    pub fn palette(&self) -> &[BlockData] {
+        &self.palette
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (WorldVec, BlockData)> + '_ {
+        self.blocks.iter().map(|block| {
+            (
+                WorldVec::from_glam(IVec3::from_array(block.offset)),
+                self.palette[block.state as usize],
+            )
+        })
+    }
+
+    fn palette_index(&mut self, data: BlockData) -> u16 {
+        *self.palette_map.entry(data).or_insert_with(|| {
+            let index = u16::try_from(self.palette.len()).expect("too many states in structure");
+            self.palette.push(data);
+            index
+        })
+    }
+}
+
+impl WorldVoxelData {
+    /// Stamps `structure` into the world with its origin at `origin`. Chunks are fetched through
+    /// `policy` once per chunk the structure covers and each touched chunk is only queued as dirty
+    /// once.
+    pub fn place_structure(
+        self: Obj<Self>,
+        origin: WorldVec,
+        structure: &Structure,
+        mode: StructurePlaceMode,
+        mut policy: impl SetStatePolicy,
+    ) {
+        let mut pointer = WorldPointer::new(origin);
+
+        for (offset, data) in structure.iter() {
+            pointer.move_to(origin + offset);
+
+            if mode == StructurePlaceMode::KeepExisting
+                && pointer.state(self).is_some_and(|state| state.is_not_air())
+            {
+                continue;
+            }
+
+            pointer.set_state(self, data, &mut policy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use bevy_autoken::{
+        spawn_entity, RandomArena, RandomEntityExt as _, RandomWorldExt as _, SendsEvent,
+    };
+    use bevy_ecs::{event::Events, world::World};
+    use crucible_math::{ChunkVec, CHUNK_EDGE};
+    use crucible_utils::newtypes::Index as _;
+
+    use crate::voxel::{
+        BlockMaterial, ChunkData, ChunkVoxelData, KeepInWorld, PopulateWorld, WorldChunkCreated,
+    };
+
+    use super::*;
+
+    #[test]
+    fn structures_span_chunk_boundaries() {
+        let mut world = World::new();
+        world.init_resource::<RandomArena<WorldVoxelData>>();
+        world.init_resource::<RandomArena<ChunkVoxelData>>();
+        world.init_resource::<Events<WorldChunkCreated>>();
+
+        let log = BlockData::new(BlockMaterial::from_usize(1));
+        let leaves = BlockData::new(BlockMaterial::from_usize(2));
+        let stone = BlockData::new(BlockMaterial::from_usize(3));
+
+        // A small tree whose canopy pokes into the chunk above and the chunk to its east.
+        let mut tree = Structure::new();
+        for y in 0..3 {
+            tree.set(WorldVec::new(0, y, 0), log);
+        }
+        for x in -1..=1 {
+            tree.set(WorldVec::new(x, 3, 0), leaves);
+        }
+        assert_eq!(tree.palette(), &[log, leaves]);
+
+        world.use_random(
+            |_: PhantomData<(
+                &mut WorldVoxelData,
+                &mut ChunkVoxelData,
+                SendsEvent<WorldChunkCreated>,
+            )>| {
+                let mut voxels = spawn_entity(()).insert(WorldVoxelData::default());
+                let origin = WorldVec::new(CHUNK_EDGE - 1, CHUNK_EDGE - 2, 4);
+
+                voxels
+                    .get_or_insert(ChunkVec::ZERO)
+                    .initialize_data(ChunkData::AllAir);
+
+                let occupied = origin + WorldVec::new(1, 3, 0);
+                WorldPointer::new(occupied).set_state(voxels, stone, PopulateWorld);
+                voxels.clear_dirty();
+
+                voxels.place_structure(
+                    origin,
+                    &tree,
+                    StructurePlaceMode::KeepExisting,
+                    PopulateWorld,
+                );
+
+                for (offset, data) in tree.iter() {
+                    let pos = origin + offset;
+                    let expected = if pos == occupied { stone } else { data };
+                    assert_eq!(WorldPointer::new(pos).state(voxels), Some(expected));
+                }
+
+                // The chunk holding the preserved block received no edits so it stays clean.
+                let mut dirty = voxels
+                    .iter_dirty()
+                    .map(|chunk| chunk.pos())
+                    .collect::<Vec<_>>();
+                dirty.sort_by_key(|pos| pos.to_glam().to_array());
+                assert_eq!(dirty, [ChunkVec::ZERO, ChunkVec::Y]);
+
+                // Overwriting replaces the block we previously preserved.
+                voxels.place_structure(origin, &tree, StructurePlaceMode::Overwrite, KeepInWorld);
+                assert_eq!(WorldPointer::new(occupied).state(voxels), Some(leaves));
+            },
+        );
+    }
+
+    #[test]
+    fn setting_an_offset_twice_keeps_the_last_state() {
+        let log = BlockData::new(BlockMaterial::from_usize(1));
+        let leaves = BlockData::new(BlockMaterial::from_usize(2));
+
+        let mut structure = Structure::new();
+        structure.set(WorldVec::new(0, 0, 0), log);
+        structure.set(WorldVec::new(0, 1, 0), log);
+        structure.set(WorldVec::new(0, 0, 0), leaves);
+
+        assert_eq!(structure.len(), 2);
+        assert_eq!(
+            structure.iter().collect::<Vec<_>>(),
+            [
+                (WorldVec::new(0, 0, 0), leaves),
+                (WorldVec::new(0, 1, 0), log),
+            ],
+        );
+    }
+
+    #[test]
+    fn deserialized_structures_rebuild_their_maps() {
+        let log = BlockData::new(BlockMaterial::from_usize(1));
+        let leaves = BlockData::new(BlockMaterial::from_usize(2));
+
+        let block = |y, state| StructureBlock {
+            offset: [0, y, 0],
+            state,
+        };
+
+        let mut structure = Structure::from(SerializedStructure {
+            palette: vec![log, leaves],
+            blocks: vec![block(0, 0), block(1, 0), block(0, 1)],
+        });
+
+        // Duplicate offsets collapse into their last state...
+        assert_eq!(structure.len(), 2);
+
+        // ...and later edits find the existing blocks and palette entries.
+        structure.set(WorldVec::new(0, 1, 0), leaves);
+        assert_eq!(structure.palette(), [log, leaves]);
+        assert_eq!(
+            structure.iter().collect::<Vec<_>>(),
+            [
+                (WorldVec::new(0, 0, 0), leaves),
+                (WorldVec::new(0, 1, 0), leaves),
+            ],
+        );
+    }
+}
@@ -1,10 +1,18 @@
-use std::{ops::Deref, sync::Arc};
+use std::{
+    fmt,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Context;
-use bevy_autoken::random_component;
+use bevy_autoken::{random_component, random_event};
+use bevy_ecs::event::Event;
 use crucible_utils::fmt::DisplayFromFn;
 use winit::window::Window;
 
+/// The backends [`GfxContext::new`] picks adapters from.
+const WINDOWED_BACKENDS: wgpu::Backends = wgpu::Backends::PRIMARY;
+
 #[derive(Debug, Clone)]
 pub struct GfxContext(Arc<GfxContextInner>);
 
@@ -20,11 +28,14 @@ impl Deref for GfxContext {
 
 #[derive(Debug)]
 pub struct GfxContextInner {
-    pub instance: wgpu::Instance,
+    // The instance and adapter outlive any single device so that the device can be recreated if it
+    // is ever lost.
+    pub instance: Arc<wgpu::Instance>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub adapter: wgpu::Adapter,
+    pub adapter: Arc<wgpu::Adapter>,
     pub adapter_info: AdapterInfoBundle,
+    pub device_lost: DeviceLostFlag,
 
     // These need to be queried fairly regularly so it's best to just store them even if you can just
     // fetch them from the device.
@@ -33,37 +44,98 @@ pub struct GfxContextInner {
 }
 
 impl GfxContext {
+    /// Creates a context for the adapter picked by `selector` among those passing `compat_detector`,
+    /// falling back to the best-scoring compatible adapter if the selector matches none of them.
     pub async fn new<T>(
         main_window: Arc<Window>,
+        selector: &AdapterSelector,
         mut compat_detector: impl Judge<Table = T>,
     ) -> anyhow::Result<(Self, wgpu::Surface<'static>, T)> {
-        let backends = wgpu::Backends::PRIMARY;
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends,
-            dx12_shader_compiler: wgpu::Dx12Compiler::Dxc {
-                dxil_path: None,
-                dxc_path: None,
-            },
-            flags: wgpu::InstanceFlags::empty(),
-            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+        let instance = Self::create_instance();
+
+        let main_surface = instance
+            .create_surface(main_window)
+            .context("failed to create main surface")?;
+
+        let mut candidates = Self::judge_adapters(&instance, &main_surface, &mut compat_detector);
+        let listings = candidates
+            .iter()
+            .map(JudgedAdapter::listing)
+            .collect::<Vec<_>>();
+
+        let selected = selector.select(&listings).or_else(|| {
+            if !matches!(selector, AdapterSelector::Best) {
+                tracing::warn!("No compatible adapter matches {selector:?}; using the best one.");
+            }
+
+            candidates
+                .iter()
+                .enumerate()
+                .filter(|(_, candidate)| candidate.judgement.did_pass())
+                .max_by(|(_, a), (_, b)| a.judgement.score().total_cmp(&b.judgement.score()))
+                .map(|(i, _)| i)
         });
 
+        let req = candidates.swap_remove(
+            selected.context("no adapters satisfy the application's minimum requirements")?,
+        );
+
+        let (device, queue) = req
+            .adapter
+            .request_device(&req.descriptor, None)
+            .await
+            .context("failed to acquire wgpu device")?;
+
+        let device_lost = DeviceLostFlag::default();
+        device.set_device_lost_callback(device_lost.callback());
+
+        Ok((
+            Self(Arc::new(GfxContextInner {
+                instance: Arc::new(instance),
+                device,
+                queue,
+                adapter: Arc::new(req.adapter),
+                adapter_info: req.adapter_info,
+                device_lost,
+                requested_features: req.descriptor.required_features,
+                requested_limits: req.descriptor.required_limits,
+            })),
+            main_surface,
+            req.compat_table,
+        ))
+    }
+
+    /// Lists the adapters [`new`](Self::new) chooses from, such as for a settings menu, in the
+    /// order which [`AdapterSelector::Index`] indexes. Each adapter is judged by `compat_detector`
+    /// against a surface for `main_window` just like in `new` and only those it accepts can be
+    /// selected.
+    pub fn enumerate_adapters<T>(
+        main_window: Arc<Window>,
+        mut compat_detector: impl Judge<Table = T>,
+    ) -> anyhow::Result<Vec<AdapterListing>> {
+        let instance = Self::create_instance();
+
         let main_surface = instance
             .create_surface(main_window)
             .context("failed to create main surface")?;
 
-        struct ValidatedAdapter<'a, T> {
-            adapter: wgpu::Adapter,
-            adapter_info: AdapterInfoBundle,
-            descriptor: wgpu::DeviceDescriptor<'a>,
-            compat_table: T,
-            score: f64,
-        }
+        Ok(
+            Self::judge_adapters(&instance, &main_surface, &mut compat_detector)
+                .iter()
+                .map(JudgedAdapter::listing)
+                .collect(),
+        )
+    }
 
-        let req = instance
-            .enumerate_adapters(backends)
+    fn judge_adapters<T>(
+        instance: &wgpu::Instance,
+        main_surface: &wgpu::Surface<'static>,
+        compat_detector: &mut impl Judge<Table = T>,
+    ) -> Vec<JudgedAdapter<'static, T>> {
+        instance
+            .enumerate_adapters(WINDOWED_BACKENDS)
             .into_iter()
-            .filter_map(|adapter| {
+            .map(|adapter| {
                 // Get info about the adapter
                 let adapter_info = AdapterInfoBundle::new_for(&adapter);
 
@@ -71,8 +143,8 @@ impl GfxContext {
                 let mut descriptor = wgpu::DeviceDescriptor::default();
                 let (judgement, compat_table) = compat_detector.judge(&mut CompatQueryInfo {
                     descriptor: &mut descriptor,
-                    instance: &instance,
-                    main_surface: &main_surface,
+                    instance,
+                    main_surface,
                     adapter: &adapter,
                     adapter_info: &adapter_info,
                 });
@@ -94,39 +166,179 @@ impl GfxContext {
                 );
                 tracing::info!("Feature table: {:#?}", judgement);
 
-                judgement.did_pass().then_some(ValidatedAdapter {
+                JudgedAdapter {
                     adapter,
                     adapter_info,
                     descriptor,
                     compat_table,
-                    score: judgement.score(),
-                })
+                    judgement,
+                }
             })
-            .max_by(|a, b| a.score.total_cmp(&b.score))
-            .context("no adapters satisfy the application's minimum requirements")?;
+            .collect()
+    }
 
-        let (device, queue) = req
-            .adapter
-            .request_device(&req.descriptor, None)
+    fn create_instance() -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: WINDOWED_BACKENDS,
+            dx12_shader_compiler: wgpu::Dx12Compiler::Dxc {
+                dxil_path: None,
+                dxc_path: None,
+            },
+            flags: wgpu::InstanceFlags::empty(),
+            gles_minor_version: wgpu::Gles3MinorVersion::Automatic,
+        })
+    }
+
+    /// Creates a context without a window or surface using whichever adapter wgpu deems best. This
+    /// is intended for tests and offline rendering.
+    pub async fn new_headless() -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::default();
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await
+            .context("no adapters are available")?;
+
+        let descriptor = wgpu::DeviceDescriptor::default();
+        let (device, queue) = adapter
+            .request_device(&descriptor, None)
             .await
             .context("failed to acquire wgpu device")?;
 
-        Ok((
-            Self(Arc::new(GfxContextInner {
-                instance,
-                device,
-                queue,
-                adapter: req.adapter,
-                adapter_info: req.adapter_info,
-                requested_features: req.descriptor.required_features,
-                requested_limits: req.descriptor.required_limits,
-            })),
-            main_surface,
-            req.compat_table,
-        ))
+        let device_lost = DeviceLostFlag::default();
+        device.set_device_lost_callback(device_lost.callback());
+
+        Ok(Self(Arc::new(GfxContextInner {
+            instance: Arc::new(instance),
+            device,
+            queue,
+            adapter_info: AdapterInfoBundle::new_for(&adapter),
+            adapter: Arc::new(adapter),
+            device_lost,
+            requested_features: descriptor.required_features,
+            requested_limits: descriptor.required_limits,
+        })))
+    }
+
+    /// Creates a [headless](Self::new_headless) context for a GPU test. Machines without a
+    /// (software) adapter have nothing to test against so, rather than failing the test, this
+    /// reports it as skipped and returns `None`.
+    ///
+    /// Only tests can call this: other crates reach it through the `test-support` feature, which
+    /// they should only enable on their dev-dependency.
+    #[cfg(any(test, feature = "test-support"))]
+    pub async fn new_headless_or_skip() -> Option<Self> {
+        match Self::new_headless().await {
+            Ok(gfx) => Some(gfx),
+            Err(err) => {
+                let thread = std::thread::current();
+                let test = thread.name().unwrap_or("<unnamed>");
+                eprintln!("skipping GPU test {test}: {err:#}");
+                None
+            }
+        }
+    }
+
+    /// Requests a new device and queue from the same adapter with the same features and limits as
+    /// the current device. Existing surfaces remain valid but must be reconfigured against the new
+    /// device and every other GPU resource must be recreated.
+    pub async fn recreate_device(&self) -> anyhow::Result<Self> {
+        let (device, queue) = self
+            .adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: self.requested_features,
+                    required_limits: self.requested_limits.clone(),
+                },
+                None,
+            )
+            .await
+            .context("failed to reacquire wgpu device")?;
+
+        let device_lost = DeviceLostFlag::default();
+        device.set_device_lost_callback(device_lost.callback());
+
+        Ok(Self(Arc::new(GfxContextInner {
+            instance: self.instance.clone(),
+            device,
+            queue,
+            adapter: self.adapter.clone(),
+            adapter_info: self.adapter_info.clone(),
+            device_lost,
+            requested_features: self.requested_features,
+            requested_limits: self.requested_limits.clone(),
+        })))
     }
 }
 
+// === Device Loss === //
+
+/// Records whether a device has been lost. This is set from wgpu's device-lost callback, which may
+/// run on any thread.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceLostFlag(Arc<Mutex<Option<DeviceLostInfo>>>);
+
+#[derive(Debug, Clone)]
+pub struct DeviceLostInfo {
+    pub reason: wgpu::DeviceLostReason,
+    pub message: String,
+}
+
+impl DeviceLostFlag {
+    /// Creates a callback suitable for [`wgpu::Device::set_device_lost_callback`].
+    pub fn callback(&self) -> impl Fn(wgpu::DeviceLostReason, String) + Send + 'static {
+        let flag = self.clone();
+
+        move |reason, message| {
+            // These are raised when we drop the device or replace its callback ourselves.
+            if matches!(
+                reason,
+                wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback
+            ) {
+                return;
+            }
+
+            tracing::error!("Graphics device was lost ({reason:?}): {message}");
+            *flag.0.lock().unwrap() = Some(DeviceLostInfo { reason, message });
+        }
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+
+    pub fn info(&self) -> Option<DeviceLostInfo> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Sent once a lost device has been replaced. Subsystems holding GPU resources should recreate
+/// them against the new [`GfxContext`].
+#[derive(Debug, Clone, Event)]
+pub struct GfxDeviceRecreated;
+
+random_event!(GfxDeviceRecreated);
+
+/// Runs the device recovery flow if `flag` reports that its device was lost: `recreate` is asked
+/// for a replacement context, which is then passed to `install`. Returns whether a recovery took
+/// place.
+pub fn recover_lost_device<C>(
+    flag: &DeviceLostFlag,
+    recreate: impl FnOnce() -> anyhow::Result<C>,
+    install: impl FnOnce(C),
+) -> anyhow::Result<bool> {
+    if !flag.is_lost() {
+        return Ok(false);
+    }
+
+    tracing::info!("Recreating lost graphics device...");
+    install(recreate()?);
+    tracing::info!("Graphics device recreated.");
+
+    Ok(true)
+}
+
 #[derive(Debug, Clone)]
 pub struct AdapterInfoBundle {
     pub info: wgpu::AdapterInfo,
@@ -157,6 +369,97 @@ pub struct CompatQueryInfo<'a, 'l> {
     pub adapter_info: &'a AdapterInfoBundle,
 }
 
+struct JudgedAdapter<'a, T> {
+    adapter: wgpu::Adapter,
+    adapter_info: AdapterInfoBundle,
+    descriptor: wgpu::DeviceDescriptor<'a>,
+    compat_table: T,
+    judgement: Judgement,
+}
+
+impl<T> JudgedAdapter<'_, T> {
+    fn listing(&self) -> AdapterListing {
+        AdapterListing {
+            info: self.adapter_info.info.clone(),
+            compatible: self.judgement.did_pass(),
+        }
+    }
+}
+
+// === AdapterSelector === //
+
+/// An adapter listed by [`GfxContext::enumerate_adapters`].
+#[derive(Debug, Clone)]
+pub struct AdapterListing {
+    pub info: wgpu::AdapterInfo,
+
+    /// Whether the adapter passed the application's compatibility judge. Only compatible adapters
+    /// can be selected.
+    pub compatible: bool,
+}
+
+/// Picks a specific adapter on machines with more than one, such as laptops with both an integrated
+/// and a discrete GPU. Selectors only consider the adapters which passed the application's
+/// compatibility judge.
+#[derive(Default)]
+pub enum AdapterSelector {
+    /// Uses the adapter with the best compatibility score.
+    #[default]
+    Best,
+
+    /// Uses the first discrete GPU.
+    PreferDiscrete,
+
+    /// Uses the first adapter whose name contains the given string, ignoring case.
+    Named(String),
+
+    /// Uses the adapter at the given index of [`GfxContext::enumerate_adapters`] if it's compatible.
+    Index(usize),
+
+    /// Uses the first adapter the predicate accepts.
+    Custom(Box<dyn Fn(&wgpu::AdapterInfo) -> bool + Send + Sync>),
+}
+
+impl fmt::Debug for AdapterSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Best => f.write_str("Best"),
+            Self::PreferDiscrete => f.write_str("PreferDiscrete"),
+            Self::Named(name) => f.debug_tuple("Named").field(name).finish(),
+            Self::Index(index) => f.debug_tuple("Index").field(index).finish(),
+            Self::Custom(_) => f.debug_tuple("Custom").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl AdapterSelector {
+    /// Determines the index of the adapter to use in `adapters`, returning `None` if the selector
+    /// doesn't match any of the compatible ones.
+    pub fn select(&self, adapters: &[AdapterListing]) -> Option<usize> {
+        let position = |predicate: &dyn Fn(&wgpu::AdapterInfo) -> bool| {
+            adapters
+                .iter()
+                .position(|adapter| adapter.compatible && predicate(&adapter.info))
+        };
+
+        match self {
+            AdapterSelector::Best => None,
+            AdapterSelector::PreferDiscrete => {
+                position(&|info| info.device_type == wgpu::DeviceType::DiscreteGpu)
+            }
+            AdapterSelector::Named(name) => {
+                let name = name.to_lowercase();
+                position(&|info| info.name.to_lowercase().contains(&name))
+            }
+            &AdapterSelector::Index(index) => adapters
+                .get(index)
+                .is_some_and(|adapter| adapter.compatible)
+                .then_some(index),
+            AdapterSelector::Custom(predicate) => position(&|info| predicate(info)),
+        }
+    }
+}
+
 // === Judgement === //
 
 #[derive(Debug)]
@@ -390,3 +693,105 @@ pub fn feat_requires_power_pref(pref: wgpu::PowerPreference) -> impl Judge<Table
         .with_table(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_after_device_lost_callback() {
+        let flag = DeviceLostFlag::default();
+        let callback = flag.callback();
+        let mut events = Vec::new();
+
+        // Dropping our own device isn't a loss.
+        callback(wgpu::DeviceLostReason::Dropped, String::new());
+        assert!(!recover_lost_device(&flag, || Ok(1), |gen| events.push(gen)).unwrap());
+
+        // ...but a driver reset is.
+        callback(wgpu::DeviceLostReason::Unknown, "driver reset".to_string());
+        assert!(flag.is_lost());
+
+        let mut recreations = 0;
+        let recovered = recover_lost_device(
+            &flag,
+            || {
+                recreations += 1;
+                Ok(2)
+            },
+            |gen| events.push(gen),
+        )
+        .unwrap();
+
+        assert!(recovered);
+        assert_eq!(recreations, 1);
+        assert_eq!(events, [2]);
+    }
+
+    #[test]
+    fn selects_requested_adapters() {
+        let adapter = |name: &str, device_type, compatible| AdapterListing {
+            info: wgpu::AdapterInfo {
+                name: name.to_string(),
+                vendor: 0,
+                device: 0,
+                device_type,
+                driver: String::new(),
+                driver_info: String::new(),
+                backend: wgpu::Backend::Vulkan,
+            },
+            compatible,
+        };
+
+        let adapters = [
+            adapter(
+                "Intel(R) UHD Graphics 620",
+                wgpu::DeviceType::IntegratedGpu,
+                true,
+            ),
+            adapter(
+                "llvmpipe (LLVM 15.0.7, 256 bits)",
+                wgpu::DeviceType::Cpu,
+                true,
+            ),
+            adapter(
+                "NVIDIA GeForce RTX 3060",
+                wgpu::DeviceType::DiscreteGpu,
+                true,
+            ),
+            adapter("AMD Radeon RX 580", wgpu::DeviceType::DiscreteGpu, false),
+        ];
+
+        assert_eq!(AdapterSelector::Best.select(&adapters), None);
+        assert_eq!(AdapterSelector::PreferDiscrete.select(&adapters), Some(2));
+        assert_eq!(AdapterSelector::PreferDiscrete.select(&adapters[..2]), None);
+        assert_eq!(
+            AdapterSelector::Named("llvmpipe".to_string()).select(&adapters),
+            Some(1)
+        );
+        assert_eq!(
+            AdapterSelector::Named("geforce".to_string()).select(&adapters),
+            Some(2)
+        );
+        assert_eq!(
+            AdapterSelector::Named("radeon".to_string()).select(&adapters),
+            None
+        );
+        assert_eq!(AdapterSelector::Index(0).select(&adapters), Some(0));
+        assert_eq!(AdapterSelector::Index(2).select(&adapters), Some(2));
+        assert_eq!(AdapterSelector::Index(4).select(&adapters), None);
+
+        // Incompatible adapters keep their place in the list but are never selected.
+        assert_eq!(AdapterSelector::Index(3).select(&adapters), None);
+        assert_eq!(
+            AdapterSelector::PreferDiscrete.select(&[adapters[3].clone(), adapters[2].clone()]),
+            Some(1)
+        );
+
+        let integrated = AdapterSelector::Custom(Box::new(|info| {
+            info.device_type == wgpu::DeviceType::IntegratedGpu
+        }));
+        assert_eq!(integrated.select(&adapters), Some(0));
+        assert_eq!(integrated.select(&adapters[1..]), None);
+    }
+}
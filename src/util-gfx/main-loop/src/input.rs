@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use bevy_autoken::random_component;
 use crucible_utils::{
     hash::FxHashMap,
@@ -6,8 +8,8 @@ use crucible_utils::{
 use typed_glam::glam::DVec2;
 use winit::{
     dpi::PhysicalPosition,
-    event::{DeviceEvent, DeviceId, ElementState, KeyEvent, MouseButton, WindowEvent},
-    keyboard::{Key, PhysicalKey},
+    event::{DeviceEvent, DeviceId, ElementState, Ime, KeyEvent, MouseButton, WindowEvent},
+    keyboard::{Key, NamedKey, PhysicalKey},
     platform::modifier_supplement::KeyEventExtModifierSupplement,
     window::WindowId,
 };
@@ -18,16 +20,45 @@ use winit::{
 pub struct InputManager {
     windows: FxHashMap<WindowId, WindowDeviceState>,
     mouse_delta: DVec2,
+    gestures: GestureConfig,
 }
 
 random_component!(InputManager);
 
+/// Timing thresholds for the mouse button gestures exposed by [`InputManagerWindow`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GestureConfig {
+    /// The maximum time between two presses of a button for them to count as a double-click.
+    pub double_click_interval: Duration,
+
+    /// The minimum time a button must be held down for [`InputManagerWindow::is_held`] to report
+    /// it as held.
+    pub hold_threshold: Duration,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            double_click_interval: Duration::from_millis(500),
+            hold_threshold: Duration::from_millis(250),
+        }
+    }
+}
+
 impl InputManager {
     pub fn process_window_event(&mut self, window: WindowId, event: &WindowEvent) {
+        self.process_window_event_at(window, event, Instant::now());
+    }
+
+    /// Processes a window event which occurred at `now`.
+    pub fn process_window_event_at(&mut self, window: WindowId, event: &WindowEvent, now: Instant) {
         if let WindowEvent::Destroyed = event {
             self.windows.remove(&window);
         } else {
-            self.windows.entry(window).or_default().process(event);
+            self.windows
+                .entry(window)
+                .or_default()
+                .process(event, now, &self.gestures);
         }
     }
 
@@ -38,17 +69,66 @@ impl InputManager {
     }
 
     pub fn end_tick(&mut self) {
+        self.end_tick_at(Instant::now());
+    }
+
+    /// Ends the tick at `now`. Hold durations reported during the next tick are measured up to
+    /// this instant.
+    pub fn end_tick_at(&mut self, now: Instant) {
         self.mouse_delta = DVec2::ZERO;
 
         for win in self.windows.values_mut() {
-            win.end_tick();
+            win.end_tick(now);
         }
     }
 
+    pub fn gestures(&self) -> GestureConfig {
+        self.gestures
+    }
+
+    pub fn set_gestures(&mut self, gestures: GestureConfig) {
+        self.gestures = gestures;
+    }
+
+    /// Enables or disables text-input mode for the given window. While enabled, character and IME
+    /// events are accumulated into the window's [`TextInput`] buffer and gameplay key presses are
+    /// suppressed.
+    ///
+    /// Note that IME events will only be delivered if the window itself has been configured with
+    /// `Window::set_ime_allowed`.
+    pub fn set_text_input(&mut self, window: WindowId, enabled: bool) {
+        let state = self.windows.entry(window).or_default();
+
+        match (enabled, state.text_input.is_some()) {
+            (true, false) => {
+                // Release every held key so that gameplay doesn't see it as stuck while typing.
+                state.agg_keyboard.release_all();
+                for keyboard in state.keyboards.values_mut() {
+                    keyboard.release_all();
+                }
+
+                state.text_input = Some(TextInput::default());
+            }
+            (false, true) => {
+                state.text_input = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn text_input_mut(&mut self, window: WindowId) -> Option<&mut TextInput> {
+        self.windows
+            .get_mut(&window)
+            .and_then(|v| v.text_input.as_mut())
+    }
+
     pub fn window(&self, window: WindowId) -> InputManagerWindow<'_> {
-        InputManagerWindow(self.windows.get(&window))
+        InputManagerWindow(self.windows.get(&window), &self.gestures)
     }
 
+    /// The raw, unaccelerated mouse motion reported by the OS since the last
+    /// [`end_tick`](Self::end_tick). Unlike the cursor position, this keeps updating while the
+    /// cursor is grabbed with [`Viewport::set_cursor_grab`](crate::Viewport::set_cursor_grab).
     pub fn mouse_delta(&self) -> DVec2 {
         self.mouse_delta
     }
@@ -61,14 +141,28 @@ struct WindowDeviceState {
     agg_mouse_pos: Option<PhysicalPosition<f64>>,
     keyboards: FxHashMap<DeviceId, KeyboardDeviceState>,
     mice: FxHashMap<DeviceId, MouseDeviceState>,
+    text_input: Option<TextInput>,
+    gestures: FxHashMap<MouseButton, ButtonGesture>,
+    clock: Option<Instant>,
 }
 
 impl WindowDeviceState {
-    fn process(&mut self, event: &WindowEvent) {
+    fn process(&mut self, event: &WindowEvent, now: Instant, config: &GestureConfig) {
+        self.clock = Some(now);
+
         match event {
             WindowEvent::KeyboardInput {
                 device_id, event, ..
             } => {
+                // While in text-input mode, presses are routed to the text buffer. Releases are
+                // still tracked so keys can't get stuck across mode switches.
+                if let Some(text_input) = &mut self.text_input {
+                    if event.state.is_pressed() {
+                        text_input.process_key(&event.logical_key, event.text.as_deref());
+                        return;
+                    }
+                }
+
                 self.agg_keyboard.process(event);
                 self.keyboards.entry(*device_id).or_default().process(event);
             }
@@ -77,7 +171,19 @@ impl WindowDeviceState {
                 state,
                 button,
             } => {
+                let was_pressed = self.agg_mouse.button(*button).state();
                 self.agg_mouse.process(*button, *state);
+
+                // Gestures follow the aggregate state so that pressing the same button on two
+                // mice doesn't count as a click.
+                let is_pressed = self.agg_mouse.button(*button).state();
+                if was_pressed != is_pressed {
+                    self.gestures
+                        .entry(*button)
+                        .or_default()
+                        .set_state(is_pressed, now, config);
+                }
+
                 self.mice
                     .entry(*device_id)
                     .or_default()
@@ -86,11 +192,20 @@ impl WindowDeviceState {
             WindowEvent::CursorMoved { position, .. } => {
                 self.agg_mouse_pos = Some(*position);
             }
+            WindowEvent::Ime(ime) => {
+                if let Some(text_input) = &mut self.text_input {
+                    text_input.process_ime(ime);
+                }
+            }
             _ => {}
         }
     }
 
-    fn end_tick(&mut self) {
+    fn end_tick(&mut self, now: Instant) {
+        self.clock = Some(now);
+        for gesture in self.gestures.values_mut() {
+            gesture.end_tick();
+        }
         self.keyboards.retain(|_, v| v.end_tick());
         self.mice.retain(|_, v| v.end_tick());
         self.agg_keyboard.end_tick();
@@ -119,6 +234,16 @@ impl KeyboardDeviceState {
             .set_state(is_pressed);
     }
 
+    fn release_all(&mut self) {
+        for key in self.logical_keys.values_mut() {
+            key.set_state(false);
+        }
+
+        for key in self.physical_keys.values_mut() {
+            key.set_state(false);
+        }
+    }
+
     fn end_tick(&mut self) -> bool {
         self.logical_keys.retain(|_, v| v.end_tick());
         self.physical_keys.retain(|_, v| v.end_tick());
@@ -159,7 +284,7 @@ impl MouseDeviceState {
 
         for (i, button) in self.buttons.raw.iter_mut().enumerate() {
             if button.end_tick() {
-                max_kept = i;
+                max_kept = i + 1;
             }
         }
 
@@ -176,6 +301,39 @@ impl MouseDeviceState {
     }
 }
 
+#[derive(Debug, Copy, Clone, Default)]
+struct ButtonGesture {
+    pressed_at: Option<Instant>,
+    last_click: Option<Instant>,
+    double_clicked: bool,
+}
+
+impl ButtonGesture {
+    fn set_state(&mut self, is_pressed: bool, now: Instant, config: &GestureConfig) {
+        if !is_pressed {
+            self.pressed_at = None;
+            return;
+        }
+
+        self.pressed_at = Some(now);
+
+        // The click completing a double-click can't also start the next one.
+        if self
+            .last_click
+            .is_some_and(|last| now.duration_since(last) <= config.double_click_interval)
+        {
+            self.double_clicked = true;
+            self.last_click = None;
+        } else {
+            self.last_click = Some(now);
+        }
+    }
+
+    fn end_tick(&mut self) {
+        self.double_clicked = false;
+    }
+}
+
 define_index! {
     struct MouseButtonIndex: u32;
 }
@@ -209,7 +367,7 @@ impl From<MouseButtonIndex> for MouseButton {
 // === InputManager Facades === //
 
 #[derive(Debug, Copy, Clone)]
-pub struct InputManagerWindow<'a>(Option<&'a WindowDeviceState>);
+pub struct InputManagerWindow<'a>(Option<&'a WindowDeviceState>, &'a GestureConfig);
 
 impl<'a> InputManagerWindow<'a> {
     pub fn physical_key(self, key: impl Into<PhysicalKey>) -> BoolAction {
@@ -227,6 +385,33 @@ impl<'a> InputManagerWindow<'a> {
             .map_or(BoolAction::default(), |v| v.agg_mouse.button(button))
     }
 
+    /// Returns whether `button` was pressed twice within the
+    /// [`double_click_interval`](GestureConfig::double_click_interval) since the last tick ended.
+    pub fn double_clicked(self, button: MouseButton) -> bool {
+        self.0
+            .and_then(|v| v.gestures.get(&button))
+            .is_some_and(|v| v.double_clicked)
+    }
+
+    /// Returns how long `button` has been held down for, as of the most recent event or tick end.
+    pub fn held_for(self, button: MouseButton) -> Option<Duration> {
+        let state = self.0?;
+        let pressed_at = state.gestures.get(&button)?.pressed_at?;
+
+        Some(state.clock?.saturating_duration_since(pressed_at))
+    }
+
+    /// Returns whether `button` has been held down for at least the
+    /// [`hold_threshold`](GestureConfig::hold_threshold).
+    pub fn is_held(self, button: MouseButton) -> bool {
+        self.held_for(button)
+            .is_some_and(|held| held >= self.1.hold_threshold)
+    }
+
+    pub fn text_input(self) -> Option<&'a TextInput> {
+        self.0.and_then(|v| v.text_input.as_ref())
+    }
+
     pub fn mouse_pos(self) -> Option<PhysicalPosition<f64>> {
         self.0.and_then(|v| v.agg_mouse_pos)
     }
@@ -263,6 +448,122 @@ impl InputManagerMouse<'_> {
     }
 }
 
+// === TextInput === //
+
+/// An editable text buffer fed by character and IME events while text-input mode is enabled.
+///
+/// The cursor is a byte offset into [`text`](Self::text) and always lies on a `char` boundary.
+#[derive(Debug, Clone, Default)]
+pub struct TextInput {
+    text: String,
+    cursor: usize,
+    preedit: String,
+    preedit_cursor: Option<(usize, usize)>,
+}
+
+impl TextInput {
+    /// Applies a key press to the buffer. `text` is the text produced by the key press, if any.
+    pub fn process_key(&mut self, key: &Key, text: Option<&str>) {
+        // While the IME is composing, it owns the keyboard.
+        if !self.preedit.is_empty() {
+            return;
+        }
+
+        match key {
+            Key::Named(NamedKey::Backspace) => self.backspace(),
+            Key::Named(NamedKey::Delete) => self.delete(),
+            Key::Named(NamedKey::ArrowLeft) => self.move_left(),
+            Key::Named(NamedKey::ArrowRight) => self.move_right(),
+            Key::Named(NamedKey::Home) => self.cursor = 0,
+            Key::Named(NamedKey::End) => self.cursor = self.text.len(),
+            _ => {
+                if let Some(text) = text {
+                    self.insert(text);
+                }
+            }
+        }
+    }
+
+    pub fn process_ime(&mut self, ime: &Ime) {
+        match ime {
+            Ime::Enabled => {}
+            Ime::Preedit(preedit, cursor) => {
+                self.preedit.clone_from(preedit);
+                self.preedit_cursor = *cursor;
+            }
+            Ime::Commit(text) => {
+                self.preedit.clear();
+                self.preedit_cursor = None;
+                self.insert(text);
+            }
+            Ime::Disabled => {
+                self.preedit.clear();
+                self.preedit_cursor = None;
+            }
+        }
+    }
+
+    /// Inserts text at the cursor, skipping control characters, and moves the cursor past it.
+    pub fn insert(&mut self, text: &str) {
+        for ch in text.chars().filter(|ch| !ch.is_control()) {
+            self.text.insert(self.cursor, ch);
+            self.cursor += ch.len_utf8();
+        }
+    }
+
+    /// Removes the character before the cursor.
+    pub fn backspace(&mut self) {
+        if let Some(ch) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= ch.len_utf8();
+            self.text.remove(self.cursor);
+        }
+    }
+
+    /// Removes the character after the cursor.
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(ch) = self.text[..self.cursor].chars().next_back() {
+            self.cursor -= ch.len_utf8();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(ch) = self.text[self.cursor..].chars().next() {
+            self.cursor += ch.len_utf8();
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// The text currently being composed by the IME. This is not yet part of [`text`](Self::text)
+    /// and should be drawn at the cursor.
+    pub fn preedit(&self) -> &str {
+        &self.preedit
+    }
+
+    /// The byte range of the IME's cursor within [`preedit`](Self::preedit), if it should be shown.
+    pub fn preedit_cursor(&self) -> Option<(usize, usize)> {
+        self.preedit_cursor
+    }
+
+    /// Clears the buffer, returning its previous contents.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+}
+
 // === BoolAction === //
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -346,3 +647,145 @@ impl BoolAction {
         self.state
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_input_edits() {
+        let mut input = TextInput::default();
+        let backspace = Key::Named(NamedKey::Backspace);
+        let left = Key::Named(NamedKey::ArrowLeft);
+
+        for ch in ["h", "e", "y", "é"] {
+            input.process_key(&Key::Character(ch.into()), Some(ch));
+        }
+        input.process_key(&backspace, None);
+        assert_eq!(input.text(), "hey");
+        assert_eq!(input.cursor(), 3);
+
+        input.process_key(&left, None);
+        input.process_key(&backspace, None);
+        input.process_key(&Key::Character("a".into()), Some("a"));
+        assert_eq!(input.text(), "hay");
+        assert_eq!(input.cursor(), 2);
+
+        input.process_ime(&Ime::Preedit("に".into(), Some((0, 3))));
+        input.process_key(&backspace, None);
+        input.process_ime(&Ime::Preedit(String::new(), None));
+        input.process_ime(&Ime::Commit("日".into()));
+        assert_eq!(input.text(), "ha日y");
+        assert_eq!(input.cursor(), 5);
+        assert_eq!(input.preedit(), "");
+    }
+
+    #[test]
+    fn raw_mouse_motion_accumulates_per_tick() {
+        let mut input = InputManager::default();
+        let motion = |delta| DeviceEvent::MouseMotion { delta };
+
+        input.process_device_event(DeviceId::dummy(), &motion((3., -1.)));
+        input.process_device_event(DeviceId::dummy(), &motion((2., 4.)));
+        assert_eq!(input.mouse_delta(), DVec2::new(5., 3.));
+
+        // Cursor movement within the window doesn't contribute to the raw delta.
+        let window = WindowId::dummy();
+        input.process_window_event(
+            window,
+            &WindowEvent::CursorMoved {
+                device_id: DeviceId::dummy(),
+                position: (100., 100.).into(),
+            },
+        );
+        assert_eq!(input.mouse_delta(), DVec2::new(5., 3.));
+
+        input.end_tick();
+        assert_eq!(input.mouse_delta(), DVec2::ZERO);
+
+        input.process_device_event(DeviceId::dummy(), &motion((-1., 0.5)));
+        assert_eq!(input.mouse_delta(), DVec2::new(-1., 0.5));
+    }
+
+    fn click(input: &mut InputManager, window: WindowId, state: ElementState, at: Instant) {
+        let event = WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button: MouseButton::Left,
+        };
+        input.process_window_event_at(window, &event, at);
+    }
+
+    #[test]
+    fn double_clicks_require_quick_presses() {
+        let window = WindowId::dummy();
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        let mut input = InputManager::default();
+        input.set_gestures(GestureConfig {
+            double_click_interval: ms(300),
+            ..Default::default()
+        });
+
+        // Two quick clicks.
+        click(&mut input, window, ElementState::Pressed, start);
+        click(&mut input, window, ElementState::Released, start + ms(50));
+        assert!(!input.window(window).double_clicked(MouseButton::Left));
+
+        click(&mut input, window, ElementState::Pressed, start + ms(200));
+        click(&mut input, window, ElementState::Released, start + ms(250));
+        assert!(input.window(window).double_clicked(MouseButton::Left));
+        assert!(!input.window(window).double_clicked(MouseButton::Right));
+
+        input.end_tick_at(start + ms(260));
+        assert!(!input.window(window).double_clicked(MouseButton::Left));
+
+        // Two slow clicks.
+        let start = start + ms(1000);
+        click(&mut input, window, ElementState::Pressed, start);
+        click(&mut input, window, ElementState::Released, start + ms(50));
+        click(&mut input, window, ElementState::Pressed, start + ms(400));
+        click(&mut input, window, ElementState::Released, start + ms(450));
+        assert!(!input.window(window).double_clicked(MouseButton::Left));
+    }
+
+    #[test]
+    fn held_buttons_report_increasing_duration() {
+        let window = WindowId::dummy();
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        let mut input = InputManager::default();
+        input.set_gestures(GestureConfig {
+            hold_threshold: ms(200),
+            ..Default::default()
+        });
+
+        assert_eq!(input.window(window).held_for(MouseButton::Left), None);
+
+        click(&mut input, window, ElementState::Pressed, start);
+        assert_eq!(
+            input.window(window).held_for(MouseButton::Left),
+            Some(ms(0))
+        );
+
+        input.end_tick_at(start + ms(100));
+        assert_eq!(
+            input.window(window).held_for(MouseButton::Left),
+            Some(ms(100))
+        );
+        assert!(!input.window(window).is_held(MouseButton::Left));
+
+        input.end_tick_at(start + ms(300));
+        assert_eq!(
+            input.window(window).held_for(MouseButton::Left),
+            Some(ms(300))
+        );
+        assert!(input.window(window).is_held(MouseButton::Left));
+
+        click(&mut input, window, ElementState::Released, start + ms(350));
+        assert_eq!(input.window(window).held_for(MouseButton::Left), None);
+        assert!(!input.window(window).is_held(MouseButton::Left));
+    }
+}
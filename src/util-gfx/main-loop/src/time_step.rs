@@ -1,8 +1,14 @@
 use std::{
+    collections::VecDeque,
+    hint,
     num::NonZeroU32,
+    thread,
     time::{Duration, Instant},
 };
 
+use crucible_utils::hash::FxHashMap;
+use winit::{event::WindowEvent, window::WindowId};
+
 // === Common === //
 
 #[derive(Debug, Copy, Clone)]
@@ -174,3 +180,490 @@ impl FixedRate {
         }
     }
 }
+
+// === FramePacer === //
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PaceAction {
+    /// The frame should be presented now.
+    Ready,
+
+    /// The frame is far enough away that the thread can sleep until the specified instant. This
+    /// always leaves a bit of slack before the frame to absorb the OS scheduler's imprecision.
+    Sleep(Instant),
+
+    /// The frame is close enough that the thread should busy-wait.
+    Spin,
+}
+
+/// Caps the rate at which frames are presented using a hybrid of sleeping and spinning so that
+/// frames land close to their deadlines without burning CPU for the entire interval.
+#[derive(Debug)]
+pub struct FramePacer {
+    interval: Option<Duration>,
+    spin_margin: Duration,
+    next_frame: Option<Instant>,
+}
+
+impl FramePacer {
+    pub const DEFAULT_SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+    /// Creates a pacer capped at `fps` frames per second. A rate of `None` disables the cap.
+    pub fn new(fps: Option<f64>) -> Self {
+        Self {
+            interval: fps.map(|fps| Duration::from_secs_f64(1. / fps)),
+            spin_margin: Self::DEFAULT_SPIN_MARGIN,
+            next_frame: None,
+        }
+    }
+
+    pub fn set_target_fps(&mut self, fps: Option<f64>) {
+        self.interval = fps.map(|fps| Duration::from_secs_f64(1. / fps));
+        self.next_frame = None;
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    pub fn spin_margin(&self) -> Duration {
+        self.spin_margin
+    }
+
+    /// Sets how long before a frame's deadline the pacer stops sleeping and starts spinning.
+    pub fn set_spin_margin(&mut self, margin: Duration) {
+        self.spin_margin = margin;
+    }
+
+    pub fn poll(&mut self, now: Instant) -> PaceAction {
+        let Some(interval) = self.interval else {
+            return PaceAction::Ready;
+        };
+
+        let Some(next_frame) = self.next_frame else {
+            self.next_frame = Some(now + interval);
+            return PaceAction::Ready;
+        };
+
+        if now >= next_frame {
+            // If we fell more than a frame behind, don't try to catch up by rendering a burst of
+            // frames.
+            self.next_frame = Some(if now - next_frame > interval {
+                now + interval
+            } else {
+                next_frame + interval
+            });
+
+            return PaceAction::Ready;
+        }
+
+        match next_frame.checked_sub(self.spin_margin) {
+            Some(wake) if wake > now => PaceAction::Sleep(wake),
+            _ => PaceAction::Spin,
+        }
+    }
+
+    /// Blocks the current thread until the next frame should be presented.
+    pub fn wait(&mut self) {
+        loop {
+            match self.poll(Instant::now()) {
+                PaceAction::Ready => break,
+                PaceAction::Sleep(until) => {
+                    thread::sleep(until.saturating_duration_since(Instant::now()))
+                }
+                PaceAction::Spin => hint::spin_loop(),
+            }
+        }
+    }
+}
+
+// === FrameStats === //
+
+/// A summary of the frame durations in a [`FrameStats`] window. Percentiles use the nearest-rank
+/// method so each of them is the duration of an actual frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FrameStatsSummary {
+    pub frames: usize,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// Tracks the durations of the most recent frames so that occasional spikes, which an average
+/// frame rate hides, can be displayed or logged.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    window: VecDeque<Duration>,
+    capacity: usize,
+    spike_threshold: Duration,
+    spikes: u64,
+}
+
+impl FrameStats {
+    pub const DEFAULT_CAPACITY: usize = 240;
+    pub const DEFAULT_SPIKE_THRESHOLD: Duration = Duration::from_millis(50);
+
+    pub fn new(capacity: usize, spike_threshold: Duration) -> Self {
+        assert!(
+            capacity > 0,
+            "frame stats window must hold at least one frame"
+        );
+
+        Self {
+            window: VecDeque::with_capacity(capacity),
+            capacity,
+            spike_threshold,
+            spikes: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn spike_threshold(&self) -> Duration {
+        self.spike_threshold
+    }
+
+    pub fn set_spike_threshold(&mut self, threshold: Duration) {
+        self.spike_threshold = threshold;
+    }
+
+    /// The number of frames which have exceeded the spike threshold since the stats were created
+    /// or last [`reset`](Self::reset). Unlike the summary, this isn't limited to the window.
+    pub fn spikes(&self) -> u64 {
+        self.spikes
+    }
+
+    /// Records the duration of a frame, evicting the oldest frame if the window is full. Returns
+    /// whether the frame counted as a spike.
+    pub fn record(&mut self, duration: Duration) -> bool {
+        if self.window.len() == self.capacity {
+            self.window.pop_front();
+        }
+        self.window.push_back(duration);
+
+        let is_spike = duration > self.spike_threshold;
+        if is_spike {
+            self.spikes += 1;
+        }
+
+        is_spike
+    }
+
+    pub fn reset(&mut self) {
+        self.window.clear();
+        self.spikes = 0;
+    }
+
+    /// Summarizes the frames in the window or returns `None` if no frames have been recorded.
+    pub fn summary(&self) -> Option<FrameStatsSummary> {
+        if self.window.is_empty() {
+            return None;
+        }
+
+        let mut sorted = Vec::from_iter(self.window.iter().copied());
+        sorted.sort_unstable();
+
+        let percentile = |p: usize| sorted[(p * sorted.len()).div_ceil(100).max(1) - 1];
+        let total = sorted.iter().sum::<Duration>();
+
+        Some(FrameStatsSummary {
+            frames: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+        })
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY, Self::DEFAULT_SPIKE_THRESHOLD)
+    }
+}
+
+// === BackgroundThrottle === //
+
+/// What the app should do while none of its windows are focused and visible.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BackgroundPolicy {
+    /// The frame rate cap to apply while in the background.
+    pub fps: f64,
+
+    /// Whether the simulation should keep ticking while in the background. If this is `false`,
+    /// only rendering continues, at the reduced frame rate.
+    pub simulate: bool,
+}
+
+impl Default for BackgroundPolicy {
+    fn default() -> Self {
+        Self {
+            fps: 10.,
+            simulate: true,
+        }
+    }
+}
+
+/// Tracks whether any of the app's windows are focused and visible and throttles a
+/// [`FramePacer`] accordingly, restoring the foreground frame rate as soon as focus returns.
+#[derive(Debug)]
+pub struct BackgroundThrottle {
+    policy: BackgroundPolicy,
+    foreground_fps: Option<f64>,
+    windows: FxHashMap<WindowId, WindowFocus>,
+    applied_background: Option<bool>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct WindowFocus {
+    focused: bool,
+    occluded: bool,
+}
+
+impl Default for WindowFocus {
+    fn default() -> Self {
+        // Windows are assumed to start in the foreground until told otherwise.
+        Self {
+            focused: true,
+            occluded: false,
+        }
+    }
+}
+
+impl BackgroundThrottle {
+    /// Creates a throttle for an app which renders at `foreground_fps` frames per second while in
+    /// the foreground. Like [`FramePacer::new`], a rate of `None` disables the cap.
+    pub fn new(foreground_fps: Option<f64>, policy: BackgroundPolicy) -> Self {
+        Self {
+            policy,
+            foreground_fps,
+            windows: FxHashMap::default(),
+            applied_background: None,
+        }
+    }
+
+    pub fn policy(&self) -> BackgroundPolicy {
+        self.policy
+    }
+
+    /// Changes what happens while the app is in the background. Gameplay can use this to choose
+    /// whether the simulation pauses or just rendering throttles.
+    pub fn set_policy(&mut self, policy: BackgroundPolicy) {
+        self.policy = policy;
+        self.applied_background = None;
+    }
+
+    pub fn foreground_fps(&self) -> Option<f64> {
+        self.foreground_fps
+    }
+
+    pub fn set_foreground_fps(&mut self, fps: Option<f64>) {
+        self.foreground_fps = fps;
+        self.applied_background = None;
+    }
+
+    pub fn process_window_event(&mut self, window: WindowId, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Focused(focused) => {
+                self.windows.entry(window).or_default().focused = focused;
+            }
+            WindowEvent::Occluded(occluded) => {
+                self.windows.entry(window).or_default().occluded = occluded;
+            }
+            WindowEvent::Destroyed => {
+                self.windows.remove(&window);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns whether none of the app's windows are both focused and visible. The app is assumed
+    /// to be in the foreground until a window reports otherwise.
+    pub fn is_background(&self) -> bool {
+        !self.windows.is_empty()
+            && !self
+                .windows
+                .values()
+                .any(|window| window.focused && !window.occluded)
+    }
+
+    /// Returns whether the simulation should tick this frame.
+    pub fn should_simulate(&self) -> bool {
+        self.policy.simulate || !self.is_background()
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        if self.is_background() {
+            Some(match self.foreground_fps {
+                Some(fps) => fps.min(self.policy.fps),
+                None => self.policy.fps,
+            })
+        } else {
+            self.foreground_fps
+        }
+    }
+
+    /// Updates `pacer`'s frame rate if the app moved into or out of the background since the last
+    /// call. Resetting the pacer lets the first frame after regaining focus be presented right
+    /// away.
+    pub fn apply(&mut self, pacer: &mut FramePacer) {
+        let is_background = self.is_background();
+
+        if self.applied_background != Some(is_background) {
+            self.applied_background = Some(is_background);
+            pacer.set_target_fps(self.target_fps());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacer_spaces_frames() {
+        let mut pacer = FramePacer::new(Some(100.));
+        let start = Instant::now();
+        let mut now = start;
+        let mut frames = Vec::new();
+
+        while frames.len() < 20 {
+            match pacer.poll(now) {
+                PaceAction::Ready => {
+                    frames.push(now);
+                    // Pretend rendering the frame took a bit of time.
+                    now += Duration::from_millis(3);
+                }
+                // Simulate a sloppy OS sleep which overshoots its deadline.
+                PaceAction::Sleep(until) => now = until + Duration::from_micros(500),
+                PaceAction::Spin => now += Duration::from_micros(10),
+            }
+        }
+
+        for pair in frames.windows(2) {
+            let delta = pair[1] - pair[0];
+            assert!(
+                delta >= Duration::from_millis(10) && delta < Duration::from_micros(10_020),
+                "frame delta was {delta:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn pacer_does_not_burst_after_hitch() {
+        let mut pacer = FramePacer::new(Some(100.));
+        let start = Instant::now();
+
+        assert_eq!(pacer.poll(start), PaceAction::Ready);
+
+        let after_hitch = start + Duration::from_millis(100);
+        assert_eq!(pacer.poll(after_hitch), PaceAction::Ready);
+        assert_ne!(pacer.poll(after_hitch), PaceAction::Ready);
+    }
+
+    #[test]
+    fn frame_stats_report_percentiles_and_spikes() {
+        let ms = Duration::from_millis;
+        let mut stats = FrameStats::new(100, ms(50));
+
+        // Ten frames each of 10ms through 18ms, nine of 19ms, and a single 100ms spike.
+        for i in 0..99 {
+            assert!(!stats.record(ms(10 + i % 10)));
+        }
+        assert!(stats.record(ms(100)));
+
+        assert_eq!(
+            stats.summary(),
+            Some(FrameStatsSummary {
+                frames: 100,
+                min: ms(10),
+                max: ms(100),
+                mean: Duration::from_micros(15_310),
+                p50: ms(14),
+                p95: ms(19),
+                p99: ms(19),
+            })
+        );
+        assert_eq!(stats.spikes(), 1);
+
+        // The spike eventually leaves the window but is still counted.
+        for _ in 0..100 {
+            stats.record(ms(16));
+        }
+
+        let summary = stats.summary().unwrap();
+        assert_eq!(
+            (summary.min, summary.max, summary.p99),
+            (ms(16), ms(16), ms(16))
+        );
+        assert_eq!(stats.spikes(), 1);
+
+        stats.reset();
+        assert_eq!(stats.summary(), None);
+        assert_eq!(stats.spikes(), 0);
+    }
+
+    #[test]
+    fn background_throttle_drops_and_recovers_frame_rate() {
+        let window = WindowId::dummy();
+        let mut throttle = BackgroundThrottle::new(
+            Some(100.),
+            BackgroundPolicy {
+                fps: 10.,
+                simulate: false,
+            },
+        );
+        let mut pacer = FramePacer::new(None);
+        let mut now = Instant::now();
+
+        // Counts the frames presented over the next second.
+        let run_for_a_second =
+            |throttle: &mut BackgroundThrottle, pacer: &mut FramePacer, now: &mut Instant| {
+                let end = *now + Duration::from_secs(1);
+                let mut frames = 0;
+
+                while *now < end {
+                    throttle.apply(pacer);
+
+                    match pacer.poll(*now) {
+                        PaceAction::Ready => {
+                            frames += 1;
+                            *now += Duration::from_millis(1);
+                        }
+                        PaceAction::Sleep(until) => *now = until,
+                        PaceAction::Spin => *now += Duration::from_micros(100),
+                    }
+                }
+
+                frames
+            };
+
+        throttle.process_window_event(window, &WindowEvent::Focused(true));
+        assert!(throttle.should_simulate());
+        assert!((99..=101).contains(&run_for_a_second(&mut throttle, &mut pacer, &mut now)));
+
+        // Losing focus throttles rendering and, with this policy, pauses the simulation.
+        throttle.process_window_event(window, &WindowEvent::Focused(false));
+        assert!(!throttle.should_simulate());
+        assert!((9..=11).contains(&run_for_a_second(&mut throttle, &mut pacer, &mut now)));
+
+        // Regaining focus presents a frame right away and restores the full rate.
+        throttle.process_window_event(window, &WindowEvent::Focused(true));
+        throttle.apply(&mut pacer);
+        assert_eq!(pacer.poll(now), PaceAction::Ready);
+        assert!(throttle.should_simulate());
+        assert!((99..=101).contains(&run_for_a_second(&mut throttle, &mut pacer, &mut now)));
+
+        // A minimized window counts as being in the background even if it's focused.
+        throttle.process_window_event(window, &WindowEvent::Occluded(true));
+        assert!(throttle.is_background());
+        assert_eq!(throttle.target_fps(), Some(10.));
+    }
+}
@@ -5,7 +5,7 @@ use bevy_ecs::removal_detection::RemovedComponents;
 use crucible_utils::hash::FxHashMap;
 use thiserror::Error;
 use typed_glam::glam::UVec2;
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Window, WindowId};
 
 use crate::GfxContext;
 
@@ -118,6 +118,12 @@ impl Viewport {
         self.config_dirty = true;
     }
 
+    /// Forces the surface to be reconfigured before the next frame is acquired. This is required
+    /// after the [`GfxContext`]'s device has been recreated.
+    pub fn mark_config_dirty(&mut self) {
+        self.config_dirty = true;
+    }
+
     pub fn curr_surface_size(&self) -> Option<UVec2> {
         surface_size_from_config(&self.curr_config)
     }
@@ -133,12 +139,30 @@ impl Viewport {
         &self.window
     }
 
+    /// Hides the cursor and locks it in place for first-person controls, or releases it again. If
+    /// the platform can't lock the cursor, it is confined to the window instead.
+    ///
+    /// While grabbed, read movement from
+    /// [`InputManager::mouse_delta`](crate::InputManager::mouse_delta) since the cursor's absolute
+    /// position stops changing.
+    pub fn set_cursor_grab(&self, grab: bool) {
+        if grab {
+            for mode in [CursorGrabMode::Locked, CursorGrabMode::Confined] {
+                if self.window.set_cursor_grab(mode).is_ok() {
+                    break;
+                }
+            }
+        } else {
+            let _ = self.window.set_cursor_grab(CursorGrabMode::None);
+        }
+
+        self.window.set_cursor_visible(!grab);
+    }
+
     pub fn get_current_texture(
         &mut self,
         gfx: &GfxContext,
-    ) -> Result<Option<wgpu::SurfaceTexture>, OutOfDeviceMemoryError> {
-        use wgpu::SurfaceError::*;
-
+    ) -> Result<FrameAcquire, OutOfDeviceMemoryError> {
         fn normalize_swapchain_config(
             gfx: &GfxContext,
             window: &Window,
@@ -168,6 +192,20 @@ impl Viewport {
 
             debug_assert!(supported_formats.contains(&config.format));
 
+            // Ensure that we're using a supported present mode.
+            let supported_modes = surface.get_capabilities(&gfx.adapter).present_modes;
+            let present_mode = select_present_mode(config.present_mode, &supported_modes);
+
+            if config.present_mode != present_mode {
+                tracing::warn!(
+                    "Present mode {:?} is unsupported by surface-adapter pair. Falling back to {:?}.",
+                    config.present_mode,
+                    present_mode,
+                );
+                config.present_mode = present_mode;
+                *config_changed = true;
+            }
+
             // Ensure that the surface texture matches the window's physical (backing buffer) size
             let win_size = window.inner_size();
 
@@ -189,77 +227,47 @@ impl Viewport {
             true
         }
 
-        // Get window
+        let Self {
+            window,
+            surface,
+            curr_config,
+            next_config,
+            config_dirty,
+            ..
+        } = self;
+
         // Normalize the swapchain
-        if !normalize_swapchain_config(
-            gfx,
-            &self.window,
-            &self.surface,
-            &mut self.next_config,
-            &mut self.config_dirty,
-        ) {
-            return Ok(None);
+        if !normalize_swapchain_config(gfx, window, surface, next_config, config_dirty) {
+            return Ok(FrameAcquire::Skip);
         }
 
         // Try to reconfigure the surface if it was updated
-        if self.config_dirty {
-            self.surface.configure(&gfx.device, &self.next_config);
-            self.curr_config = self.next_config.clone();
-            self.config_dirty = false;
+        if *config_dirty {
+            surface.configure(&gfx.device, next_config);
+            *curr_config = next_config.clone();
+            *config_dirty = false;
         }
 
         // Acquire the frame
-        match self.surface.get_current_texture() {
-            Ok(frame) => Ok(Some(frame)),
-            Err(Timeout) => {
-                tracing::warn!(
-                    "Request to acquire swap-chain for window {:?} timed out.",
-                    self.window.id()
-                );
-                Ok(None)
-            }
-            Err(OutOfMemory) => Err(OutOfDeviceMemoryError),
-            Err(Outdated) | Err(Lost) => {
-                tracing::warn!(
-                    "Swap-chain for window {:?} is outdated or was lost.",
-                    self.window.id()
-                );
-
+        acquire_frame(
+            window.id(),
+            || surface.get_current_texture(),
+            || {
                 // Renormalize the swapchain config
                 // This is done in case the swapchain settings changed since then. This event is
                 // exceedingly rare but we're already in the slow path anyways so we might as well
                 // do things right.
-                if !normalize_swapchain_config(
-                    gfx,
-                    &self.window,
-                    &self.surface,
-                    &mut self.next_config,
-                    &mut self.config_dirty,
-                ) {
-                    return Ok(None);
-                }
-
-                if self.config_dirty {
-                    self.curr_config = self.next_config.clone();
-                    self.config_dirty = false;
+                if !normalize_swapchain_config(gfx, window, surface, next_config, config_dirty) {
+                    return false;
                 }
 
-                // Try to recreate the swapchain and try again
-                self.surface.configure(&gfx.device, &self.next_config);
-
-                match self.surface.get_current_texture() {
-                    Ok(frame) => Ok(Some(frame)),
-                    Err(OutOfMemory) => Err(OutOfDeviceMemoryError),
-                    _ => {
-                        tracing::warn!(
-							"Failed to acquire swap-chain for window {:?} after swap-chain was recreated.",
-							self.window.id()
-						);
-                        Ok(None)
-                    }
-                }
-            }
-        }
+                // Recreate the swapchain. We'll try to acquire a frame from it next time around.
+                surface.configure(&gfx.device, next_config);
+                *curr_config = next_config.clone();
+                *config_dirty = false;
+                true
+            },
+        )
     }
 
     pub fn manager(&self) -> Option<Obj<ViewportManager>> {
@@ -267,6 +275,71 @@ impl Viewport {
     }
 }
 
+/// The outcome of [`Viewport::get_current_texture`].
+#[derive(Debug)]
+pub enum FrameAcquire<T = wgpu::SurfaceTexture> {
+    /// A frame was acquired and can be rendered to.
+    Frame(T),
+
+    /// The surface was outdated or lost and has been reconfigured. There is no frame to render to
+    /// this time around but the next acquisition should succeed.
+    Reconfigured,
+
+    /// No frame could be acquired, e.g. because the window was minimized. Rendering should be
+    /// skipped.
+    Skip,
+}
+
+fn acquire_frame<T>(
+    window: WindowId,
+    acquire: impl FnOnce() -> Result<T, wgpu::SurfaceError>,
+    reconfigure: impl FnOnce() -> bool,
+) -> Result<FrameAcquire<T>, OutOfDeviceMemoryError> {
+    use wgpu::SurfaceError::*;
+
+    match acquire() {
+        Ok(frame) => Ok(FrameAcquire::Frame(frame)),
+        Err(Timeout) => {
+            tracing::warn!("Request to acquire swap-chain for window {window:?} timed out.");
+            Ok(FrameAcquire::Skip)
+        }
+        Err(OutOfMemory) => Err(OutOfDeviceMemoryError),
+        Err(Outdated) | Err(Lost) => {
+            tracing::warn!("Swap-chain for window {window:?} is outdated or was lost.");
+
+            if reconfigure() {
+                Ok(FrameAcquire::Reconfigured)
+            } else {
+                Ok(FrameAcquire::Skip)
+            }
+        }
+    }
+}
+
+/// Picks the closest present mode to `requested` which appears in `supported`. `Fifo` is used as
+/// the final fallback since every surface is required to support it.
+pub fn select_present_mode(
+    requested: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::*;
+
+    let preferences: &[wgpu::PresentMode] = match requested {
+        // These are resolved by wgpu itself.
+        AutoVsync | AutoNoVsync => return requested,
+        Fifo => &[Fifo],
+        FifoRelaxed => &[FifoRelaxed, Fifo],
+        Mailbox => &[Mailbox, Immediate, Fifo],
+        Immediate => &[Immediate, Mailbox, Fifo],
+    };
+
+    preferences
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(Fifo)
+}
+
 #[derive(Debug, Copy, Clone, Error)]
 #[error("out of device memory")]
 pub struct OutOfDeviceMemoryError;
@@ -288,3 +361,48 @@ pub fn sys_unregister_dead_viewports(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn present_mode_falls_back_to_supported() {
+        use wgpu::PresentMode::*;
+
+        assert_eq!(select_present_mode(Mailbox, &[Fifo, Mailbox]), Mailbox);
+        assert_eq!(select_present_mode(Mailbox, &[Fifo, Immediate]), Immediate);
+        assert_eq!(select_present_mode(Immediate, &[Fifo]), Fifo);
+        assert_eq!(select_present_mode(FifoRelaxed, &[Fifo, Mailbox]), Fifo);
+        assert_eq!(select_present_mode(AutoNoVsync, &[Fifo]), AutoNoVsync);
+    }
+
+    #[test]
+    fn outdated_surface_reconfigures_then_yields_frame() {
+        let window = WindowId::dummy();
+        let mut results = [Err(wgpu::SurfaceError::Outdated), Ok(1)].into_iter();
+        let mut reconfigures = 0;
+
+        let first = acquire_frame(
+            window,
+            || results.next().unwrap(),
+            || {
+                reconfigures += 1;
+                true
+            },
+        );
+        assert!(matches!(first, Ok(FrameAcquire::Reconfigured)));
+        assert_eq!(reconfigures, 1);
+
+        let second = acquire_frame(
+            window,
+            || results.next().unwrap(),
+            || unreachable!("surface was reconfigured twice"),
+        );
+        assert!(matches!(second, Ok(FrameAcquire::Frame(1))));
+
+        // A minimized window can't be reconfigured so the frame must be skipped.
+        let minimized = acquire_frame(window, || Err::<(), _>(wgpu::SurfaceError::Lost), || false);
+        assert!(matches!(minimized, Ok(FrameAcquire::Skip)));
+    }
+}
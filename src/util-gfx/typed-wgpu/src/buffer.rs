@@ -1,4 +1,9 @@
-use std::{marker::PhantomData, mem, ops::RangeBounds};
+use std::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Range, RangeBounds},
+};
 
 use bytemuck::Pod;
 use crucible_utils::newtypes::transparent;
@@ -90,6 +95,141 @@ pub struct BufferInitDescriptor<'a, T> {
     pub usage: wgpu::BufferUsages,
 }
 
+// === TrackedBuffer === //
+
+/// A buffer holding a single `T` alongside a CPU-side copy of its contents. Fields are set through
+/// [`get_mut`](Self::get_mut) and [`flush`](Self::flush) uploads only the bytes which changed
+/// since the last flush.
+pub struct TrackedBuffer<T: GpuStruct> {
+    buffer: Buffer<T>,
+    value: TrackedValue<T::Pod>,
+}
+
+impl<T: GpuStruct> fmt::Debug for TrackedBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrackedBuffer")
+            .field("buffer", &self.buffer)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: GpuStruct> TrackedBuffer<T> {
+    /// Creates a buffer initialized to `value`. The buffer is always given
+    /// [`COPY_DST`](wgpu::BufferUsages::COPY_DST) usage so that it can be flushed.
+    pub fn new(
+        gfx: &wgpu::Device,
+        label: wgpu::Label<'_>,
+        usage: wgpu::BufferUsages,
+        value: T::Pod,
+    ) -> Self {
+        Self {
+            buffer: Buffer::create_init(
+                gfx,
+                &BufferInitDescriptor {
+                    label,
+                    contents: &[value],
+                    usage: usage | wgpu::BufferUsages::COPY_DST,
+                },
+            ),
+            value: TrackedValue::new(value),
+        }
+    }
+
+    pub fn buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+
+    pub fn get(&self) -> &T::Pod {
+        self.value.get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut T::Pod {
+        self.value.get_mut()
+    }
+
+    /// Enqueues a write for every range of the buffer which changed since the last flush.
+    pub fn flush(&mut self, queue: &wgpu::Queue) {
+        self.value.flush(|offset, data| {
+            queue.write_buffer(&self.buffer.raw, offset, data);
+        });
+    }
+}
+
+/// The CPU-side half of a [`TrackedBuffer`], which remembers the last value it uploaded so that it
+/// can tell which bytes have changed since.
+#[derive(Debug, Copy, Clone)]
+pub struct TrackedValue<P> {
+    value: P,
+    uploaded: P,
+}
+
+impl<P: Pod> TrackedValue<P> {
+    const GRANULARITY: usize = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+
+    /// Creates a tracker for a value which has already been uploaded.
+    pub fn new(value: P) -> Self {
+        assert_eq!(
+            mem::size_of::<P>() % Self::GRANULARITY,
+            0,
+            "tracked values must be a multiple of `COPY_BUFFER_ALIGNMENT` in size",
+        );
+
+        Self {
+            value,
+            uploaded: value,
+        }
+    }
+
+    pub fn get(&self) -> &P {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.value
+    }
+
+    /// Returns the byte ranges which changed since the last flush. Changes are tracked with a
+    /// granularity of [`COPY_BUFFER_ALIGNMENT`](wgpu::COPY_BUFFER_ALIGNMENT) bytes and adjacent
+    /// changes, such as two neighboring fields, are merged into a single range.
+    pub fn dirty_ranges(&self) -> Vec<Range<wgpu::BufferAddress>> {
+        let curr = bytemuck::bytes_of(&self.value).chunks(Self::GRANULARITY);
+        let prev = bytemuck::bytes_of(&self.uploaded).chunks(Self::GRANULARITY);
+
+        let mut ranges = Vec::<Range<wgpu::BufferAddress>>::new();
+
+        for (i, (curr, prev)) in curr.zip(prev).enumerate() {
+            if curr == prev {
+                continue;
+            }
+
+            let start = (i * Self::GRANULARITY) as wgpu::BufferAddress;
+            let end = start + Self::GRANULARITY as wgpu::BufferAddress;
+
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+
+        ranges
+    }
+
+    /// Passes every range which changed since the last flush to `write` along with its new
+    /// contents before marking the value as uploaded.
+    pub fn flush(&mut self, mut write: impl FnMut(wgpu::BufferAddress, &[u8])) {
+        let bytes = bytemuck::bytes_of(&self.value);
+
+        for range in self.dirty_ranges() {
+            write(
+                range.start,
+                &bytes[range.start as usize..range.end as usize],
+            );
+        }
+
+        self.uploaded = self.value;
+    }
+}
+
 // === BufferSlice === //
 
 #[derive_where(Debug, Copy, Clone)]
@@ -169,3 +309,74 @@ impl<T: GpuStruct> DynamicOffset<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{offset_of, size_of};
+
+    use super::*;
+
+    #[derive(Debug, Copy, Clone)]
+    #[repr(C)]
+    struct Uniforms {
+        camera: [f32; 16],
+        light_dir: [f32; 3],
+        fog_start: f32,
+        fog_color: [f32; 3],
+        fog_end: f32,
+    }
+
+    unsafe impl bytemuck::Zeroable for Uniforms {}
+
+    unsafe impl bytemuck::Pod for Uniforms {}
+
+    fn field_range(offset: usize, size: usize) -> Range<wgpu::BufferAddress> {
+        offset as wgpu::BufferAddress..(offset + size) as wgpu::BufferAddress
+    }
+
+    #[test]
+    fn only_changed_fields_are_written() {
+        let mut uniforms = TrackedValue::new(Uniforms {
+            camera: [1.; 16],
+            light_dir: [0., -1., 0.],
+            fog_start: 10.,
+            fog_color: [0.5; 3],
+            fog_end: 100.,
+        });
+        assert!(uniforms.dirty_ranges().is_empty());
+
+        // Writing the same value doesn't dirty anything.
+        uniforms.get_mut().fog_end = 100.;
+        assert!(uniforms.dirty_ranges().is_empty());
+
+        uniforms.get_mut().fog_color = [0.25, 0.4, 0.75];
+
+        let mut writes = Vec::new();
+        uniforms.flush(|offset, data| writes.push((offset, data.to_vec())));
+
+        let fog_color = field_range(offset_of!(Uniforms, fog_color), size_of::<[f32; 3]>());
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].0, fog_color.start);
+        assert_eq!(
+            writes[0].1,
+            bytemuck::cast_slice::<f32, u8>(&[0.25, 0.4, 0.75]),
+        );
+        assert!(uniforms.dirty_ranges().is_empty());
+
+        // Neighboring fields are coalesced into a single write while distant ones are not.
+        uniforms.get_mut().light_dir = [1., 0.5, 0.5];
+        uniforms.get_mut().fog_start = 20.;
+        uniforms.get_mut().fog_end = 200.;
+
+        assert_eq!(
+            uniforms.dirty_ranges(),
+            [
+                field_range(
+                    offset_of!(Uniforms, light_dir),
+                    size_of::<[f32; 3]>() + size_of::<f32>(),
+                ),
+                field_range(offset_of!(Uniforms, fog_end), size_of::<f32>()),
+            ],
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::{any::TypeId, borrow::Cow, marker::PhantomData, num::NonZeroU32};
+use std::{any::TypeId, borrow::Cow, marker::PhantomData, num::NonZeroU32, ops::Range};
 
 use crucible_utils::{macros::impl_tuples, newtypes::transparent};
 use derive_where::derive_where;
@@ -371,6 +371,41 @@ impl<U: PipelineSet, V: PipelineSet> RenderPipeline<U, V> {
         pass.set_vertex_buffer(V::index(), buffer.raw);
     }
 
+    /// Binds `group` to the slot the pipeline declared for it. Binding a group whose type isn't
+    /// part of the pipeline's layout is rejected at compile time rather than by wgpu's validation
+    /// at draw time:
+    ///
+    /// ```compile_fail
+    /// use typed_wgpu::{
+    ///     BindGroup, BindGroupBuilder, BindGroupInstance, NoDynamicOffsets, RenderPipeline,
+    /// };
+    ///
+    /// struct Camera;
+    /// struct Lights;
+    ///
+    /// impl BindGroup for Camera {
+    ///     type Config = ();
+    ///     type DynamicOffsets = NoDynamicOffsets;
+    ///
+    ///     fn layout(_builder: &mut impl BindGroupBuilder<Self>, (): &Self::Config) {}
+    /// }
+    ///
+    /// impl BindGroup for Lights {
+    ///     type Config = ();
+    ///     type DynamicOffsets = NoDynamicOffsets;
+    ///
+    ///     fn layout(_builder: &mut impl BindGroupBuilder<Self>, (): &Self::Config) {}
+    /// }
+    ///
+    /// fn draw<'a>(
+    ///     pipeline: &RenderPipeline<(Camera,)>,
+    ///     pass: &mut wgpu::RenderPass<'a>,
+    ///     lights: &'a BindGroupInstance<Lights>,
+    /// ) {
+    ///     // `Lights` isn't part of the pipeline's `(Camera,)` layout.
+    ///     pipeline.bind_group(pass, lights, &[]);
+    /// }
+    /// ```
     pub fn bind_group<'a, L, D>(
         &self,
         pass: &mut wgpu::RenderPass<'a>,
@@ -393,4 +428,23 @@ impl<U: PipelineSet, V: PipelineSet> RenderPipeline<U, V> {
     {
         Self::bind_vertex_buffer_static(pass, buffer);
     }
+
+    /// Binds a per-vertex and a per-instance buffer and draws the `instances` range of instances of
+    /// the mesh made from the `vertices` range of vertices.
+    pub fn draw_instanced<'a, T, I, DT, DI>(
+        &self,
+        pass: &mut wgpu::RenderPass<'a>,
+        vertex_buffer: BufferSlice<'a, T>,
+        instance_buffer: BufferSlice<'a, I>,
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    ) where
+        T: 'static + GpuStruct,
+        I: 'static + GpuStruct,
+        V: StaticPipelineSetHas<T, DT> + StaticPipelineSetHas<I, DI>,
+    {
+        Self::bind_vertex_buffer_static::<T, DT>(pass, vertex_buffer);
+        Self::bind_vertex_buffer_static::<I, DI>(pass, instance_buffer);
+        pass.draw(vertices, instances);
+    }
 }
@@ -1,9 +1,10 @@
 use crucible_utils::{macros::impl_tuples, newtypes::transparent};
 use derive_where::derive_where;
-use std::{any::type_name, fmt, hash::Hash, marker::PhantomData, num::NonZeroU32};
+use std::{any::type_name, fmt, hash::Hash, marker::PhantomData, mem, num::NonZeroU32};
+use wgpu::util::DeviceExt as _;
 
 use crate::{
-    buffer::{DynamicOffset, GpuStruct},
+    buffer::{BufferBinding, DynamicOffset, GpuStruct},
     pipeline::PipelineSet,
     util::SlotAssigner,
 };
@@ -696,7 +697,8 @@ pub trait DynamicOffsetSet {
 pub type NoDynamicOffsets = [wgpu::DynamicOffset; 0];
 
 impl DynamicOffsetSet for [wgpu::DynamicOffset] {
-    type OffsetSet<'a> = &'a [wgpu::DynamicOffset]
+    type OffsetSet<'a>
+        = &'a [wgpu::DynamicOffset]
     where
         Self: 'a;
 
@@ -706,7 +708,8 @@ impl DynamicOffsetSet for [wgpu::DynamicOffset] {
 }
 
 impl<const N: usize> DynamicOffsetSet for [wgpu::DynamicOffset; N] {
-    type OffsetSet<'a> = &'a [wgpu::DynamicOffset; N]
+    type OffsetSet<'a>
+        = &'a [wgpu::DynamicOffset; N]
     where
         Self: 'a;
 
@@ -730,3 +733,193 @@ macro_rules! impl_dynamic_offset_set {
 }
 
 impl_tuples!(impl_dynamic_offset_set);
+
+// === UniformArray === //
+
+/// An array of uniform blocks stored in a single buffer, each of which can be bound individually
+/// by setting a dynamic offset to its element.
+///
+/// Elements are padded to `min_uniform_buffer_offset_alignment` so that every element's offset is a
+/// valid dynamic offset. Bind groups referring to this array should use [`UniformArray::binding`]
+/// with `has_dynamic_offset` set.
+#[derive_where(Debug)]
+pub struct UniformArray<T: GpuStruct> {
+    _ty: PhantomData<fn(T)>,
+    raw: wgpu::Buffer,
+    stride: wgpu::BufferAddress,
+    len: u32,
+}
+
+impl<T: GpuStruct> UniformArray<T> {
+    pub fn create(
+        device: &wgpu::Device,
+        label: wgpu::Label<'_>,
+        len: u32,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let stride = Self::stride_for(device.limits().min_uniform_buffer_offset_alignment);
+
+        Self {
+            _ty: PhantomData,
+            raw: device.create_buffer(&wgpu::BufferDescriptor {
+                label,
+                size: stride
+                    .checked_mul(len as wgpu::BufferAddress)
+                    .expect("buffer too big"),
+                usage: usage | wgpu::BufferUsages::UNIFORM,
+                mapped_at_creation: false,
+            }),
+            stride,
+            len,
+        }
+    }
+
+    pub fn create_init(
+        device: &wgpu::Device,
+        label: wgpu::Label<'_>,
+        contents: &[T::Pod],
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let stride = Self::stride_for(device.limits().min_uniform_buffer_offset_alignment);
+
+        Self {
+            _ty: PhantomData,
+            raw: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label,
+                contents: &Self::pack(stride, contents),
+                usage: usage | wgpu::BufferUsages::UNIFORM,
+            }),
+            stride,
+            len: u32::try_from(contents.len()).expect("too many elements"),
+        }
+    }
+
+    /// The distance in bytes between consecutive elements given the device's
+    /// `min_uniform_buffer_offset_alignment`.
+    pub const fn stride_for(alignment: u32) -> wgpu::BufferAddress {
+        let alignment = alignment as wgpu::BufferAddress;
+        let size = mem::size_of::<T::Pod>() as wgpu::BufferAddress;
+
+        size.div_ceil(alignment) * alignment
+    }
+
+    /// Lays `contents` out with each element at a multiple of `stride`, zeroing the padding.
+    fn pack(stride: wgpu::BufferAddress, contents: &[T::Pod]) -> Vec<u8> {
+        let mut bytes = vec![0u8; stride as usize * contents.len()];
+
+        for (chunk, elem) in bytes.chunks_exact_mut(stride as usize).zip(contents) {
+            chunk[..mem::size_of::<T::Pod>()].copy_from_slice(bytemuck::bytes_of(elem));
+        }
+
+        bytes
+    }
+
+    fn offset_for(stride: wgpu::BufferAddress, index: u32) -> DynamicOffset<T> {
+        DynamicOffset::wrap(
+            wgpu::DynamicOffset::try_from(index as wgpu::BufferAddress * stride)
+                .expect("offset too large"),
+        )
+    }
+
+    pub fn raw(&self) -> &wgpu::Buffer {
+        &self.raw
+    }
+
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.stride
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn write(&self, queue: &wgpu::Queue, index: u32, value: &T::Pod) {
+        assert!(
+            index < self.len,
+            "index {index} out of bounds (len {})",
+            self.len
+        );
+        queue.write_buffer(
+            &self.raw,
+            index as wgpu::BufferAddress * self.stride,
+            bytemuck::bytes_of(value),
+        );
+    }
+
+    pub fn offset(&self, index: u32) -> DynamicOffset<T> {
+        assert!(
+            index < self.len,
+            "index {index} out of bounds (len {})",
+            self.len
+        );
+        Self::offset_for(self.stride, index)
+    }
+
+    /// A binding covering a single element, to be used with a dynamic offset.
+    pub fn binding(&self) -> BufferBinding<'_, T> {
+        BufferBinding::wrap(wgpu::BufferBinding {
+            buffer: &self.raw,
+            offset: 0,
+            size: wgpu::BufferSize::new(mem::size_of::<T::Pod>() as wgpu::BufferAddress),
+        })
+    }
+
+    /// Binds `group` at slot `group_index` with its dynamic offset set to element `index`. The
+    /// group's only dynamic binding must be this array.
+    pub fn bind<'a, L: BindGroup>(
+        &self,
+        pass: &mut wgpu::RenderPass<'a>,
+        group_index: u32,
+        group: &'a BindGroupInstance<L>,
+        index: u32,
+    ) {
+        pass.set_bind_group(group_index, &group.raw, &[self.offset(index).raw]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Block;
+
+    impl GpuStruct for Block {
+        type Pod = [f32; 5];
+    }
+
+    #[test]
+    fn stride_respects_alignment() {
+        assert_eq!(UniformArray::<Block>::stride_for(4), 20);
+        assert_eq!(UniformArray::<Block>::stride_for(16), 32);
+        assert_eq!(UniformArray::<Block>::stride_for(256), 256);
+    }
+
+    #[test]
+    fn elements_are_uploaded_at_aligned_offsets() {
+        let stride = UniformArray::<Block>::stride_for(256);
+        let elems = [[1.; 5], [2.; 5], [3.; 5]];
+        let bytes = UniformArray::<Block>::pack(stride, &elems);
+
+        // Each element starts at a multiple of the stride and the padding after it is zeroed.
+        assert_eq!(bytes.len(), 3 * 256);
+
+        for (i, elem) in elems.iter().enumerate() {
+            let (data, padding) =
+                bytes[i * 256..(i + 1) * 256].split_at(mem::size_of::<[f32; 5]>());
+            assert_eq!(data, bytemuck::bytes_of(elem));
+            assert!(padding.iter().all(|&byte| byte == 0));
+        }
+
+        // Binding element 1 offsets the binding to the start of its data.
+        let offset = UniformArray::<Block>::offset_for(stride, 1).raw;
+        assert_eq!(offset, 256);
+        assert_eq!(
+            bytemuck::cast_slice::<u8, f32>(&bytes[offset as usize..][..20]),
+            elems[1]
+        );
+    }
+}
@@ -64,6 +64,10 @@ impl<T> VertexBufferLayout<T> {
             raw: buffer,
         }
     }
+
+    pub fn step_mode(&self) -> wgpu::VertexStepMode {
+        self.raw.step_mode
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -234,6 +238,18 @@ impl VertexBufferLayoutBuilder {
         self
     }
 
+    /// Finishes a layout for a buffer of per-vertex data.
+    pub fn finish_vertex<T>(self) -> VertexBufferLayout<T> {
+        self.finish(wgpu::VertexStepMode::Vertex)
+    }
+
+    /// Finishes a layout for a buffer of per-instance data, which advances once per instance rather
+    /// than once per vertex. Instance attributes share the shader location space with the vertex
+    /// buffers so these layouts typically start with a [`with_location`](Self::with_location).
+    pub fn finish_instance<T>(self) -> VertexBufferLayout<T> {
+        self.finish(wgpu::VertexStepMode::Instance)
+    }
+
     pub fn finish<T>(mut self, step_mode: wgpu::VertexStepMode) -> VertexBufferLayout<T> {
         self.push_alignment(self.align);
 
@@ -315,3 +331,41 @@ fn round_up_u64(value: u64, align: u64) -> u64 {
 
     (value.saturating_add(mask)) & !mask
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_layout_steps_per_instance() {
+        let vertex = VertexBufferLayout::builder()
+            .with_attribute(Std430VertexFormat::Float32x3)
+            .with_attribute(Std430VertexFormat::Float32x2)
+            .finish_vertex::<()>();
+
+        let instance = VertexBufferLayout::builder()
+            .with_location(2)
+            .with_attribute(Std430VertexFormat::Float32x3) // translation
+            .with_attribute(Std430VertexFormat::Float32x4) // color
+            .with_attribute(Std430VertexFormat::Float32) // scale
+            .finish_instance::<()>();
+
+        assert_eq!(vertex.step_mode(), wgpu::VertexStepMode::Vertex);
+        assert_eq!(instance.step_mode(), wgpu::VertexStepMode::Instance);
+
+        assert_eq!(
+            instance
+                .raw
+                .attributes
+                .iter()
+                .map(|attr| (attr.shader_location, attr.offset))
+                .collect::<Vec<_>>(),
+            [(2, 0), (3, 16), (4, 32)],
+        );
+        assert_eq!(instance.raw.stride, 48);
+        assert_eq!(
+            instance.raw.as_wgpu().step_mode,
+            wgpu::VertexStepMode::Instance
+        );
+    }
+}
@@ -1,8 +1,87 @@
+use std::any::TypeId;
+
 use crucible_assets::{Asset, AssetArgs, AssetManager};
 use crucible_utils::macros::impl_tuples;
+use image::DynamicImage;
 use main_loop::GfxContext;
 use typed_wgpu::{BindGroup, BindGroupInstance, BindGroupLayout, PipelineLayout, PipelineSet};
 
+// === EmbeddedAsset === //
+
+/// Bytes embedded into the binary (usually through [`embedded_asset!`]) which are decoded lazily
+/// through an [`AssetManager`]. Requests for the same embedded asset share a single decode for as
+/// long as the manager keeps it alive, just like on-disk assets.
+#[derive(Debug, Copy, Clone)]
+pub struct EmbeddedAsset {
+    name: &'static str,
+    bytes: &'static [u8],
+}
+
+/// Embeds the file at `path`, relative to the invoking file, as an [`EmbeddedAsset`].
+#[macro_export]
+macro_rules! embedded_asset {
+    ($path:literal) => {
+        $crate::EmbeddedAsset::new(
+            ::std::concat!(::std::module_path!(), "/", $path),
+            ::std::include_bytes!($path),
+        )
+    };
+}
+
+impl EmbeddedAsset {
+    /// Wraps `bytes` as an embedded asset. `name` identifies the asset in the cache and must be
+    /// unique among embedded assets.
+    pub const fn new(name: &'static str, bytes: &'static [u8]) -> Self {
+        Self { name, bytes }
+    }
+
+    pub fn name(self) -> &'static str {
+        self.name
+    }
+
+    pub fn bytes(self) -> &'static [u8] {
+        self.bytes
+    }
+
+    /// Decodes the asset with the decoder `D`, reusing the result of earlier loads which used the
+    /// same decoder.
+    pub fn load<D: EmbeddedDecoder>(self, assets: &AssetManager) -> Asset<D::Output> {
+        assets.load(self, (&self.name, &TypeId::of::<D>()), |_, me, _| {
+            D::decode(me)
+        })
+    }
+
+    pub fn load_image(self, assets: &AssetManager) -> Asset<DynamicImage> {
+        self.load::<ImageDecoder>(assets)
+    }
+
+    /// Decodes the asset as an image, panicking if it isn't a valid one.
+    pub fn decode_image(self) -> DynamicImage {
+        image::load_from_memory(self.bytes)
+            .unwrap_or_else(|err| panic!("failed to decode embedded image {:?}: {err}", self.name))
+    }
+}
+
+/// Decodes [`EmbeddedAsset`]s for [`EmbeddedAsset::load`]. Decodes are cached per asset and decoder
+/// type so the output must only depend on the asset being decoded.
+pub trait EmbeddedDecoder: 'static {
+    type Output: 'static + Send + Sync;
+
+    fn decode(asset: EmbeddedAsset) -> Self::Output;
+}
+
+/// Decodes an [`EmbeddedAsset`] with [`EmbeddedAsset::decode_image`].
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ImageDecoder;
+
+impl EmbeddedDecoder for ImageDecoder {
+    type Output = DynamicImage;
+
+    fn decode(asset: EmbeddedAsset) -> Self::Output {
+        asset.decode_image()
+    }
+}
+
 // === BindGroupExt === //
 
 pub trait BindGroupExt: BindGroup<Config = Self::Config2> {
@@ -141,3 +220,75 @@ impl<T: PipelineSet> PipelineLayoutExt for PipelineLayout<T> {
         configs.load(assets, gfx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::atomic::{AtomicUsize, Ordering::Relaxed},
+    };
+
+    use image::{ImageOutputFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn embedded_assets_decode_once() {
+        static DECODES: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountedDecoder;
+
+        impl EmbeddedDecoder for CountedDecoder {
+            type Output = DynamicImage;
+
+            fn decode(asset: EmbeddedAsset) -> Self::Output {
+                DECODES.fetch_add(1, Relaxed);
+                asset.decode_image()
+            }
+        }
+
+        struct GrayDecoder;
+
+        impl EmbeddedDecoder for GrayDecoder {
+            type Output = DynamicImage;
+
+            fn decode(asset: EmbeddedAsset) -> Self::Output {
+                asset.decode_image().grayscale()
+            }
+        }
+
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+
+        let assets = AssetManager::new();
+        let embedded = EmbeddedAsset::new("test/red.png", png.leak());
+
+        let first = embedded.load::<CountedDecoder>(&assets);
+        let second = embedded.load::<CountedDecoder>(&assets);
+
+        assert_eq!(DECODES.load(Relaxed), 1);
+        assert_eq!(first, second);
+        assert_eq!(first.to_rgba8().get_pixel(1, 1), &Rgba([255, 0, 0, 255]));
+
+        // Other decoders get their own cache entries, even when their outputs share a type.
+        assert_ne!(embedded.load_image(&assets), first);
+        assert_eq!(
+            embedded.load_image(&assets),
+            embedded.load::<ImageDecoder>(&assets)
+        );
+        assert_ne!(
+            embedded.load::<GrayDecoder>(&assets),
+            embedded.load_image(&assets)
+        );
+        assert_eq!(
+            embedded
+                .load::<GrayDecoder>(&assets)
+                .to_rgba8()
+                .get_pixel(1, 1),
+            &Rgba([54, 54, 54, 255])
+        );
+        assert_eq!(DECODES.load(Relaxed), 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crucible_utils::{hash::FxHashSet, iter::VolumetricIter};
+use crucible_utils::{define_index, hash::FxHashSet, iter::VolumetricIter, newtypes::IndexVec};
 use image::{imageops, GenericImageView, Pixel, Rgba, Rgba32FImage};
 use main_loop::GfxContext;
 use typed_glam::glam::{UVec2, Vec2};
@@ -15,11 +15,18 @@ const MIP_DBG_SPLIT_COLORS: [[f32; 4]; 5] = [
     [1., 0., 1., 0.5],
 ];
 
+define_index! {
+    /// A handle to an image packed into an [`AtlasTexture`]. Handles are never reused, even after
+    /// their image is removed.
+    pub struct AtlasHandle: u32;
+}
+
 #[derive(Debug)]
 pub struct AtlasTexture {
     tile_size: UVec2,
     tile_counts: UVec2,
     free_tiles: FxHashSet<UVec2>,
+    entries: IndexVec<AtlasHandle, Option<UVec2>>,
     atlas: Vec<Rgba32FImage>,
 }
 
@@ -33,6 +40,7 @@ impl AtlasTexture {
             free_tiles: VolumetricIter::new_exclusive_iter(tile_counts.to_array())
                 .map(UVec2::from_array)
                 .collect::<FxHashSet<_>>(),
+            entries: IndexVec::new(),
             atlas: (0..mips)
                 .map(|level| {
                     let size = wgpu::Extent3d {
@@ -84,22 +92,27 @@ impl AtlasTexture {
         self.free_tile_count() == 0
     }
 
-    pub fn add(&mut self, sub: &Rgba32FImage) -> UVec2 {
-        debug_assert_eq!(sub.width(), self.tile_size.x);
-        debug_assert_eq!(sub.height(), self.tile_size.y);
-
-        // Allocate a free tile
+    pub fn add(&mut self, sub: &Rgba32FImage) -> AtlasHandle {
         let free_tile = *self
             .free_tiles
             .iter()
             .next()
             .expect("no free tiles in atlas");
 
-        self.free_tiles.remove(&free_tile);
+        self.add_at(free_tile, sub)
+    }
 
-        // Write to the free tile
+    fn add_at(&mut self, tile: UVec2, sub: &Rgba32FImage) -> AtlasHandle {
+        debug_assert_eq!(sub.width(), self.tile_size.x);
+        debug_assert_eq!(sub.height(), self.tile_size.y);
+
+        // Allocate the tile
+        let was_free = self.free_tiles.remove(&tile);
+        assert!(was_free, "atlas tile {tile} is already occupied");
+
+        // Write to the tile
         let atlas_size = self.atlas_size().as_dvec2();
-        let offset = free_tile * self.tile_size;
+        let offset = tile * self.tile_size;
 
         for (layer, &dbg_split) in self
             .atlas
@@ -160,14 +173,27 @@ impl AtlasTexture {
             }
         }
 
-        free_tile
+        self.entries.push(Some(tile))
     }
 
-    pub fn remove(&mut self, sub: UVec2) {
-        debug_assert!(sub.x < self.tile_counts.x);
-        debug_assert!(sub.y < self.tile_counts.y);
+    pub fn remove(&mut self, handle: AtlasHandle) {
+        let tile = self.entries[handle]
+            .take()
+            .expect("atlas entry was already removed");
 
-        self.free_tiles.insert(sub);
+        self.free_tiles.insert(tile);
+    }
+
+    /// Fetches the tile an entry was packed into. This is only valid until the atlas is resized or
+    /// repacked; prefer [`uv_rect`](Self::uv_rect) where possible.
+    pub fn tile(&self, handle: AtlasHandle) -> UVec2 {
+        self.entries[handle].expect("atlas entry was removed")
+    }
+
+    /// Fetches the `[min, max]` bounds of an entry in normalized texture coordinates.
+    pub fn uv_rect(&self, handle: AtlasHandle) -> [Vec2; 2] {
+        let (origin, size) = self.decode_uv_percent_bounds(self.tile(handle));
+        [origin, origin + size]
     }
 
     pub fn decode_uv_percent_bounds(&self, tile: UVec2) -> (Vec2, Vec2) {
@@ -183,6 +209,63 @@ impl AtlasTexture {
     }
 }
 
+/// An albedo [`AtlasTexture`] paired with a normal map atlas of the same dimensions. Every entry
+/// occupies the same tile in both atlases so a single UV can be used to sample either.
+#[derive(Debug)]
+pub struct PairedAtlasTexture {
+    albedo: AtlasTexture,
+    normal: AtlasTexture,
+}
+
+impl PairedAtlasTexture {
+    /// The normal used for entries added without a normal map. This is `(0, 0, 1)` encoded with
+    /// the usual `n * 0.5 + 0.5` mapping.
+    pub const FLAT_NORMAL: [f32; 4] = [0.5, 0.5, 1., 1.];
+
+    pub fn new(tile_size: UVec2, tile_counts: UVec2, mips: u32) -> Self {
+        Self {
+            albedo: AtlasTexture::new(tile_size, tile_counts, mips),
+            normal: AtlasTexture::new(tile_size, tile_counts, mips),
+        }
+    }
+
+    pub fn albedo(&self) -> &AtlasTexture {
+        &self.albedo
+    }
+
+    pub fn normal(&self) -> &AtlasTexture {
+        &self.normal
+    }
+
+    /// Packs an albedo image and its normal map into the same tile of their respective atlases. If
+    /// `normal` is `None`, the tile is filled with [`FLAT_NORMAL`](Self::FLAT_NORMAL).
+    pub fn add(&mut self, albedo: &Rgba32FImage, normal: Option<&Rgba32FImage>) -> AtlasHandle {
+        let handle = self.albedo.add(albedo);
+        let tile = self.albedo.tile(handle);
+
+        let normal_handle = match normal {
+            Some(normal) => self.normal.add_at(tile, normal),
+            None => {
+                let size = self.normal.tile_size();
+                let flat = Rgba32FImage::from_pixel(size.x, size.y, Rgba(Self::FLAT_NORMAL));
+                self.normal.add_at(tile, &flat)
+            }
+        };
+        debug_assert_eq!(handle, normal_handle);
+
+        handle
+    }
+
+    pub fn remove(&mut self, handle: AtlasHandle) {
+        self.albedo.remove(handle);
+        self.normal.remove(handle);
+    }
+
+    pub fn uv_rect(&self, handle: AtlasHandle) -> [Vec2; 2] {
+        self.albedo.uv_rect(handle)
+    }
+}
+
 #[derive(Debug)]
 pub struct AtlasTextureGfx {
     pub texture: wgpu::Texture,
@@ -228,3 +311,82 @@ impl AtlasTextureGfx {
         write_texture_data_raw(gfx, &self.texture, &mips_data);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uv_rects_are_disjoint_and_normalized() {
+        let mut atlas = AtlasTexture::new(UVec2::splat(4), UVec2::new(2, 1), 1);
+        let a = atlas.add(&Rgba32FImage::new(4, 4));
+        let b = atlas.add(&Rgba32FImage::new(4, 4));
+
+        let [a_min, a_max] = atlas.uv_rect(a);
+        let [b_min, b_max] = atlas.uv_rect(b);
+
+        for uv in [a_min, a_max, b_min, b_max] {
+            assert!(uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all());
+        }
+
+        assert_eq!(a_max - a_min, Vec2::new(0.5, 1.));
+        assert_eq!(b_max - b_min, Vec2::new(0.5, 1.));
+
+        let overlaps = a_min.cmplt(b_max).all() && b_min.cmplt(a_max).all();
+        assert!(!overlaps);
+    }
+
+    #[test]
+    fn paired_atlases_share_tiles() {
+        let mut atlas = PairedAtlasTexture::new(UVec2::splat(2), UVec2::splat(2), 1);
+        let solid = |color| Rgba32FImage::from_pixel(2, 2, Rgba(color));
+
+        let handles = [
+            atlas.add(&solid([1., 0., 0., 1.]), Some(&solid([1., 0.5, 0.5, 1.]))),
+            atlas.add(&solid([0., 1., 0., 1.]), None),
+            atlas.add(&solid([0., 0., 1., 1.]), Some(&solid([0.5, 1., 0.5, 1.]))),
+        ];
+
+        // Free a tile up and reuse it to make sure the two atlases don't drift apart.
+        atlas.remove(handles[1]);
+        let reused = atlas.add(&solid([1., 1., 1., 1.]), Some(&solid([0., 0., 1., 1.])));
+
+        for handle in [handles[0], handles[2], reused] {
+            let tile = atlas.albedo().tile(handle);
+            assert_eq!(atlas.normal().tile(handle), tile);
+            assert_eq!(atlas.normal().uv_rect(handle), atlas.uv_rect(handle));
+        }
+        assert_eq!(
+            atlas.albedo().free_tile_count(),
+            atlas.normal().free_tile_count()
+        );
+
+        // Each image landed in the matching tile of its atlas.
+        let albedo_at = |handle| {
+            let pos = atlas.albedo().tile(handle) * 2;
+            atlas.albedo().textures()[0].get_pixel(pos.x, pos.y).0
+        };
+        let normal_at = |handle| {
+            let pos = atlas.normal().tile(handle) * 2;
+            atlas.normal().textures()[0].get_pixel(pos.x, pos.y).0
+        };
+
+        assert_eq!(albedo_at(handles[2]), [0., 0., 1., 1.]);
+        assert_eq!(normal_at(handles[2]), [0.5, 1., 0.5, 1.]);
+        assert_eq!(albedo_at(reused), [1., 1., 1., 1.]);
+        assert_eq!(normal_at(reused), [0., 0., 1., 1.]);
+    }
+
+    #[test]
+    fn missing_normals_are_flat() {
+        let mut atlas = PairedAtlasTexture::new(UVec2::splat(2), UVec2::ONE, 1);
+        let handle = atlas.add(&Rgba32FImage::new(2, 2), None);
+
+        let pos = atlas.normal().tile(handle) * 2;
+        let normals = &atlas.normal().textures()[0];
+        assert_eq!(
+            normals.get_pixel(pos.x, pos.y).0,
+            PairedAtlasTexture::FLAT_NORMAL
+        );
+    }
+}
@@ -1,5 +1,13 @@
-use std::sync::{mpsc, Arc};
+use std::{
+    future::Future,
+    marker::PhantomData,
+    ops::Range,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
 
+use bytemuck::Pod;
 use crucible_utils::polyfill::OptionExt;
 use main_loop::GfxContext;
 use wgpu::util::DeviceExt;
@@ -173,3 +181,550 @@ impl<'a> Extend<&'a u8> for DynamicBuffer {
         self.data.extend(iter.into_iter().copied());
     }
 }
+
+// === AppendBuffer === //
+
+/// A storage buffer of `T`s which shaders append to through an atomic counter living in a paired
+/// indirect draw buffer. Draws issued through [`draw_indirect`](Self::draw_indirect) render one
+/// instance per appended element without the count ever being read back to the CPU.
+///
+/// Shaders should bind [`data`](Self::data) as an `array<T>` and [`args`](Self::args) using
+/// [`AppendBuffer::ARGS_WGSL`] and append like so:
+///
+/// ```wgsl
+/// let index = atomicAdd(&args.instance_count, 1u);
+/// if index < args.capacity {
+///     data[index] = value;
+/// } else {
+///     atomicSub(&args.instance_count, 1u);
+/// }
+/// ```
+///
+/// Undoing failed appends keeps the final count clamped to the buffer's capacity. The capacity is
+/// stored alongside the draw arguments rather than derived from `arrayLength` because some backends
+/// round the length of storage bindings up.
+#[derive(Debug)]
+pub struct AppendBuffer<T> {
+    _ty: PhantomData<fn(T) -> T>,
+    data: wgpu::Buffer,
+    args: wgpu::Buffer,
+    capacity: u32,
+    vertices_per_instance: u32,
+}
+
+impl<T: Pod> AppendBuffer<T> {
+    /// The WGSL declaration of the indirect draw arguments with an atomic instance count, followed
+    /// by the buffer's capacity.
+    pub const ARGS_WGSL: &'static str = "
+        struct AppendArgs {
+            vertex_count: u32,
+            instance_count: atomic<u32>,
+            first_vertex: u32,
+            first_instance: u32,
+            capacity: u32,
+        }
+    ";
+
+    /// Creates an empty buffer which can hold up to `capacity` elements and draws
+    /// `vertices_per_instance` vertices for each of them. `usage` is added to the data buffer's
+    /// `STORAGE` usage.
+    pub fn new(
+        device: &wgpu::Device,
+        label: Option<&str>,
+        capacity: u32,
+        vertices_per_instance: u32,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        assert!(capacity > 0, "append buffers must have a non-zero capacity");
+
+        let data = device.create_buffer(&wgpu::BufferDescriptor {
+            label,
+            size: capacity as u64 * std::mem::size_of::<T>() as u64,
+            usage: usage | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let args = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(&Self::empty_args(vertices_per_instance, capacity)),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        Self {
+            _ty: PhantomData,
+            data,
+            args,
+            capacity,
+            vertices_per_instance,
+        }
+    }
+
+    fn empty_args(vertices_per_instance: u32, capacity: u32) -> [u32; 5] {
+        // Matches the layout of `ARGS_WGSL`, the first four fields of which are a
+        // `wgpu::util::DrawIndirectArgs`.
+        [vertices_per_instance, 0, 0, 0, capacity]
+    }
+
+    pub fn data(&self) -> &wgpu::Buffer {
+        &self.data
+    }
+
+    pub fn args(&self) -> &wgpu::Buffer {
+        &self.args
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Empties the buffer. This should be called once per frame before the passes appending to it
+    /// are submitted.
+    pub fn reset(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.args,
+            0,
+            bytemuck::cast_slice(&Self::empty_args(self.vertices_per_instance, self.capacity)),
+        );
+    }
+
+    /// Draws one instance for every appended element.
+    pub fn draw_indirect<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.draw_indirect(&self.args, 0);
+    }
+
+    /// Reads the number of appended elements back to the CPU. This is mostly useful for debugging
+    /// since [`draw_indirect`](Self::draw_indirect) doesn't need it.
+    pub fn read_len(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> impl Future<Output = Result<u32, wgpu::BufferAsyncError>> + 'static {
+        let count = read_back_buffer(device, queue, &self.args, 4..8);
+
+        async move { Ok(bytemuck::pod_read_unaligned(&count.await?)) }
+    }
+}
+
+// === Read-back === //
+
+pub trait BufferExt {
+    /// Reads the entire buffer back to the CPU. See [`read_back_buffer`] for details.
+    fn read_back(
+        &self,
+        gfx: &GfxContext,
+    ) -> impl Future<Output = Result<Vec<u8>, wgpu::BufferAsyncError>> + 'static;
+
+    /// Reads the entire buffer back to the CPU as a sequence of `T`s. Trailing bytes which don't
+    /// make up an entire `T` are discarded.
+    fn read_back_typed<T: Pod>(
+        &self,
+        gfx: &GfxContext,
+    ) -> impl Future<Output = Result<Vec<T>, wgpu::BufferAsyncError>> + 'static {
+        let bytes = self.read_back(gfx);
+
+        async move {
+            let bytes = bytes.await?;
+            let whole_len = bytes.len() - bytes.len() % std::mem::size_of::<T>();
+            Ok(bytemuck::pod_collect_to_vec(&bytes[..whole_len]))
+        }
+    }
+}
+
+impl BufferExt for wgpu::Buffer {
+    fn read_back(
+        &self,
+        gfx: &GfxContext,
+    ) -> impl Future<Output = Result<Vec<u8>, wgpu::BufferAsyncError>> + 'static {
+        read_back_buffer(&gfx.device, &gfx.queue, self, 0..self.size())
+    }
+}
+
+/// Copies the bytes in `range` of `buffer` into a mappable staging buffer and reads them back to the
+/// CPU. `buffer` must have been created with [`wgpu::BufferUsages::COPY_SRC`].
+///
+/// The copy is widened to satisfy [`wgpu::COPY_BUFFER_ALIGNMENT`] and trimmed back down once read so
+/// `range` can have any alignment. Buffers whose size isn't a multiple of that alignment can't be
+/// copied past their last aligned offset so their last few bytes are routed through a tiny
+/// texture instead.
+///
+/// Like all buffer mappings, the returned future only resolves once the device has been polled
+/// after the copy completes.
+pub fn read_back_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    range: Range<wgpu::BufferAddress>,
+) -> BufferReadBack {
+    assert!(range.start <= range.end);
+    assert!(
+        buffer.usage().contains(wgpu::BufferUsages::COPY_SRC),
+        "buffer must have `COPY_SRC` usage to be read back"
+    );
+
+    if range.is_empty() {
+        return BufferReadBack { pending: None };
+    }
+
+    assert!(
+        range.end <= buffer.size(),
+        "cannot read back {range:?} because it exceeds the buffer's size of {}",
+        buffer.size(),
+    );
+
+    let copy_start = range.start - range.start % wgpu::COPY_BUFFER_ALIGNMENT;
+    let copy_end = range.end.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+
+    // Buffer-to-buffer copies can't extend past the buffer's size, even into its padding, so an
+    // unaligned tail has to be copied separately.
+    let body_end = copy_end.min(buffer.size() - buffer.size() % wgpu::COPY_BUFFER_ALIGNMENT);
+
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("read-back staging buffer"),
+        size: copy_end - copy_start,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("read-back copy encoder"),
+    });
+    if body_end > copy_start {
+        encoder.copy_buffer_to_buffer(buffer, copy_start, &staging, 0, body_end - copy_start);
+    }
+    if range.end > body_end {
+        copy_tail_through_texture(
+            device,
+            &mut encoder,
+            buffer,
+            body_end,
+            &staging,
+            body_end - copy_start,
+        );
+    }
+    queue.submit([encoder.finish()]);
+
+    let state = Arc::new(Mutex::new(ReadBackState::default()));
+    staging.slice(..).map_async(wgpu::MapMode::Read, {
+        let state = state.clone();
+        move |result| {
+            let mut state = state.lock().unwrap();
+            state.result = Some(result);
+
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    });
+
+    BufferReadBack {
+        pending: Some(PendingReadBack {
+            staging,
+            state,
+            trim: (range.start - copy_start) as usize..(range.end - copy_start) as usize,
+        }),
+    }
+}
+
+/// Copies the bytes of `buffer` from `offset` to its end into `staging` at `staging_offset`.
+/// Unlike buffer-to-buffer copies, buffer-to-texture copies of a single-byte format only need
+/// their offsets aligned to a byte so they can pick up a tail which isn't a multiple of
+/// [`wgpu::COPY_BUFFER_ALIGNMENT`] long.
+fn copy_tail_through_texture(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    buffer: &wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    staging: &wgpu::Buffer,
+    staging_offset: wgpu::BufferAddress,
+) {
+    let size = wgpu::Extent3d {
+        width: (buffer.size() - offset) as u32,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("read-back tail texture"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Uint,
+        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    encoder.copy_buffer_to_texture(
+        wgpu::ImageCopyBuffer {
+            buffer,
+            layout: wgpu::ImageDataLayout {
+                offset,
+                bytes_per_row: None,
+                rows_per_image: None,
+            },
+        },
+        texture.as_image_copy(),
+        size,
+    );
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: staging,
+            layout: wgpu::ImageDataLayout {
+                offset: staging_offset,
+                bytes_per_row: None,
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+}
+
+#[derive(Debug)]
+pub struct BufferReadBack {
+    pending: Option<PendingReadBack>,
+}
+
+#[derive(Debug)]
+struct PendingReadBack {
+    staging: wgpu::Buffer,
+    state: Arc<Mutex<ReadBackState>>,
+    trim: Range<usize>,
+}
+
+#[derive(Debug, Default)]
+struct ReadBackState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+impl Future for BufferReadBack {
+    type Output = Result<Vec<u8>, wgpu::BufferAsyncError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Some(pending) = &self.pending else {
+            return Poll::Ready(Ok(Vec::new()));
+        };
+
+        let result = {
+            let mut state = pending.state.lock().unwrap();
+            let Some(result) = state.result.take() else {
+                state.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            };
+            result
+        };
+
+        let pending = self.pending.take().unwrap();
+        result?;
+
+        let bytes = pending.staging.slice(..).get_mapped_range()[pending.trim].to_vec();
+        pending.staging.unmap();
+
+        Poll::Ready(Ok(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_back_returns_written_bytes() {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+        let (device, queue) = (&gfx.device, &gfx.queue);
+
+        let contents = (0..64u8).collect::<Vec<_>>();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &contents,
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+
+        // Both ends of this range are unaligned.
+        let read = read_back_buffer(device, queue, &buffer, 3..38);
+        device.poll(wgpu::Maintain::Wait);
+
+        assert_eq!(futures::executor::block_on(read).unwrap(), &contents[3..38]);
+    }
+
+    #[test]
+    fn read_back_handles_unaligned_buffer_sizes() {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+        let (device, queue) = (&gfx.device, &gfx.queue);
+
+        // Neither `write_buffer` nor buffer-to-buffer copies can reach the last two bytes of this
+        // buffer so we fill them through a texture.
+        let contents = [1, 2, 3, 4, 5, 6u8];
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: contents.len() as u64,
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&buffer, 0, &contents[..4]);
+
+        let tail_size = wgpu::Extent3d {
+            width: 2,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let tail = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: tail_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Uint,
+            usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            tail.as_image_copy(),
+            &contents[4..],
+            wgpu::ImageDataLayout::default(),
+            tail_size,
+        );
+
+        let mut cmd = device.create_command_encoder(&Default::default());
+        cmd.copy_texture_to_buffer(
+            tail.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 4,
+                    ..Default::default()
+                },
+            },
+            tail_size,
+        );
+        queue.submit([cmd.finish()]);
+
+        let whole = buffer.read_back(&gfx);
+        let straddling = read_back_buffer(device, queue, &buffer, 1..6);
+        let tail_only = read_back_buffer(device, queue, &buffer, 5..6);
+        device.poll(wgpu::Maintain::Wait);
+
+        assert_eq!(futures::executor::block_on(whole).unwrap(), &contents);
+        assert_eq!(
+            futures::executor::block_on(straddling).unwrap(),
+            &contents[1..]
+        );
+        assert_eq!(
+            futures::executor::block_on(tail_only).unwrap(),
+            &contents[5..]
+        );
+    }
+
+    #[test]
+    fn append_buffer_counts_gpu_appends() {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+        let (device, queue) = (&gfx.device, &gfx.queue);
+
+        let buffer = AppendBuffer::<u32>::new(
+            device,
+            None,
+            64,
+            6,
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(
+                format!(
+                    "
+                    {}
+
+                    @group(0) @binding(0) var<storage, read_write> data: array<u32>;
+                    @group(0) @binding(1) var<storage, read_write> args: AppendArgs;
+
+                    @compute @workgroup_size(1)
+                    fn main(@builtin(global_invocation_id) id: vec3u) {{
+                        let index = atomicAdd(&args.instance_count, 1u);
+                        if index < args.capacity {{
+                            data[index] = id.x;
+                        }} else {{
+                            atomicSub(&args.instance_count, 1u);
+                        }}
+                    }}
+                    ",
+                    AppendBuffer::<u32>::ARGS_WGSL,
+                )
+                .into(),
+            ),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.data().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: buffer.args().as_entire_binding(),
+                },
+            ],
+        });
+
+        let append = |count: u32| {
+            let mut encoder = device.create_command_encoder(&Default::default());
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(count, 1, 1);
+            }
+            queue.submit([encoder.finish()]);
+        };
+
+        // Appends accumulate until the buffer is reset.
+        append(10);
+        append(30);
+        let len = buffer.read_len(device, queue);
+        device.poll(wgpu::Maintain::Wait);
+        assert_eq!(futures::executor::block_on(len).unwrap(), 40);
+
+        let args = read_back_buffer(device, queue, buffer.args(), 0..20);
+        let data = read_back_buffer(device, queue, buffer.data(), 0..40 * 4);
+        device.poll(wgpu::Maintain::Wait);
+
+        let args: Vec<u32> =
+            bytemuck::pod_collect_to_vec(&futures::executor::block_on(args).unwrap());
+        assert_eq!(args, [6, 40, 0, 0, 64]);
+
+        let mut data: Vec<u32> =
+            bytemuck::pod_collect_to_vec(&futures::executor::block_on(data).unwrap());
+        let mut expected = (0..10).chain(0..30).collect::<Vec<_>>();
+        data.sort();
+        expected.sort();
+        assert_eq!(data, expected);
+
+        // Appends past the capacity are dropped.
+        buffer.reset(queue);
+        append(100);
+        let len = buffer.read_len(device, queue);
+        device.poll(wgpu::Maintain::Wait);
+        assert_eq!(futures::executor::block_on(len).unwrap(), 64);
+    }
+}
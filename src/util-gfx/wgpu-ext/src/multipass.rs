@@ -2,6 +2,7 @@ use std::{cell::RefCell, fmt};
 
 use crucible_utils::mem::DropBump;
 use main_loop::GfxContext;
+use typed_glam::glam::UVec2;
 use typed_wgpu::{BufferAddress, GpuStruct};
 
 use super::DynamicBuffer;
@@ -23,11 +24,15 @@ impl MultiPassDriver {
         Self::default()
     }
 
+    /// Runs `f` once to collect its buffer writes and again to record its draws into `pass`. If a
+    /// `region` is given, the pass' viewport and scissor rect are restricted to it and nothing is
+    /// drawn if it lies entirely outside the attachments.
     pub fn drive<'p>(
         &'p self,
         gfx: &GfxContext,
         pass: &mut wgpu::RenderPass<'p>,
         buffer: &mut DynamicBuffer,
+        region: Option<PassRegion>,
         mut f: impl FnMut(&mut MultiPass<'_, 'p>),
     ) {
         let mut offset_buff = self
@@ -42,8 +47,26 @@ impl MultiPassDriver {
             offsets: &mut offset_buff,
         }));
 
+        let buffer = buffer.finish(gfx);
+
+        if let Some(region) = region {
+            let Some((origin, size)) = region.clamped() else {
+                return;
+            };
+
+            pass.set_viewport(
+                origin.x as f32,
+                origin.y as f32,
+                size.x as f32,
+                size.y as f32,
+                0.,
+                1.,
+            );
+            pass.set_scissor_rect(origin.x, origin.y, size.x, size.y);
+        }
+
         f(&mut MultiPass(MultiPassInner::Pass {
-            buffer: buffer.finish(gfx),
+            buffer,
             bump: &self.bump,
             pass,
             offsets: &offset_buff,
@@ -51,6 +74,32 @@ impl MultiPassDriver {
     }
 }
 
+/// A sub-rectangle of a render pass' attachments, e.g. one player's half of a split-screen frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PassRegion {
+    pub origin: UVec2,
+    pub size: UVec2,
+
+    /// The size of the pass' attachments. The region is clamped to these bounds since wgpu rejects
+    /// viewports and scissor rects which extend past them.
+    pub attachment_size: UVec2,
+}
+
+impl PassRegion {
+    /// Returns the `(origin, size)` of the part of the region which lies inside the attachments or
+    /// `None` if there is no such part.
+    pub fn clamped(self) -> Option<(UVec2, UVec2)> {
+        let min = self.origin.min(self.attachment_size);
+        let max = self
+            .origin
+            .saturating_add(self.size)
+            .min(self.attachment_size);
+        let size = max - min;
+
+        (size.x > 0 && size.y > 0).then_some((min, size))
+    }
+}
+
 #[derive(Debug)]
 pub struct MultiPass<'a, 'p>(MultiPassInner<'a, 'p>);
 
@@ -119,3 +168,37 @@ impl<'a, 'p> MultiPass<'a, 'p> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_clamped_to_attachments() {
+        let attachment_size = UVec2::new(800, 600);
+        let region = |origin, size| PassRegion {
+            origin,
+            size,
+            attachment_size,
+        };
+
+        // Regions inside the attachments are left alone.
+        assert_eq!(
+            region(UVec2::new(400, 0), UVec2::new(400, 600)).clamped(),
+            Some((UVec2::new(400, 0), UVec2::new(400, 600)))
+        );
+
+        // Regions hanging off the edge are cut down...
+        assert_eq!(
+            region(UVec2::new(600, 500), UVec2::new(400, u32::MAX)).clamped(),
+            Some((UVec2::new(600, 500), UVec2::new(200, 100)))
+        );
+
+        // ...and those entirely outside of the attachments are dropped.
+        assert_eq!(
+            region(UVec2::new(800, 0), UVec2::new(100, 100)).clamped(),
+            None
+        );
+        assert_eq!(region(UVec2::ZERO, UVec2::new(0, 100)).clamped(), None);
+    }
+}
@@ -1,6 +1,7 @@
 use std::{borrow::Borrow, hash};
 
 use crucible_assets::{Asset, AssetManager};
+use image::Rgba32FImage;
 use main_loop::{GfxContext, Viewport};
 use typed_glam::glam::UVec2;
 
@@ -118,6 +119,91 @@ impl FullScreenTexture {
     }
 }
 
+// === TextureArray === //
+
+/// A `texture_2d_array` of equally-sized `Rgba32Float` layers. Unlike an [`AtlasTexture`], each
+/// image is addressed by its layer index rather than a UV sub-rectangle so neighboring images can
+/// never bleed into one another.
+///
+/// [`AtlasTexture`]: crate::AtlasTexture
+#[derive(Debug)]
+pub struct TextureArray {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub layer_size: UVec2,
+    pub layers: u32,
+}
+
+impl TextureArray {
+    pub fn new(device: &wgpu::Device, label: Option<&str>, layer_size: UVec2, layers: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: layer_size.x,
+                height: layer_size.y,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        // N.B. an array with a single layer would otherwise default to a `D2` view.
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label,
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            layer_size,
+            layers,
+        }
+    }
+
+    /// Replaces the contents of the layer at `index`. The image must be exactly
+    /// [`layer_size`](Self::layer_size) large.
+    pub fn upload_layer(&self, queue: &wgpu::Queue, index: u32, image: &Rgba32FImage) {
+        assert!(index < self.layers, "layer {index} is out of bounds");
+        assert_eq!(
+            UVec2::new(image.width(), image.height()),
+            self.layer_size,
+            "layer image has the wrong size"
+        );
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: index,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(image.as_raw()),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.layer_size.x * 16),
+                rows_per_image: Some(self.layer_size.y),
+            },
+            wgpu::Extent3d {
+                width: self.layer_size.x,
+                height: self.layer_size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
 // === Texture Uploads === //
 
 // This is largely stolen from wgpu's own `create_texture_with_data` method.
@@ -274,3 +360,84 @@ impl SamplerDesc {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use crate::read_back_buffer;
+
+    use super::*;
+
+    #[test]
+    fn texture_array_layers_are_independent() {
+        let Some(gfx) = futures::executor::block_on(GfxContext::new_headless_or_skip()) else {
+            return;
+        };
+        let (device, queue) = (&gfx.device, &gfx.queue);
+
+        // 16 texels of 16 bytes each keeps every row at wgpu's required copy alignment.
+        let size = UVec2::splat(16);
+        let colors = [
+            [1., 0., 0., 1.],
+            [0., 1., 0., 1.],
+            [0., 0., 1., 1.],
+            [1., 1., 1., 0.5],
+        ];
+
+        let array = TextureArray::new(device, None, size, 4);
+        for (layer, color) in colors.iter().enumerate() {
+            let image = Rgba32FImage::from_pixel(size.x, size.y, Rgba(*color));
+            array.upload_layer(queue, layer as u32, &image);
+        }
+
+        // Copy the layers out in reverse order to make sure they're addressed by index.
+        let layer_bytes = (size.x * size.y * 16) as u64;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: layer_bytes * 4,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut cmd = device.create_command_encoder(&Default::default());
+        for layer in 0..4 {
+            cmd.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: &array.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: 3 - layer,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset: layer_bytes * layer as u64,
+                        bytes_per_row: Some(size.x * 16),
+                        rows_per_image: Some(size.y),
+                    },
+                },
+                wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        queue.submit([cmd.finish()]);
+
+        let read = read_back_buffer(device, queue, &staging, 0..layer_bytes * 4);
+        device.poll(wgpu::Maintain::Wait);
+        let bytes = futures::executor::block_on(read).unwrap();
+        let texels = bytemuck::cast_slice::<u8, [f32; 4]>(&bytes);
+
+        for (i, layer) in texels.chunks(texels.len() / 4).enumerate() {
+            let expected = colors[3 - i];
+            assert!(layer.iter().all(|&texel| texel == expected));
+        }
+    }
+}
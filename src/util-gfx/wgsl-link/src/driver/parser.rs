@@ -16,6 +16,14 @@ define_keywords! {
     }
 }
 
+/// Parses the `//#use` directives at the start of any line in `source`. Directives have the form:
+///
+/// ```text
+/// //#use foo, bar as baz in "path/to/module.wgsl"
+/// ```
+///
+/// Only the listed symbols are imported, optionally under a new name. Everything else in the
+/// dependency stays out of scope.
 pub fn parse_directives(
     source: Span,
     mut f: impl FnMut(TokenStringLit, &[(TokenIdent, Option<TokenIdent>)]),
@@ -11,7 +11,8 @@ use crucible_utils::{
 };
 use dsl_utils::{
     diagnostic::{
-        report_diagnostic, Diagnostic, DiagnosticReporter, DiagnosticReporterCap, DiagnosticWindow,
+        emit_pretty_diagnostics, report_diagnostic, Diagnostic, DiagnosticReporter,
+        DiagnosticReporterCap, DiagnosticWindow,
     },
     span::{NaiveUtf8Segmenter, Span, SpanFile, SpanManager, SpanManagerCap},
     symbol::{Interner, InternerCap},
@@ -20,7 +21,12 @@ use dsl_utils::{
 
 use crate::{
     driver::parser::parse_directives,
-    module::linker::{LinkerImport, LinkerImportError, ModuleHandle, ModuleLinker},
+    module::{
+        entry_points::{alias_entry_points, EntryPointAliasError},
+        fold::fold_constants,
+        linker::{LinkerImport, LinkerImportError, ModuleHandle, ModuleLinker},
+        overrides::{inject_overrides, OverrideError},
+    },
 };
 
 // === Language === //
@@ -140,6 +146,9 @@ pub struct Session {
     services: SessionServices,
     linker: ModuleLinker,
     files: FxHashMap<PathBuf, ModuleLoadStatus>,
+    imports: FxHashMap<PathBuf, Vec<PathBuf>>,
+    dependents: FxHashMap<PathBuf, Vec<PathBuf>>,
+    fold_constants: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -174,6 +183,9 @@ impl Session {
             linker: ModuleLinker::new(),
             services: SessionServices::default(),
             files: FxHashMap::default(),
+            imports: FxHashMap::default(),
+            dependents: FxHashMap::default(),
+            fold_constants: false,
         }
     }
 
@@ -181,6 +193,20 @@ impl Session {
         &self.services.span_mgr
     }
 
+    /// Renders the diagnostics returned by a failed [`parse`](Self::parse) as plain text so that
+    /// they can be logged by callers linking shaders at runtime.
+    pub fn format_diagnostics(&self, diag: &DiagnosticReporter) -> String {
+        let mut out = termcolor::NoColor::new(Vec::new());
+        emit_pretty_diagnostics(&mut out, self.span_mgr(), diag).unwrap();
+        String::from_utf8_lossy(&out.into_inner()).into_owned()
+    }
+
+    /// Sets whether constant arithmetic in the linked module should be folded before it's emitted.
+    /// This is off by default. See [`fold_constants`] for details.
+    pub fn set_fold_constants(&mut self, enabled: bool) {
+        self.fold_constants = enabled;
+    }
+
     pub fn linker(&self) -> &ModuleLinker {
         &self.linker
     }
@@ -202,7 +228,74 @@ impl Session {
     }
 
     pub fn build(&mut self, modules: impl IntoIterator<Item = ModuleHandle>) -> String {
-        self.language.emit(&self.linker.shake_module(modules))
+        let module = self.linker.shake_module(modules);
+        self.emit(module)
+    }
+
+    /// Builds the linked module with the given values substituted for its `override` declarations.
+    /// See [`inject_overrides`] for details.
+    pub fn build_with_overrides<'a>(
+        &mut self,
+        modules: impl IntoIterator<Item = ModuleHandle>,
+        overrides: impl IntoIterator<Item = (&'a str, naga::Literal)>,
+    ) -> Result<String, OverrideError> {
+        let mut module = self.linker.shake_module(modules);
+        inject_overrides(&mut module, overrides)?;
+        Ok(self.emit(module))
+    }
+
+    /// Builds the linked module with some of its entry points exposed under additional names. See
+    /// [`alias_entry_points`] for details.
+    pub fn build_with_entry_aliases<'a>(
+        &mut self,
+        modules: impl IntoIterator<Item = ModuleHandle>,
+        aliases: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<String, EntryPointAliasError> {
+        let mut module = self.linker.shake_module(modules);
+        alias_entry_points(&mut module, aliases)?;
+        Ok(self.emit(module))
+    }
+
+    fn emit(&mut self, mut module: naga::Module) -> String {
+        if self.fold_constants {
+            fold_constants(&mut module);
+        }
+
+        self.language.emit(&module)
+    }
+
+    /// Forgets the cached module for `path` and for every module which transitively imports it so
+    /// that the next [`parse`](Self::parse) re-reads them from disk. Modules unaffected by the
+    /// change stay cached and are reused when relinking. `path` must be canonicalized.
+    ///
+    /// Returns the invalidated paths, starting with `path`.
+    pub fn invalidate(&mut self, path: &Path) -> Vec<PathBuf> {
+        let mut invalidated = Vec::new();
+        let mut stack = vec![path.to_owned()];
+
+        while let Some(path) = stack.pop() {
+            if self.files.remove(&path).is_none() {
+                continue;
+            }
+
+            // The edited source may no longer import the same modules so forget the edges it
+            // registered. They're added back when it's parsed again.
+            for import in self.imports.remove(&path).into_iter().flatten() {
+                let Some(dependents) = self.dependents.get_mut(&import) else {
+                    continue;
+                };
+
+                dependents.retain(|dependent| *dependent != path);
+                if dependents.is_empty() {
+                    self.dependents.remove(&import);
+                }
+            }
+
+            stack.extend(self.dependents.remove(&path).into_iter().flatten());
+            invalidated.push(path);
+        }
+
+        invalidated
     }
 
     fn ensure_imported(
@@ -305,7 +398,20 @@ impl Session {
         });
 
         // Ensure that all its source files are imported.
+        me.imports.insert(
+            path.to_owned(),
+            directives
+                .iter()
+                .map(|directive| directive.abs_path.clone())
+                .collect(),
+        );
+
         for directive in &mut directives {
+            let dependents = me.dependents.entry(directive.abs_path.clone()).or_default();
+            if !dependents.iter().any(|dependent| dependent == path) {
+                dependents.push(path.to_owned());
+            }
+
             directive.module = me.ensure_imported(diag, Some(directive.span), &directive.abs_path);
         }
 
@@ -383,3 +489,80 @@ impl Session {
         Some(module)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalidated_modules_relink_against_cached_deps() {
+        let dir =
+            std::env::temp_dir().join(format!("wgsl-link-relink-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let main = dir.join("main.wgsl");
+        let dep = dir.join("dep.wgsl");
+
+        let write_main = |entry: &str| {
+            fs::write(
+                &main,
+                format!(
+                    "//#use helper in \"dep.wgsl\"\n\
+                     @fragment fn {entry}() -> @location(0) vec4f {{ return vec4f(helper()); }}\n"
+                ),
+            )
+            .unwrap();
+        };
+
+        fs::write(&dep, "fn helper() -> f32 { return 1.0; }\n").unwrap();
+        write_main("fs_first");
+
+        let mut sess = Session::new(Wgsl::default());
+        let module = sess.parse(&main).unwrap();
+        let first = sess.build([module]);
+        assert!(first.contains("fs_first"));
+
+        let cached_dep = |sess: &Session| match sess.files.get(&dep) {
+            Some(ModuleLoadStatus::Loaded(module)) => Some(*module),
+            _ => None,
+        };
+        let dep_module = cached_dep(&sess).unwrap();
+
+        // Editing the entry module only relinks it; its dependency is reused as-is.
+        write_main("fs_second");
+        assert_eq!(sess.invalidate(&main), std::slice::from_ref(&main));
+
+        let module = sess.parse(&main).unwrap();
+        assert!(sess.build([module]).contains("fs_second"));
+        assert_eq!(cached_dep(&sess), Some(dep_module));
+
+        // A broken edit fails to parse, leaving the caller with its last good build...
+        fs::write(&main, "@fragment fn fs_broken( {").unwrap();
+        sess.invalidate(&main);
+        assert!(sess.parse(&main).is_err());
+
+        // ...and fixing it recovers.
+        write_main("fs_third");
+        sess.invalidate(&main);
+        let module = sess.parse(&main).unwrap();
+        assert!(sess.build([module]).contains("fs_third"));
+
+        // Editing a dependency invalidates everything which imports it.
+        assert_eq!(sess.invalidate(&dep), [dep.clone(), main.clone()]);
+
+        // Once an edit drops an import, editing the former dependency leaves the module alone.
+        sess.parse(&main).unwrap();
+        fs::write(
+            &main,
+            "@fragment fn fs_fourth() -> @location(0) vec4f { return vec4f(1.0); }\n",
+        )
+        .unwrap();
+        assert_eq!(sess.invalidate(&main), std::slice::from_ref(&main));
+
+        let module = sess.parse(&main).unwrap();
+        assert!(sess.build([module]).contains("fs_fourth"));
+        assert_eq!(sess.invalidate(&dep), std::slice::from_ref(&dep));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::{error::Error, fmt};
+
+use crucible_utils::hash::FxHashSet;
+
+// === EntryPointAliasError === //
+
+#[derive(Debug, Clone)]
+pub enum EntryPointAliasError {
+    UnknownEntryPoint(String),
+    NameCollision(String),
+}
+
+impl fmt::Display for EntryPointAliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownEntryPoint(name) => {
+                write!(
+                    f,
+                    "linked module does not declare an entry point named {name:?}"
+                )
+            }
+            Self::NameCollision(name) => write!(
+                f,
+                "entry point alias {name:?} collides with an existing symbol in the linked module"
+            ),
+        }
+    }
+}
+
+impl Error for EntryPointAliasError {}
+
+// === Aliasing === //
+
+/// Exposes each entry point `original` in `module` under the additional name `alias`, leaving the
+/// original in place. This lets several pipelines specialize the same entry point (e.g. through
+/// [`inject_overrides`](super::overrides::inject_overrides)) without duplicating its source.
+///
+/// Aliases must not collide with any named type, constant, global, function, or entry point in
+/// the module, including other aliases.
+pub fn alias_entry_points<'a>(
+    module: &mut naga::Module,
+    aliases: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<(), EntryPointAliasError> {
+    let mut taken = FxHashSet::default();
+    taken.extend(module.types.iter().filter_map(|(_, ty)| ty.name.clone()));
+    taken.extend(module.constants.iter().filter_map(|(_, c)| c.name.clone()));
+    taken.extend(module.overrides.iter().filter_map(|(_, o)| o.name.clone()));
+    taken.extend(
+        module
+            .global_variables
+            .iter()
+            .filter_map(|(_, g)| g.name.clone()),
+    );
+    taken.extend(module.functions.iter().filter_map(|(_, f)| f.name.clone()));
+    taken.extend(module.entry_points.iter().map(|entry| entry.name.clone()));
+
+    for (original, alias) in aliases {
+        let Some(entry) = module
+            .entry_points
+            .iter()
+            .find(|entry| entry.name == original)
+        else {
+            return Err(EntryPointAliasError::UnknownEntryPoint(
+                original.to_string(),
+            ));
+        };
+
+        if !taken.insert(alias.to_string()) {
+            return Err(EntryPointAliasError::NameCollision(alias.to_string()));
+        }
+
+        let mut entry = entry.clone();
+        entry.name = alias.to_string();
+        entry.function.name = Some(alias.to_string());
+        module.entry_points.push(entry);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        driver::session::{Language, Wgsl},
+        module::linker::{ImportStubs, ModuleLinker},
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "
+        fn shade() -> vec4f {
+            return vec4f(1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4f {
+            return shade();
+        }
+    ";
+
+    fn link() -> naga::Module {
+        let mut linker = ModuleLinker::new();
+        let module = naga::front::wgsl::parse_str(SOURCE).unwrap();
+        let module = linker.link(module, &ImportStubs::empty());
+        linker.shake_module([module])
+    }
+
+    #[test]
+    fn aliases_entry_points() {
+        let mut module = link();
+        alias_entry_points(&mut module, [("fs_main", "fs_main_shadow")]).unwrap();
+
+        let output = Wgsl::default().emit(&module);
+        assert!(output.contains("fn fs_main("));
+        assert!(output.contains("fn fs_main_shadow("));
+    }
+
+    #[test]
+    fn rejects_colliding_aliases() {
+        let mut module = link();
+        let err = alias_entry_points(&mut module, [("fs_main", "shade")]);
+        assert!(matches!(err, Err(EntryPointAliasError::NameCollision(_))));
+
+        let err = alias_entry_points(&mut module, [("fs_missing", "fs_other")]);
+        assert!(matches!(
+            err,
+            Err(EntryPointAliasError::UnknownEntryPoint(_))
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+use naga::{BinaryOperator as Bo, Literal as Lit, UnaryOperator as Uo};
+
+// === Folding === //
+
+/// Evaluates unary and binary operations whose operands are all literals, replacing them with their
+/// result. This is mostly useful after [`inject_overrides`](super::overrides::inject_overrides),
+/// which can leave expressions like `4 * 2` lying around in function bodies.
+///
+/// Folding is conservative: only concrete `i32`, `u32`, `f32`, and `bool` operations are folded, and
+/// operations which could overflow, divide by zero, produce a non-finite value, or which depend on
+/// the backend's float rounding behavior are left as-is.
+pub fn fold_constants(module: &mut naga::Module) {
+    fold_arena(&mut module.global_expressions);
+
+    for (_, func) in module.functions.iter_mut() {
+        fold_function(func);
+    }
+
+    for entry in &mut module.entry_points {
+        fold_function(&mut entry.function);
+    }
+}
+
+fn fold_function(func: &mut naga::Function) {
+    if fold_arena(&mut func.expressions) {
+        // Literals are never emitted so we have to remove the folded expressions from the emit
+        // ranges covering them.
+        strip_literal_emits(&mut func.body, &func.expressions);
+    }
+}
+
+fn fold_arena(exprs: &mut naga::Arena<naga::Expression>) -> bool {
+    let mut folded_any = false;
+
+    // Expressions may only reference expressions which come before them in the arena so folding in
+    // arena order lets folds cascade through nested operations.
+    let handles = exprs.iter().map(|(handle, _)| handle).collect::<Vec<_>>();
+
+    for handle in handles {
+        let literal = |operand| match exprs[operand] {
+            naga::Expression::Literal(lit) => Some(lit),
+            _ => None,
+        };
+
+        let folded = match exprs[handle] {
+            naga::Expression::Unary { op, expr } => literal(expr).and_then(|v| fold_unary(op, v)),
+            naga::Expression::Binary { op, left, right } => literal(left)
+                .zip(literal(right))
+                .and_then(|(lhs, rhs)| fold_binary(op, lhs, rhs)),
+            _ => None,
+        };
+
+        if let Some(folded) = folded {
+            exprs[handle] = naga::Expression::Literal(folded);
+            folded_any = true;
+        }
+    }
+
+    folded_any
+}
+
+fn fold_unary(op: Uo, value: Lit) -> Option<Lit> {
+    Some(match (op, value) {
+        (Uo::Negate, Lit::I32(v)) => Lit::I32(v.checked_neg()?),
+        (Uo::Negate, Lit::F32(v)) => Lit::F32(-v),
+        (Uo::LogicalNot, Lit::Bool(v)) => Lit::Bool(!v),
+        (Uo::BitwiseNot, Lit::I32(v)) => Lit::I32(!v),
+        (Uo::BitwiseNot, Lit::U32(v)) => Lit::U32(!v),
+        _ => return None,
+    })
+}
+
+fn fold_binary(op: Bo, lhs: Lit, rhs: Lit) -> Option<Lit> {
+    macro_rules! fold_int {
+        ($variant:ident, $lhs:expr, $rhs:expr) => {
+            match op {
+                Bo::Add => Lit::$variant($lhs.checked_add($rhs)?),
+                Bo::Subtract => Lit::$variant($lhs.checked_sub($rhs)?),
+                Bo::Multiply => Lit::$variant($lhs.checked_mul($rhs)?),
+                Bo::Divide => Lit::$variant($lhs.checked_div($rhs)?),
+                Bo::Modulo => Lit::$variant($lhs.checked_rem($rhs)?),
+                Bo::And => Lit::$variant($lhs & $rhs),
+                Bo::ExclusiveOr => Lit::$variant($lhs ^ $rhs),
+                Bo::InclusiveOr => Lit::$variant($lhs | $rhs),
+                _ => fold_comparison(op, $lhs, $rhs)?,
+            }
+        };
+    }
+
+    Some(match (lhs, rhs) {
+        (Lit::I32(lhs), Lit::I32(rhs)) => fold_int!(I32, lhs, rhs),
+        (Lit::U32(lhs), Lit::U32(rhs)) => fold_int!(U32, lhs, rhs),
+        (Lit::F32(lhs), Lit::F32(rhs)) => match op {
+            // These are all correctly rounded in both Rust and WGSL. Float modulo is not.
+            Bo::Add => Lit::F32(finite(lhs + rhs)?),
+            Bo::Subtract => Lit::F32(finite(lhs - rhs)?),
+            Bo::Multiply => Lit::F32(finite(lhs * rhs)?),
+            Bo::Divide => Lit::F32(finite(lhs / rhs)?),
+            Bo::Modulo => return None,
+            _ => fold_comparison(op, lhs, rhs)?,
+        },
+        (Lit::Bool(lhs), Lit::Bool(rhs)) => match op {
+            Bo::And | Bo::LogicalAnd => Lit::Bool(lhs & rhs),
+            Bo::InclusiveOr | Bo::LogicalOr => Lit::Bool(lhs | rhs),
+            Bo::Equal => Lit::Bool(lhs == rhs),
+            Bo::NotEqual => Lit::Bool(lhs != rhs),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+fn fold_comparison<T: PartialOrd>(op: Bo, lhs: T, rhs: T) -> Option<Lit> {
+    Some(Lit::Bool(match op {
+        Bo::Equal => lhs == rhs,
+        Bo::NotEqual => lhs != rhs,
+        Bo::Less => lhs < rhs,
+        Bo::LessEqual => lhs <= rhs,
+        Bo::Greater => lhs > rhs,
+        Bo::GreaterEqual => lhs >= rhs,
+        _ => return None,
+    }))
+}
+
+fn finite(value: f32) -> Option<f32> {
+    value.is_finite().then_some(value)
+}
+
+// === Emit Fixup === //
+
+fn strip_literal_emits(block: &mut naga::Block, exprs: &naga::Arena<naga::Expression>) {
+    let old = std::mem::take(block);
+
+    for (mut stmt, span) in old.span_into_iter() {
+        match &mut stmt {
+            naga::Statement::Emit(range) => {
+                let mut push_emit = |indices: std::ops::Range<u32>| {
+                    if !indices.is_empty() {
+                        let range = naga::Range::from_index_range(indices, exprs);
+                        block.push(naga::Statement::Emit(range), span);
+                    }
+                };
+
+                let indices = range.index_range();
+                let mut start = indices.start;
+
+                for (index, handle) in indices.clone().zip(range.clone()) {
+                    if matches!(exprs[handle], naga::Expression::Literal(_)) {
+                        push_emit(start..index);
+                        start = index + 1;
+                    }
+                }
+
+                push_emit(start..indices.end);
+                continue;
+            }
+            naga::Statement::Block(inner) => strip_literal_emits(inner, exprs),
+            naga::Statement::If { accept, reject, .. } => {
+                strip_literal_emits(accept, exprs);
+                strip_literal_emits(reject, exprs);
+            }
+            naga::Statement::Switch { cases, .. } => {
+                for case in cases {
+                    strip_literal_emits(&mut case.body, exprs);
+                }
+            }
+            naga::Statement::Loop {
+                body, continuing, ..
+            } => {
+                strip_literal_emits(body, exprs);
+                strip_literal_emits(continuing, exprs);
+            }
+            _ => {}
+        }
+
+        block.push(stmt, span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        driver::session::{Language, Wgsl},
+        module::{
+            linker::{ImportStubs, ModuleLinker},
+            overrides::inject_overrides,
+        },
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "
+        override sample_count: i32 = 4;
+
+        @fragment
+        fn fs_main() -> @location(0) vec4f {
+            var total = 0;
+            for (var i = 0; i < sample_count * 2 + 1; i++) {
+                total += i;
+            }
+            return vec4f(f32(total) / f32(sample_count * 8 / 0));
+        }
+    ";
+
+    #[test]
+    fn folds_injected_overrides() {
+        let mut linker = ModuleLinker::new();
+        let module = naga::front::wgsl::parse_str(SOURCE).unwrap();
+        let module = linker.link(module, &ImportStubs::empty());
+        let mut module = linker.shake_module([module]);
+
+        inject_overrides(&mut module, [("sample_count", naga::Literal::I32(3))]).unwrap();
+        fold_constants(&mut module);
+
+        let output = Wgsl::default().emit(&module);
+        assert!(output.contains("7i"), "{output}");
+        assert!(!output.contains("3i * 2i"), "{output}");
+
+        // Division by zero is left for the driver to complain about.
+        assert!(output.contains("24i / 0i"), "{output}");
+    }
+}
@@ -484,6 +484,29 @@ impl ModuleLinker {
         &self.module
     }
 
+    /// Fetches the layout of the struct exported by `module` under the (un-mangled) `name`.
+    /// Returns `None` if the module has no such export or if it isn't a struct.
+    pub fn struct_layout(&self, module: ModuleHandle, name: &str) -> Option<StructLayout> {
+        let export = self.files[module].exports.get(name)?;
+        if export.kind != ExportKind::Types {
+            return None;
+        }
+
+        let naga::TypeInner::Struct { members, span } =
+            &self.module.types[export.raw.as_typed::<naga::Type>()].inner
+        else {
+            return None;
+        };
+
+        Some(StructLayout {
+            size: *span,
+            fields: members
+                .iter()
+                .map(|member| (member.name.clone().unwrap_or_default(), member.offset))
+                .collect(),
+        })
+    }
+
     pub fn shake_module(&self, modules: impl IntoIterator<Item = ModuleHandle>) -> naga::Module {
         // Create a shaker for each arena.
         let types = NagaUniqueArenaShaker::new(&self.module.types).wrap(());
@@ -643,6 +666,17 @@ impl ModuleLinker {
     }
 }
 
+/// The layout of a struct type as it appears in the linked module. Mangling only ever renames the
+/// type itself so this is exactly the layout of the struct in the source file which defined it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLayout {
+    /// The size of the struct in bytes, including trailing padding.
+    pub size: u32,
+
+    /// Each field's name and byte offset in declaration order.
+    pub fields: Vec<(String, u32)>,
+}
+
 #[derive(Debug)]
 pub struct ImportStubs {
     module: naga::Module,
@@ -744,3 +778,142 @@ impl NameMangler {
         name
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::session::{Language, Wgsl};
+
+    use super::*;
+
+    #[test]
+    fn selective_imports_only_expose_imported_symbols() {
+        let mut linker = ModuleLinker::new();
+        let dep = naga::front::wgsl::parse_str(
+            "
+            fn foo() -> f32 { return 1.0; }
+            fn bar() -> f32 { return 2.0; }
+            ",
+        )
+        .unwrap();
+        let dep = linker.link(dep, &ImportStubs::empty());
+
+        let stubs = linker.gen_stubs(
+            [LinkerImport {
+                file: dep,
+                orig_name: "foo",
+                rename_to: Some("baz"),
+                meta: (),
+            }],
+            |err| panic!("{err:?}"),
+        );
+        let stubs = stubs.apply_names_to_stub(Wgsl::default().emit(stubs.module()));
+
+        assert!(stubs.contains("fn baz("));
+        assert!(!stubs.contains("foo"));
+        assert!(!stubs.contains("bar"));
+
+        // The alias can be used but the un-imported function cannot.
+        let uses = |body: &str| {
+            naga::front::wgsl::parse_str(&format!(
+                "fn user() -> f32 {{ return {body}(); }}\n{stubs}"
+            ))
+        };
+        assert!(uses("baz").is_ok());
+        assert!(uses("bar").is_err());
+    }
+
+    #[test]
+    fn mangling_preserves_struct_layouts() {
+        const DEP: &str = "
+            struct Light {
+                color: vec3<f32>,
+                intensity: f32,
+                direction: vec2<f32>,
+                range: vec4<f32>,
+            }
+
+            fn unlit() -> Light { return Light(vec3(0.0), 0.0, vec2(0.0), vec4(0.0)); }
+            ";
+
+        // Compute the layout naga gives the struct when compiled on its own.
+        let source = naga::front::wgsl::parse_str(DEP).unwrap();
+        let (_, source) = source
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some("Light"))
+            .unwrap();
+        let naga::TypeInner::Struct { members, span } = &source.inner else {
+            unreachable!();
+        };
+        let expected = StructLayout {
+            size: *span,
+            fields: members
+                .iter()
+                .map(|member| (member.name.clone().unwrap(), member.offset))
+                .collect(),
+        };
+        assert_eq!(
+            expected
+                .fields
+                .iter()
+                .map(|(_, offset)| *offset)
+                .collect::<Vec<_>>(),
+            [0, 12, 16, 32],
+        );
+
+        // Link the struct and import it, under a new name, into another module.
+        let mut linker = ModuleLinker::new();
+        let dep = linker.link(
+            naga::front::wgsl::parse_str(DEP).unwrap(),
+            &ImportStubs::empty(),
+        );
+
+        let stubs = linker.gen_stubs(
+            [LinkerImport {
+                file: dep,
+                orig_name: "Light",
+                rename_to: Some("Lamp"),
+                meta: (),
+            }],
+            |err| panic!("{err:?}"),
+        );
+        let user = naga::front::wgsl::parse_str(&format!(
+            "fn brightness(lamp: Lamp) -> f32 {{ return lamp.intensity; }}\n{}",
+            stubs.apply_names_to_stub(Wgsl::default().emit(stubs.module())),
+        ))
+        .unwrap();
+        let user = linker.link(user, &stubs);
+
+        // The struct was mangled in the linked module...
+        assert!(linker
+            .full_module()
+            .types
+            .iter()
+            .any(|(_, ty)| ty.name.as_deref().is_some_and(|name| {
+                try_demangle(name).is_some_and(|(name, _)| name == "Light")
+            })));
+
+        // ...but its layout is untouched, both in the linker and in the shaken output.
+        assert_eq!(linker.struct_layout(dep, "Light"), Some(expected.clone()));
+        assert_eq!(linker.struct_layout(dep, "unlit"), None);
+        assert_eq!(linker.struct_layout(user, "Lamp"), None);
+
+        let shaken = linker.shake_module([user]);
+        let (_, shaken) = shaken
+            .types
+            .iter()
+            .find(|(_, ty)| ty.name.as_deref() == Some("Light"))
+            .unwrap();
+        let naga::TypeInner::Struct { members, span } = &shaken.inner else {
+            unreachable!();
+        };
+        assert_eq!(*span, expected.size);
+        assert_eq!(
+            members
+                .iter()
+                .map(|member| (member.name.clone().unwrap(), member.offset))
+                .collect::<Vec<_>>(),
+            expected.fields,
+        );
+    }
+}
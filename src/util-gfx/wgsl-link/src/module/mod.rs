@@ -1,5 +1,8 @@
+pub mod entry_points;
+pub mod fold;
 pub mod linker;
 pub mod map;
 pub mod map_naga;
 pub mod merge;
+pub mod overrides;
 pub mod shake;
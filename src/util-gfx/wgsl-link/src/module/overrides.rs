@@ -0,0 +1,164 @@
+use std::{error::Error, fmt};
+
+use crucible_utils::hash::FxHashMap;
+
+// === OverrideError === //
+
+#[derive(Debug, Clone)]
+pub enum OverrideError {
+    UnknownOverride(String),
+    TypeMismatch {
+        name: String,
+        expected: naga::Scalar,
+        found: naga::Scalar,
+    },
+    MissingValue(String),
+}
+
+impl fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOverride(name) => {
+                write!(f, "linked module does not declare an override named {name:?}")
+            }
+            Self::TypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "override {name:?} is declared as {expected:?} but was given a value of type {found:?}"
+            ),
+            Self::MissingValue(name) => write!(
+                f,
+                "override {name:?} has neither an injected value nor a literal default"
+            ),
+        }
+    }
+}
+
+impl Error for OverrideError {}
+
+// === Injection === //
+
+/// Injects literal values for the overrides declared in `module`, replacing every use of each
+/// override with its value and removing the declarations themselves.
+///
+/// Overrides not in `values` fall back to their default value, which must be a literal. This lets
+/// us emit the module with backends which don't support pipeline-overridable constants.
+pub fn inject_overrides<'a>(
+    module: &mut naga::Module,
+    values: impl IntoIterator<Item = (&'a str, naga::Literal)>,
+) -> Result<(), OverrideError> {
+    let mut values = values.into_iter().collect::<FxHashMap<_, _>>();
+
+    // Determine the value of each override.
+    let mut resolved = FxHashMap::default();
+
+    for (handle, ov) in module.overrides.iter() {
+        let name = ov.name.as_deref().unwrap_or_default();
+
+        let value = match values.remove(name) {
+            Some(value) => {
+                let naga::TypeInner::Scalar(expected) = module.types[ov.ty].inner else {
+                    unreachable!("overrides must have a scalar type");
+                };
+
+                if value.scalar() != expected {
+                    return Err(OverrideError::TypeMismatch {
+                        name: name.to_string(),
+                        expected,
+                        found: value.scalar(),
+                    });
+                }
+
+                value
+            }
+            None => match ov.init.map(|init| &module.global_expressions[init]) {
+                Some(&naga::Expression::Literal(value)) => value,
+                _ => return Err(OverrideError::MissingValue(name.to_string())),
+            },
+        };
+
+        resolved.insert(handle, value);
+    }
+
+    if let Some(&name) = values.keys().next() {
+        return Err(OverrideError::UnknownOverride(name.to_string()));
+    }
+
+    // Replace every reference to the overrides with their values. Both `Override` and `Literal`
+    // expressions are never emitted so we can swap them in-place without touching the function
+    // bodies.
+    let replace = |expr: &mut naga::Expression| {
+        if let naga::Expression::Override(handle) = *expr {
+            *expr = naga::Expression::Literal(resolved[&handle]);
+        }
+    };
+
+    for (_, expr) in module.global_expressions.iter_mut() {
+        replace(expr);
+    }
+
+    for (_, func) in module.functions.iter_mut() {
+        for (_, expr) in func.expressions.iter_mut() {
+            replace(expr);
+        }
+    }
+
+    for entry in &mut module.entry_points {
+        for (_, expr) in entry.function.expressions.iter_mut() {
+            replace(expr);
+        }
+    }
+
+    module.overrides = naga::Arena::new();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        driver::session::{Language, Wgsl},
+        module::linker::{ImportStubs, ModuleLinker},
+    };
+
+    use super::*;
+
+    const SOURCE: &str = "
+        override ao_enabled: bool = false;
+
+        @fragment
+        fn fs_main() -> @location(0) vec4f {
+            if ao_enabled {
+                return vec4f(1.0);
+            }
+            return vec4f(0.0);
+        }
+    ";
+
+    fn link() -> naga::Module {
+        let mut linker = ModuleLinker::new();
+        let module = naga::front::wgsl::parse_str(SOURCE).unwrap();
+        let module = linker.link(module, &ImportStubs::empty());
+        linker.shake_module([module])
+    }
+
+    #[test]
+    fn injects_override_values() {
+        let mut module = link();
+        inject_overrides(&mut module, [("ao_enabled", naga::Literal::Bool(true))]).unwrap();
+
+        let output = Wgsl::default().emit(&module);
+        assert!(!output.contains("override"));
+        assert!(output.contains("true"));
+    }
+
+    #[test]
+    fn rejects_mismatched_override_types() {
+        let mut module = link();
+        let err = inject_overrides(&mut module, [("ao_enabled", naga::Literal::U32(1))]);
+        assert!(matches!(err, Err(OverrideError::TypeMismatch { .. })));
+    }
+}
@@ -1,11 +1,17 @@
 #![allow(clippy::missing_safety_doc)]
 
+#[cfg(feature = "debug-registry")]
+use std::panic::Location;
 use std::{
+    any::type_name,
     cell::Cell,
     collections::hash_map,
+    error::Error,
+    fmt,
     marker::PhantomData,
+    mem,
     ops::{Deref, DerefMut},
-    ptr::NonNull,
+    ptr::{self, NonNull},
     thread::LocalKey,
 };
 
@@ -16,8 +22,12 @@ use bevy_ecs::{
     component::{Component, ComponentId, Tick},
     entity::Entity,
     event::{Event, Events},
+    query::With,
     removal_detection::RemovedComponents,
-    system::{Commands, In, Res, ResMut, Resource, RunSystemOnce, SystemMeta, SystemParam},
+    system::{
+        Commands, In, NonSend, NonSendMut, Res, ResMut, Resource, RunSystemOnce, SystemMeta,
+        SystemParam,
+    },
     world::{unsafe_world_cell::UnsafeWorldCell, World},
 };
 use crucible_utils::newtypes::{Arena, Handle};
@@ -30,6 +40,8 @@ use rustc_hash::FxHashMap;
 pub struct RandomArena<T> {
     pub arena: Arena<(Entity, T)>,
     pub map: FxHashMap<Entity, Obj<T>>,
+    #[cfg(feature = "debug-registry")]
+    pub alloc_sites: FxHashMap<Entity, &'static Location<'static>>,
 }
 
 impl<T> Default for RandomArena<T> {
@@ -37,10 +49,38 @@ impl<T> Default for RandomArena<T> {
         Self {
             arena: Arena::default(),
             map: FxHashMap::default(),
+            #[cfg(feature = "debug-registry")]
+            alloc_sites: FxHashMap::default(),
         }
     }
 }
 
+impl<T> RandomArena<T> {
+    /// Ensures that at least `additional` more components can be inserted without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.arena.reserve(additional);
+        self.map.reserve(additional);
+    }
+
+    /// Moves the live components to the front of the arena, which improves iteration locality after
+    /// heavy churn. Returns the new handle of every moved component, keyed by its old handle.
+    ///
+    /// This only updates the arena's entity map. Use [`RandomComponent::compact`] to update the
+    /// `Obj` components of the owning entities as well.
+    pub fn compact(&mut self) -> FxHashMap<Obj<T>, Obj<T>> {
+        let mut moved = FxHashMap::default();
+        self.arena.compact(|old, new| {
+            moved.insert(Obj(old), Obj(new));
+        });
+
+        for &obj in moved.values() {
+            self.map.insert(self.arena[obj.0].0, obj);
+        }
+
+        moved
+    }
+}
+
 // === RandomAccess === //
 
 mod sealed {
@@ -130,21 +170,102 @@ unsafe impl<'w2, 's2, L: RandomResourceList> SystemParam for RandomAccessInner<'
 
 impl<'w, 's, L: RandomResourceList> RandomAccess<'w, 's, L> {
     pub fn provide<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let state = self.inner.state;
+        unsafe { self.provide_as::<L, R>(state, f) }
+    }
+
+    /// Like [`provide`](Self::provide) but only gives `f` access to the resources in the
+    /// sub-list `S`. This is useful for handing a helper the narrowest set of borrows it needs.
+    ///
+    /// Every resource in `S` must also be in `L`, although a `&mut T` in `L` can be narrowed
+    /// down to a `&T`:
+    ///
+    /// ```
+    /// # use std::marker::PhantomData;
+    /// # use bevy_autoken::{random_component, RandomAccess};
+    /// # struct Health(u32);
+    /// # struct Transform(u32);
+    /// # random_component!(Health, Transform);
+    /// fn system(mut rand: RandomAccess<(&mut Health, &mut Transform)>) {
+    ///     rand.provide_subset(|_: PhantomData<&Health>| {
+    ///         // Only `Health` can be read in here.
+    ///     });
+    /// }
+    /// ```
+    ///
+    /// Asking for a resource which `L` doesn't provide is rejected at compile time:
+    ///
+    /// ```compile_fail
+    /// # use std::marker::PhantomData;
+    /// # use bevy_autoken::{random_component, RandomAccess};
+    /// # struct Health(u32);
+    /// # struct Transform(u32);
+    /// # random_component!(Health, Transform);
+    /// fn system(mut rand: RandomAccess<&Health>) {
+    ///     rand.provide_subset(|_: PhantomData<(&Health, &Transform)>| {});
+    /// }
+    /// ```
+    pub fn provide_subset<S, D, R>(&mut self, f: impl FnOnce(PhantomData<S>) -> R) -> R
+    where
+        S: RandomResourceSubsetOf<L, D>,
+    {
+        let state = S::project_state(self.inner.state);
+        unsafe { self.provide_as::<S, R>(&state, || f(PhantomData)) }
+    }
+
+    /// Returns a handle which narrows the resources available within a [`provide`](Self::provide)
+    /// scope of this access. Unlike [`provide_subset`](Self::provide_subset), the handle doesn't
+    /// borrow the access so it can be used from inside the `provide` closure:
+    ///
+    /// ```
+    /// # use std::marker::PhantomData;
+    /// # use bevy_autoken::{random_component, RandomAccess};
+    /// # struct Health(u32);
+    /// # struct Transform(u32);
+    /// # random_component!(Health, Transform);
+    /// fn system(mut rand: RandomAccess<(&mut Health, &mut Transform)>) {
+    ///     let narrower = rand.narrower();
+    ///
+    ///     rand.provide(|| {
+    ///         narrower.provide_subset(|_: PhantomData<&Health>| {
+    ///             // Only `Health` can be read in here.
+    ///         });
+    ///     });
+    /// }
+    /// ```
+    pub fn narrower(&self) -> RandomNarrower<'w, 's, L> {
+        RandomNarrower {
+            world: self.inner.world,
+            state: self.inner.state,
+        }
+    }
+
+    unsafe fn provide_as<S: RandomResourceList, R>(
+        &mut self,
+        state: &S::ParamState,
+        f: impl FnOnce() -> R,
+    ) -> R {
         unsafe {
-            autoken::absorb::<L::TokensMut, R>(|| {
-                let new_snap = L::tls_snapshot_from_world(self.inner.state, self.inner.world);
-                let _guard = scopeguard::guard(L::fetch_tls_snapshot(), |snap| {
-                    L::apply_tls_snapshot(&snap);
+            autoken::absorb::<S::TokensMut, R>(|| {
+                #[cfg(feature = "trace-provide")]
+                let _profile = ProvideProfile::start(S::LEN);
+
+                let old_snap = S::fetch_tls_snapshot();
+                S::assert_not_provided(&old_snap);
+
+                let new_snap = S::tls_snapshot_from_world(state, self.inner.world);
+                let _guard = scopeguard::guard(old_snap, |snap| {
+                    S::apply_tls_snapshot(&snap);
                 });
-                L::apply_tls_snapshot(&new_snap);
+                S::apply_tls_snapshot(&new_snap);
 
                 fn dummy<'a, S: TokenSet>() -> &'a () {
                     autoken::tie!('a => set S);
                     &()
                 }
 
-                let _all = dummy::<L::TokensMut>();
-                autoken::absorb::<L::Tokens, R>(|| {
+                let _all = dummy::<S::TokensMut>();
+                autoken::absorb::<S::Tokens, R>(|| {
                     CommandsCap::provide(&mut self.commands, || {
                         WorldCap::provide(&self.inner.world, f)
                     })
@@ -154,6 +275,82 @@ impl<'w, 's, L: RandomResourceList> RandomAccess<'w, 's, L> {
     }
 }
 
+/// A handle to narrow the resources provided by a [`RandomAccess`] from within one of its
+/// [`provide`](RandomAccess::provide) scopes. See [`RandomAccess::narrower`].
+#[derive_where(Copy, Clone)]
+pub struct RandomNarrower<'w, 's, L: RandomResourceList> {
+    world: UnsafeWorldCell<'w>,
+    state: &'s L::ParamState,
+}
+
+impl<'w, 's, L: RandomResourceList> RandomNarrower<'w, 's, L> {
+    /// Like [`RandomAccess::provide_subset`] but called from within a
+    /// [`provide`](RandomAccess::provide) scope of the access. Every resource of the access which
+    /// isn't in `S` is hidden from `f` until it returns.
+    ///
+    /// Panics if called outside of such a scope.
+    pub fn provide_subset<S, D, R>(self, f: impl FnOnce(PhantomData<S>) -> R) -> R
+    where
+        S: RandomResourceSubsetOf<L, D>,
+    {
+        let outer_snap = L::fetch_tls_snapshot();
+        let provided_snap = unsafe { L::tls_snapshot_from_world(self.state, self.world) };
+        assert!(
+            outer_snap == provided_snap,
+            "RandomNarrower::provide_subset called outside of its access's provide scope",
+        );
+
+        let state = S::project_state(self.state);
+
+        unsafe {
+            let inner_snap = S::tls_snapshot_from_world(&state, self.world);
+            let _guard = scopeguard::guard(outer_snap, |snap| {
+                L::apply_tls_snapshot(&snap);
+            });
+            L::apply_tls_snapshot(&L::empty_tls_snapshot());
+            S::apply_tls_snapshot(&inner_snap);
+
+            fn dummy<'a, S: TokenSet>() -> &'a () {
+                autoken::tie!('a => set S);
+                &()
+            }
+
+            let _all = dummy::<L::TokensMut>();
+            autoken::absorb::<S::Tokens, R>(|| f(PhantomData))
+        }
+    }
+}
+
+#[cfg(feature = "trace-provide")]
+struct ProvideProfile {
+    span: tracing::span::EnteredSpan,
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "trace-provide")]
+impl ProvideProfile {
+    fn start(resources: usize) -> Self {
+        Self {
+            span: tracing::trace_span!(
+                "RandomAccess::provide",
+                resources,
+                held_ns = tracing::field::Empty,
+            )
+            .entered(),
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "trace-provide")]
+impl Drop for ProvideProfile {
+    fn drop(&mut self) {
+        // This is dropped after the previous TLS snapshot is restored so the restore is counted.
+        self.span
+            .record("held_ns", self.start.elapsed().as_nanos() as u64);
+    }
+}
+
 // === RandomComponentList === //
 
 pub type RandBorrowsMut<'a, T> = &'a mut Borrows<RandTokensOf<T>>;
@@ -172,13 +369,18 @@ pub unsafe trait RandomResourceList {
     /// The state of our [`RandomAccess`] system parameter.
     type ParamState: 'static + Copy + Send + Sync;
 
-    type TlsSnapshot: 'static + Copy;
+    type TlsSnapshot: 'static + Copy + PartialEq;
+
+    /// The number of resources in the list.
+    const LEN: usize;
 
     /// Fetches the set of [`ComponentId`]s that this component list, ensuring that the existing
-    /// system meta doesn't have any conflicting borrows with other systems.
+    /// system meta doesn't have any conflicting borrows with other systems and registering the
+    /// list's own borrows with it.
     fn get_param_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::ParamState;
 
-    /// Appends this set's resource set to the system metadata.
+    /// Appends this set's resource set to the system metadata if
+    /// [`get_param_state`](Self::get_param_state) didn't already do so.
     fn update_access_sets(
         state: &Self::ParamState,
         world: &mut World,
@@ -188,6 +390,13 @@ pub unsafe trait RandomResourceList {
     /// Fetch a snapshot of all previous arena TLS states.
     fn fetch_tls_snapshot() -> Self::TlsSnapshot;
 
+    /// Panics if a snapshot fetched with [`fetch_tls_snapshot`](Self::fetch_tls_snapshot) shows
+    /// that one of the list's resources is already being provided.
+    fn assert_not_provided(snap: &Self::TlsSnapshot);
+
+    /// The snapshot in which none of the list's resources are provided.
+    fn empty_tls_snapshot() -> Self::TlsSnapshot;
+
     /// Compute new snapshot from world resources.
     unsafe fn tls_snapshot_from_world(
         state: &Self::ParamState,
@@ -198,11 +407,22 @@ pub unsafe trait RandomResourceList {
     unsafe fn apply_tls_snapshot(snap: &Self::TlsSnapshot);
 }
 
+fn assert_not_provided<T: ?Sized>(is_unprovided: bool) {
+    // Restoring the snapshot of an inner scope would otherwise clobber the outer scope's pointer
+    // while its borrows are still live.
+    assert!(
+        is_unprovided,
+        "RandomAccess::provide for {} called re-entrantly",
+        type_name::<T>(),
+    );
+}
+
 unsafe impl<T: RandomComponent> RandomResourceList for &'_ T {
     type Tokens = autoken::Ref<RandomComponentToken<T>>;
     type TokensMut = autoken::Mut<RandomComponentToken<T>>;
     type ParamState = ComponentId;
     type TlsSnapshot = *mut RandomArena<T>;
+    const LEN: usize = 1;
 
     fn get_param_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::ParamState {
         // TODO: Use an alias-permitting technique
@@ -218,7 +438,7 @@ unsafe impl<T: RandomComponent> RandomResourceList for &'_ T {
         //
         // component_id
 
-        <Res<RandomArena<T>> as SystemParam>::init_state(world, system_meta)
+        T::Storage::init_access(world, system_meta, false)
     }
 
     fn update_access_sets(
@@ -246,20 +466,24 @@ unsafe impl<T: RandomComponent> RandomResourceList for &'_ T {
         unsafe { T::tls().get() }
     }
 
+    fn assert_not_provided(&snap: &Self::TlsSnapshot) {
+        assert_not_provided::<T>(snap.is_null());
+    }
+
+    fn empty_tls_snapshot() -> Self::TlsSnapshot {
+        ptr::null_mut()
+    }
+
     unsafe fn tls_snapshot_from_world(
         &state: &Self::ParamState,
         world: UnsafeWorldCell<'_>,
     ) -> Self::TlsSnapshot {
-        world
-            .get_resource_by_id(state)
-            .unwrap_or_else(|| {
-                panic!(
-                    "Random component never registered: {}",
-                    std::any::type_name::<T>()
-                )
-            })
-            .as_ptr()
-            .cast()
+        T::Storage::arena_from_world(state, world).unwrap_or_else(|| {
+            panic!(
+                "Random component never registered: {}",
+                std::any::type_name::<T>()
+            )
+        })
     }
 
     unsafe fn apply_tls_snapshot(&snap: &Self::TlsSnapshot) {
@@ -272,6 +496,7 @@ unsafe impl<T: RandomComponent> RandomResourceList for &'_ mut T {
     type TokensMut = autoken::Mut<RandomComponentToken<T>>;
     type ParamState = ComponentId;
     type TlsSnapshot = *mut RandomArena<T>;
+    const LEN: usize = 1;
 
     fn get_param_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::ParamState {
         // TODO: Use an alias-permitting technique
@@ -287,7 +512,7 @@ unsafe impl<T: RandomComponent> RandomResourceList for &'_ mut T {
         //
         // component_id
 
-        <ResMut<RandomArena<T>> as SystemParam>::init_state(world, system_meta)
+        T::Storage::init_access(world, system_meta, true)
     }
 
     fn update_access_sets(
@@ -315,20 +540,24 @@ unsafe impl<T: RandomComponent> RandomResourceList for &'_ mut T {
         unsafe { T::tls().get() }
     }
 
+    fn assert_not_provided(&snap: &Self::TlsSnapshot) {
+        assert_not_provided::<T>(snap.is_null());
+    }
+
+    fn empty_tls_snapshot() -> Self::TlsSnapshot {
+        ptr::null_mut()
+    }
+
     unsafe fn tls_snapshot_from_world(
         &state: &Self::ParamState,
         world: UnsafeWorldCell<'_>,
     ) -> Self::TlsSnapshot {
-        world
-            .get_resource_by_id(state)
-            .unwrap_or_else(|| {
-                panic!(
-                    "Random component never registered: {}",
-                    std::any::type_name::<T>()
-                )
-            })
-            .as_ptr()
-            .cast()
+        T::Storage::arena_from_world(state, world).unwrap_or_else(|| {
+            panic!(
+                "Random component never registered: {}",
+                std::any::type_name::<T>()
+            )
+        })
     }
 
     unsafe fn apply_tls_snapshot(&snap: &Self::TlsSnapshot) {
@@ -343,6 +572,7 @@ unsafe impl<T: RandomEvent> RandomResourceList for SendsEvent<T> {
     type TokensMut = autoken::Mut<RandomEventToken<T>>;
     type ParamState = ComponentId;
     type TlsSnapshot = *mut Events<T>;
+    const LEN: usize = 1;
 
     fn get_param_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::ParamState {
         // TODO: Use an alias-permitting technique
@@ -386,6 +616,14 @@ unsafe impl<T: RandomEvent> RandomResourceList for SendsEvent<T> {
         unsafe { T::tls().get() }
     }
 
+    fn assert_not_provided(&snap: &Self::TlsSnapshot) {
+        assert_not_provided::<SendsEvent<T>>(snap.is_null());
+    }
+
+    fn empty_tls_snapshot() -> Self::TlsSnapshot {
+        ptr::null_mut()
+    }
+
     unsafe fn tls_snapshot_from_world(
         &state: &Self::ParamState,
         world: UnsafeWorldCell<'_>,
@@ -407,6 +645,7 @@ unsafe impl RandomResourceList for () {
     type TokensMut = ();
     type ParamState = ();
     type TlsSnapshot = ();
+    const LEN: usize = 0;
 
     fn get_param_state(_world: &mut World, _system_meta: &mut SystemMeta) -> Self::ParamState {}
 
@@ -419,6 +658,10 @@ unsafe impl RandomResourceList for () {
 
     fn fetch_tls_snapshot() -> Self::TlsSnapshot {}
 
+    fn assert_not_provided(_snap: &Self::TlsSnapshot) {}
+
+    fn empty_tls_snapshot() -> Self::TlsSnapshot {}
+
     unsafe fn tls_snapshot_from_world(
         _state: &Self::ParamState,
         _world: UnsafeWorldCell<'_>,
@@ -436,6 +679,7 @@ macro_rules! impl_random_resource_list {
             type TokensMut = ($first::TokensMut, $($rest::TokensMut,)*);
             type ParamState = ($first::ParamState, $($rest::ParamState,)*);
             type TlsSnapshot = ($first::TlsSnapshot, $($rest::TlsSnapshot,)*);
+            const LEN: usize = $first::LEN $(+ $rest::LEN)*;
 
             fn get_param_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::ParamState {
                 ($first::get_param_state(world, system_meta), $($rest::get_param_state(world, system_meta),)*)
@@ -455,6 +699,16 @@ macro_rules! impl_random_resource_list {
                 ($first::fetch_tls_snapshot(), $($rest::fetch_tls_snapshot(),)*)
             }
 
+            #[allow(non_snake_case)]
+            fn assert_not_provided(($first, $($rest,)*): &Self::TlsSnapshot) {
+                $first::assert_not_provided($first);
+                $($rest::assert_not_provided($rest);)*
+            }
+
+            fn empty_tls_snapshot() -> Self::TlsSnapshot {
+                ($first::empty_tls_snapshot(), $($rest::empty_tls_snapshot(),)*)
+            }
+
             #[allow(non_snake_case)]
             unsafe fn tls_snapshot_from_world(($first, $($rest,)*): &Self::ParamState, world: UnsafeWorldCell<'_>,) -> Self::TlsSnapshot {
                 ($first::tls_snapshot_from_world($first, world), $($rest::tls_snapshot_from_world($rest, world),)*)
@@ -473,42 +727,322 @@ macro_rules! impl_random_resource_list {
 
 impl_random_resource_list!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12);
 
+// === RandomResourceSubsetOf === //
+
+/// Implemented by resource lists which provide the resource `T`. `D` disambiguates which element
+/// of the list provides it and is always inferred.
+pub unsafe trait RandomResourceListHas<T: RandomResourceList, D>:
+    RandomResourceList
+{
+    fn project_state(state: &Self::ParamState) -> T::ParamState;
+}
+
+/// Implemented by resource lists whose every resource is provided by the list `L`. `D` is always
+/// inferred.
+pub unsafe trait RandomResourceSubsetOf<L: RandomResourceList, D>:
+    RandomResourceList
+{
+    fn project_state(state: &L::ParamState) -> Self::ParamState;
+}
+
+#[allow(dead_code)]
+mod disambiguators {
+    pub struct Exact;
+    pub struct Downgrade;
+
+    pub struct T1;
+    pub struct T2;
+    pub struct T3;
+    pub struct T4;
+    pub struct T5;
+    pub struct T6;
+    pub struct T7;
+    pub struct T8;
+    pub struct T9;
+    pub struct T10;
+    pub struct T11;
+    pub struct T12;
+}
+
+unsafe impl<T: RandomComponent> RandomResourceListHas<&'_ T, disambiguators::Exact> for &'_ T {
+    fn project_state(&state: &Self::ParamState) -> ComponentId {
+        state
+    }
+}
+
+unsafe impl<T: RandomComponent> RandomResourceListHas<&'_ mut T, disambiguators::Exact>
+    for &'_ mut T
+{
+    fn project_state(&state: &Self::ParamState) -> ComponentId {
+        state
+    }
+}
+
+// Both `Res` and `ResMut` resolve to the same `ComponentId` so mutable borrows can be narrowed.
+unsafe impl<T: RandomComponent> RandomResourceListHas<&'_ T, disambiguators::Downgrade>
+    for &'_ mut T
+{
+    fn project_state(&state: &Self::ParamState) -> ComponentId {
+        state
+    }
+}
+
+unsafe impl<T: RandomEvent> RandomResourceListHas<SendsEvent<T>, disambiguators::Exact>
+    for SendsEvent<T>
+{
+    fn project_state(&state: &Self::ParamState) -> ComponentId {
+        state
+    }
+}
+
+unsafe impl<L: RandomResourceList> RandomResourceSubsetOf<L, ()> for () {
+    fn project_state(_state: &L::ParamState) -> Self::ParamState {}
+}
+
+unsafe impl<'a, T, L, D> RandomResourceSubsetOf<L, D> for &'a T
+where
+    T: RandomComponent,
+    L: RandomResourceListHas<&'a T, D>,
+{
+    fn project_state(state: &L::ParamState) -> Self::ParamState {
+        L::project_state(state)
+    }
+}
+
+unsafe impl<'a, T, L, D> RandomResourceSubsetOf<L, D> for &'a mut T
+where
+    T: RandomComponent,
+    L: RandomResourceListHas<&'a mut T, D>,
+{
+    fn project_state(state: &L::ParamState) -> Self::ParamState {
+        L::project_state(state)
+    }
+}
+
+unsafe impl<T, L, D> RandomResourceSubsetOf<L, D> for SendsEvent<T>
+where
+    T: RandomEvent,
+    L: RandomResourceListHas<SendsEvent<T>, D>,
+{
+    fn project_state(state: &L::ParamState) -> Self::ParamState {
+        L::project_state(state)
+    }
+}
+
+macro_rules! impl_random_resource_subset {
+    () => {};
+    ($first:ident:$first_d:ident $($rest:ident:$rest_d:ident)*) => {
+        impl_random_resource_subset!(@has $first $($rest)*; $first $($rest)*);
+
+        unsafe impl<L, $first, $($rest,)* $first_d, $($rest_d,)*> RandomResourceSubsetOf<L, ($first_d, $($rest_d,)*)> for ($first, $($rest,)*)
+        where
+            L: RandomResourceList,
+            $first: RandomResourceSubsetOf<L, $first_d>,
+            $($rest: RandomResourceSubsetOf<L, $rest_d>,)*
+        {
+            fn project_state(state: &L::ParamState) -> Self::ParamState {
+                ($first::project_state(state), $($rest::project_state(state),)*)
+            }
+        }
+
+        impl_random_resource_subset!($($rest:$rest_d)*);
+    };
+    (@has $($para:ident)*; ) => {};
+    (@has $($para:ident)*; $the_para:ident $($rest:ident)*) => {
+        unsafe impl<S, D, $($para,)*> RandomResourceListHas<S, (disambiguators::$the_para, D)> for ($($para,)*)
+        where
+            S: RandomResourceList,
+            $($para: RandomResourceList,)*
+            $the_para: RandomResourceListHas<S, D>,
+        {
+            #[allow(non_snake_case)]
+            fn project_state(($($para,)*): &Self::ParamState) -> S::ParamState {
+                $(let _ = $para;)*
+                $the_para::project_state($the_para)
+            }
+        }
+
+        impl_random_resource_subset!(@has $($para)*; $($rest)*);
+    };
+}
+
+impl_random_resource_subset!(
+    T1:D1 T2:D2 T3:D3 T4:D4 T5:D5 T6:D6 T7:D7 T8:D8 T9:D9 T10:D10 T11:D11 T12:D12
+);
+
 // === RandomComponent === //
 
 pub struct RandomComponentToken<T> {
     _ty: PhantomData<fn() -> T>,
 }
 
-pub unsafe trait RandomComponent: 'static + Sized + Send + Sync {
+/// A component stored in a [`RandomArena`] and accessed through [`Obj`]s.
+///
+/// Implement this with [`random_component!`] or, for components which aren't `Send + Sync`,
+/// [`non_send_random_component!`].
+pub unsafe trait RandomComponent: 'static + Sized {
+    /// Determines how this component's arena is stored in the world.
+    type Storage: RandomArenaStorage<Self>;
+
     unsafe fn tls() -> &'static LocalKey<Cell<*mut RandomArena<Self>>>;
 
     fn arena<'a>() -> &'a RandomArena<Self> {
         autoken::tie!('a => ref RandomComponentToken<Self>);
         autoken::tie!('a => ref WorldCap);
 
-        unsafe { &*Self::tls().get() }
+        unsafe { &*arena_ptr::<Self>() }
     }
 
     fn arena_mut<'a>() -> &'a mut RandomArena<Self> {
         autoken::tie!('a => mut RandomComponentToken<Self>);
         autoken::tie!('a => ref WorldCap);
 
-        unsafe { &mut *Self::tls().get() }
+        unsafe { &mut *arena_ptr::<Self>() }
+    }
+
+    /// Iterates over every live instance of this component alongside the entity owning it. This is
+    /// useful for systems which have to sweep over every instance without keeping their own
+    /// registry.
+    fn iter<'a>() -> impl Iterator<Item = (Entity, Obj<Self>)> + 'a {
+        autoken::tie!('a => ref RandomComponentToken<Self>);
+        autoken::tie!('a => ref WorldCap);
+
+        Self::arena()
+            .arena
+            .iter()
+            .map(|(handle, &(entity, _))| (entity, Obj(handle)))
+    }
+
+    /// Reserves room in this component's arena for `additional` more components. This is useful
+    /// before inserting many components at once.
+    ///
+    /// Unlike most accessors, this checks that the arena is actually available even in release
+    /// builds since it is typically called speculatively at the start of a bulk operation.
+    fn reserve(additional: usize) {
+        assert!(
+            !unsafe { Self::tls().get() }.is_null(),
+            "Random component never registered: {}",
+            type_name::<Self>(),
+        );
+
+        Self::arena_mut().reserve(additional);
+    }
+
+    /// Compacts this component's arena like [`RandomArena::compact`] and updates the `Obj`
+    /// components of the moved components' owners.
+    ///
+    /// The old handles of moved components are invalidated so handles stored elsewhere must be
+    /// remapped through the returned map.
+    fn compact() -> FxHashMap<Obj<Self>, Obj<Self>> {
+        let arena = Self::arena_mut();
+        let moved = arena.compact();
+
+        cap!(mut CommandsCap => v in {
+            for &obj in moved.values() {
+                // The owner may have been despawned without being unlinked yet.
+                v.entity(arena.arena[obj.0].0).try_insert(obj);
+            }
+        });
+
+        moved
+    }
+}
+
+/// The strategy used to store the [`RandomArena`] of a [`RandomComponent`] in the world.
+pub unsafe trait RandomArenaStorage<T: 'static> {
+    fn init_arena(world: &mut World);
+
+    fn init_access(world: &mut World, system_meta: &mut SystemMeta, mutable: bool) -> ComponentId;
+
+    unsafe fn arena_from_world(
+        id: ComponentId,
+        world: UnsafeWorldCell<'_>,
+    ) -> Option<*mut RandomArena<T>>;
+}
+
+/// Stores the arena as a regular bevy [`Resource`]. This is the storage used by
+/// [`random_component!`].
+pub struct SendArena;
+
+unsafe impl<T: 'static + Send + Sync> RandomArenaStorage<T> for SendArena {
+    fn init_arena(world: &mut World) {
+        world.init_resource::<RandomArena<T>>();
+    }
+
+    fn init_access(world: &mut World, system_meta: &mut SystemMeta, mutable: bool) -> ComponentId {
+        if mutable {
+            <ResMut<RandomArena<T>> as SystemParam>::init_state(world, system_meta)
+        } else {
+            <Res<RandomArena<T>> as SystemParam>::init_state(world, system_meta)
+        }
+    }
+
+    unsafe fn arena_from_world(
+        id: ComponentId,
+        world: UnsafeWorldCell<'_>,
+    ) -> Option<*mut RandomArena<T>> {
+        world
+            .get_resource_by_id(id)
+            .map(|arena| arena.as_ptr().cast())
+    }
+}
+
+/// Stores the arena as a bevy non-send resource. This is the storage used by
+/// [`non_send_random_component!`]. Systems accessing these components are scheduled on the main
+/// thread.
+pub struct NonSendArena;
+
+unsafe impl<T: 'static> RandomArenaStorage<T> for NonSendArena {
+    fn init_arena(world: &mut World) {
+        world.init_non_send_resource::<RandomArena<T>>();
+    }
+
+    fn init_access(world: &mut World, system_meta: &mut SystemMeta, mutable: bool) -> ComponentId {
+        if mutable {
+            <NonSendMut<RandomArena<T>> as SystemParam>::init_state(world, system_meta)
+        } else {
+            <NonSend<RandomArena<T>> as SystemParam>::init_state(world, system_meta)
+        }
     }
+
+    unsafe fn arena_from_world(
+        id: ComponentId,
+        world: UnsafeWorldCell<'_>,
+    ) -> Option<*mut RandomArena<T>> {
+        world
+            .get_non_send_resource_by_id(id)
+            .map(|arena| arena.as_ptr().cast())
+    }
+}
+
+fn arena_ptr<T: RandomComponent>() -> *mut RandomArena<T> {
+    let ptr = unsafe { T::tls().get() };
+
+    // Well-typed code can't get here without a `provide` scope but the unsafe `Deref` impls of
+    // `Obj` can.
+    debug_assert!(
+        !ptr.is_null(),
+        "accessed RandomComponent<{}> outside of a RandomAccess::provide/use_random scope",
+        type_name::<T>(),
+    );
+
+    ptr
 }
 
 #[doc(hidden)]
 pub mod random_component_internals {
     pub use {
-        super::{RandomArena, RandomComponent},
+        super::{NonSendArena, RandomArena, RandomComponent, SendArena},
         std::{cell::Cell, ptr::null_mut, thread::LocalKey, thread_local},
     };
 }
 
 #[macro_export]
 macro_rules! random_component {
-    ($($ty:ty),*$(,)?) => {$(
+    (@impl $storage:ident for $ty:ty) => {
         unsafe impl $crate::random_component_internals::RandomComponent for $ty {
+            type Storage = $crate::random_component_internals::$storage;
+
             unsafe fn tls() -> &'static $crate::random_component_internals::LocalKey<
                 $crate::random_component_internals::Cell<
                     *mut $crate::random_component_internals::RandomArena<Self>,
@@ -527,6 +1061,18 @@ macro_rules! random_component {
                 &TLS
             }
         }
+    };
+    ($($ty:ty),*$(,)?) => {$(
+        $crate::random_component!(@impl SendArena for $ty);
+    )*};
+}
+
+/// Like [`random_component!`] but for components which aren't `Send + Sync`. Their arenas are
+/// stored as non-send resources so every system accessing them runs on the main thread.
+#[macro_export]
+macro_rules! non_send_random_component {
+    ($($ty:ty),*$(,)?) => {$(
+        $crate::random_component!(@impl NonSendArena for $ty);
     )*};
 }
 
@@ -586,27 +1132,52 @@ macro_rules! random_event {
 
 // === Obj === //
 
+/// A handle to a [`RandomComponent`] stored in its [`RandomArena`].
+///
+/// `Obj`s are also inserted as regular bevy components on their owning entity so they can be found
+/// through ordinary queries: a system can take a `Query<(Entity, &Obj<T>)>` and copy the `Obj` out
+/// of it or use [`WithObj<T>`] to filter for entities owning a `T`.
+///
+/// `Obj`s are ordered by their arena slot and then by their slot's generation. This ordering is
+/// stable across runs which spawn objects in the same order, making it suitable for sorting and for
+/// keys of a `BTreeMap`, but it does not reflect creation time since freed slots are reused.
 #[derive_where(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 #[derive(Component)]
 #[repr(transparent)]
 pub struct Obj<T>(Handle<(Entity, T)>);
 
 impl<T: RandomComponent> Obj<T> {
+    #[track_caller]
     fn new(owner: Entity, value: T) -> Self {
-        let arena = T::arena_mut();
+        let (obj, is_new) = Self::new_no_link(T::arena_mut(), owner, value);
+
+        if is_new {
+            cap!(mut CommandsCap => v in {
+                v.entity(owner).insert(obj);
+            });
+        }
+
+        obj
+    }
+
+    /// Places `value` in `owner`'s arena slot, overwriting its existing value if it has one.
+    /// Returns whether the slot is new, in which case the caller is responsible for inserting the
+    /// `Obj` as a component on `owner`.
+    #[track_caller]
+    fn new_no_link(arena: &mut RandomArena<T>, owner: Entity, value: T) -> (Self, bool) {
+        #[cfg(feature = "debug-registry")]
+        arena.alloc_sites.insert(owner, Location::caller());
+
         match arena.map.entry(owner) {
             hash_map::Entry::Occupied(entry) => {
                 let obj = *entry.into_mut();
                 arena.arena[obj.0] = (owner, value);
-                obj
+                (obj, false)
             }
             hash_map::Entry::Vacant(entry) => {
                 let obj = Obj(arena.arena.insert((owner, value)));
-                cap!(mut CommandsCap => v in {
-                    v.entity(owner).insert(obj);
-                });
                 entry.insert(obj);
-                obj
+                (obj, true)
             }
         }
     }
@@ -619,24 +1190,115 @@ impl<T: RandomComponent> Obj<T> {
         self.entity().get::<V>()
     }
 
+    /// Fetches the `U` component of the entity `f` reads out of this component. This is useful for
+    /// components which store a reference to a sibling entity.
+    pub fn get_related<U: RandomComponent>(self, f: impl FnOnce(&T) -> Entity) -> Obj<U> {
+        f(self.deref()).get::<U>()
+    }
+
     pub fn is_alive(self) -> bool {
         T::arena().arena.contains(self.0)
     }
 
     #[allow(clippy::should_implement_trait)]
+    #[track_caller]
     pub fn deref<'a>(self) -> &'a T {
         autoken::tie!('a => ref RandomComponentToken<T>);
         autoken::tie!('a => ref WorldCap);
 
-        &T::arena().arena[self.0].1
+        let arena = T::arena();
+        let Some((owner, value)) = arena.arena.get(self.0) else {
+            self.stale()
+        };
+        self.debug_check_owner(&arena.map, *owner);
+        value
     }
 
     #[allow(clippy::should_implement_trait)]
+    #[track_caller]
     pub fn deref_mut<'a>(self) -> &'a mut T {
         autoken::tie!('a => mut RandomComponentToken<T>);
         autoken::tie!('a => ref WorldCap);
 
-        &mut T::arena_mut().arena[self.0].1
+        let arena = T::arena_mut();
+        let Some((owner, value)) = arena.arena.get_mut(self.0) else {
+            self.stale()
+        };
+        self.debug_check_owner(&arena.map, *owner);
+        value
+    }
+
+    #[track_caller]
+    fn stale(self) -> ! {
+        panic!("stale Obj<{}> dereferenced: {self:?}", type_name::<T>());
+    }
+
+    /// Ensures that the arena's entity map agrees that this object is the component of the entity
+    /// owning its slot. The generation check on the arena's side should make this impossible to
+    /// violate but it's cheap insurance in debug builds.
+    #[track_caller]
+    fn debug_check_owner(self, map: &FxHashMap<Entity, Obj<T>>, owner: Entity) {
+        debug_assert!(
+            map.get(&owner) == Some(&self),
+            "stale Obj<{}> dereferenced: {self:?} is not the component of its owner {owner:?}",
+            type_name::<T>(),
+        );
+    }
+
+    /// Like [`deref`](Self::deref) but returns `None` if the component has been unlinked from its
+    /// arena. This is useful for handles which are cached across frames.
+    pub fn try_deref<'a>(self) -> Option<&'a T> {
+        autoken::tie!('a => ref RandomComponentToken<T>);
+        autoken::tie!('a => ref WorldCap);
+
+        T::arena().arena.get(self.0).map(|(_, value)| value)
+    }
+
+    /// Like [`deref_mut`](Self::deref_mut) but returns `None` if the component has been unlinked
+    /// from its arena.
+    pub fn try_deref_mut<'a>(self) -> Option<&'a mut T> {
+        autoken::tie!('a => mut RandomComponentToken<T>);
+        autoken::tie!('a => ref WorldCap);
+
+        T::arena_mut().arena.get_mut(self.0).map(|(_, value)| value)
+    }
+
+    /// Mutably borrows several components at once, which `deref_mut` can't do since each call
+    /// borrows the entire arena. Panics if any two objects refer to the same component.
+    pub fn get_many_mut<'a, const N: usize>(objs: [Obj<T>; N]) -> [&'a mut T; N] {
+        autoken::tie!('a => mut RandomComponentToken<T>);
+        autoken::tie!('a => ref WorldCap);
+
+        for (i, obj) in objs.iter().enumerate() {
+            assert!(
+                !objs[..i].contains(obj),
+                "attempted to borrow {obj:?} more than once in `get_many_mut`",
+            );
+        }
+
+        T::arena_mut()
+            .arena
+            .get_many_mut(objs.map(|obj| obj.0))
+            .map(|(_, value)| value)
+    }
+
+    /// Moves `value` into the component, returning its previous value.
+    pub fn replace(self, value: T) -> T {
+        mem::replace(self.deref_mut(), value)
+    }
+
+    /// Exchanges the values of two components, which is handy for double-buffered state. Panics
+    /// if both objects refer to the same component.
+    pub fn swap(self, other: Obj<T>) {
+        assert_ne!(
+            self,
+            other,
+            "attempted to swap Obj<{}> with itself",
+            type_name::<T>(),
+        );
+
+        let [lhs, rhs] = T::arena_mut().arena.get_many_mut([self.0, other.0]);
+        mem::swap(&mut lhs.1, &mut rhs.1);
     }
 }
 
@@ -646,6 +1308,9 @@ impl<T> Obj<T> {
     }
 }
 
+/// A query filter matching entities which own an [`Obj<T>`].
+pub type WithObj<T> = With<Obj<T>>;
+
 impl<T: RandomComponent> Deref for Obj<T> {
     type Target = T;
 
@@ -678,13 +1343,31 @@ pub trait RandomEntityExt {
     fn try_get<T: RandomComponent>(self) -> Option<Obj<T>>;
 
     fn get<T: RandomComponent>(self) -> Obj<T>;
+
+    /// Fetches the entity's `T` component, inserting the value produced by `f` if it doesn't have
+    /// one yet. `f` only runs if the component is missing.
+    fn get_or_insert_with<T: RandomComponent>(self, f: impl FnOnce() -> T) -> Obj<T>;
+
+    /// Inserts many components at once, returning their handles in order. Entities which already
+    /// have a `T` have their value overwritten like with [`insert`](Self::insert).
+    ///
+    /// This is faster than repeated calls to [`insert`](Self::insert) since it reserves arena
+    /// capacity up front and links every new `Obj` to its entity in a single queued command. Like
+    /// [`insert`](Self::insert), this panics if an owner has been despawned.
+    fn insert_many<T: RandomComponent>(
+        values: impl IntoIterator<Item = (Entity, T)>,
+    ) -> Vec<Obj<T>>
+    where
+        Self: Sized;
 }
 
 impl RandomEntityExt for Entity {
+    #[track_caller]
     fn insert<T: RandomComponent>(self, value: T) -> Obj<T> {
         Obj::new(self, value)
     }
 
+    #[track_caller]
     fn with<T: RandomComponent>(self, value: T) -> Self {
         self.insert(value);
         self
@@ -707,25 +1390,141 @@ impl RandomEntityExt for Entity {
     fn get<T: RandomComponent>(self) -> Obj<T> {
         self.try_get::<T>().unwrap()
     }
-}
 
-// === System Link === //
+    #[track_caller]
+    fn get_or_insert_with<T: RandomComponent>(self, f: impl FnOnce() -> T) -> Obj<T> {
+        match self.try_get::<T>() {
+            Some(obj) => obj,
+            None => self.insert(f()),
+        }
+    }
 
-pub trait RandomAppExt {
-    fn add_random_component<T: RandomComponent>(&mut self);
-}
+    #[track_caller]
+    fn insert_many<T: RandomComponent>(
+        values: impl IntoIterator<Item = (Entity, T)>,
+    ) -> Vec<Obj<T>> {
+        let values = values.into_iter();
+        let arena = T::arena_mut();
+        arena.reserve(values.size_hint().0);
 
-impl RandomAppExt for App {
-    fn add_random_component<T: RandomComponent>(&mut self) {
-        self.init_resource::<RandomArena<T>>();
-        self.add_systems(Last, make_unlinker_system::<T>());
-    }
-}
+        let mut objs = Vec::with_capacity(values.size_hint().0);
+        let mut links = Vec::new();
 
-pub trait RandomWorldExt {
-    fn use_random<L, R>(&mut self, f: impl FnOnce(PhantomData<L>) -> R) -> R
-    where
-        L: 'static + RandomResourceList;
+        for (owner, value) in values {
+            let (obj, is_new) = Obj::new_no_link(arena, owner, value);
+
+            if is_new {
+                links.push((owner, obj));
+            }
+
+            objs.push(obj);
+        }
+
+        if !links.is_empty() {
+            cap!(mut CommandsCap => v in {
+                v.add(move |world: &mut World| {
+                    for (owner, obj) in links {
+                        let Some(mut owner_mut) = world.get_entity_mut(owner) else {
+                            panic!(
+                                "could not insert {} because entity {owner:?} doesn't exist",
+                                type_name::<Obj<T>>(),
+                            );
+                        };
+
+                        owner_mut.insert(obj);
+                    }
+                });
+            });
+        }
+
+        objs
+    }
+}
+
+// === Obj Serialization === //
+
+/// The serialized form of an [`Obj`]: the bits of its owning [`Entity`] at the time it was
+/// serialized.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct SerObjId(pub u64);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ObjDeserError {
+    MalformedId(SerObjId),
+    Dangling {
+        id: SerObjId,
+        component: &'static str,
+    },
+}
+
+impl fmt::Display for ObjDeserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedId(id) => write!(f, "{id:?} does not encode a valid entity"),
+            Self::Dangling { id, component } => {
+                write!(f, "{id:?} no longer refers to a live {component}")
+            }
+        }
+    }
+}
+
+impl Error for ObjDeserError {}
+
+/// Maps [`Obj`]s to and from [`SerObjId`]s. Ids resolve to the entity they were serialized from
+/// unless a loader has [`remap`](Self::remap)ped them to the entity it respawned in its place.
+#[derive(Debug, Default)]
+pub struct ObjSerContext {
+    remap: FxHashMap<SerObjId, Entity>,
+}
+
+impl ObjSerContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn remap(&mut self, id: SerObjId, entity: Entity) {
+        self.remap.insert(id, entity);
+    }
+
+    pub fn serialize_obj<T: RandomComponent>(&self, obj: Obj<T>) -> SerObjId {
+        SerObjId(obj.entity().to_bits())
+    }
+
+    /// Resolves `id` to a live `Obj<T>`. Must be called within a [`RandomAccess::provide`] block
+    /// giving access to `T`.
+    pub fn deserialize_obj<T: RandomComponent>(
+        &self,
+        id: SerObjId,
+    ) -> Result<Obj<T>, ObjDeserError> {
+        let entity = match self.remap.get(&id) {
+            Some(&entity) => entity,
+            None => Entity::try_from_bits(id.0).map_err(|_| ObjDeserError::MalformedId(id))?,
+        };
+
+        entity.try_get::<T>().ok_or(ObjDeserError::Dangling {
+            id,
+            component: type_name::<T>(),
+        })
+    }
+}
+
+// === System Link === //
+
+pub trait RandomAppExt {
+    fn add_random_component<T: RandomComponent>(&mut self);
+}
+
+impl RandomAppExt for App {
+    fn add_random_component<T: RandomComponent>(&mut self) {
+        T::Storage::init_arena(self.world_mut());
+        self.add_systems(Last, make_unlinker_system::<T>());
+    }
+}
+
+pub trait RandomWorldExt {
+    fn use_random<L, R>(&mut self, f: impl FnOnce(PhantomData<L>) -> R) -> R
+    where
+        L: 'static + RandomResourceList;
 }
 
 impl RandomWorldExt for App {
@@ -798,6 +1597,9 @@ pub fn make_unlinker_system<T: RandomComponent>(
                 if let Some(obj) = arena.map.remove(&removed) {
                     arena.arena.remove(obj.0);
                 }
+
+                #[cfg(feature = "debug-registry")]
+                arena.alloc_sites.remove(&removed);
             }
         });
     }
@@ -815,12 +1617,791 @@ pub fn despawn_entity(entity: Entity) {
     })
 }
 
+/// Lists every live `T` alongside the location of the [`insert`](RandomEntityExt::insert) call
+/// which created it. Objects which stick around longer than expected point to an owner which was
+/// never despawned.
+#[cfg(feature = "debug-registry")]
+pub fn dump_live_objs<T: RandomComponent>() -> Vec<(Obj<T>, &'static Location<'static>)> {
+    let arena = T::arena();
+
+    arena
+        .map
+        .iter()
+        .map(|(entity, &obj)| (obj, arena.alloc_sites[entity]))
+        .collect()
+}
+
 pub fn send_event<E: RandomEvent>(event: E) {
     E::events_mut().send(event);
 }
 
+/// Sends every event in `events`, fetching the event queue only once.
+pub fn send_event_batch<E: RandomEvent>(events: impl IntoIterator<Item = E>) {
+    E::events_mut().send_batch(events);
+}
+
+/// Removes every event from the queue, including those which haven't been observed by every reader
+/// yet. Events which aren't yielded by the time the iterator is dropped are discarded.
+pub fn drain_events<'a, E: RandomEvent>() -> impl Iterator<Item = E> + 'a {
+    autoken::tie!('a => mut RandomEventToken<E>);
+
+    E::events_mut().drain()
+}
+
 pub fn world_mut<'a>() -> &'a mut World {
     autoken::tie!('a => mut WorldCap);
 
     cap!(mut WorldCap => world in unsafe { world.world_mut() })
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::system::{IntoSystem, Query, System};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Health(u32);
+
+    random_component!(Health);
+
+    #[derive(Debug)]
+    struct Controller {
+        body: Entity,
+    }
+
+    #[derive(Debug)]
+    struct Transform(u32);
+
+    random_component!(Controller, Transform);
+
+    #[derive(Debug)]
+    struct GpuHandle(std::rc::Rc<Cell<u32>>);
+
+    non_send_random_component!(GpuHandle);
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outside of a RandomAccess::provide/use_random scope")]
+    fn arena_access_outside_provide_panics() {
+        let _ = Health::arena();
+    }
+
+    #[test]
+    #[should_panic(expected = "called re-entrantly")]
+    fn reentrant_provide_panics() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            world_mut().use_random(|_: PhantomData<&Health>| {});
+        });
+    }
+
+    #[test]
+    fn narrowers_hide_resources_outside_the_subset() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+        app.add_random_component::<Transform>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            spawn_entity(()).insert(Health(3));
+        });
+
+        app.world_mut().run_system_once(
+            |mut rand: RandomAccess<(&mut Health, &mut Transform)>, query: Query<&Obj<Health>>| {
+                let narrower = rand.narrower();
+
+                rand.provide(|| {
+                    let health = *query.single();
+
+                    narrower.provide_subset(|_: PhantomData<&Health>| {
+                        assert_eq!(health.deref().0, 3);
+                        assert!(<&Transform>::fetch_tls_snapshot().is_null());
+                    });
+
+                    // The rest of the scope gets the hidden resources back.
+                    spawn_entity(()).insert(Transform(health.deref().0));
+                });
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of its access's provide scope")]
+    fn narrowers_reject_use_outside_of_provide() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.world_mut()
+            .run_system_once(|rand: RandomAccess<&Health>| {
+                rand.narrower().provide_subset(|_: PhantomData<&Health>| {});
+            });
+    }
+
+    #[test]
+    #[should_panic(expected = "Random component never registered")]
+    fn reserve_outside_of_scope_panics() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+        app.use_random(|_: PhantomData<&mut Health>| {});
+
+        Health::reserve(1000);
+    }
+
+    #[test]
+    fn reserve_prevents_reallocation() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            Health::reserve(1000);
+
+            let arena = Health::arena();
+            let capacity = (arena.arena.capacity(), arena.map.capacity());
+
+            for i in 0..1000 {
+                spawn_entity(()).insert(Health(i));
+            }
+
+            let arena = Health::arena();
+            assert_eq!(arena.arena.len(), 1000);
+            assert_eq!((arena.arena.capacity(), arena.map.capacity()), capacity);
+        });
+    }
+
+    #[test]
+    fn insert_many_links_and_overwrites() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let (entities, objs) = app.use_random(|_: PhantomData<&mut Health>| {
+            let existing = spawn_entity(()).insert(Health(1));
+            let entities = [spawn_entity(()), existing.entity(), spawn_entity(())];
+
+            let objs = Entity::insert_many(entities.into_iter().zip([10, 20, 30].map(Health)));
+            assert_eq!(objs[1], existing);
+            assert_eq!(
+                objs.iter().map(|obj| obj.deref().0).collect::<Vec<_>>(),
+                [10, 20, 30]
+            );
+            assert_eq!(Health::arena().arena.len(), 3);
+
+            (entities, objs)
+        });
+
+        for (entity, obj) in entities.into_iter().zip(objs) {
+            assert_eq!(app.world().get::<Obj<Health>>(entity), Some(&obj));
+        }
+    }
+
+    #[test]
+    #[should_panic = "doesn't exist"]
+    fn insert_many_rejects_despawned_owners() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let despawned = app.use_random(|_: PhantomData<&mut Health>| {
+            let entity = spawn_entity(());
+            despawn_entity(entity);
+            entity
+        });
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            Entity::insert_many([(spawn_entity(()), Health(1)), (despawned, Health(2))]);
+        });
+    }
+
+    #[test]
+    fn get_or_insert_with_only_constructs_on_miss() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let entity = spawn_entity(());
+
+            let created = entity.get_or_insert_with(|| Health(1));
+            assert_eq!(created.deref().0, 1);
+
+            let fetched = entity.get_or_insert_with(|| -> Health { panic!("constructed twice") });
+            assert_eq!(fetched, created);
+            assert_eq!(Health::arena().arena.len(), 1);
+        });
+    }
+
+    #[test]
+    fn compact_remaps_objs() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let kept = app.use_random(|_: PhantomData<&mut Health>| {
+            let objs = (0..8)
+                .map(|i| spawn_entity(()).insert(Health(i)))
+                .collect::<Vec<_>>();
+
+            for obj in &objs[..4] {
+                despawn_entity(obj.entity());
+            }
+
+            objs[4..].to_vec()
+        });
+
+        // Unlink the despawned objects, leaving holes at the front of the arena.
+        app.update();
+
+        let kept = app.use_random(|_: PhantomData<&mut Health>| {
+            let moved = Health::compact();
+            assert_eq!(moved.len(), 4);
+
+            let kept = kept
+                .iter()
+                .map(|obj| moved.get(obj).copied().unwrap_or(*obj))
+                .collect::<Vec<_>>();
+
+            for (i, &obj) in kept.iter().enumerate() {
+                assert_eq!(obj.deref().0, i as u32 + 4);
+                assert_eq!(obj.entity().get::<Health>(), obj);
+            }
+
+            // Stale handles are invalidated rather than aliasing the moved values or values
+            // inserted into the slots they used to point to.
+            spawn_entity(()).insert(Health(100));
+
+            for old in moved.keys() {
+                assert!(!old.is_alive());
+            }
+
+            let arena = Health::arena();
+            assert!(arena.arena.iter().all(|(handle, _)| handle.index().0 < 5));
+
+            kept
+        });
+
+        // The owners' `Obj` components are updated too.
+        app.update();
+
+        for obj in kept {
+            let entity = app.use_random(|_: PhantomData<&Health>| obj.entity());
+            assert_eq!(app.world().get::<Obj<Health>>(entity), Some(&obj));
+        }
+    }
+
+    #[test]
+    fn replace_and_swap_move_values() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let front = spawn_entity(()).insert(Health(1));
+            let back = spawn_entity(()).insert(Health(2));
+
+            assert_eq!(front.replace(Health(3)).0, 1);
+            assert_eq!(front.deref().0, 3);
+
+            front.swap(back);
+            assert_eq!((front.deref().0, back.deref().0), (2, 3));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "attempted to swap Obj")]
+    fn swapping_an_obj_with_itself_panics() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let obj = spawn_entity(()).insert(Health(1));
+            obj.swap(obj);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "stale Obj<bevy_autoken::tests::Health> dereferenced")]
+    fn dereferencing_unlinked_objs_panics() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let obj = app.use_random(|_: PhantomData<&mut Health>| {
+            let obj = spawn_entity(()).insert(Health(1));
+            despawn_entity(obj.entity());
+            obj
+        });
+
+        app.update();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            // Reuse the slot to ensure that the generation check catches it.
+            spawn_entity(()).insert(Health(2));
+            obj.deref_mut().0 += 1;
+        });
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is not the component of its owner")]
+    fn dereferencing_objs_missing_from_the_map_panics() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let a = spawn_entity(()).insert(Health(1));
+            let b = spawn_entity(()).insert(Health(2));
+
+            Health::arena_mut().map.insert(a.entity(), b);
+            let _ = a.deref();
+        });
+    }
+
+    #[derive(Debug, Eq, PartialEq, Event)]
+    struct Damaged(u32);
+
+    random_event!(Damaged);
+
+    #[test]
+    fn batched_events_drain_in_order() {
+        let mut app = App::new();
+        app.add_event::<Damaged>();
+
+        let drained = app.use_random(|_: PhantomData<SendsEvent<Damaged>>| {
+            send_event(Damaged(1));
+            send_event_batch((2..5).map(Damaged));
+            drain_events::<Damaged>().collect::<Vec<_>>()
+        });
+        assert_eq!(drained, [Damaged(1), Damaged(2), Damaged(3), Damaged(4)]);
+
+        let drained =
+            app.use_random(|_: PhantomData<SendsEvent<Damaged>>| drain_events::<Damaged>().count());
+        assert_eq!(drained, 0);
+    }
+
+    #[test]
+    fn objs_are_ordered_by_slot() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let [a, b, c] = app.use_random(|_: PhantomData<&mut Health>| {
+            [1, 2, 3].map(|hp| spawn_entity(()).insert(Health(hp)))
+        });
+        assert!(a < b && b < c);
+
+        app.use_random(|_: PhantomData<&mut Health>| despawn_entity(a.entity()));
+        app.update();
+
+        // The newer object reuses the first slot and therefore sorts first.
+        let d = app.use_random(|_: PhantomData<&mut Health>| spawn_entity(()).insert(Health(4)));
+        let mut objs = vec![c, d, b];
+        objs.sort();
+        assert_eq!(objs, [d, b, c]);
+
+        let by_obj = [c, d, b]
+            .into_iter()
+            .enumerate()
+            .map(|(i, obj)| (obj, i))
+            .collect::<std::collections::BTreeMap<_, _>>();
+        assert_eq!(by_obj.keys().copied().collect::<Vec<_>>(), [d, b, c]);
+    }
+
+    #[test]
+    fn conflicting_systems_are_serialized() {
+        fn reads(_: RandomAccess<&Health>) {}
+        fn writes(_: RandomAccess<&mut Health>) {}
+        fn also_writes(_: RandomAccess<(&mut Health, SendsEvent<Damaged>)>) {}
+
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+        app.add_event::<Damaged>();
+        let world = app.world_mut();
+
+        let mut reads = IntoSystem::into_system(reads);
+        let mut writes = IntoSystem::into_system(writes);
+        let mut also_writes = IntoSystem::into_system(also_writes);
+        reads.initialize(world);
+        writes.initialize(world);
+        also_writes.initialize(world);
+
+        assert!(!reads
+            .component_access()
+            .is_compatible(writes.component_access()));
+        assert!(!writes
+            .component_access()
+            .is_compatible(also_writes.component_access()));
+
+        let mut other_reads = IntoSystem::into_system(|_: RandomAccess<&Health>| {});
+        other_reads.initialize(world);
+        assert!(reads
+            .component_access()
+            .is_compatible(other_reads.component_access()));
+    }
+
+    #[test]
+    #[should_panic(expected = "error[B0002]")]
+    fn conflicting_accesses_within_a_system_panic() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let mut system = IntoSystem::into_system(|_: RandomAccess<(&Health, &mut Health)>| {});
+        system.initialize(app.world_mut());
+    }
+
+    #[test]
+    fn try_deref_handles_unlinked_objs() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let (kept, despawned) = app.use_random(|_: PhantomData<&mut Health>| {
+            let kept = spawn_entity(()).insert(Health(1));
+            let despawned = spawn_entity(()).insert(Health(2));
+
+            kept.try_deref_mut().unwrap().0 += 10;
+            assert_eq!(despawned.try_deref().unwrap().0, 2);
+
+            despawn_entity(despawned.entity());
+            (kept, despawned)
+        });
+
+        // Let the unlinker drop the despawned entity's component from the arena.
+        app.update();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            assert_eq!(kept.try_deref().unwrap().0, 11);
+            assert!(despawned.try_deref().is_none());
+            assert!(despawned.try_deref_mut().is_none());
+        });
+    }
+
+    #[test]
+    fn iter_visits_every_live_obj() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let despawned = app.use_random(|_: PhantomData<&mut Health>| {
+            for i in 0..3 {
+                spawn_entity(()).insert(Health(i));
+            }
+
+            let despawned = spawn_entity(()).insert(Health(100));
+            despawn_entity(despawned.entity());
+            despawned
+        });
+
+        app.update();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let mut seen = Health::iter()
+                .map(|(entity, obj)| {
+                    assert_ne!(obj, despawned);
+                    assert_eq!(obj.entity(), entity);
+                    obj.deref().0
+                })
+                .collect::<Vec<_>>();
+
+            seen.sort();
+            assert_eq!(seen, [0, 1, 2]);
+        });
+    }
+
+    #[test]
+    fn get_many_mut_borrows_distinct_objs() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let a = spawn_entity(()).insert(Health(10));
+            let b = spawn_entity(()).insert(Health(20));
+
+            let [a_health, b_health] = Obj::get_many_mut([a, b]);
+            mem::swap(&mut a_health.0, &mut b_health.0);
+            a_health.0 += 1;
+
+            assert_eq!((a.deref().0, b.deref().0), (21, 10));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once in `get_many_mut`")]
+    fn get_many_mut_rejects_duplicates() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let a = spawn_entity(()).insert(Health(10));
+            let b = spawn_entity(()).insert(Health(20));
+            Obj::get_many_mut([a, b, a]);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid duplicate or dead entry in `get_many_mut`")]
+    fn get_many_mut_rejects_objs_truncated_by_compaction() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let (kept, stale) = app.use_random(|_: PhantomData<&mut Health>| {
+            let kept = spawn_entity(()).insert(Health(10));
+            let stale = spawn_entity(()).insert(Health(20));
+            despawn_entity(stale.entity());
+            (kept, stale)
+        });
+
+        app.update();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            // Compaction truncates the stale object's slot so its index is now out of bounds.
+            assert!(Health::compact().is_empty());
+            Obj::get_many_mut([kept, stale]);
+        });
+    }
+
+    #[test]
+    fn obj_ids_survive_reload() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let id = app.use_random(|_: PhantomData<&mut Health>| {
+            let obj = spawn_entity(()).insert(Health(42));
+            ObjSerContext::new().serialize_obj(obj)
+        });
+
+        // Simulate loading a save into a fresh world, where entity ids are reassigned.
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+        let mut cx = ObjSerContext::new();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            spawn_entity(());
+            let loaded = spawn_entity(()).insert(Health(42));
+            cx.remap(id, loaded.entity());
+
+            let resolved = cx.deserialize_obj::<Health>(id).unwrap();
+            assert_eq!(resolved, loaded);
+            assert_eq!(resolved.deref().0, 42);
+
+            loaded.entity().remove::<Health>();
+        });
+
+        // Let the unlinker drop the removed component from the arena.
+        app.update();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            assert!(matches!(
+                cx.deserialize_obj::<Health>(id),
+                Err(ObjDeserError::Dangling { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn objs_are_queryable() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let mut spawned = app.use_random(|_: PhantomData<&mut Health>| {
+            (0..3)
+                .map(|i| spawn_entity(()).insert(Health(i)))
+                .collect::<Vec<_>>()
+        });
+
+        // An entity without a `Health` shouldn't show up.
+        app.world_mut().spawn_empty();
+
+        let mut queried = app
+            .world_mut()
+            .query_filtered::<(Entity, &Obj<Health>), WithObj<Health>>()
+            .iter(app.world())
+            .map(|(entity, &obj)| (entity, obj))
+            .collect::<Vec<_>>();
+
+        queried.sort();
+        spawned.sort();
+        assert_eq!(
+            queried.iter().map(|&(_, obj)| obj).collect::<Vec<_>>(),
+            spawned
+        );
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            for (entity, obj) in queried {
+                assert_eq!(obj.entity(), entity);
+                assert!(obj.deref().0 < 3);
+            }
+        });
+    }
+
+    #[test]
+    fn get_related_follows_entity_references() {
+        let mut app = App::new();
+        app.add_random_component::<Controller>();
+        app.add_random_component::<Transform>();
+
+        app.use_random(|_: PhantomData<(&mut Controller, &mut Transform)>| {
+            let body = spawn_entity(()).insert(Transform(7));
+            let controller = spawn_entity(()).insert(Controller {
+                body: body.entity(),
+            });
+
+            let related = controller.get_related::<Transform>(|c| c.body);
+            assert_eq!(related, body);
+            assert_eq!(related.deref().0, 7);
+        });
+    }
+
+    #[test]
+    fn non_send_components_are_accessible_from_main_thread_systems() {
+        let mut app = App::new();
+        app.add_random_component::<GpuHandle>();
+
+        let uses = std::rc::Rc::new(Cell::new(0));
+        app.use_random(|_: PhantomData<&mut GpuHandle>| {
+            spawn_entity(()).insert(GpuHandle(uses.clone()));
+        });
+
+        app.add_systems(
+            bevy_app::Update,
+            |mut rand: RandomAccess<&mut GpuHandle>, query: Query<&Obj<GpuHandle>>| {
+                rand.provide(|| {
+                    for handle in &query {
+                        let uses = &handle.deref().0;
+                        uses.set(uses.get() + 1);
+                    }
+                });
+            },
+        );
+
+        app.update();
+        app.update();
+        assert_eq!(uses.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-registry")]
+    fn live_objs_remember_their_allocation_site() {
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+
+        let (kept, kept_line, leaked, leaked_line) =
+            app.use_random(|_: PhantomData<&mut Health>| {
+                let (kept, kept_line) = (spawn_entity(()).insert(Health(1)), line!());
+                let (leaked, leaked_line) = (spawn_entity(()).insert(Health(2)), line!());
+                (kept, kept_line, leaked, leaked_line)
+            });
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let mut live = dump_live_objs::<Health>();
+            live.sort_by_key(|&(obj, _)| obj);
+
+            assert_eq!(live.len(), 2);
+            for ((obj, site), (expected, line)) in
+                live.iter().zip([(kept, kept_line), (leaked, leaked_line)])
+            {
+                assert_eq!(*obj, expected);
+                assert_eq!(site.file(), file!());
+                assert_eq!(site.line(), line);
+            }
+
+            kept.entity().remove::<Health>();
+        });
+
+        // Once unlinked, only the leaked object remains.
+        app.update();
+
+        app.use_random(|_: PhantomData<&mut Health>| {
+            let live = dump_live_objs::<Health>();
+            assert_eq!(live.len(), 1);
+            assert_eq!(live[0].0, leaked);
+            assert_eq!(live[0].1.line(), leaked_line);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "trace-provide")]
+    fn provide_scopes_are_traced() {
+        use std::sync::{Arc, Mutex};
+
+        use tracing::{
+            field::{Field, Visit},
+            span,
+        };
+        use tracing_subscriber::{
+            layer::{Context, SubscriberExt as _},
+            Layer,
+        };
+
+        #[derive(Debug, Default)]
+        struct ProvideSpan {
+            resources: Option<u64>,
+            held_ns: Option<u64>,
+        }
+
+        impl Visit for ProvideSpan {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                match field.name() {
+                    "resources" => self.resources = Some(value),
+                    "held_ns" => self.held_ns = Some(value),
+                    _ => {}
+                }
+            }
+
+            fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+        }
+
+        #[derive(Debug, Default)]
+        struct Captured {
+            open: FxHashMap<span::Id, ProvideSpan>,
+            closed: Vec<ProvideSpan>,
+        }
+
+        #[derive(Debug, Default, Clone)]
+        struct Capture(Arc<Mutex<Captured>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for Capture {
+            fn on_new_span(
+                &self,
+                attrs: &span::Attributes<'_>,
+                id: &span::Id,
+                _cx: Context<'_, S>,
+            ) {
+                if attrs.metadata().name() == "RandomAccess::provide" {
+                    let mut span = ProvideSpan::default();
+                    attrs.record(&mut span);
+                    self.0.lock().unwrap().open.insert(id.clone(), span);
+                }
+            }
+
+            fn on_record(&self, id: &span::Id, values: &span::Record<'_>, _cx: Context<'_, S>) {
+                if let Some(span) = self.0.lock().unwrap().open.get_mut(id) {
+                    values.record(span);
+                }
+            }
+
+            fn on_close(&self, id: span::Id, _cx: Context<'_, S>) {
+                let mut captured = self.0.lock().unwrap();
+                if let Some(span) = captured.open.remove(&id) {
+                    captured.closed.push(span);
+                }
+            }
+        }
+
+        let mut app = App::new();
+        app.add_random_component::<Health>();
+        app.add_random_component::<Transform>();
+
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            app.use_random(|_: PhantomData<(&mut Health, &Transform)>| {});
+            app.use_random(|_: PhantomData<&Health>| {});
+        });
+
+        let captured = capture.0.lock().unwrap();
+        assert!(captured.open.is_empty());
+        assert_eq!(
+            captured
+                .closed
+                .iter()
+                .map(|span| span.resources)
+                .collect::<Vec<_>>(),
+            [Some(2), Some(1)],
+        );
+        assert!(captured.closed.iter().all(|span| span.held_ns.is_some()));
+    }
+}
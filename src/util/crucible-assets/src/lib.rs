@@ -1,2 +1,5 @@
 mod manager;
+mod watch;
+
 pub use manager::*;
+pub use watch::*;
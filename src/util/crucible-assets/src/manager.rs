@@ -5,7 +5,7 @@ use std::{
     ptr::NonNull,
     sync::{
         atomic::{AtomicBool, Ordering::*},
-        Arc, OnceLock, RwLock,
+        Arc, Mutex, OnceLock, RwLock,
     },
 };
 
@@ -14,11 +14,14 @@ use crucible_utils::hash::{fx_hash_one, hashbrown::hash_map, FxHashMap, ManyToOw
 use derive_where::derive_where;
 use smallbox::{smallbox, SmallBox};
 
+use crate::watch::AssetWatcher;
+
 // === AssetManager === //
 
 #[derive(Default)]
 pub struct AssetManager {
     assets: RwLock<FxHashMap<AssetKey, AssetValue>>,
+    pub(crate) watcher: Mutex<Option<AssetWatcher>>,
 }
 
 random_component!(AssetManager);
@@ -68,6 +71,12 @@ impl AssetManager {
         });
     }
 
+    /// Drops every cached asset regardless of whether it is still in use. Existing [`Asset`]
+    /// handles remain valid but subsequent loads will rebuild their assets from scratch.
+    pub fn clear(&mut self) {
+        self.assets.get_mut().unwrap().clear();
+    }
+
     fn load_inner<A, R>(&self, args: A, loader_ptr: usize) -> Arc<OnceLock<R>>
     where
         A: AssetArgs,
@@ -0,0 +1,265 @@
+use std::{
+    any::Any,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering::*},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use crucible_utils::hash::FxHashMap;
+use derive_where::derive_where;
+
+use crate::AssetManager;
+
+// === WatchedAsset === //
+
+/// A handle to an asset loaded from a file on disk. If the [`AssetManager`] is in
+/// [`watch`](AssetManager::watch) mode, the asset is reloaded whenever its source file changes and
+/// every handle to it starts resolving to the new value.
+///
+/// Dependents which derive resources from the asset (e.g. pipelines built from a shader) should
+/// remember the [`version`](Self::version) they were built against and rebuild once it changes.
+#[derive_where(Clone)]
+pub struct WatchedAsset<T> {
+    slot: Arc<WatchedSlot<T>>,
+}
+
+struct WatchedSlot<T> {
+    version: AtomicU64,
+    value: RwLock<Arc<T>>,
+}
+
+impl<T> WatchedAsset<T> {
+    pub fn get(&self) -> Arc<T> {
+        self.slot.value.read().unwrap().clone()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.slot.version.load(Acquire)
+    }
+}
+
+// === AssetWatcher === //
+
+type ReloadFn = Arc<dyn Fn(&AssetManager, &[u8]) + Send + Sync>;
+
+pub(crate) struct AssetWatcher {
+    debounce: Duration,
+    files: FxHashMap<PathBuf, WatchedFile>,
+}
+
+struct WatchedFile {
+    modified: Option<SystemTime>,
+    dirty_since: Option<Instant>,
+    assets: Vec<WatchedEntry>,
+}
+
+struct WatchedEntry {
+    loader_ptr: usize,
+    slot: Arc<dyn Any + Send + Sync>,
+    reload: ReloadFn,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl AssetWatcher {
+    pub(crate) fn new(debounce: Duration) -> Self {
+        Self {
+            debounce,
+            files: FxHashMap::default(),
+        }
+    }
+}
+
+impl AssetManager {
+    /// Puts the manager in watch mode. Assets subsequently loaded through
+    /// [`load_file`](Self::load_file) will be reloaded by [`poll_watched`](Self::poll_watched) once
+    /// their source file has stopped changing for `debounce`.
+    pub fn watch(&mut self, debounce: Duration) {
+        let watcher = self.watcher.get_mut().unwrap();
+
+        match watcher {
+            Some(watcher) => watcher.debounce = debounce,
+            None => *watcher = Some(AssetWatcher::new(debounce)),
+        }
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.lock().unwrap().is_some()
+    }
+
+    pub fn load_file<R>(
+        &self,
+        path: impl AsRef<Path>,
+        loader: fn(&Self, &[u8]) -> R,
+    ) -> io::Result<WatchedAsset<R>>
+    where
+        R: 'static + Send + Sync,
+    {
+        let path = path.as_ref();
+        let loader_ptr = loader as usize;
+
+        // Reuse the existing handle if this file was already loaded with this loader.
+        if let Some(watcher) = &*self.watcher.lock().unwrap() {
+            let existing = watcher
+                .files
+                .get(path)
+                .and_then(|file| {
+                    file.assets
+                        .iter()
+                        .find(|entry| entry.loader_ptr == loader_ptr)
+                })
+                .map(|entry| entry.slot.clone());
+
+            if let Some(slot) = existing {
+                return Ok(WatchedAsset {
+                    slot: slot.downcast::<WatchedSlot<R>>().unwrap(),
+                });
+            }
+        }
+
+        // N.B. we don't hold the watcher lock while loading since the loader may itself load other
+        // files.
+        let modified = modified_time(path);
+        let value = loader(self, &fs::read(path)?);
+        let slot = Arc::new(WatchedSlot {
+            version: AtomicU64::new(0),
+            value: RwLock::new(Arc::new(value)),
+        });
+
+        if let Some(watcher) = &mut *self.watcher.lock().unwrap() {
+            let reload_slot = slot.clone();
+
+            watcher
+                .files
+                .entry(path.to_path_buf())
+                .or_insert_with(|| WatchedFile {
+                    modified,
+                    dirty_since: None,
+                    assets: Vec::new(),
+                })
+                .assets
+                .push(WatchedEntry {
+                    loader_ptr,
+                    slot: slot.clone(),
+                    reload: Arc::new(move |assets, data| {
+                        *reload_slot.value.write().unwrap() = Arc::new(loader(assets, data));
+                        reload_slot.version.fetch_add(1, Release);
+                    }),
+                });
+        }
+
+        Ok(WatchedAsset { slot })
+    }
+
+    /// Records that the file at `path` changed at `now`. Rapid successive changes push back the
+    /// reload until the file has been quiet for the debounce period.
+    pub fn notify_changed(&self, path: &Path, now: Instant) {
+        if let Some(watcher) = &mut *self.watcher.lock().unwrap() {
+            if let Some(file) = watcher.files.get_mut(path) {
+                file.dirty_since = Some(now);
+            }
+        }
+    }
+
+    /// Checks every watched file for modifications and reloads the assets of those which have
+    /// settled. Returns the number of files reloaded.
+    pub fn poll_watched(&self, now: Instant) -> usize {
+        let mut due = Vec::new();
+
+        if let Some(watcher) = &mut *self.watcher.lock().unwrap() {
+            let debounce = watcher.debounce;
+
+            for (path, file) in &mut watcher.files {
+                let modified = modified_time(path);
+                if modified != file.modified {
+                    file.modified = modified;
+                    file.dirty_since = Some(now);
+                }
+
+                if file
+                    .dirty_since
+                    .is_some_and(|since| now.duration_since(since) >= debounce)
+                {
+                    file.dirty_since = None;
+                    due.push((
+                        path.clone(),
+                        file.assets
+                            .iter()
+                            .map(|entry| entry.reload.clone())
+                            .collect::<Vec<_>>(),
+                    ));
+                }
+            }
+        }
+
+        let mut reloaded = 0;
+
+        for (path, reloads) in due {
+            // If the file is mid-write or was removed, we'll just pick up the next change to it.
+            let Ok(data) = fs::read(&path) else {
+                continue;
+            };
+
+            for reload in reloads {
+                reload(self, &data);
+            }
+
+            reloaded += 1;
+        }
+
+        reloaded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watched_asset_reloads_after_debounce() {
+        let path = std::env::temp_dir().join(format!(
+            "crucible-assets-watch-test-{}.txt",
+            std::process::id()
+        ));
+        fs::write(&path, "v1").unwrap();
+
+        let mut assets = AssetManager::new();
+        assets.watch(Duration::from_millis(50));
+
+        let load = |assets: &AssetManager| {
+            assets
+                .load_file(&path, |_, data| String::from_utf8(data.to_vec()).unwrap())
+                .unwrap()
+        };
+        let asset = load(&assets);
+        assert_eq!(*asset.get(), "v1");
+        assert_eq!(asset.version(), 0);
+
+        // Simulate two writes in quick succession.
+        let start = Instant::now();
+        fs::write(&path, "v2").unwrap();
+        assets.notify_changed(&path, start);
+        assert_eq!(assets.poll_watched(start), 0);
+
+        assets.notify_changed(&path, start + Duration::from_millis(30));
+        assert_eq!(assets.poll_watched(start + Duration::from_millis(60)), 0);
+        assert_eq!(*asset.get(), "v1");
+
+        assert_eq!(assets.poll_watched(start + Duration::from_millis(100)), 1);
+        assert_eq!(*asset.get(), "v2");
+        assert_eq!(asset.version(), 1);
+
+        // Loading the same file again shares the reloaded handle.
+        let again = load(&assets);
+        assert_eq!(*again.get(), "v2");
+        assert_eq!(again.version(), 1);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
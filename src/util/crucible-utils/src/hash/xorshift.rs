@@ -257,6 +257,12 @@ impl XorshiftPool {
     }
 
     pub fn dealloc_local(&self, shift: Xorshift) {
+        // Exhausted generators, including those created with `new_empty`, would hand out the end of
+        // their range on reuse.
+        if shift.state == shift.end_excl {
+            return;
+        }
+
         self.thread_states.lock().unwrap().push(shift);
     }
 
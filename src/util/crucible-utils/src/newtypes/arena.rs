@@ -2,7 +2,7 @@ use derive_where::derive_where;
 use std::{
     fmt,
     marker::PhantomData,
-    mem::MaybeUninit,
+    mem::{self, MaybeUninit},
     num::NonZeroU32,
     ops::{Index, IndexMut},
 };
@@ -19,6 +19,10 @@ pub struct Arena<T> {
     // Invariant: this vector can never be u32::MAX elements in length.
     slots: IndexVec<ArenaSlotIndex, ArenaSlot<T>>,
     free_slots: Vec<ArenaSlotIndex>,
+
+    // The generation given to newly pushed slots. Compaction raises this above the generation of
+    // every slot it truncates so that stale handles to those slots can't alias new values.
+    fresh_gen: u32,
 }
 
 struct ArenaSlot<T> {
@@ -27,6 +31,12 @@ struct ArenaSlot<T> {
     value: MaybeUninit<T>,
 }
 
+impl<T> ArenaSlot<T> {
+    fn is_occupied(&self) -> bool {
+        self.gen % 2 == 1
+    }
+}
+
 impl<T> Drop for ArenaSlot<T> {
     fn drop(&mut self) {
         if self.gen % 2 == 1 {
@@ -46,6 +56,7 @@ impl<T> Arena<T> {
         Self {
             slots: IndexVec::new(),
             free_slots: Vec::new(),
+            fresh_gen: 1,
         }
     }
 
@@ -90,11 +101,17 @@ impl<T> Arena<T> {
         }
 
         // Fetch the entire set.
+        let len = self.slots.raw.len();
         let slots = self.slots.raw.as_mut_ptr().cast::<ArenaSlot<T>>();
 
         indices.map(|index| {
+            // Handles to slots truncated by `compact` or `clear` may point past the end.
+            if index.index.as_usize() >= len {
+                illegal_set(&indices);
+            }
+
             let slot = unsafe {
-                // Safety: we already ensured that indices are distinct.
+                // Safety: the index is in bounds and we already ensured that indices are distinct.
                 &mut *slots.add(index.index.as_usize())
             };
 
@@ -128,14 +145,17 @@ impl<T> Arena<T> {
             }
         } else {
             let index = self.slots.push(ArenaSlot {
-                gen: 1,
+                gen: self.fresh_gen,
                 value: MaybeUninit::new(value),
             });
 
             Handle {
                 _ty: PhantomData,
                 index,
-                gen: NonZeroU32::new(1).unwrap(),
+                gen: unsafe {
+                    // Safety: `fresh_gen` is always odd.
+                    NonZeroU32::new_unchecked(self.fresh_gen)
+                },
             }
         }
     }
@@ -161,17 +181,130 @@ impl<T> Arena<T> {
         Some(value)
     }
 
+    /// Moves values from the end of the arena into its lowest free slots so that the live values
+    /// are contiguous, calling `on_move` with the old and new handle of each moved value.
+    ///
+    /// The old handles of moved values are invalidated and will never alias a value inserted
+    /// later.
+    pub fn compact(&mut self, mut on_move: impl FnMut(Handle<T>, Handle<T>)) {
+        let mut free_slots = mem::take(&mut self.free_slots);
+        free_slots.sort_unstable();
+
+        // Fill the lowest free slots with the highest live values.
+        let slots = &mut self.slots.raw;
+        let mut end = slots.len();
+
+        for dst in free_slots {
+            let dst_idx = dst.as_usize();
+
+            while end > dst_idx + 1 && !slots[end - 1].is_occupied() {
+                end -= 1;
+            }
+
+            if end <= dst_idx + 1 {
+                break;
+            }
+
+            end -= 1;
+
+            let (head, tail) = slots.split_at_mut(end);
+            let (dst_slot, src_slot) = (&mut head[dst_idx], &mut tail[0]);
+
+            let old = Handle {
+                _ty: PhantomData,
+                index: ArenaSlotIndex::from_usize(end),
+                gen: unsafe {
+                    // Safety: `src_slot.gen` is odd because we skipped vacant slots.
+                    NonZeroU32::new_unchecked(src_slot.gen)
+                },
+            };
+
+            // This cannot overflow because free slots have an even generation and `u32::MAX` is
+            // odd.
+            dst_slot.gen += 1;
+            dst_slot.value = MaybeUninit::new(unsafe {
+                // Safety: `src_slot.gen` is odd and we mark the slot as vacant right after.
+                src_slot.value.assume_init_read()
+            });
+            src_slot.gen = src_slot.gen.wrapping_add(1);
+
+            on_move(
+                old,
+                Handle {
+                    _ty: PhantomData,
+                    index: dst,
+                    gen: unsafe {
+                        // Safety: `dst_slot.gen` was even and we incremented it.
+                        NonZeroU32::new_unchecked(dst_slot.gen)
+                    },
+                },
+            );
+        }
+
+        self.truncate_vacant();
+    }
+
+    /// Truncates the trailing vacant slots and rebuilds the free list. Retired slots, whose
+    /// generation wrapped around to zero, are kept since no generation is fresh enough to reuse them.
+    fn truncate_vacant(&mut self) {
+        let slots = &mut self.slots.raw;
+
+        while let Some(slot) = slots.last() {
+            if slot.is_occupied() || slot.gen == 0 {
+                break;
+            }
+
+            // `slot.gen` is even so this is odd and greater than the generation of any handle to
+            // this slot.
+            self.fresh_gen = self.fresh_gen.max(slot.gen + 1);
+            slots.pop();
+        }
+
+        self.free_slots = self
+            .slots
+            .enumerate()
+            .filter(|(_, slot)| !slot.is_occupied() && slot.gen != 0)
+            .map(|(index, _)| index)
+            .collect();
+    }
+
     pub fn len(&self) -> usize {
         self.slots.raw.len() - self.free_slots.len()
     }
 
+    /// The number of values the arena can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.slots.raw.capacity()
+    }
+
+    /// Ensures that at least `additional` more values can be inserted without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        self.slots
+            .raw
+            .reserve(additional.saturating_sub(self.free_slots.len()));
+    }
+
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
 
+    /// Removes every value. Like with [`compact`](Self::compact), handles from before the clear
+    /// will never alias a value inserted later.
     pub fn clear(&mut self) {
-        self.slots.raw.clear();
-        self.free_slots.clear();
+        for slot in &mut self.slots.raw {
+            if slot.is_occupied() {
+                // Mark the slot as vacant first so that a panicking destructor can't cause a
+                // double drop.
+                slot.gen = slot.gen.wrapping_add(1);
+
+                unsafe {
+                    // Safety: the slot was occupied before we marked it as vacant.
+                    slot.value.assume_init_drop();
+                }
+            }
+        }
+
+        self.truncate_vacant();
     }
 
     pub fn iter(&self) -> ArenaIter<'_, T> {
@@ -337,3 +470,42 @@ impl<'a, T> Iterator for ArenaIterMut<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic = "invalid duplicate or dead entry"]
+    fn get_many_mut_rejects_handles_truncated_by_compaction() {
+        let mut arena = Arena::new();
+        let a = arena.insert(1);
+        let _b = arena.insert(2);
+        let c = arena.insert(3);
+        let d = arena.insert(4);
+
+        arena.remove(c);
+        arena.remove(d);
+        arena.compact(|_, _| unreachable!());
+        assert_eq!(arena.slots.raw.len(), 2);
+
+        arena.get_many_mut([a, d]);
+    }
+
+    #[test]
+    fn cleared_handles_never_alias_new_values() {
+        let mut arena = Arena::new();
+        let old = [arena.insert(1), arena.insert(2)];
+
+        arena.clear();
+        assert!(arena.is_empty());
+
+        let new = [arena.insert(3), arena.insert(4)];
+        assert_eq!(new.map(|handle| handle.index), old.map(|handle| handle.index));
+
+        for handle in old {
+            assert_eq!(arena.get(handle), None);
+        }
+        assert_eq!(arena.get_many_mut(new), [&mut 3, &mut 4]);
+    }
+}
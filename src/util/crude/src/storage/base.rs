@@ -4,6 +4,7 @@ use std::{
     cell::RefCell,
     hash,
     marker::PhantomData,
+    mem,
     ops::{Deref, DerefMut},
     ptr::NonNull,
     sync::Arc,
@@ -163,6 +164,81 @@ pub unsafe trait Storage: Sized {
         me: &Self,
         handle: Self::Handle,
     ) -> Option<(Entity, *mut Self::Component)>;
+
+    /// Fetches the set of change observers attached to this storage. Storages which don't support
+    /// observers can return `None`, in which case [`StorageView::on_change`] will panic.
+    fn change_observers(me: &mut Self) -> Option<&mut ChangeObservers<Self::Component>> {
+        let _ = me;
+        None
+    }
+}
+
+// === ChangeObservers === //
+
+type ChangeCallback<T> = Box<dyn FnMut(Entity, &T) + Send + Sync>;
+
+/// A set of callbacks to be notified of the entities whose components were mutably accessed since
+/// the last [`flush`](ChangeObservers::flush).
+///
+/// Observers are opt-in: until the first callback is registered, mutable accesses aren't tracked.
+pub struct ChangeObservers<T> {
+    callbacks: Vec<ChangeCallback<T>>,
+    dirty: Vec<Entity>,
+}
+
+impl<T> fmt::Debug for ChangeObservers<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChangeObservers")
+            .field("callbacks", &self.callbacks.len())
+            .field("dirty", &self.dirty)
+            .finish()
+    }
+}
+
+impl<T> Default for ChangeObservers<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ChangeObservers<T> {
+    pub const fn new() -> Self {
+        Self {
+            callbacks: Vec::new(),
+            dirty: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, callback: impl 'static + FnMut(Entity, &T) + Send + Sync) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.callbacks.is_empty()
+    }
+
+    pub fn mark(&mut self, entity: Entity) {
+        if self.is_active() {
+            self.dirty.push(entity);
+        }
+    }
+
+    /// Notifies every callback of each marked entity which still has a value according to `get`
+    /// and clears the dirty set.
+    pub(crate) fn flush(&mut self, mut get: impl FnMut(Entity) -> Option<*mut T>) {
+        self.dirty.sort();
+        self.dirty.dedup();
+
+        for entity in self.dirty.drain(..) {
+            let Some(value) = get(entity) else {
+                continue;
+            };
+
+            for callback in &mut self.callbacks {
+                callback(entity, unsafe { &*value });
+            }
+        }
+    }
 }
 
 // === ChangeQueue === //
@@ -453,7 +529,9 @@ impl<'a, T: Component> StorageView<T> {
     }
 
     pub fn try_get_entity_mut(&mut self, entity: Entity) -> Option<&mut T> {
-        <T::Storage>::entity_to_value(&self.storage(), entity).map(|v| unsafe { &mut *v })
+        let value = <T::Storage>::entity_to_value(self.storage(), entity)?;
+        self.mark_changed(entity);
+        Some(unsafe { &mut *value })
     }
 
     pub fn get_entity(&self, entity: Entity) -> &T {
@@ -471,7 +549,10 @@ impl<'a, T: Component> StorageView<T> {
     }
 
     pub fn try_get_mut(&mut self, handle: Obj<T>) -> Option<&mut T> {
-        <T::Storage>::handle_to_value(&self.storage(), handle.raw()).map(|v| unsafe { &mut *v })
+        let (entity, value) =
+            <T::Storage>::handle_to_entity_and_value(self.storage(), handle.raw())?;
+        self.mark_changed(entity);
+        Some(unsafe { &mut *value })
     }
 
     pub fn get(&self, handle: Obj<T>) -> &T {
@@ -483,4 +564,128 @@ impl<'a, T: Component> StorageView<T> {
         self.try_get_mut(handle)
             .unwrap_or_else(|| Self::missing_error(handle))
     }
+
+    /// Registers a `callback` to be invoked with every entity whose component was mutably accessed
+    /// through this storage once changes are [flushed](Self::flush_changes).
+    pub fn on_change(&mut self, callback: impl 'static + FnMut(Entity, &T) + Send + Sync) {
+        <T::Storage>::change_observers(self.storage_mut())
+            .unwrap_or_else(|| {
+                panic!(
+                    "storage for {} does not support change observers",
+                    <T::Storage>::friendly_name()
+                )
+            })
+            .push(callback);
+    }
+
+    /// Delivers all pending change notifications to this storage's observers.
+    pub fn flush_changes(&mut self) {
+        flush_changes(self.storage_mut());
+    }
+
+    fn mark_changed(&mut self, entity: Entity) {
+        if let Some(observers) = <T::Storage>::change_observers(self.storage_mut()) {
+            observers.mark(entity);
+        }
+    }
+}
+
+pub(crate) fn flush_changes<S: Storage>(storage: &mut S) {
+    let Some(observers) = S::change_observers(storage) else {
+        return;
+    };
+
+    // `flush` needs to look values up in the storage while the observers are borrowed so we
+    // temporarily move them out. They're put back even if an observer panics.
+    let observers = mem::take(observers);
+    let mut guard = scopeguard::guard((storage, observers), |(storage, observers)| {
+        *S::change_observers(storage).unwrap() = observers;
+    });
+
+    let (storage, observers) = &mut *guard;
+    observers.flush(|entity| S::entity_to_value(storage, entity));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, sync::Mutex};
+
+    use crate::StorageRand;
+
+    use super::*;
+
+    struct Health(u32);
+
+    impl Component for Health {
+        type Storage = StorageRand<Self>;
+    }
+
+    #[test]
+    fn observers_see_mutated_components() {
+        let queue = ChangeQueue::default();
+        let mut storage = StorageRand::<Health>::new();
+        let mut view = StorageViewMut::<Health>::new(&queue, &mut storage);
+        let mut entities = EntityAllocator::new();
+
+        let [a, b, c] = view.spawn_arr(
+            &mut entities,
+            [("a", Health(1)), ("b", Health(2)), ("c", Health(3))],
+        );
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        view.on_change({
+            let changes = changes.clone();
+            move |entity, value: &Health| changes.lock().unwrap().push((entity, value.0))
+        });
+
+        view.get_mut(a).0 += 10;
+        view.get_mut(c).0 += 10;
+        view.get_mut(c).0 += 10;
+        let _ = view.get(b);
+        view.flush_changes();
+
+        let entity_of = |obj: Obj<Health>| StorageRand::handle_to_entity(view.storage(), obj.raw());
+        let mut expected = vec![(entity_of(a).unwrap(), 11), (entity_of(c).unwrap(), 23)];
+        expected.sort();
+
+        let mut seen = changes.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, expected);
+
+        // The dirty set is cleared by the flush.
+        view.flush_changes();
+        assert_eq!(changes.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn observers_survive_panicking_callbacks() {
+        let queue = ChangeQueue::default();
+        let mut storage = StorageRand::<Health>::new();
+        let mut view = StorageViewMut::<Health>::new(&queue, &mut storage);
+        let mut entities = EntityAllocator::new();
+
+        let [a] = view.spawn_arr(&mut entities, [("a", Health(1))]);
+
+        let calls = Arc::new(Mutex::new(0));
+        view.on_change({
+            let calls = calls.clone();
+            move |_, _: &Health| {
+                let mut calls = calls.lock().unwrap();
+                *calls += 1;
+                if *calls == 1 {
+                    drop(calls);
+                    panic!("observer failed");
+                }
+            }
+        });
+
+        view.get_mut(a).0 += 1;
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| view.flush_changes()));
+        assert!(res.is_err());
+
+        // The observer is still registered after the panic.
+        view.get_mut(a).0 += 1;
+        view.flush_changes();
+        assert_eq!(*calls.lock().unwrap(), 2);
+    }
 }
@@ -0,0 +1,219 @@
+use std::slice;
+
+use crucible_utils::hash::{hashbrown::hash_map, new_nop_hash_map, NopHashMap};
+
+use crate::Entity;
+
+// === Hierarchy === //
+
+/// A relationship storage tracking each entity's parent and its ordered list of children.
+///
+/// Unlike component storages, entities don't need to be housed in an archetype to take part in
+/// the hierarchy. The [`Universe`](crate::Universe) owns one and [detaches](Self::remove) every
+/// despawned entity from it, orphaning the entity's children.
+#[derive(Debug, Default)]
+pub struct Hierarchy {
+    nodes: NopHashMap<Entity, HierarchyNode>,
+}
+
+#[derive(Debug, Default)]
+struct HierarchyNode {
+    parent: Option<Entity>,
+    children: Vec<Entity>,
+}
+
+impl HierarchyNode {
+    fn is_empty(&self) -> bool {
+        self.parent.is_none() && self.children.is_empty()
+    }
+}
+
+impl Hierarchy {
+    pub const fn new() -> Self {
+        Self {
+            nodes: new_nop_hash_map(),
+        }
+    }
+
+    pub fn parent_of(&self, entity: Entity) -> Option<Entity> {
+        self.nodes.get(&entity).and_then(|node| node.parent)
+    }
+
+    /// Fetches the children of `entity` in the order in which they were attached.
+    pub fn children_of(&self, entity: Entity) -> &[Entity] {
+        self.nodes
+            .get(&entity)
+            .map_or(&[], |node| node.children.as_slice())
+    }
+
+    /// Iterates through every descendant of `entity` depth-first, visiting each child before its
+    /// own children and siblings in attachment order. `entity` itself is not included.
+    pub fn descendants(&self, entity: Entity) -> HierarchyDescendants<'_> {
+        HierarchyDescendants {
+            hierarchy: self,
+            stack: vec![self.children_of(entity).iter()],
+        }
+    }
+
+    /// Returns whether `ancestor` is a strict ancestor of `entity`.
+    pub fn is_ancestor_of(&self, ancestor: Entity, entity: Entity) -> bool {
+        let mut curr = self.parent_of(entity);
+
+        while let Some(parent) = curr {
+            if parent == ancestor {
+                return true;
+            }
+            curr = self.parent_of(parent);
+        }
+
+        false
+    }
+
+    /// Moves `child` to the end of `parent`'s children, detaching it from its previous parent. A
+    /// `parent` of `None` makes `child` a root.
+    ///
+    /// Panics if this would make `child` its own ancestor.
+    pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) {
+        if let Some(parent) = parent {
+            assert!(
+                parent != child && !self.is_ancestor_of(child, parent),
+                "cannot parent {child:?} to its own descendant {parent:?}",
+            );
+        }
+
+        self.detach(child);
+
+        let Some(parent) = parent else {
+            self.prune(child);
+            return;
+        };
+
+        self.nodes.entry(child).or_default().parent = Some(parent);
+        self.nodes.entry(parent).or_default().children.push(child);
+    }
+
+    /// Removes `entity` from the hierarchy, detaching it from its parent and turning each of its
+    /// children into a root. Returns the orphaned children.
+    pub fn remove(&mut self, entity: Entity) -> Vec<Entity> {
+        self.detach(entity);
+
+        let Some(node) = self.nodes.remove(&entity) else {
+            return Vec::new();
+        };
+
+        for &child in &node.children {
+            self.nodes.get_mut(&child).unwrap().parent = None;
+            self.prune(child);
+        }
+
+        node.children
+    }
+
+    fn detach(&mut self, child: Entity) {
+        let Some(parent) = self
+            .nodes
+            .get_mut(&child)
+            .and_then(|node| node.parent.take())
+        else {
+            return;
+        };
+
+        let siblings = &mut self.nodes.get_mut(&parent).unwrap().children;
+        let index = siblings
+            .iter()
+            .position(|&sibling| sibling == child)
+            .unwrap();
+        siblings.remove(index);
+
+        self.prune(parent);
+    }
+
+    fn prune(&mut self, entity: Entity) {
+        if let hash_map::Entry::Occupied(entry) = self.nodes.entry(entity) {
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HierarchyDescendants<'a> {
+    hierarchy: &'a Hierarchy,
+    stack: Vec<slice::Iter<'a, Entity>>,
+}
+
+impl Iterator for HierarchyDescendants<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(&entity) = self.stack.last_mut()?.next() else {
+                self.stack.pop();
+                continue;
+            };
+
+            self.stack.push(self.hierarchy.children_of(entity).iter());
+            return Some(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChangeQueue, EntityAllocator, Universe};
+
+    use super::*;
+
+    #[test]
+    fn despawning_a_parent_orphans_its_children() {
+        let mut entities = EntityAllocator::new();
+        let [root, a, b, a1, a2, b1] =
+            ["root", "a", "b", "a1", "a2", "b1"].map(|label| entities.spawn(label));
+
+        let mut universe = Universe::new();
+        let hierarchy = universe.hierarchy_mut();
+
+        hierarchy.set_parent(a, Some(root));
+        hierarchy.set_parent(b, Some(root));
+        hierarchy.set_parent(a2, Some(a));
+        hierarchy.set_parent(b1, Some(b));
+        hierarchy.set_parent(a1, Some(a));
+
+        // Re-parenting moves the child to the end of its new parent's children.
+        hierarchy.set_parent(a2, Some(root));
+        hierarchy.set_parent(a2, Some(a));
+
+        assert_eq!(hierarchy.children_of(root), [a, b]);
+        assert_eq!(hierarchy.children_of(a), [a1, a2]);
+        assert_eq!(
+            hierarchy.descendants(root).collect::<Vec<_>>(),
+            [a, a1, a2, b, b1]
+        );
+        assert!(hierarchy.is_ancestor_of(root, b1));
+        assert!(!hierarchy.is_ancestor_of(a, b1));
+
+        // Despawn the middle of the tree.
+        let queue = ChangeQueue::default();
+        queue.push_destroy(a);
+        universe.apply(&[queue.into_inner()]);
+
+        let hierarchy = universe.hierarchy();
+        assert_eq!(hierarchy.children_of(root), [b]);
+        assert_eq!(hierarchy.parent_of(a), None);
+        assert_eq!(hierarchy.parent_of(a1), None);
+        assert_eq!(hierarchy.parent_of(a2), None);
+        assert_eq!(hierarchy.descendants(root).collect::<Vec<_>>(), [b, b1]);
+    }
+
+    #[test]
+    #[should_panic = "its own descendant"]
+    fn cycles_are_rejected() {
+        let mut entities = EntityAllocator::new();
+        let [a, b] = ["a", "b"].map(|label| entities.spawn(label));
+
+        let mut hierarchy = Hierarchy::new();
+        hierarchy.set_parent(b, Some(a));
+        hierarchy.set_parent(a, Some(b));
+    }
+}
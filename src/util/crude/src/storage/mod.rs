@@ -1,6 +1,9 @@
 mod base;
 pub use base::*;
 
+mod hierarchy;
+pub use hierarchy::*;
+
 mod rand;
 pub use rand::*;
 
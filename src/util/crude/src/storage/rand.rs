@@ -8,7 +8,7 @@ use derive_where::derive_where;
 
 use crate::{ArchetypeId, Entity, EntityLocation, Storage};
 
-use super::InsertionResultGeneric;
+use super::{ChangeObservers, InsertionResultGeneric};
 
 // === StorageRandHandle === //
 
@@ -27,6 +27,7 @@ pub struct StorageRand<T> {
     arena: Arena<Slot<T>>,
     entity_map: NopHashMap<Entity, StorageRandHandle<T>>,
     archetypes: FxHashMap<ArchetypeId, Vec<StorageRandHandle<T>>>,
+    observers: ChangeObservers<T>,
 }
 
 struct Slot<T> {
@@ -44,6 +45,10 @@ impl<T: fmt::Debug> fmt::Debug for StorageRand<T> {
     }
 }
 
+// Safety: values are only ever mutated through pointers obtained from a mutable borrow of the
+// storage, so a shared borrow of the storage only ever hands out shared borrows of its values.
+unsafe impl<T: Sync> Sync for StorageRand<T> {}
+
 impl<T> Default for StorageRand<T> {
     fn default() -> Self {
         Self::new()
@@ -56,6 +61,7 @@ impl<T> StorageRand<T> {
             arena: Arena::new(),
             entity_map: new_nop_hash_map(),
             archetypes: new_fx_hash_map(),
+            observers: ChangeObservers::new(),
         }
     }
 }
@@ -202,4 +208,39 @@ unsafe impl<T> Storage for StorageRand<T> {
             .get(handle.0)
             .map(|slot| (slot.entity, slot.value.get()))
     }
+
+    fn change_observers(me: &mut Self) -> Option<&mut ChangeObservers<Self::Component>> {
+        Some(&mut me.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::EntityAllocator;
+
+    use super::*;
+
+    #[test]
+    fn stale_handles_do_not_alias_reused_slots() {
+        let mut storage = StorageRand::<u32>::new();
+        let mut entities = EntityAllocator::new();
+
+        let old_entity = entities.spawn("old");
+        let old = StorageRand::insert(&mut storage, old_entity, 1).handle;
+        assert_eq!(StorageRand::remove_handle(&mut storage, old, None), 1);
+
+        // The freed arena slot is recycled for the next insertion but under a new generation.
+        let new_entity = entities.spawn("new");
+        let new = StorageRand::insert(&mut storage, new_entity, 2).handle;
+        assert_eq!(old.0.index(), new.0.index());
+        assert_ne!(old_entity, new_entity);
+
+        assert!(StorageRand::handle_to_value(&storage, old).is_none());
+        assert!(StorageRand::handle_to_entity(&storage, old).is_none());
+        assert!(StorageRand::entity_to_value(&storage, old_entity).is_none());
+        assert_eq!(
+            StorageRand::handle_to_entity(&storage, new),
+            Some(new_entity)
+        );
+    }
 }
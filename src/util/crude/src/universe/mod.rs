@@ -7,5 +7,8 @@ pub use component::*;
 mod entity;
 pub use entity::*;
 
+mod schedule;
+pub use schedule::*;
+
 mod universe;
 pub use universe::*;
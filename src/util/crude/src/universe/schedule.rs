@@ -0,0 +1,281 @@
+use std::{any::type_name, cell::Cell};
+
+use crucible_utils::hash::FxHashMap;
+
+use crate::{ChangeQueue, Component, EntityAllocator, StorageOf, StorageViewMut, StorageViewRef};
+
+use super::{ComponentId, StorageErased};
+
+// === System === //
+
+/// A unit of work run by [`Universe::run_parallel`](crate::Universe::run_parallel). Systems declare
+/// the storages they access up-front so that the scheduler can run non-conflicting systems
+/// concurrently.
+pub trait System: Send {
+    fn accesses(&self, accesses: &mut SystemAccesses);
+
+    fn run(&mut self, entities: &mut EntityAllocator, cx: &SystemContext<'_>);
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemAccesses {
+    reads: Vec<ComponentId>,
+    writes: Vec<ComponentId>,
+}
+
+impl SystemAccesses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: Component>(mut self) -> Self {
+        self.add_read::<T>();
+        self
+    }
+
+    pub fn write<T: Component>(mut self) -> Self {
+        self.add_write::<T>();
+        self
+    }
+
+    pub fn add_read<T: Component>(&mut self) -> &mut Self {
+        self.reads.push(ComponentId::of::<T>());
+        self
+    }
+
+    pub fn add_write<T: Component>(&mut self) -> &mut Self {
+        self.writes.push(ComponentId::of::<T>());
+        self
+    }
+
+    /// Builds a [`System`] running `f` with these accesses.
+    pub fn with<F>(self, f: F) -> FnSystem<F>
+    where
+        F: Send + FnMut(&mut EntityAllocator, &SystemContext<'_>),
+    {
+        FnSystem { accesses: self, f }
+    }
+
+    pub fn reads(&self) -> &[ComponentId] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[ComponentId] {
+        &self.writes
+    }
+
+    /// Returns whether two systems with these accesses cannot run at the same time.
+    pub fn conflicts_with(&self, other: &SystemAccesses) -> bool {
+        self.writes
+            .iter()
+            .any(|comp| other.writes.contains(comp) || other.reads.contains(comp))
+            || other.writes.iter().any(|comp| self.reads.contains(comp))
+    }
+}
+
+#[derive(Debug)]
+pub struct FnSystem<F> {
+    accesses: SystemAccesses,
+    f: F,
+}
+
+impl<F> System for FnSystem<F>
+where
+    F: Send + FnMut(&mut EntityAllocator, &SystemContext<'_>),
+{
+    fn accesses(&self, accesses: &mut SystemAccesses) {
+        accesses.reads.extend_from_slice(&self.accesses.reads);
+        accesses.writes.extend_from_slice(&self.accesses.writes);
+    }
+
+    fn run(&mut self, entities: &mut EntityAllocator, cx: &SystemContext<'_>) {
+        (self.f)(entities, cx)
+    }
+}
+
+// === SystemContext === //
+
+/// The set of storages a running [`System`] has access to. Only storages declared in the system's
+/// [`SystemAccesses`] can be viewed.
+pub struct SystemContext<'a> {
+    queue: &'a ChangeQueue,
+    reads: FxHashMap<ComponentId, &'a dyn StorageErased>,
+    writes: FxHashMap<ComponentId, Cell<Option<&'a mut dyn StorageErased>>>,
+}
+
+impl<'a> SystemContext<'a> {
+    pub(crate) fn new(
+        queue: &'a ChangeQueue,
+        reads: FxHashMap<ComponentId, &'a dyn StorageErased>,
+        writes: FxHashMap<ComponentId, &'a mut dyn StorageErased>,
+    ) -> Self {
+        Self {
+            queue,
+            reads,
+            writes: writes
+                .into_iter()
+                .map(|(comp, storage)| (comp, Cell::new(Some(storage))))
+                .collect(),
+        }
+    }
+
+    pub fn queue(&self) -> &'a ChangeQueue {
+        self.queue
+    }
+
+    /// Views a storage the system declared as read.
+    pub fn read<T: Component>(&self) -> StorageViewRef<'_, T> {
+        let storage = self
+            .reads
+            .get(&ComponentId::of::<T>())
+            .unwrap_or_else(|| panic!("system did not declare a read of {}", type_name::<T>()));
+
+        StorageViewRef::new(
+            self.queue,
+            storage.as_any().downcast_ref::<StorageOf<T>>().unwrap(),
+        )
+    }
+
+    /// Views a storage the system declared as written. Each written storage can only be viewed
+    /// once per run.
+    pub fn write<T: Component>(&self) -> StorageViewMut<'_, T> {
+        let storage = self
+            .writes
+            .get(&ComponentId::of::<T>())
+            .unwrap_or_else(|| panic!("system did not declare a write of {}", type_name::<T>()))
+            .take()
+            .unwrap_or_else(|| panic!("{} was already viewed mutably", type_name::<T>()));
+
+        StorageViewMut::new(
+            self.queue,
+            storage.as_any_mut().downcast_mut::<StorageOf<T>>().unwrap(),
+        )
+    }
+}
+
+// === Scheduling === //
+
+/// Assigns each system to a stage such that no two systems in the same stage conflict and
+/// conflicting systems run in the order in which they were given.
+pub(crate) fn schedule_stages(accesses: &[SystemAccesses]) -> Vec<Vec<usize>> {
+    let mut system_stages = Vec::<usize>::with_capacity(accesses.len());
+    let mut stages = Vec::<Vec<usize>>::new();
+
+    for (i, access) in accesses.iter().enumerate() {
+        let stage = accesses[..i]
+            .iter()
+            .zip(&system_stages)
+            .filter(|(other, _)| access.conflicts_with(other))
+            .map(|(_, &stage)| stage + 1)
+            .max()
+            .unwrap_or(0);
+
+        system_stages.push(stage);
+
+        if stage == stages.len() {
+            stages.push(Vec::new());
+        }
+        stages[stage].push(i);
+    }
+
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    };
+
+    use crate::{Obj, StorageRand, StorageViewModify, Universe};
+
+    use super::*;
+
+    struct Counter(u64);
+
+    impl Component for Counter {
+        type Storage = StorageRand<Self>;
+    }
+
+    struct Other(u64);
+
+    impl Component for Other {
+        type Storage = StorageRand<Self>;
+    }
+
+    #[test]
+    fn conflicting_systems_are_staged() {
+        let stages = schedule_stages(&[
+            SystemAccesses::new().write::<Counter>(),
+            SystemAccesses::new().write::<Counter>(),
+            SystemAccesses::new().write::<Other>(),
+            SystemAccesses::new().read::<Other>(),
+        ]);
+
+        assert_eq!(stages, vec![vec![0, 2], vec![1, 3]]);
+    }
+
+    #[test]
+    fn parallel_writes_do_not_race() {
+        let mut universe = Universe::new();
+        universe.register::<Counter>();
+        universe.register::<Other>();
+
+        // Spawn the entities we'll be mutating.
+        let spawned = Mutex::new(None::<(Obj<Counter>, Obj<Other>)>);
+        let mut spawner = SystemAccesses::new()
+            .write::<Counter>()
+            .write::<Other>()
+            .with(|entities, cx| {
+                let counter = cx.write::<Counter>().spawn(entities, "counter", Counter(0));
+                let other = cx.write::<Other>().spawn(entities, "other", Other(0));
+                *spawned.lock().unwrap() = Some((counter, other));
+            });
+        universe.run_parallel(&mut [&mut spawner]);
+        let (counter, other) = spawned.lock().unwrap().unwrap();
+
+        // Stress the scheduler with two systems contending for `Counter` and one touching `Other`.
+        let counter_busy = AtomicBool::new(false);
+        let other_busy = AtomicBool::new(false);
+
+        // N.B. this closure only captures references and `Copy` values so it is itself `Copy`.
+        let writer = |_: &mut EntityAllocator, cx: &SystemContext<'_>| {
+            assert!(
+                !counter_busy.swap(true, Ordering::SeqCst),
+                "storage accessed concurrently"
+            );
+            let mut counters = cx.write::<Counter>();
+            for _ in 0..1000 {
+                counters.get_mut(counter).0 += 1;
+            }
+            counter_busy.store(false, Ordering::SeqCst);
+        };
+
+        let mut writer_a = SystemAccesses::new().write::<Counter>().with(writer);
+        let mut writer_b = SystemAccesses::new().write::<Counter>().with(writer);
+        let mut writer_other = SystemAccesses::new().write::<Other>().with(|_, cx| {
+            assert!(!other_busy.swap(true, Ordering::SeqCst));
+            cx.write::<Other>().get_mut(other).0 += 1;
+            other_busy.store(false, Ordering::SeqCst);
+        });
+
+        for _ in 0..100 {
+            universe.run_parallel(&mut [&mut writer_a, &mut writer_b, &mut writer_other]);
+        }
+
+        let totals = Mutex::new((0, 0));
+        let mut reader = SystemAccesses::new()
+            .read::<Counter>()
+            .read::<Other>()
+            .with(|_, cx| {
+                *totals.lock().unwrap() = (
+                    cx.read::<Counter>().get(counter).0,
+                    cx.read::<Other>().get(other).0,
+                );
+            });
+        universe.run_parallel(&mut [&mut reader]);
+
+        assert_eq!(*totals.lock().unwrap(), (200_000, 100));
+    }
+}
@@ -1,13 +1,21 @@
+use std::{any::Any, mem, thread};
+
 use crucible_utils::{
-    hash::{FxBuildHasher, NopHashMap},
+    hash::{FxBuildHasher, FxHashMap, NopHashMap},
     iter::RemoveSortedIter,
     newtypes::IndexVec,
 };
 use dashmap::DashMap;
 
-use crate::{ChangeQueueFinished, Storage};
+use crate::{
+    flush_changes, ChangeQueue, ChangeQueueFinished, Component, EntityAllocator, Hierarchy,
+    Storage, StorageOf,
+};
 
-use super::{ArchetypeId, ArchetypeManager, ComponentId, Entity, EntityLocation};
+use super::{
+    schedule_stages, ArchetypeId, ArchetypeManager, ComponentId, Entity, EntityLocation, System,
+    SystemAccesses, SystemContext,
+};
 
 // === Universe === //
 
@@ -17,6 +25,7 @@ pub struct Universe {
     archetype_graph: ArchetypeManager,
     archetype_states: IndexVec<ArchetypeId, Vec<Entity>>,
     entities: NopHashMap<Entity, EntityLocation>,
+    hierarchy: Hierarchy,
 }
 
 impl Universe {
@@ -24,6 +33,152 @@ impl Universe {
         Self::default()
     }
 
+    /// Registers the storage for component `T` if it hasn't been registered already.
+    pub fn register<T: Component>(&mut self)
+    where
+        StorageOf<T>: Default + Send + Sync,
+    {
+        self.storages
+            .entry(ComponentId::of::<T>())
+            .or_insert_with(|| Box::<StorageOf<T>>::default());
+    }
+
+    /// Returns whether `entity` is alive. Entities come alive once the changes giving them their
+    /// first components are [`apply`](Self::apply)d and stay alive until a change queue destroys
+    /// them. Entities which have never been given a component aren't tracked.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.contains_key(&entity)
+    }
+
+    /// Returns the number of [alive](Self::is_alive) entities.
+    pub fn alive_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Iterates over the [alive](Self::is_alive) entities in no particular order.
+    pub fn iter_alive(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.keys().copied()
+    }
+
+    pub fn hierarchy(&self) -> &Hierarchy {
+        &self.hierarchy
+    }
+
+    pub fn hierarchy_mut(&mut self) -> &mut Hierarchy {
+        &mut self.hierarchy
+    }
+
+    /// Delivers the pending change notifications of every storage to their observers. This is
+    /// typically called once at the end of each frame.
+    pub fn flush_changes(&mut self) {
+        for mut storage in self.storages.iter_mut() {
+            storage.value_mut().flush_changes_erased();
+        }
+    }
+
+    /// Runs a batch of systems, running systems whose [`SystemAccesses`] don't conflict
+    /// concurrently. Systems which do conflict are run in the order in which they were given. The
+    /// changes queued by every system are applied once all of them have finished.
+    ///
+    /// Panics if a system accesses a storage which hasn't been [`register`](Self::register)ed.
+    pub fn run_parallel(&mut self, systems: &mut [&mut dyn System]) {
+        let accesses = systems
+            .iter()
+            .map(|system| {
+                let mut accesses = SystemAccesses::new();
+                system.accesses(&mut accesses);
+                accesses
+            })
+            .collect::<Vec<_>>();
+
+        // Take the storages out of the map so that we can hand out disjoint borrows to each of the
+        // systems in a stage. They're put back even if a system panics.
+        let mut storages = scopeguard::guard(
+            mem::take(&mut self.storages)
+                .into_iter()
+                .collect::<FxHashMap<_, _>>(),
+            |storages| self.storages = storages.into_iter().collect(),
+        );
+
+        let mut changes = Vec::new();
+
+        for stage in schedule_stages(&accesses) {
+            let mut available = storages
+                .iter_mut()
+                .map(|(&comp, storage)| (comp, &mut **storage))
+                .collect::<FxHashMap<ComponentId, &mut dyn StorageErased>>();
+
+            // Writes are exclusive within a stage so we can hand those out first...
+            let mut stage_writes = stage
+                .iter()
+                .map(|&i| {
+                    accesses[i]
+                        .writes()
+                        .iter()
+                        .map(|comp| {
+                            let storage = available.remove(comp).unwrap_or_else(|| {
+                                panic!("storage for {comp:?} is not registered")
+                            });
+                            (*comp, storage)
+                        })
+                        .collect::<FxHashMap<_, _>>()
+                })
+                .collect::<Vec<_>>();
+
+            // ...and share the remaining storages among all readers.
+            let available = available
+                .into_iter()
+                .map(|(comp, storage)| (comp, &*storage))
+                .collect::<FxHashMap<ComponentId, &dyn StorageErased>>();
+
+            let mut stage_systems = systems
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| stage.contains(i))
+                .map(|(_, system)| &mut **system)
+                .collect::<Vec<_>>();
+
+            thread::scope(|s| {
+                let handles = stage
+                    .iter()
+                    .zip(&mut stage_systems)
+                    .zip(&mut stage_writes)
+                    .map(|((&i, system), writes)| {
+                        let writes = mem::take(writes);
+                        let reads = accesses[i]
+                            .reads()
+                            .iter()
+                            .filter(|&comp| !writes.contains_key(comp))
+                            .map(|comp| {
+                                let storage = available.get(comp).copied().unwrap_or_else(|| {
+                                    panic!("storage for {comp:?} is not registered")
+                                });
+                                (*comp, storage)
+                            })
+                            .collect::<FxHashMap<_, _>>();
+
+                        s.spawn(move || {
+                            let queue = ChangeQueue::default();
+                            let mut entities = EntityAllocator::new();
+                            system.run(&mut entities, &SystemContext::new(&queue, reads, writes));
+                            queue.into_inner()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                for handle in handles {
+                    match handle.join() {
+                        Ok(change) => changes.push(change),
+                        Err(panic) => std::panic::resume_unwind(panic),
+                    }
+                }
+            });
+        }
+
+        drop(storages);
+        self.apply(&changes);
+    }
+
     pub fn apply(&mut self, changes: &[ChangeQueueFinished]) {
         // Apply de-novo entity insertions. We do these first to avoid later modifications from
         // removing the de-novo assumption.
@@ -47,7 +202,7 @@ impl Universe {
             }
 
             // Update the entities' states.
-            let dest_arch_state = &mut self.archetype_states[dest_arch];
+            let dest_arch_state = self.archetype_states.entry(dest_arch);
 
             let base_dest_slot = dest_arch_state.len();
             dest_arch_state.extend_from_slice(entities);
@@ -96,7 +251,7 @@ impl Universe {
                 self.archetype_states[src_loc.archetype].swap_remove(src_loc.slot);
             }
 
-            let dest_arch_ent_list = &mut self.archetype_states[dest_arch];
+            let dest_arch_ent_list = self.archetype_states.entry(dest_arch);
             let dest_slot = dest_arch_ent_list.len();
             dest_arch_ent_list.push(entity);
 
@@ -108,6 +263,10 @@ impl Universe {
         // Apply entity deletions first since that could avoid some duplicate work while processing
         // component deletions.
         for &entity in changes.iter().flat_map(|v| v.removed_entities.iter()) {
+            // Entities don't need any components to take part in the hierarchy so we detach them
+            // before checking whether they're housed in an archetype.
+            self.hierarchy.remove(entity);
+
             let Some(loc) = self.entities.remove(&entity) else {
                 // The entity must have been deleted before.
                 continue;
@@ -167,7 +326,7 @@ impl Universe {
             // Remove the entity from `archetype_states`.
             self.archetype_states[src_loc.archetype].swap_remove(src_loc.slot);
 
-            let dest_arch_ent_list = &mut self.archetype_states[dest_arch];
+            let dest_arch_ent_list = self.archetype_states.entry(dest_arch);
             let dest_slot = dest_arch_ent_list.len();
             dest_arch_ent_list.push(entity);
 
@@ -180,15 +339,29 @@ impl Universe {
 
 // === StorageErased === //
 
-trait StorageErased {
+pub(crate) trait StorageErased: 'static + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
     fn reshape_erased(&mut self, entity: Entity, src: Option<EntityLocation>, dst: ArchetypeId);
 
     fn reshape_extend_erased(&mut self, archetype: ArchetypeId, entities: &[Entity]);
 
     fn remove_entity_erased(&mut self, entity: Entity, location: Option<EntityLocation>);
+
+    fn flush_changes_erased(&mut self);
 }
 
-impl<T: Storage> StorageErased for T {
+impl<T: Storage + 'static + Send + Sync> StorageErased for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
     fn reshape_erased(&mut self, entity: Entity, src: Option<EntityLocation>, dst: ArchetypeId) {
         Self::reshape(self, entity, src, dst);
     }
@@ -200,4 +373,92 @@ impl<T: Storage> StorageErased for T {
     fn remove_entity_erased(&mut self, entity: Entity, location: Option<EntityLocation>) {
         Self::remove_entity(self, entity, location);
     }
+
+    fn flush_changes_erased(&mut self) {
+        flush_changes(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{panic, sync::Mutex};
+
+    use crate::{ChangeQueue, StorageRand, StorageViewModify, SystemAccesses};
+
+    use super::*;
+
+    struct Health;
+
+    impl Component for Health {
+        type Storage = StorageRand<Self>;
+    }
+
+    #[test]
+    fn tracks_live_entities_across_runs() {
+        let mut universe = Universe::new();
+        universe.register::<Health>();
+
+        // Every run hands its systems a fresh allocator so entities spawned by separate runs
+        // have to be tracked by the universe.
+        let spawned = Mutex::new(Vec::new());
+        let mut spawner = SystemAccesses::new()
+            .write::<Health>()
+            .with(|entities, cx| {
+                let mut health = cx.write::<Health>();
+                let objs = health.spawn_arr(entities, [("a", Health), ("b", Health)]);
+
+                spawned.lock().unwrap().extend(objs.map(|obj| {
+                    StorageRand::handle_to_entity(health.storage(), obj.raw()).unwrap()
+                }));
+            });
+
+        universe.run_parallel(&mut [&mut spawner]);
+        universe.run_parallel(&mut [&mut spawner]);
+
+        let spawned = spawned.into_inner().unwrap();
+        assert_eq!(universe.alive_count(), 4);
+        assert!(spawned.iter().all(|&entity| universe.is_alive(entity)));
+
+        // Destroying an entity removes it along with its components.
+        let queue = ChangeQueue::default();
+        queue.push_destroy(spawned[1]);
+        universe.apply(&[queue.into_inner()]);
+
+        assert!(!universe.is_alive(spawned[1]));
+        assert_eq!(universe.alive_count(), 3);
+
+        let mut alive = universe.iter_alive().collect::<Vec<_>>();
+        alive.sort();
+        let mut expected = vec![spawned[0], spawned[2], spawned[3]];
+        expected.sort();
+        assert_eq!(alive, expected);
+    }
+
+    #[test]
+    fn storages_survive_panicking_systems() {
+        let mut universe = Universe::new();
+        universe.register::<Health>();
+
+        let mut panicking = SystemAccesses::new()
+            .write::<Health>()
+            .with(|_, _| panic!("system failed"));
+
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            universe.run_parallel(&mut [&mut panicking]);
+        }));
+        assert!(res.is_err());
+
+        // The storages taken out for the run were put back so later runs can still use them.
+        let spawned = Mutex::new(false);
+        let mut spawner = SystemAccesses::new()
+            .write::<Health>()
+            .with(|entities, cx| {
+                cx.write::<Health>().spawn(entities, "a", Health);
+                *spawned.lock().unwrap() = true;
+            });
+
+        universe.run_parallel(&mut [&mut spawner]);
+        assert!(spawned.into_inner().unwrap());
+        assert_eq!(universe.alive_count(), 1);
+    }
 }
@@ -166,6 +166,16 @@ pub trait IntegerVector:
     + Shr<Output = Self>
     + Not<Output = Self>
 {
+    /// Adds `rhs` component-wise, returning `None` if any component overflows.
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+
+    /// Subtracts `rhs` component-wise, returning `None` if any component overflows.
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_sub(self, rhs: Self) -> Self;
 }
 
 pub trait SignedVector: NumericVector + Neg<Output = Self> {
@@ -209,6 +219,12 @@ pub trait FloatingVector: SignedVector {
     fn clamp_length_max(self, max: Self::Comp) -> Self;
     fn clamp_length_min(self, min: Self::Comp) -> Self;
     fn mul_add(self, a: Self, b: Self) -> Self;
+
+    // Typed-glam extensions
+    fn reflect(self, normal: Self) -> Self {
+        let dot = self.dot(normal);
+        self - normal * Self::splat(dot + dot)
+    }
 }
 
 pub trait NumericVector2:
@@ -308,6 +324,41 @@ pub trait FloatingVector3: FloatingVector + SignedNumericVector3 {
 
 pub trait FloatingVector4: FloatingVector + SignedNumericVector4 {}
 
+/// Component type conversions between the `glam` vectors of a given dimension.
+///
+/// Every conversion is performed component-wise with an `as` cast, mirroring `glam`'s own `as_*`
+/// methods. This means that float-to-integer conversions truncate towards zero and saturate at the
+/// target's bounds (with `NaN` becoming `0`), `f64`-to-`f32` conversions round to the nearest
+/// representable value, and conversions between `i32` and `u32` reinterpret the bits.
+pub trait CompCastVector2: NumericVector2 {
+    fn as_vec2(self) -> glam::Vec2;
+    fn as_dvec2(self) -> glam::DVec2;
+    fn as_ivec2(self) -> glam::IVec2;
+    fn as_uvec2(self) -> glam::UVec2;
+
+    fn comp_cast_from<V: CompCastVector2>(v: V) -> Self;
+}
+
+/// The three-dimensional version of [`CompCastVector2`].
+pub trait CompCastVector3: NumericVector3 {
+    fn as_vec3(self) -> glam::Vec3;
+    fn as_dvec3(self) -> glam::DVec3;
+    fn as_ivec3(self) -> glam::IVec3;
+    fn as_uvec3(self) -> glam::UVec3;
+
+    fn comp_cast_from<V: CompCastVector3>(v: V) -> Self;
+}
+
+/// The four-dimensional version of [`CompCastVector2`].
+pub trait CompCastVector4: NumericVector4 {
+    fn as_vec4(self) -> glam::Vec4;
+    fn as_dvec4(self) -> glam::DVec4;
+    fn as_ivec4(self) -> glam::IVec4;
+    fn as_uvec4(self) -> glam::UVec4;
+
+    fn comp_cast_from<V: CompCastVector4>(v: V) -> Self;
+}
+
 // === Implementations === //
 
 macro_rules! impl_glam_convert_identity {
@@ -503,7 +554,39 @@ impl_numeric_vector!(glam::Vec4, glam::BVec4A, f32, Dim4);
 
 macro_rules! impl_integer_vector {
 	($($ty:ty),*$(,)?) => {$(
-		impl IntegerVector for $ty {}
+		impl IntegerVector for $ty {
+			fn checked_add(self, rhs: Self) -> Option<Self> {
+				let mut out = self.to_array();
+				for (out, rhs) in out.iter_mut().zip(rhs.to_array()) {
+					*out = out.checked_add(rhs)?;
+				}
+				Some(Self::from_array(out))
+			}
+
+			fn checked_sub(self, rhs: Self) -> Option<Self> {
+				let mut out = self.to_array();
+				for (out, rhs) in out.iter_mut().zip(rhs.to_array()) {
+					*out = out.checked_sub(rhs)?;
+				}
+				Some(Self::from_array(out))
+			}
+
+			fn saturating_add(self, rhs: Self) -> Self {
+				Self::saturating_add(self, rhs)
+			}
+
+			fn saturating_sub(self, rhs: Self) -> Self {
+				Self::saturating_sub(self, rhs)
+			}
+
+			fn wrapping_add(self, rhs: Self) -> Self {
+				Self::wrapping_add(self, rhs)
+			}
+
+			fn wrapping_sub(self, rhs: Self) -> Self {
+				Self::wrapping_sub(self, rhs)
+			}
+		}
 	)*};
 }
 
@@ -928,3 +1011,51 @@ macro_rules! impl_floating_vector_4 {
 }
 
 impl_floating_vector_4!(glam::Vec4, glam::DVec4);
+
+macro_rules! impl_comp_cast_vector {
+	($trait:ident [$($method:ident: $ty:ty => $comp:ty),*$(,)?]) => {
+		impl_comp_cast_vector!(@impl $trait [$($method: $ty => $comp),*] [$($method: $ty => $comp),*]);
+	};
+	(@impl $trait:ident [] $all:tt) => {};
+	(
+		@impl $trait:ident
+		[$self_method:ident: $self_ty:ty => $self_comp:ty $(, $rest_method:ident: $rest_ty:ty => $rest_comp:ty)*]
+		[$($method:ident: $ty:ty => $comp:ty),*]
+	) => {
+		#[allow(clippy::unnecessary_cast)]
+		impl $trait for $self_ty {
+			$(
+				fn $method(self) -> $ty {
+					<$ty>::from_array(self.to_array().map(|comp| comp as $comp))
+				}
+			)*
+
+			fn comp_cast_from<V: $trait>(v: V) -> Self {
+				v.$self_method()
+			}
+		}
+
+		impl_comp_cast_vector!(@impl $trait [$($rest_method: $rest_ty => $rest_comp),*] [$($method: $ty => $comp),*]);
+	};
+}
+
+impl_comp_cast_vector!(CompCastVector2 [
+    as_vec2: glam::Vec2 => f32,
+    as_dvec2: glam::DVec2 => f64,
+    as_ivec2: glam::IVec2 => i32,
+    as_uvec2: glam::UVec2 => u32,
+]);
+
+impl_comp_cast_vector!(CompCastVector3 [
+    as_vec3: glam::Vec3 => f32,
+    as_dvec3: glam::DVec3 => f64,
+    as_ivec3: glam::IVec3 => i32,
+    as_uvec3: glam::UVec3 => u32,
+]);
+
+impl_comp_cast_vector!(CompCastVector4 [
+    as_vec4: glam::Vec4 => f32,
+    as_dvec4: glam::DVec4 => f64,
+    as_ivec4: glam::IVec4 => i32,
+    as_uvec4: glam::UVec4 => u32,
+]);
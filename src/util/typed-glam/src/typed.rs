@@ -8,10 +8,11 @@ use std::{
 use crucible_utils::{newtypes::transparent, traits::ArrayLike};
 
 use crate::traits::{
-    floating_vector_forwards, numeric_vector_forwards, signed_vector_forwards, CastVecFrom, Dim2,
-    Dim3, Dim4, DimClass, FloatingVector, FloatingVector2, FloatingVector3, FloatingVector4,
-    GlamBacked, IntegerVector, NumericVector, NumericVector2, NumericVector3, NumericVector4,
-    SignedNumericVector2, SignedNumericVector3, SignedNumericVector4, SignedVector,
+    floating_vector_forwards, numeric_vector_forwards, signed_vector_forwards, CastVecFrom,
+    CompCastVector2, CompCastVector3, CompCastVector4, Dim2, Dim3, Dim4, DimClass, FloatingVector,
+    FloatingVector2, FloatingVector3, FloatingVector4, GlamBacked, IntegerVector, NumericVector,
+    NumericVector2, NumericVector3, NumericVector4, SignedNumericVector2, SignedNumericVector3,
+    SignedNumericVector4, SignedVector,
 };
 
 // === Flavor traits === //
@@ -19,6 +20,8 @@ use crate::traits::{
 pub trait VecFlavor: 'static + FlavorCastFrom<TypedVector<Self>> {
     type Backing: NumericVector;
 
+    /// The name prefixed to the vector's [`Debug`](fmt::Debug) output so that vectors in different
+    /// coordinate spaces can be told apart in logs. [`Display`](fmt::Display) omits it.
     const DEBUG_NAME: &'static str;
 }
 
@@ -155,18 +158,19 @@ impl<F: ?Sized + VecFlavor> TypedVector<F> {
 // NumericVector
 impl<F: ?Sized + VecFlavor> fmt::Debug for TypedVector<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Display::fmt(&self, f)
+        let mut tuple = f.debug_tuple(F::DEBUG_NAME);
+
+        for comp in self.as_glam().to_array().as_slice() {
+            tuple.field(comp);
+        }
+
+        tuple.finish()
     }
 }
 
 impl<F: ?Sized + VecFlavor> fmt::Display for TypedVector<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}({:?})",
-            F::DEBUG_NAME,
-            self.as_glam().to_array().as_slice()
-        )
+        fmt::Display::fmt(self.as_glam(), f)
     }
 }
 
@@ -254,14 +258,29 @@ where
         self.to_glam().to_array()
     }
 
+    /// Reads the vector from the first components of `slice`, which is useful when unpacking
+    /// staging buffers. Panics if `slice` is too short.
     pub fn from_slice(slice: &[B::Comp]) -> Self {
+        Self::debug_check_slice_len(slice.len());
         Self::from_glam(B::from_slice(slice))
     }
 
+    /// Writes the vector into the first components of `slice`, which is useful when packing
+    /// staging buffers. Panics if `slice` is too short.
     pub fn write_to_slice(self, slice: &mut [B::Comp]) {
+        Self::debug_check_slice_len(slice.len());
         self.to_glam().write_to_slice(slice)
     }
 
+    fn debug_check_slice_len(len: usize) {
+        debug_assert!(
+            len >= B::CompArray::DIM,
+            "{} has {} components but the slice only has {len}",
+            F::DEBUG_NAME,
+            B::CompArray::DIM,
+        );
+    }
+
     pub fn splat(v: B::Comp) -> Self {
         Self::from_glam(B::splat(v))
     }
@@ -335,11 +354,70 @@ where
     }
 }
 
+impl<B, F> TypedVector<F>
+where
+    B: IntegerVector,
+    F: ?Sized + VecFlavor<Backing = B>,
+{
+    /// Adds `rhs` component-wise, returning `None` if any component overflows.
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.to_glam()
+            .checked_add(rhs.to_glam())
+            .map(Self::from_glam)
+    }
+
+    /// Subtracts `rhs` component-wise, returning `None` if any component overflows.
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.to_glam()
+            .checked_sub(rhs.to_glam())
+            .map(Self::from_glam)
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        self.map_glam(|v| v.saturating_add(rhs.to_glam()))
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        self.map_glam(|v| v.saturating_sub(rhs.to_glam()))
+    }
+
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        self.map_glam(|v| v.wrapping_add(rhs.to_glam()))
+    }
+
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        self.map_glam(|v| v.wrapping_sub(rhs.to_glam()))
+    }
+}
+
 impl<B, F> IntegerVector for TypedVector<F>
 where
     B: ?Sized + IntegerVector,
     F: ?Sized + VecFlavor<Backing = B>,
 {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs)
+    }
+
+    fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.checked_sub(rhs)
+    }
+
+    fn saturating_add(self, rhs: Self) -> Self {
+        self.saturating_add(rhs)
+    }
+
+    fn saturating_sub(self, rhs: Self) -> Self {
+        self.saturating_sub(rhs)
+    }
+
+    fn wrapping_add(self, rhs: Self) -> Self {
+        self.wrapping_add(rhs)
+    }
+
+    fn wrapping_sub(self, rhs: Self) -> Self {
+        self.wrapping_sub(rhs)
+    }
 }
 
 // SignedVector
@@ -455,6 +533,11 @@ where
         self.map_glam(|raw| raw.reject_from_normalized(rhs.to_glam()))
     }
 
+    /// Reflects `self` across the plane with the given `normal`, which must be normalized.
+    pub fn reflect(self, normal: Self) -> Self {
+        self.map_glam(|raw| raw.reflect(normal.to_glam()))
+    }
+
     pub fn round(self) -> Self {
         self.map_glam(|raw| raw.round())
     }
@@ -984,6 +1067,106 @@ where
 {
 }
 
+// mint
+#[cfg(feature = "mint")]
+macro_rules! impl_mint_conversions {
+	($($trait:ident => $mint:ident;)*) => {$(
+		impl<B, F> From<mint::$mint<B::Comp>> for TypedVector<F>
+		where
+			B: $trait + From<mint::$mint<B::Comp>>,
+			F: ?Sized + VecFlavor<Backing = B>,
+		{
+			fn from(vec: mint::$mint<B::Comp>) -> Self {
+				Self::from_glam(B::from(vec))
+			}
+		}
+
+		impl<B, F> From<TypedVector<F>> for mint::$mint<B::Comp>
+		where
+			B: $trait + Into<mint::$mint<B::Comp>>,
+			F: ?Sized + VecFlavor<Backing = B>,
+		{
+			fn from(vec: TypedVector<F>) -> Self {
+				vec.to_glam().into()
+			}
+		}
+	)*};
+}
+
+#[cfg(feature = "mint")]
+impl_mint_conversions! {
+    NumericVector2 => Vector2;
+    NumericVector3 => Vector3;
+    NumericVector4 => Vector4;
+}
+
+// CompCastVector2, CompCastVector3, and CompCastVector4
+macro_rules! impl_comp_cast_vector {
+	($(
+		$dim:ty, $trait:ident [$($method:ident -> $ty:ty),*$(,)?];
+	)*) => {$(
+		impl<B, F> TypedVectorImpl<F, $dim>
+		where
+			B: $trait,
+			F: ?Sized + VecFlavor<Backing = B>,
+		{
+			$(
+				pub fn $method(self) -> $ty {
+					self.to_glam().$method()
+				}
+			)*
+
+			/// Converts this vector into a vector of flavor `G`, casting each component to the
+			/// component type of `G`'s backing. See [`CompCastVector2`] for how components are
+			/// rounded.
+			pub fn as_flavor<G>(self) -> TypedVector<G>
+			where
+				G: ?Sized + VecFlavor,
+				G::Backing: $trait,
+			{
+				TypedVector::<G>::from_glam(<G::Backing as $trait>::comp_cast_from(self.to_glam()))
+			}
+		}
+
+		impl<B, F> $trait for TypedVector<F>
+		where
+			B: $trait,
+			F: ?Sized + VecFlavor<Backing = B>,
+		{
+			$(
+				fn $method(self) -> $ty {
+					self.$method()
+				}
+			)*
+
+			fn comp_cast_from<V: $trait>(v: V) -> Self {
+				Self::from_glam(B::comp_cast_from(v))
+			}
+		}
+	)*};
+}
+
+impl_comp_cast_vector!(
+    Dim2, CompCastVector2 [
+        as_vec2 -> glam::Vec2,
+        as_dvec2 -> glam::DVec2,
+        as_ivec2 -> glam::IVec2,
+        as_uvec2 -> glam::UVec2,
+    ];
+    Dim3, CompCastVector3 [
+        as_vec3 -> glam::Vec3,
+        as_dvec3 -> glam::DVec3,
+        as_ivec3 -> glam::IVec3,
+        as_uvec3 -> glam::UVec3,
+    ];
+    Dim4, CompCastVector4 [
+        as_vec4 -> glam::Vec4,
+        as_dvec4 -> glam::DVec4,
+        as_ivec4 -> glam::IVec4,
+        as_uvec4 -> glam::UVec4,
+    ];
+);
+
 // Overload derivations
 macro_rules! derive_bin_ops {
 	($(
@@ -1063,3 +1246,181 @@ where
         self.map_glam(ops::Neg::neg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::{DVec3, IVec3, Vec3};
+
+    use super::*;
+
+    struct TestFlavor;
+
+    impl VecFlavor for TestFlavor {
+        type Backing = Vec3;
+
+        const DEBUG_NAME: &'static str = "TestVec";
+    }
+
+    type TestVec = TypedVector<TestFlavor>;
+
+    struct TestIntFlavor;
+
+    impl VecFlavor for TestIntFlavor {
+        type Backing = IVec3;
+
+        const DEBUG_NAME: &'static str = "TestIntVec";
+    }
+
+    type TestIntVec = TypedVector<TestIntFlavor>;
+
+    struct TestDoubleFlavor;
+
+    impl VecFlavor for TestDoubleFlavor {
+        type Backing = DVec3;
+
+        const DEBUG_NAME: &'static str = "TestDoubleVec";
+    }
+
+    type TestDoubleVec = TypedVector<TestDoubleFlavor>;
+
+    #[test]
+    fn reflect_across_axis() {
+        let v = TestVec::new(1.0, -2.0, 3.0);
+
+        assert_eq!(v.reflect(TestVec::Y), TestVec::new(1.0, 2.0, 3.0));
+        assert_eq!(v.reflect(TestVec::NEG_X), TestVec::new(-1.0, -2.0, 3.0));
+    }
+
+    #[test]
+    fn projection_and_rejection_sum_to_original() {
+        let v = TestVec::new(3.0, -1.5, 2.0);
+        let other = TestVec::new(0.5, 2.0, -1.0);
+
+        let projected = v.project_onto(other);
+        let rejected = v.reject_from(other);
+
+        assert!((projected + rejected).abs_diff_eq(v, 1e-5));
+        assert!(rejected.dot(other).abs() < 1e-5);
+    }
+
+    #[test]
+    fn int_to_double_conversions() {
+        let v = TestIntVec::new(-3, 0, 7);
+
+        assert_eq!(v.as_dvec3(), DVec3::new(-3., 0., 7.));
+        assert_eq!(
+            v.as_flavor::<TestDoubleFlavor>(),
+            TestDoubleVec::new(-3., 0., 7.)
+        );
+    }
+
+    #[test]
+    fn double_to_float_and_int_conversions() {
+        let v = TestDoubleVec::new(0.1, -2.75, 1e300);
+
+        assert_eq!(v.as_vec3(), Vec3::new(0.1, -2.75, f32::INFINITY));
+        assert_eq!(
+            v.as_flavor::<TestFlavor>(),
+            TestVec::new(0.1, -2.75, f32::INFINITY)
+        );
+
+        // Float to integer conversions truncate towards zero and saturate.
+        assert_eq!(
+            v.as_flavor::<TestIntFlavor>(),
+            TestIntVec::new(0, -2, i32::MAX)
+        );
+    }
+
+    #[test]
+    fn splat_and_constants() {
+        assert_eq!(TestVec::splat(100.0), TestVec::new(100.0, 100.0, 100.0));
+        assert_eq!(TestIntVec::splat(-3), TestIntVec::new(-3, -3, -3));
+
+        assert_eq!(TestVec::ZERO, TestVec::splat(0.0));
+        assert_eq!(TestVec::ONE, TestVec::splat(1.0));
+        assert_eq!(TestIntVec::NEG_ONE, TestIntVec::splat(-1));
+
+        for (i, axis) in [TestIntVec::X, TestIntVec::Y, TestIntVec::Z]
+            .into_iter()
+            .enumerate()
+        {
+            let mut expected = [0; 3];
+            expected[i] = 1;
+            assert_eq!(axis.to_array(), expected);
+            assert_eq!(axis, TestIntVec::unit_axis(i));
+        }
+    }
+
+    #[test]
+    fn slice_round_trip() {
+        let mut staging = [0.; 5];
+        TestVec::new(1., -2., 3.5).write_to_slice(&mut staging[1..]);
+        assert_eq!(staging, [0., 1., -2., 3.5, 0.]);
+
+        assert_eq!(
+            TestVec::from_slice(&staging[1..]),
+            TestVec::new(1., -2., 3.5)
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "TestVec has 3 components but the slice only has 2")]
+    fn short_slices_panic() {
+        TestVec::from_slice(&[1., 2.]);
+    }
+
+    #[test]
+    fn integer_overflow_handling() {
+        let edge = TestIntVec::new(i32::MAX - 1, 0, i32::MIN + 1);
+
+        assert_eq!(
+            edge.checked_add(TestIntVec::new(1, 5, 0)),
+            Some(TestIntVec::new(i32::MAX, 5, i32::MIN + 1))
+        );
+        assert_eq!(edge.checked_add(TestIntVec::new(2, 0, 0)), None);
+        assert_eq!(edge.checked_sub(TestIntVec::new(0, 0, 2)), None);
+
+        assert_eq!(
+            edge.saturating_add(TestIntVec::splat(10)),
+            TestIntVec::new(i32::MAX, 10, i32::MIN + 11)
+        );
+        assert_eq!(
+            edge.saturating_sub(TestIntVec::splat(10)),
+            TestIntVec::new(i32::MAX - 11, -10, i32::MIN)
+        );
+        assert_eq!(
+            edge.wrapping_add(TestIntVec::new(2, 0, 0)),
+            TestIntVec::new(i32::MIN, 0, i32::MIN + 1)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "mint")]
+    fn mint_round_trip() {
+        let vec = TestIntVec::new(4, -2, 9);
+        let raw: mint::Vector3<i32> = vec.into();
+
+        assert_eq!(raw, mint::Vector3 { x: 4, y: -2, z: 9 });
+        assert_eq!(TestIntVec::from(raw), vec);
+
+        let vec = TestVec::from(mint::Vector3::from([0.5, 1.5, -3.]));
+        assert_eq!(vec, TestVec::new(0.5, 1.5, -3.));
+        assert_eq!(
+            mint::Vector3::from(vec),
+            mint::Vector3::from([0.5, 1.5, -3.])
+        );
+    }
+
+    #[test]
+    fn debug_output_names_the_flavor() {
+        let vec = TestIntVec::new(1, -5, 3);
+
+        assert_eq!(format!("{vec:?}"), "TestIntVec(1, -5, 3)");
+        assert_eq!(format!("{vec}"), "[1, -5, 3]");
+        assert_eq!(
+            format!("{:?}", TestVec::new(0.5, 0., -1.)),
+            "TestVec(0.5, 0.0, -1.0)"
+        );
+    }
+}